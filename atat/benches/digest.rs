@@ -0,0 +1,92 @@
+//! Time spent digesting responses of increasing length, as received through
+//! a receive interrupt feeding one byte at a time, or through DMA feeding the
+//! whole response at once.
+//!
+//! Run with `cargo bench -p atat --bench digest`.
+
+use std::time::Instant;
+
+use atat::{AtatClient, AtatCmd, AtatResp, Config, Error, Mode, NoopUrcMatcher, TimerlessMode};
+use embedded_hal::serial;
+use heapless::{consts, String};
+
+const ITERATIONS: u32 = 2000;
+
+struct TxMock;
+
+impl serial::Write<u8> for TxMock {
+    type Error = ();
+
+    fn write(&mut self, _c: u8) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+struct ListContexts;
+
+struct Contexts;
+
+impl AtatResp for Contexts {}
+
+impl AtatCmd for ListContexts {
+    type CommandLen = consts::U16;
+    type Response = Contexts;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        String::from("AT+CGDCONT?\r\n")
+    }
+
+    fn parse(&self, _resp: &str) -> Result<Self::Response, Error> {
+        Ok(Contexts)
+    }
+}
+
+fn main() {
+    let (mut client, mut ingress) = atat::new_without_timer(
+        TxMock,
+        TimerlessMode::NonBlocking,
+        Config::new(Mode::NonBlocking),
+        None::<NoopUrcMatcher>,
+    );
+
+    println!(
+        "{:>10} {:>16} {:>16}",
+        "length", "per byte (us)", "at once (us)"
+    );
+    for &lines in &[1, 2, 4, 7] {
+        let mut response = std::string::String::from("AT+CGDCONT?\r\r\n");
+        for cid in 0..lines {
+            response.push_str(&format!("+CGDCONT: {},\"IP\",\"internet\"\r\n", cid));
+        }
+        response.push_str("\r\nOK\r\n");
+
+        let mut timings = [0.0; 2];
+        for (at_once, timing) in timings.iter_mut().enumerate() {
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                client.send(&ListContexts).ok();
+                if at_once == 1 {
+                    ingress.write(response.as_bytes());
+                    ingress.digest();
+                } else {
+                    for byte in response.as_bytes() {
+                        ingress.write(&[*byte]);
+                        ingress.digest();
+                    }
+                }
+                client.check_response(&ListContexts).ok().unwrap();
+            }
+            *timing = start.elapsed().as_secs_f64() * 1e6 / f64::from(ITERATIONS);
+        }
+        println!(
+            "{:>10} {:>16.2} {:>16.2}",
+            response.len(),
+            timings[0],
+            timings[1]
+        );
+    }
+}
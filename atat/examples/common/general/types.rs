@@ -1,9 +1,10 @@
 //! Argument and parameter types used by General Commands and Responses
 
+use atat::atat_derive::AtatLen;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use ufmt::derive::uDebug;
 
-#[derive(uDebug, Clone, PartialEq, Serialize_repr, Deserialize_repr)]
+#[derive(uDebug, Clone, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
 #[repr(u8)]
 pub enum Snt {
     /// (default value): International Mobile station Equipment Identity (IMEI)
@@ -6,7 +6,35 @@ use atat::{
     atat_derive::{AtatCmd, AtatResp},
     AtatCmd, AtatResp,
 };
+use embedded_hal::timer::CountDown;
 use heapless::String;
+use stm32l4xx_hal::time::Hertz;
+
+/// The timers of the HAL are started with the frequency of their timeouts,
+/// rather than the duration the client starts its timers with. Durations are
+/// rounded to the period of a whole frequency, of at most 1 s, which is
+/// plenty for these examples.
+pub struct MsTimer<T>(pub T);
+
+impl<T> CountDown for MsTimer<T>
+where
+    T: CountDown,
+    T::Time: From<Hertz>,
+{
+    type Time = atat::Milliseconds;
+
+    fn start<C>(&mut self, count: C)
+    where
+        C: Into<Self::Time>,
+    {
+        let ms = count.into().0.max(1);
+        self.0.start(Hertz((1000 / ms).max(1)));
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        self.0.wait()
+    }
+}
 
 #[derive(Clone, AtatResp)]
 pub struct NoResponse;
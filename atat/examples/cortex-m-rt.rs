@@ -63,6 +63,7 @@ fn main() -> ! {
 
     let mut timer = Timer::tim7(p.TIM7, 1.hz(), clocks, &mut rcc.apb1r1);
     let at_timer = Timer::tim6(p.TIM6, 100.hz(), clocks, &mut rcc.apb1r1);
+    let cooldown_timer = Timer::tim15(p.TIM15, 100.hz(), clocks, &mut rcc.apb2);
 
     let mut serial = Serial::usart2(
         p.USART2,
@@ -75,7 +76,7 @@ fn main() -> ! {
     serial.listen(Rxne);
 
     let (tx, rx) = serial.split();
-    let (mut client, ingress) = atat::new(tx, at_timer, atat::Config::new(atat::Mode::Timeout), None);
+    let (mut client, ingress) = atat::new(tx, common::MsTimer(at_timer), common::MsTimer(cooldown_timer), atat::Config::new(atat::Mode::Timeout), None);
 
     unsafe { INGRESS = Some(ingress) };
     unsafe { RX = Some(rx) };
@@ -65,6 +65,7 @@ const APP: () = {
         let rx = gpioa.pa3.into_af7(&mut gpioa.moder, &mut gpioa.afrl);
 
         let mut timer = Timer::tim7(p.TIM7, 1.hz(), clocks, &mut rcc.apb1r1);
+        let cooldown_timer = Timer::tim15(p.TIM15, 100.hz(), clocks, &mut rcc.apb2);
 
         let mut serial = Serial::usart2(
             p.USART2,
@@ -77,7 +78,7 @@ const APP: () = {
         serial.listen(Rxne);
 
         let (tx, rx) = serial.split();
-        let (mut client, ingress) = atat::new(tx, timer, atat::Config::new(atat::Mode::Timeout), None);
+        let (mut client, ingress) = atat::new(tx, common::MsTimer(timer), common::MsTimer(cooldown_timer), atat::Config::new(atat::Mode::Timeout), None);
 
         ctx.spawn.at_loop().unwrap();
 
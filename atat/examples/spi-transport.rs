@@ -0,0 +1,224 @@
+//! Example of a module exposing its AT interface through the byte FIFOs of a
+//! SPI register map, instead of a UART.
+#![no_main]
+#![no_std]
+// TEMP: Crate wide allow this
+#![allow(deprecated, unused)]
+
+extern crate atat;
+extern crate cortex_m;
+extern crate cortex_m_rt as rt;
+extern crate heapless;
+extern crate nb;
+extern crate panic_halt;
+extern crate stm32l4xx_hal as hal;
+
+mod common;
+
+use core::cell::RefCell;
+
+use cortex_m::{asm, interrupt::Mutex};
+use embedded_hal::{blocking::spi::Transfer, digital::v2::OutputPin};
+use hal::{
+    gpio::{
+        gpioa::{PA4, PA5, PA6, PA7},
+        Alternate, Floating, Input, Output, PushPull, AF5,
+    },
+    pac::{interrupt, Peripherals, SPI1},
+    prelude::*,
+    spi::{Mode, Phase, Polarity, Spi},
+    timer::{Event, Timer},
+};
+
+use atat::prelude::*;
+
+use crate::rt::entry;
+
+/// Register map of the module
+const REG_TX_FIFO: u8 = 0x01;
+const REG_RX_FIFO: u8 = 0x02;
+const REG_TX_SPACE: u8 = 0x03;
+const REG_RX_LEVEL: u8 = 0x04;
+const WRITE_FLAG: u8 = 0x80;
+const FIFO_SIZE: u8 = 64;
+
+/// Byte FIFOs of the module, accessed through SPI registers
+struct SpiFifo<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS, E> SpiFifo<SPI, CS>
+where
+    SPI: Transfer<u8, Error = E>,
+    CS: OutputPin,
+{
+    fn transfer<'w>(&mut self, buf: &'w mut [u8]) -> Result<&'w [u8], E> {
+        self.cs.set_low().ok();
+        let res = self.spi.transfer(buf);
+        self.cs.set_high().ok();
+        res
+    }
+
+    fn read_reg(&mut self, reg: u8) -> Result<u8, E> {
+        let mut buf = [reg, 0];
+        Ok(self.transfer(&mut buf)?[1])
+    }
+
+    /// Write as much of `buf` as there is room for in the TX FIFO.
+    fn write_tx(&mut self, buf: &[u8]) -> Result<usize, E> {
+        let n = core::cmp::min(self.read_reg(REG_TX_SPACE)? as usize, buf.len());
+        let mut frame = [0u8; FIFO_SIZE as usize + 1];
+        frame[0] = REG_TX_FIFO | WRITE_FLAG;
+        frame[1..=n].copy_from_slice(&buf[..n]);
+        self.transfer(&mut frame[..=n])?;
+        Ok(n)
+    }
+
+    /// Drain the RX FIFO into `buf`.
+    fn read_rx<'w>(&mut self, buf: &'w mut [u8; FIFO_SIZE as usize + 1]) -> Result<&'w [u8], E> {
+        let n = self.read_reg(REG_RX_LEVEL)? as usize;
+        buf[0] = REG_RX_FIFO;
+        Ok(&self.transfer(&mut buf[..=n])?[1..])
+    }
+}
+
+impl<SPI, CS, E> atat::AtTransport for SpiFifo<SPI, CS>
+where
+    SPI: Transfer<u8, Error = E>,
+    CS: OutputPin,
+{
+    type Error = E;
+
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<(), Self::Error> {
+        while !buf.is_empty() {
+            let n = self.write_tx(buf)?;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while self.read_reg(REG_TX_SPACE)? < FIFO_SIZE {}
+        Ok(())
+    }
+}
+
+type Fifo = SpiFifo<
+    Spi<
+        SPI1,
+        (
+            PA5<Alternate<AF5, Input<Floating>>>,
+            PA6<Alternate<AF5, Input<Floating>>>,
+            PA7<Alternate<AF5, Input<Floating>>>,
+        ),
+    >,
+    PA4<Output<PushPull>>,
+>;
+
+/// The SPI bus is shared between the client, writing commands, and the timer
+/// interrupt, polling the RX FIFO.
+static FIFO: Mutex<RefCell<Option<Fifo>>> = Mutex::new(RefCell::new(None));
+static mut INGRESS: Option<atat::IngressManager<atat::NoopUrcMatcher>> = None;
+
+/// Handle to the shared FIFO, handed to the client as its transport
+struct SharedFifo;
+
+impl atat::AtTransport for SharedFifo {
+    type Error = hal::spi::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        cortex_m::interrupt::free(|cs| {
+            FIFO.borrow(cs)
+                .borrow_mut()
+                .as_mut()
+                .unwrap()
+                .write_all(buf)
+        })
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        cortex_m::interrupt::free(|cs| FIFO.borrow(cs).borrow_mut().as_mut().unwrap().flush())
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    let mut flash = p.FLASH.constrain();
+    let mut rcc = p.RCC.constrain();
+    let mut pwr = p.PWR.constrain(&mut rcc.apb1r1);
+
+    let mut gpioa = p.GPIOA.split(&mut rcc.ahb2);
+
+    let clocks = rcc.cfgr.freeze(&mut flash.acr, &mut pwr);
+
+    let sck = gpioa.pa5.into_af5(&mut gpioa.moder, &mut gpioa.afrl);
+    let miso = gpioa.pa6.into_af5(&mut gpioa.moder, &mut gpioa.afrl);
+    let mosi = gpioa.pa7.into_af5(&mut gpioa.moder, &mut gpioa.afrl);
+    let mut cs = gpioa
+        .pa4
+        .into_push_pull_output(&mut gpioa.moder, &mut gpioa.otyper);
+    cs.set_high().ok();
+
+    let spi = Spi::spi1(
+        p.SPI1,
+        (sck, miso, mosi),
+        Mode {
+            polarity: Polarity::IdleLow,
+            phase: Phase::CaptureOnFirstTransition,
+        },
+        1.mhz(),
+        clocks,
+        &mut rcc.apb2,
+    );
+
+    cortex_m::interrupt::free(|c| FIFO.borrow(c).replace(Some(SpiFifo { spi, cs })));
+
+    let mut timer = Timer::tim7(p.TIM7, 100.hz(), clocks, &mut rcc.apb1r1);
+    let at_timer = Timer::tim6(p.TIM6, 100.hz(), clocks, &mut rcc.apb1r1);
+    let cooldown_timer = Timer::tim15(p.TIM15, 100.hz(), clocks, &mut rcc.apb2);
+
+    let (mut client, ingress) = atat::new(
+        SharedFifo,
+        common::MsTimer(at_timer),
+        common::MsTimer(cooldown_timer),
+        atat::Config::new(atat::Mode::Timeout),
+        None,
+    );
+
+    unsafe { INGRESS = Some(ingress) };
+
+    // configure NVIC interrupts
+    unsafe { cortex_m::peripheral::NVIC::unmask(hal::stm32::Interrupt::TIM7) };
+    timer.listen(Event::TimeOut);
+
+    // if all goes well you should reach this breakpoint
+    asm::bkpt();
+
+    loop {
+        asm::wfi();
+
+        match client.send(&common::AT) {
+            Ok(response) => {
+                // Do something with response here
+            }
+            Err(e) => {}
+        }
+    }
+}
+
+#[interrupt]
+fn TIM7() {
+    let ingress = unsafe { INGRESS.as_mut().unwrap() };
+    let mut buf = [0u8; FIFO_SIZE as usize + 1];
+    cortex_m::interrupt::free(|cs| {
+        if let Some(fifo) = FIFO.borrow(cs).borrow_mut().as_mut() {
+            if let Ok(data) = fifo.read_rx(&mut buf) {
+                ingress.write(data);
+            }
+        }
+    });
+    ingress.digest();
+}
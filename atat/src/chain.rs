@@ -0,0 +1,207 @@
+//! Sending several commands on one command line, e.g.
+//! `AT+CMEE=1;+CREG=2;+CFUN=1`
+//!
+//! V.25ter allows concatenating commands with `;`, which the device executes
+//! in order, answering with the intermediate responses of all of them and a
+//! single final result code. A [`CommandChain`] saves the round-trips (and
+//! cooldowns) of sending the commands one by one, e.g. during initialization:
+//!
+//! ```ignore
+//! use atat::chain::CommandChain;
+//!
+//! let chain = CommandChain::new(SetCmee { n: 1 }, SetCreg { n: 2 })
+//!     .then(GetSignalQuality);
+//! let ((NoResponse, NoResponse), quality) = block!(client.send(&chain))?;
+//! ```
+//!
+//! The device stops at the first command failing, so the chain fails as a
+//! whole, without telling which command failed.
+//!
+//! [`CommandChain`]: struct.CommandChain.html
+
+use core::ops::Add;
+
+use heapless::{ArrayLength, String};
+
+use crate::error::{Error, ErrorContext};
+use crate::timer::Milliseconds;
+use crate::traits::AtatCmd;
+
+/// Two commands sent on one command line, responding with the responses of
+/// both.
+///
+/// Both commands must start with `AT`. Longer chains are built with
+/// [`then`](#method.then), nesting the responses.
+#[derive(Debug, Clone)]
+pub struct CommandChain<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<A, B> CommandChain<A, B> {
+    pub fn new(first: A, next: B) -> Self {
+        CommandChain { first, next }
+    }
+
+    /// Append `next` to the chain.
+    pub fn then<C>(self, next: C) -> CommandChain<Self, C> {
+        CommandChain::new(self, next)
+    }
+}
+
+impl<A, B> AtatCmd for CommandChain<A, B>
+where
+    A: AtatCmd,
+    B: AtatCmd,
+    A::CommandLen: Add<B::CommandLen>,
+    <A::CommandLen as Add<B::CommandLen>>::Output: ArrayLength<u8>,
+{
+    // The `AT` dropped from the next command makes room for the `;`
+    type CommandLen = <A::CommandLen as Add<B::CommandLen>>::Output;
+    type Response = (A::Response, B::Response);
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        join(&self.first.as_string(), &self.next.as_string())
+    }
+
+    fn try_as_string(&self) -> Result<String<Self::CommandLen>, Error> {
+        Ok(join(
+            &self.first.try_as_string()?,
+            &self.next.try_as_string()?,
+        ))
+    }
+
+    /// The intermediate responses are split between the commands at the first
+    /// line boundary both of them parse.
+    fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+        let boundaries = core::iter::once(0)
+            .chain(resp.match_indices('\n').map(|(i, _)| i + 1))
+            .chain(core::iter::once(resp.len()));
+        for i in boundaries {
+            let (first, next) = resp.split_at(i);
+            if let Ok(first) = self.first.parse(trim_lines(first)) {
+                if let Ok(next) = self.next.parse(trim_lines(next)) {
+                    return Ok((first, next));
+                }
+            }
+        }
+        Err(Error::ParseString(ErrorContext::response(resp)))
+    }
+
+    fn can_abort(&self) -> bool {
+        self.first.can_abort() && self.next.can_abort()
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        Milliseconds(
+            self.first
+                .max_timeout()
+                .0
+                .saturating_add(self.next.max_timeout().0),
+        )
+    }
+
+    /// The cooldown follows the response to both commands, so it is the one
+    /// of the last command.
+    fn cooldown(&self) -> Option<Milliseconds> {
+        self.next.cooldown()
+    }
+
+    fn max_cmd_len(&self) -> Option<usize> {
+        match (self.first.max_cmd_len(), self.next.max_cmd_len()) {
+            (Some(a), Some(b)) => Some(core::cmp::min(a, b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn max_response_len(&self) -> Option<usize> {
+        match (self.first.max_response_len(), self.next.max_response_len()) {
+            // Plus the line terminator in between
+            (Some(a), Some(b)) => Some(a + b + 2),
+            _ => None,
+        }
+    }
+
+    fn force_receive_state(&self) -> bool {
+        self.first.force_receive_state()
+    }
+}
+
+/// The commands `first` and `next`, joined by a `;`. The sum of their
+/// capacities leaves room for the `;` in place of the `AT` of `next`.
+fn join<N: ArrayLength<u8>>(first: &str, next: &str) -> String<N> {
+    let mut s = String::new();
+    s.push_str(first.trim_end_matches(&['\r', '\n'][..])).ok();
+    s.push(';').ok();
+    s.push_str(next.strip_prefix("AT").unwrap_or(next)).ok();
+    s
+}
+
+/// `s` without the line terminators around it.
+fn trim_lines(s: &str) -> &str {
+    s.trim_matches(&['\r', '\n'][..])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate as atat;
+    use crate::atat_derive::{AtatCmd, AtatResp};
+    use heapless::consts;
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct NoResponse;
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct RegistrationStatus {
+        #[at_arg(position = 0)]
+        n: u8,
+        #[at_arg(position = 1)]
+        stat: u8,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct SignalQuality {
+        #[at_arg(position = 0)]
+        rssi: u8,
+        #[at_arg(position = 1)]
+        ber: u8,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CMEE", NoResponse)]
+    pub struct SetCmee {
+        #[at_arg(position = 0)]
+        n: u8,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CREG?", RegistrationStatus, timeout_ms = 2000)]
+    pub struct GetRegistrationStatus;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CSQ", SignalQuality)]
+    pub struct GetSignalQuality;
+
+    #[test]
+    fn chain() {
+        let chain =
+            CommandChain::new(SetCmee { n: 1 }, GetRegistrationStatus).then(GetSignalQuality);
+        assert_eq!(
+            chain.as_string(),
+            String::<consts::U64>::from("AT+CMEE=1;+CREG?;+CSQ\r\n")
+        );
+        assert_eq!(chain.max_timeout(), Milliseconds(1000 + 2000 + 1000));
+        assert_eq!(
+            chain.parse("+CREG: 0,1\r\n+CSQ: 31,99"),
+            Ok((
+                (NoResponse, RegistrationStatus { n: 0, stat: 1 }),
+                SignalQuality { rssi: 31, ber: 99 }
+            ))
+        );
+        assert_eq!(
+            chain.parse("+CREG: 0,1"),
+            Err(Error::ParseString(ErrorContext::response("+CREG: 0,1")))
+        );
+    }
+}
@@ -1,8 +1,25 @@
-use embedded_hal::{serial, timer::CountDown};
+use embedded_hal::timer::CountDown;
+use heapless::{consts, spsc::Queue, String, Vec};
 
 use crate::error::Error;
-use crate::queues::{ComProducer, ResConsumer, UrcConsumer};
-use crate::traits::{AtatClient, AtatCmd, AtatUrc};
+use crate::ingress_manager::State;
+use crate::queues::{
+    ComProducer, DataConsumer, PriorityUrcConsumer, ResConsumer, ResponseGuard, UrcConsumer,
+    UrcFrame,
+};
+use crate::stats::{DropCause, DropStats, QueueCounters, QueueLevels, QueueStats};
+use crate::timer::Milliseconds;
+use crate::trace::{TraceDirection, TraceHandler};
+use crate::traits::{AtTransport, AtatClient, AtatCmd, CmdMeta, DynClient, UrcParser};
+use crate::v25ter::{Attention, EscapeSequence};
+
+/// Number of `AT` commands sent to resynchronize after a raw transfer
+const RESYNC_ATTEMPTS: usize = 3;
+/// Sent to abort a running command. V.25ter allows any character, `ESC` is
+/// the least likely to be taken for the start of a command line.
+const ABORT_BYTE: &[u8] = b"\x1b";
+/// Max number of pipelined commands, whose responses have not been collected
+type PipelineDepth = consts::U4;
 use crate::{Command, Config, Mode};
 
 #[derive(Debug, PartialEq)]
@@ -11,15 +28,62 @@ enum ClientState {
     AwaitingResponse,
 }
 
+/// Sequence number of a pipelined command, see [`Client::pipeline`].
+///
+/// [`Client::pipeline`]: struct.Client.html#method.pipeline
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct Seq(u8);
+
+/// Compact snapshot of the client bookkeeping, returned by
+/// [`Client::suspend`] and consumed by [`Client::resume`].
+///
+/// This allows systems that power down RAM and peripherals between events to
+/// re-create the client cheaply, without losing track of an in-flight command
+/// or a pending cooldown. The state can be stored in retained memory using
+/// [`to_bytes`](#method.to_bytes) and [`from_bytes`](#method.from_bytes).
+///
+/// As the remaining time of a `CountDown` cannot be queried, pending cooldowns
+/// and response timeouts are restarted in full on resume. A pending cooldown
+/// is restarted with the cooldown of the last command sent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SuspendedState {
+    awaiting_response: bool,
+    cooldown_pending: bool,
+    timeout_ms: u32,
+    /// Cooldown following the response to the last command sent
+    cooldown_ms: u32,
+}
+
+impl SuspendedState {
+    /// Serialize the state into a fixed size byte array.
+    pub fn to_bytes(&self) -> [u8; 9] {
+        let t = self.timeout_ms.to_le_bytes();
+        let c = self.cooldown_ms.to_le_bytes();
+        let flags = self.awaiting_response as u8 | (self.cooldown_pending as u8) << 1;
+        [flags, t[0], t[1], t[2], t[3], c[0], c[1], c[2], c[3]]
+    }
+
+    /// Deserialize a state previously created by [`to_bytes`](#method.to_bytes).
+    pub fn from_bytes(bytes: [u8; 9]) -> Self {
+        SuspendedState {
+            awaiting_response: bytes[0] & 0x01 != 0,
+            cooldown_pending: bytes[0] & 0x02 != 0,
+            timeout_ms: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]),
+            cooldown_ms: u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]),
+        }
+    }
+}
+
 /// Client responsible for handling send, receive and timeout from the
 /// userfacing side. The client is decoupled from the ingress-manager through
 /// some spsc queue consumers, where any received responses can be dequeued. The
 /// Client also has an spsc producer, to allow signaling commands like
 /// 'clearBuffer' to the ingress-manager.
-pub struct Client<Tx, T>
+pub struct Client<Tx, T, C = T>
 where
-    Tx: serial::Write<u8>,
+    Tx: AtTransport,
     T: CountDown,
+    C: CountDown,
 {
     /// Serial writer
     tx: Tx,
@@ -28,54 +92,346 @@ where
     res_c: ResConsumer,
     /// The URC consumer receives URCs from the ingress manager
     urc_c: UrcConsumer,
+    /// Receives the high priority URCs, which are handled first, if set
+    priority_urc_c: Option<PriorityUrcConsumer>,
     /// The command producer can send commands to the ingress manager
     com_p: ComProducer,
+    /// The data consumer receives raw bytes from the ingress manager while
+    /// in data mode
+    data_c: DataConsumer,
 
     state: ClientState,
+    /// Times out the command awaiting a response
     timer: T,
+    /// Enforces the cooldown between a response or URC and the next command
+    cooldown_timer: C,
     config: Config,
+    /// Set when the cooldown has been started, and not yet awaited
+    cooldown_pending: bool,
+    /// Timeout of the command currently awaiting a response
+    timeout_ms: u32,
+    /// Cooldown following the response to the command currently awaiting it
+    response_cooldown: Milliseconds,
+    /// Frames dropped after being received from the ingress manager
+    stats: QueueStats,
+    /// Receives all bytes transmitted
+    trace_handler: Option<TraceHandler>,
+    /// Fill levels of the queues, shared with the ingress manager
+    counters: Option<&'static QueueCounters>,
+    /// Sequence number of the next pipelined command
+    next_seq: u8,
+    /// Pipelined commands awaiting their responses, oldest first, with their
+    /// timeouts
+    in_flight: Queue<(Seq, u32), PipelineDepth, u8>,
+    /// Responses to pipelined commands, that have not been collected yet
+    completed: Vec<(Seq, Result<String<consts::U256>, Error>), PipelineDepth>,
+    /// The error of the transport behind the last `Error::Write`
+    write_error: Option<Tx::Error>,
+    /// Prefix expected of the response awaited, if validated
+    response_prefix: Option<&'static str>,
+    /// Advanced whenever a command is given up on, see
+    /// [`Command::SetGeneration`](enum.Command.html#variant.SetGeneration)
+    generation: u8,
+    /// Whether the command awaiting a response can be aborted
+    abortable: bool,
+    /// Set when the command awaiting a response has been aborted, and its
+    /// result code not yet received
+    aborting: bool,
 }
 
-impl<Tx, T> Client<Tx, T>
+impl<Tx, T, C> Client<Tx, T, C>
 where
-    Tx: serial::Write<u8>,
+    Tx: AtTransport,
     T: CountDown,
-    T::Time: From<u32>,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         tx: Tx,
         res_c: ResConsumer,
         urc_c: UrcConsumer,
         com_p: ComProducer,
+        data_c: DataConsumer,
         timer: T,
+        cooldown_timer: C,
         config: Config,
     ) -> Self {
         Self {
             tx,
             res_c,
             urc_c,
+            priority_urc_c: None,
             com_p,
+            data_c,
             state: ClientState::Idle,
             config,
             timer,
+            cooldown_timer,
+            cooldown_pending: false,
+            timeout_ms: 0,
+            response_cooldown: config.cmd_cooldown,
+            stats: QueueStats::new(),
+            counters: None,
+            trace_handler: None,
+            next_seq: 0,
+            in_flight: Queue::u8(),
+            completed: Vec::new(),
+            write_error: None,
+            response_prefix: None,
+            generation: 0,
+            abortable: false,
+            aborting: false,
         }
     }
-}
 
-impl<Tx, T> AtatClient for Client<Tx, T>
-where
-    Tx: serial::Write<u8>,
-    T: CountDown,
-    T::Time: From<u32>,
-{
-    fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+    /// The minimum time between a response or URC and the next command.
+    pub fn cooldown(&self) -> Milliseconds {
+        self.config.cmd_cooldown
+    }
+
+    /// Change the cooldown, e.g. shortening it for a bulk transfer, or
+    /// lengthening it when the device reports being busy. A cooldown that is
+    /// already running is not affected.
+    pub fn set_cooldown(&mut self, cooldown: Milliseconds) {
+        self.config.cmd_cooldown = cooldown;
+    }
+
+    /// Switch the mode, e.g. from `Mode::Blocking` while initializing the
+    /// device to `Mode::NonBlocking` in steady state. Takes effect with the
+    /// next command sent.
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.config.mode = mode;
+    }
+
+    /// Change the max command length, see
+    /// [`Config::max_cmd_len`](struct.Config.html#method.max_cmd_len).
+    pub fn set_max_cmd_len(&mut self, len: Option<usize>) {
+        self.config.max_cmd_len = len;
+    }
+
+    /// Change the guard time around the escape sequence, e.g. after setting
+    /// `ATS12`.
+    pub fn set_escape_guard_time(&mut self, guard_time: Milliseconds) {
+        self.config.escape_guard_time = guard_time;
+    }
+
+    /// Change the chunking of commands, see
+    /// [`Config::with_tx_chunks`](struct.Config.html#method.with_tx_chunks).
+    pub fn set_tx_chunks(&mut self, chunks: Option<(usize, Milliseconds)>) {
+        self.config.tx_chunks = chunks;
+    }
+
+    /// Enable or disable the validation of response prefixes, see
+    /// [`Config::with_response_validation`](struct.Config.html#method.with_response_validation).
+    pub fn set_response_validation(&mut self, v: bool) {
+        self.config.response_validation = v;
+    }
+
+    /// Tell the ingress manager that echo was switched with `ATE`. Returns
+    /// `nb::Error::WouldBlock` while the queue to the ingress manager is
+    /// full.
+    ///
+    /// The ingress manager relies on the echo to tell responses from URCs, so
+    /// disabling it is rejected with `Error::Unsupported`.
+    pub fn set_echo(&mut self, e: bool) -> nb::Result<(), Error> {
+        if !e {
+            return Err(nb::Error::Other(Error::Unsupported));
+        }
+        self.enqueue_com(Command::SetEcho(e))
+            .map_err(|_| nb::Error::WouldBlock)
+    }
+
+    /// Tell the ingress manager that numeric result codes were switched with
+    /// `ATV`, see [`set_echo`](#method.set_echo).
+    pub fn set_numeric_result_codes(&mut self, n: bool) -> nb::Result<(), Error> {
+        self.enqueue_com(Command::SetNumericResultCodes(n))
+            .map_err(|_| nb::Error::WouldBlock)
+    }
+
+    /// Tell the ingress manager that the line termination character was
+    /// changed with `ATS3`, see [`set_echo`](#method.set_echo).
+    pub fn set_line_term(&mut self, c: u8) -> nb::Result<(), Error> {
+        self.enqueue_com(Command::SetLineTerm(c))
+            .map_err(|_| nb::Error::WouldBlock)
+    }
+
+    /// Tell the ingress manager that the response formatting character was
+    /// changed with `ATS4`, see [`set_echo`](#method.set_echo).
+    pub fn set_format_char(&mut self, c: u8) -> nb::Result<(), Error> {
+        self.enqueue_com(Command::SetFormat(c))
+            .map_err(|_| nb::Error::WouldBlock)
+    }
+
+    /// Frames dropped after being received from the ingress manager, by
+    /// cause. Frames dropped before reaching the client are counted by
+    /// [`IngressManager::stats`](struct.IngressManager.html#method.stats).
+    pub fn stats(&self) -> &QueueStats {
+        &self.stats
+    }
+
+    /// Reset the counters of dropped frames.
+    pub fn clear_stats(&mut self) {
+        self.stats.clear();
+    }
+
+    /// Handle the URCs of `priority_urc_c` before the others, see
+    /// [`Config::with_priority_urcs`](struct.Config.html#method.with_priority_urcs).
+    pub(crate) fn set_priority_urc_queue(&mut self, priority_urc_c: PriorityUrcConsumer) {
+        self.priority_urc_c = Some(priority_urc_c);
+    }
+
+    /// Current and high-watermark fill levels of the queues to and from the
+    /// ingress manager, if they are tracked, i.e. if the client was created by
+    /// [`atat::new`](fn.new.html).
+    pub fn queue_levels(&self) -> Option<QueueLevels> {
+        self.counters.map(QueueCounters::levels)
+    }
+
+    /// Pass all bytes transmitted to the device to `handler`, e.g. to mirror
+    /// them to a log along with the received ones, see
+    /// [`IngressManager::set_trace_handler`](struct.IngressManager.html#method.set_trace_handler).
+    pub fn set_trace_handler(&mut self, handler: TraceHandler) {
+        self.trace_handler = Some(handler);
+    }
+
+    /// Track the fill levels of the queues in `counters`.
+    pub(crate) fn set_queue_counters(&mut self, counters: &'static QueueCounters) {
+        self.counters = Some(counters);
+    }
+
+    fn enqueue_com(&mut self, com: Command) -> Result<(), Command> {
+        self.com_p.enqueue(com)?;
+        if let Some(counters) = self.counters {
+            counters.commands.enqueued();
+        }
+        Ok(())
+    }
+
+    /// Dequeue the next response, discarding the late responses to commands
+    /// given up on.
+    fn dequeue_res(&mut self) -> Option<Result<ResponseGuard, Error>> {
+        loop {
+            let frame = self.res_c.dequeue()?;
+            if let Some(counters) = self.counters {
+                counters.responses.dequeued();
+            }
+            if frame.generation == self.generation {
+                return Some(frame.result);
+            }
+            #[cfg(feature = "logging")]
+            log::warn!("Discarding late response: {:?}", frame.result);
+            self.stats.responses.record(DropCause::Stale);
+        }
+    }
+
+    /// Advance the generation, so that the responses to the commands sent so
+    /// far are discarded, should they still arrive.
+    fn advance_generation(&mut self) {
+        let generation = self.generation.wrapping_add(1);
+        if self.enqueue_com(Command::SetGeneration(generation)).is_ok() {
+            self.generation = generation;
+        } else {
+            // TODO: Consider how to act in this situation.
+            #[cfg(feature = "logging")]
+            log::error!("Failed to signal parser to advance the generation!");
+        }
+    }
+
+    /// Dequeue the next URC, high priority ones first, with the stats of its
+    /// queue.
+    fn dequeue_urc(&mut self) -> Option<(UrcFrame, &mut DropStats)> {
+        let priority_urc = self.priority_urc_c.as_mut().and_then(|c| c.dequeue());
+        let (urc, counter, stats) = match priority_urc {
+            Some(urc) => (
+                urc,
+                self.counters.map(|c| &c.priority_urcs),
+                &mut self.stats.priority_urcs,
+            ),
+            None => (
+                self.urc_c.dequeue()?,
+                self.counters.map(|c| &c.urcs),
+                &mut self.stats.urcs,
+            ),
+        };
+        if let Some(counter) = counter {
+            counter.dequeued();
+        }
+        Some((urc, stats))
+    }
+
+    /// Take the error of the transport behind the last `Error::Write`, to
+    /// tell e.g. a framing error from a hardware fault, and react to it, e.g.
+    /// by reinitializing the UART.
+    ///
+    /// `Error::Write` carries its `Debug` rendering, to clients wrapping this
+    /// one, like [`Worker`](worker/struct.Worker.html).
+    pub fn take_write_error(&mut self) -> Option<Tx::Error> {
+        self.write_error.take()
+    }
+
+    /// Keep the error of a failed write to the transport, returning
+    /// `Error::Write` in its place.
+    fn write_result(&mut self, result: Result<(), Tx::Error>) -> Result<(), Error> {
+        result.map_err(|e| {
+            let error = Error::write(&e);
+            self.write_error = Some(e);
+            error
+        })
+    }
+
+    /// Take a snapshot of the client state, before powering down.
+    pub fn suspend(&self) -> SuspendedState {
+        SuspendedState {
+            awaiting_response: self.state == ClientState::AwaitingResponse,
+            cooldown_pending: self.cooldown_pending,
+            timeout_ms: self.timeout_ms,
+            cooldown_ms: self.response_cooldown.0,
+        }
+    }
+
+    /// Restore a snapshot taken by [`suspend`](#method.suspend) into a newly
+    /// created client.
+    pub fn resume(&mut self, state: SuspendedState) {
+        self.timeout_ms = state.timeout_ms;
+        self.cooldown_pending = state.cooldown_pending;
+        self.response_cooldown = Milliseconds(state.cooldown_ms);
+        if state.awaiting_response {
+            self.state = ClientState::AwaitingResponse;
+            if self.config.mode.has_timeout() {
+                self.timer.start(Milliseconds(self.timeout_ms));
+            }
+        } else {
+            self.state = ClientState::Idle;
+            if self.cooldown_pending {
+                self.cooldown_timer.start(self.response_cooldown);
+            }
+        }
+    }
+
+    /// Transmit `cmd`, unless a command is already awaiting a response.
+    fn transmit<A: AtatCmd>(&mut self, cmd: &A) -> Result<(), Error> {
+        self.transmit_bytes(cmd.try_as_string()?.as_bytes(), &CmdMeta::of(cmd))
+    }
+
+    /// Transmit the serialized command `cmd`, unless a command is already
+    /// awaiting a response.
+    fn transmit_bytes(&mut self, cmd: &[u8], meta: &CmdMeta) -> Result<(), Error> {
         if let ClientState::Idle = self.state {
-            if cmd.force_receive_state()
+            let max_len = match (meta.max_cmd_len, self.config.max_cmd_len) {
+                (Some(a), Some(b)) => Some(core::cmp::min(a, b)),
+                (a, b) => a.or(b),
+            };
+            if let Some(max_len) = max_len {
+                if cmd.len() > max_len {
+                    return Err(Error::CmdTooLong(cmd.len(), max_len));
+                }
+            }
+
+            if meta.force_receive_state
                 && self
-                    .com_p
-                    .enqueue(Command::ForceState(
-                        crate::ingress_manager::State::ReceivingResponse,
-                    ))
+                    .enqueue_com(Command::ForceState(State::ReceivingResponse))
                     .is_err()
             {
                 // TODO: Consider how to act in this situation.
@@ -85,180 +441,1077 @@ where
                 );
             }
 
+            if let Some(len) = meta.max_response_len {
+                if self.enqueue_com(Command::SetMaxResponseLen(len)).is_err() {
+                    // TODO: Consider how to act in this situation.
+                    #[cfg(feature = "logging")]
+                    log::error!("Failed to signal parser to limit the response length!");
+                }
+            }
+
+            if let Some(prefix) = meta.stream_prefix {
+                if self.enqueue_com(Command::StreamPayload(prefix)).is_err() {
+                    // TODO: Consider how to act in this situation.
+                    #[cfg(feature = "logging")]
+                    log::error!("Failed to signal parser to stream the response!");
+                }
+            }
+
+            if let Some(prefix) = meta.payload_prefix {
+                if self.enqueue_com(Command::DelimitPayload(prefix)).is_err() {
+                    // TODO: Consider how to act in this situation.
+                    #[cfg(feature = "logging")]
+                    log::error!("Failed to signal parser to delimit the payload!");
+                }
+            }
+
             // compare the time of the last response or URC and ensure at least
             // `self.config.cmd_cooldown` ms have passed before sending a new
             // command
-            block!(self.timer.wait()).ok();
-            let cmd_string = cmd.as_string();
+            block!(self.cooldown_timer.wait()).ok();
+            self.cooldown_pending = false;
             #[cfg(feature = "logging")]
-            log::debug!("Sending command: {:?}", cmd_string.as_str());
-            for c in cmd_string.as_bytes() {
-                block!(self.tx.write(*c)).map_err(|_e| Error::Write)?;
+            log::debug!("Sending command: {:?}", core::str::from_utf8(cmd));
+            self.write_chunked(cmd)?;
+            self.response_cooldown = meta.cooldown.unwrap_or(self.config.cmd_cooldown);
+            if meta.expects_response {
+                self.state = ClientState::AwaitingResponse;
+                self.abortable = meta.can_abort;
+                self.aborting = false;
+                self.response_prefix = meta.response_prefix;
+            } else {
+                // Nothing to wait for, so the cooldown starts right away
+                self.cooldown_timer.start(self.response_cooldown);
+                self.cooldown_pending = true;
             }
-            block!(self.tx.flush()).map_err(|_e| Error::Write)?;
-            self.state = ClientState::AwaitingResponse;
         }
+        Ok(())
+    }
 
-        match self.config.mode {
-            Mode::Blocking => Ok(block!(self.check_response(cmd))?),
-            Mode::NonBlocking => self.check_response(cmd),
-            Mode::Timeout => {
-                self.timer.start(cmd.max_timeout_ms());
-                Ok(block!(self.check_response(cmd))?)
+    /// Write `bytes`, split into chunks with a delay in between, if configured
+    /// by [`Config::with_tx_chunks`](struct.Config.html#method.with_tx_chunks).
+    fn write_chunked(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let (len, delay) = match self.config.tx_chunks {
+            Some((len, delay)) if len > 0 => (len, delay),
+            _ => (bytes.len().max(1), Milliseconds(0)),
+        };
+        for (i, chunk) in bytes.chunks(len).enumerate() {
+            if i > 0 {
+                let result = self.tx.flush();
+                self.write_result(result)?;
+                self.cooldown_timer.start(delay);
+                block!(self.cooldown_timer.wait()).ok();
+            }
+            if let Some(handler) = self.trace_handler {
+                handler(TraceDirection::Tx, chunk);
             }
+            let result = self.tx.write_all(chunk);
+            self.write_result(result)?;
         }
+        let result = self.tx.flush();
+        self.write_result(result)
     }
 
-    fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
-        if !self.urc_c.ready() {
-            return None;
+    /// Whether `resp` starts with a prefix other than the one expected by the
+    /// command awaiting it, if validated.
+    fn is_stale(&self, resp: &str) -> bool {
+        let expected = match self.response_prefix {
+            Some(prefix) if self.config.response_validation => prefix.trim_end_matches(':'),
+            _ => return false,
+        };
+        let resp = resp.trim_start();
+        match resp.find(':') {
+            Some(colon) if resp.starts_with('+') => resp[..colon].trim_end() != expected,
+            _ => false,
         }
+    }
 
-        self.timer.start(self.config.cmd_cooldown);
-        URC::parse(unsafe { &self.urc_c.dequeue_unchecked() }).ok()
+    /// Give up on the command awaiting a response, and tell the ingress
+    /// manager to clear its buffer.
+    fn cancel_response(&mut self) {
+        self.state = ClientState::Idle;
+        self.aborting = false;
+        // Tell the parser to clear the buffer due to timeout
+        if self.enqueue_com(Command::ClearBuffer).is_err() {
+            // TODO: Consider how to act in this situation.
+            #[cfg(feature = "logging")]
+            log::error!("Failed to signal parser to clear buffer on timeout!");
+        }
+        self.advance_generation();
     }
 
-    fn check_response<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
-        if let Some(result) = self.res_c.dequeue() {
-            return match result {
-                Ok(ref resp) => {
-                    if let ClientState::AwaitingResponse = self.state {
-                        self.timer.start(self.config.cmd_cooldown);
-                        self.state = ClientState::Idle;
-                        Ok(cmd.parse(resp).map_err(nb::Error::Other)?)
-                    } else {
-                        Err(nb::Error::WouldBlock)
-                    }
-                }
-                Err(e) => Err(nb::Error::Other(e)),
-            };
-        } else if let Mode::Timeout = self.config.mode {
+    /// Await the response to `cmd`, transmitted already, for at most its
+    /// `max_timeout`, regardless of the mode.
+    ///
+    /// This keeps the helpers returning to command mode from hanging on a
+    /// device that is not listening.
+    fn await_response<A: AtatCmd>(&mut self, cmd: &A) -> Result<A::Response, Error> {
+        self.timeout_ms = cmd.max_timeout().0;
+        self.timer.start(Milliseconds(self.timeout_ms));
+        loop {
+            match self.check_response(cmd) {
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(e)) => return Err(e),
+                Ok(resp) => return Ok(resp),
+            }
             if self.timer.wait().is_ok() {
-                self.state = ClientState::Idle;
-                // Tell the parser to clear the buffer due to timeout
-                if self.com_p.enqueue(Command::ClearBuffer).is_err() {
-                    // TODO: Consider how to act in this situation.
-                    #[cfg(feature = "logging")]
-                    log::error!("Failed to signal parser to clear buffer on timeout!");
-                }
-                return Err(nb::Error::Other(Error::Timeout));
+                self.cancel_response();
+                self.stats.timeouts = self.stats.timeouts.saturating_add(1);
+                return Err(Error::Timeout);
             }
         }
-        Err(nb::Error::WouldBlock)
     }
 
-    fn get_mode(&self) -> Mode {
-        self.config.mode
+    /// Give up on the command awaiting a response, if any, discarding its
+    /// response should it arrive later. Pipelined commands are given up as
+    /// well.
+    pub fn abort_current(&mut self) {
+        if let ClientState::AwaitingResponse = self.state {
+            self.cancel_response();
+            while self.dequeue_res().is_some() {
+                self.stats.responses.record(DropCause::Stale);
+            }
+            self.cooldown_timer.start(self.config.cmd_cooldown);
+            self.cooldown_pending = true;
+        }
+        while self.in_flight.dequeue().is_some() {}
+        self.completed.clear();
     }
-}
 
-#[cfg(test)]
-#[cfg_attr(tarpaulin, skip)]
-mod test {
-    use super::*;
-    use crate as atat;
-    use crate::atat_derive::{AtatCmd, AtatResp, AtatUrc};
-    use crate::queues;
-    use heapless::{consts, spsc::Queue, String, Vec};
-    use nb;
-    use serde;
-    use serde_repr::{Deserialize_repr, Serialize_repr};
-    use void::Void;
+    /// Abort the command awaiting a response, by transmitting an abort byte,
+    /// as V.25ter allows for long-running commands like `AT+COPS=?`.
+    ///
+    /// The device still terminates the aborted command with a result code
+    /// (e.g. `OK` or `ABORTED`), upon which the pending
+    /// [`check_response`](trait.AtatClient.html#tymethod.check_response)
+    /// returns `Error::Aborted`. Returns whether the command was aborted;
+    /// commands that are not
+    /// [`can_abort`](trait.AtatCmd.html#method.can_abort), and pipelined
+    /// commands, are left running.
+    ///
+    /// Example:
+    /// ```
+    /// client.send(&ScanOperators)?;
+    /// if client.abort()? {
+    ///     assert_eq!(block!(client.check_response(&ScanOperators)), Err(Error::Aborted));
+    /// }
+    /// ```
+    pub fn abort(&mut self) -> Result<bool, Error> {
+        if self.state != ClientState::AwaitingResponse
+            || !self.abortable
+            || !self.in_flight.is_empty()
+        {
+            return Ok(false);
+        }
+        if !self.aborting {
+            #[cfg(feature = "logging")]
+            log::debug!("Aborting command");
+            self.write_chunked(ABORT_BYTE)?;
+            self.aborting = true;
+        }
+        Ok(true)
+    }
 
-    struct CdMock {
-        time: u32,
+    /// Transmit `cmd` without waiting for the responses to the commands
+    /// pipelined before it, returning the sequence number to collect its
+    /// response with [`check_pipelined`](#method.check_pipelined).
+    ///
+    /// The device answers the commands in order, so the responses are matched
+    /// to the commands by their order of arrival. Up to four responses can be
+    /// outstanding; beyond that, or while a command sent through `send` is
+    /// awaiting its response, `WouldBlock` is returned. Likewise, `send` and
+    /// `check_response` return `WouldBlock` until all pipelined commands have
+    /// been answered.
+    ///
+    /// Example:
+    /// ```
+    /// let a = block!(client.pipeline(&SetEcho { enabled: false }))?;
+    /// let b = block!(client.pipeline(&SetVerbose { enabled: true }))?;
+    /// block!(client.check_pipelined(a, &SetEcho { enabled: false }))?;
+    /// block!(client.check_pipelined(b, &SetVerbose { enabled: true }))?;
+    /// ```
+    pub fn pipeline<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<Seq, Error> {
+        self.pipeline_bytes(cmd.try_as_string()?.as_bytes(), &CmdMeta::of(cmd))
     }
 
-    impl CountDown for CdMock {
-        type Time = u32;
-        fn start<T>(&mut self, count: T)
-        where
-            T: Into<Self::Time>,
+    /// Pipeline the serialized command `cmd`, see [`pipeline`](#method.pipeline).
+    fn pipeline_bytes(&mut self, cmd: &[u8], meta: &CmdMeta) -> nb::Result<Seq, Error> {
+        let pipelined = !self.in_flight.is_empty();
+        if usize::from(self.in_flight.len()) + self.completed.len() >= self.completed.capacity()
+            || (self.state == ClientState::AwaitingResponse && !pipelined)
         {
-            self.time = count.into();
+            return Err(nb::Error::WouldBlock);
         }
-        fn wait(&mut self) -> nb::Result<(), Void> {
-            Ok(())
+
+        self.state = ClientState::Idle;
+        let transmitted = self.transmit_bytes(cmd, meta);
+        if pipelined {
+            self.state = ClientState::AwaitingResponse;
+        }
+        transmitted?;
+
+        let seq = Seq(self.next_seq);
+        self.next_seq = self.next_seq.wrapping_add(1);
+        if meta.expects_response {
+            if !pipelined && self.config.mode.has_timeout() {
+                self.timer.start(Milliseconds(meta.timeout_ms));
+            }
+            self.in_flight.enqueue((seq, meta.timeout_ms)).ok();
+        } else {
+            self.completed.push((seq, Ok(String::new()))).ok();
         }
+        Ok(seq)
     }
 
-    struct TxMock {
-        s: String<consts::U64>,
+    /// Collect the response to the pipelined command `cmd`, with the sequence
+    /// number `seq` returned by [`pipeline`](#method.pipeline).
+    ///
+    /// Responses can be collected in any order. If a pipelined command times
+    /// out, the responses to the following commands can no longer be matched
+    /// reliably, so they all fail with `Error::Timeout`. Returns
+    /// `Error::Aborted` if `seq` is not pipelined, e.g. after
+    /// [`abort_current`](#method.abort_current).
+    pub fn check_pipelined<A: AtatCmd>(
+        &mut self,
+        seq: Seq,
+        cmd: &A,
+    ) -> nb::Result<A::Response, Error> {
+        let resp = self.take_pipelined(seq)?;
+        Ok(cmd.parse(&resp)?)
     }
 
-    impl TxMock {
-        fn new(s: String<consts::U64>) -> Self {
-            TxMock { s }
+    /// Take the unparsed response to the pipelined command `seq`, see
+    /// [`check_pipelined`](#method.check_pipelined).
+    fn take_pipelined(&mut self, seq: Seq) -> nb::Result<String<consts::U256>, Error> {
+        self.collect_pipelined();
+        if let Some(i) = self.completed.iter().position(|(s, _)| *s == seq) {
+            return Ok(self.completed.swap_remove(i).1?);
+        }
+        if self.in_flight.iter().any(|(s, _)| *s == seq) {
+            Err(nb::Error::WouldBlock)
+        } else {
+            Err(nb::Error::Other(Error::Aborted))
         }
     }
 
-    impl serial::Write<u8> for TxMock {
-        type Error = ();
+    /// Match the received responses to the pipelined commands.
+    fn collect_pipelined(&mut self) {
+        while let Some(&(seq, _)) = self.in_flight.peek() {
+            match self.dequeue_res() {
+                Some(Err(Error::ModemReset)) => {
+                    // Nothing in flight survives a reset of the device
+                    while let Some((seq, _)) = self.in_flight.dequeue() {
+                        self.completed.push((seq, Err(Error::ModemReset))).ok();
+                    }
+                    self.state = ClientState::Idle;
+                }
+                Some(result) => {
+                    self.in_flight.dequeue();
+                    self.completed.push((seq, result.map(String::from))).ok();
+                    match self.in_flight.peek() {
+                        Some(&(_, timeout_ms)) => {
+                            if self.config.mode.has_timeout() {
+                                self.timer.start(Milliseconds(timeout_ms));
+                            }
+                        }
+                        None => {
+                            self.state = ClientState::Idle;
+                            self.cooldown_timer.start(self.response_cooldown);
+                            self.cooldown_pending = true;
+                        }
+                    }
+                }
+                None => {
+                    if self.config.mode.has_timeout() && self.timer.wait().is_ok() {
+                        while let Some((seq, _)) = self.in_flight.dequeue() {
+                            self.completed.push((seq, Err(Error::Timeout))).ok();
+                            self.stats.timeouts = self.stats.timeouts.saturating_add(1);
+                        }
+                        self.cancel_response();
+                    }
+                    break;
+                }
+            }
+        }
+    }
 
-        fn write(&mut self, c: u8) -> nb::Result<(), Self::Error> {
-            self.s.push(c as char).map_err(nb::Error::Other)
+    /// Synchronize with the device after a cold boot, by sending `AT` every
+    /// `interval` until it is answered, up to `attempts` times.
+    ///
+    /// If `baud_rates` are given, each of them is tried in turn, calling
+    /// `set_baud` to reconfigure the UART before the attempts at that rate.
+    /// Returns the baud rate the device answered at, or `Error::Timeout` once
+    /// all attempts are exhausted.
+    ///
+    /// Example:
+    /// ```
+    /// let baud = client.autobaud(Milliseconds(100), 10, &[115_200, 9600], |baud| {
+    ///     serial.reconfigure(baud.bps())
+    /// })?;
+    /// ```
+    pub fn autobaud<F>(
+        &mut self,
+        interval: Milliseconds,
+        attempts: usize,
+        baud_rates: &[u32],
+        mut set_baud: F,
+    ) -> Result<Option<u32>, Error>
+    where
+        F: FnMut(u32),
+    {
+        if baud_rates.is_empty() && self.synchronize(interval, attempts)? {
+            return Ok(None);
+        }
+        for &rate in baud_rates {
+            #[cfg(feature = "logging")]
+            log::debug!("Trying to synchronize at {} baud", rate);
+            set_baud(rate);
+            if self.synchronize(interval, attempts)? {
+                return Ok(Some(rate));
+            }
         }
+        Err(Error::Timeout)
+    }
 
-        fn flush(&mut self) -> nb::Result<(), Self::Error> {
-            Ok(())
+    /// Send `AT` every `interval` until it is answered, up to `attempts`
+    /// times, returning whether it was answered.
+    fn synchronize(&mut self, interval: Milliseconds, attempts: usize) -> Result<bool, Error> {
+        for _ in 0..attempts {
+            self.transmit(&Attention)?;
+            self.timer.start(interval);
+            loop {
+                match self.check_response(&Attention) {
+                    Ok(_) => return Ok(true),
+                    Err(nb::Error::WouldBlock) if self.timer.wait().is_err() => {}
+                    // Unanswered, or garbled at the wrong baud rate
+                    Err(_) => break,
+                }
+            }
+            self.abort_current();
         }
+        Ok(false)
     }
 
-    #[derive(Clone, AtatCmd)]
-    #[at_cmd("+CFUN", NoResponse, timeout_ms = 180000)]
-    pub struct SetModuleFunctionality {
-        #[at_arg(position = 0)]
-        pub fun: Functionality,
-        #[at_arg(position = 1)]
-        pub rst: Option<ResetMode>,
+    /// Reset the client to its initial state, e.g. to recover from a wedged
+    /// device, discarding all received responses, URCs and data, and telling
+    /// the ingress manager to clear its buffer.
+    pub fn reset(&mut self) {
+        self.state = ClientState::Idle;
+        self.aborting = false;
+        self.cooldown_pending = false;
+        self.timeout_ms = 0;
+        while self.in_flight.dequeue().is_some() {}
+        self.completed.clear();
+        while self.dequeue_res().is_some() {}
+        while self.dequeue_urc().is_some() {}
+        while self.data_c.dequeue().is_some() {}
+        if self.enqueue_com(Command::ClearBuffer).is_err() {
+            // TODO: Consider how to act in this situation.
+            #[cfg(feature = "logging")]
+            log::error!("Failed to signal parser to clear buffer on reset!");
+        }
+        self.advance_generation();
     }
 
-    #[derive(Clone, AtatCmd)]
-    #[at_cmd("+FUN", NoResponse, timeout_ms = 180000)]
-    pub struct Test2Cmd {
-        #[at_arg(position = 1)]
-        pub fun: Functionality,
-        #[at_arg(position = 0)]
-        pub rst: Option<ResetMode>,
+    /// Take over the raw serial streams, after a command was answered with
+    /// `CONNECT` (e.g. `ATD*99#`), to run e.g. PPP or transparent TCP.
+    ///
+    /// The ingress manager switches to data mode on its own upon `CONNECT`,
+    /// passing all received bytes through untouched, until
+    /// [`DataMode::exit`] is called.
+    ///
+    /// Example:
+    /// ```
+    /// let Connect { .. } = client.send(&Dial { number: "*99#" })?;
+    /// let mut data = client.data_mode();
+    /// data.write(b"~\x7d#\xc0!")?;
+    /// let n = data.read(&mut buf);
+    /// data.escape()?;
+    /// ```
+    pub fn data_mode(&mut self) -> DataMode<'_, Tx, T, C> {
+        DataMode { client: self }
     }
-    #[derive(Clone, AtatCmd)]
-    #[at_cmd("+CUN", TestResponseVec, timeout_ms = 180000)]
-    pub struct TestRespVecCmd {
-        #[at_arg(position = 0)]
-        pub fun: Functionality,
-        #[at_arg(position = 1)]
-        pub rst: Option<ResetMode>,
+
+    /// Hand the raw serial streams over to `f`, e.g. an XMODEM/YMODEM
+    /// transfer after `AT+QFOTADL`, and resume command mode afterwards.
+    ///
+    /// AT parsing is paused for the duration of `f`. Once it returns, the
+    /// ingress manager resumes with a clean buffer, and the client
+    /// resynchronizes with the device by sending `AT` until it is answered
+    /// with `OK`, giving up with the last error after a few attempts. Each
+    /// attempt is bounded by the timeout of `AT`, whatever the mode.
+    ///
+    /// Example:
+    /// ```
+    /// client.send(&StartFota { url: "..." })?;
+    /// let result = client.handoff(|data| xmodem::receive(data, &mut flash))?;
+    /// ```
+    pub fn handoff<F, R>(&mut self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut DataMode<'_, Tx, T, C>) -> R,
+    {
+        if self
+            .enqueue_com(Command::ForceState(State::DataMode))
+            .is_err()
+        {
+            // TODO: Consider how to act in this situation.
+            #[cfg(feature = "logging")]
+            log::error!("Failed to signal parser to pause AT parsing!");
+        }
+
+        let mut data = self.data_mode();
+        let result = f(&mut data);
+        data.exit();
+
+        let mut error = Error::Timeout;
+        for _ in 0..RESYNC_ATTEMPTS {
+            match self
+                .transmit(&Attention)
+                .and_then(|_| self.await_response(&Attention))
+            {
+                Ok(_) => return Ok(result),
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
     }
-    #[derive(Clone, AtatCmd)]
-    #[at_cmd("+CUN", TestResponseString, timeout_ms = 180000)]
-    pub struct TestRespStringCmd {
-        #[at_arg(position = 0)]
-        pub fun: Functionality,
-        #[at_arg(position = 1)]
-        pub rst: Option<ResetMode>,
+
+    /// Run `f` with an overall time budget of `budget`, measured by
+    /// `timer`.
+    ///
+    /// Every command sent through the [`Deadline`] handed to `f` respects the
+    /// remaining budget rather than only its own timeout, failing with
+    /// `Error::Timeout` once the budget is exhausted. This allows state
+    /// machines to finish (or fail) a sequence of commands within a hard
+    /// real-time budget.
+    ///
+    /// Example:
+    /// ```
+    /// let result = client.with_deadline(&mut timer, Milliseconds(5000), |c| {
+    ///     c.send(&SetEcho { enabled: false })?;
+    ///     c.send(&GetSignalQuality)
+    /// });
+    /// ```
+    pub fn with_deadline<D, F, R>(&mut self, timer: &mut D, budget: Milliseconds, f: F) -> R
+    where
+        D: CountDown,
+        D::Time: From<Milliseconds>,
+        F: FnOnce(&mut Deadline<'_, Tx, T, C, D>) -> R,
+    {
+        timer.start(budget);
+        f(&mut Deadline {
+            client: self,
+            timer,
+            expired: false,
+        })
     }
-    #[derive(Clone, AtatCmd)]
-    #[at_cmd("+CUN", TestResponseStringMixed, timeout_ms = 180000)]
-    pub struct TestRespStringMixCmd {
-        #[at_arg(position = 1)]
-        pub fun: Functionality,
-        #[at_arg(position = 0)]
-        pub rst: Option<ResetMode>,
+
+    /// Read the payload of streamed responses received so far into `buf`,
+    /// returning the number of bytes read.
+    ///
+    /// Unless the ingress manager passes them to a stream sink, the payloads
+    /// of commands with a
+    /// [`stream_prefix`](trait.AtatCmd.html#method.stream_prefix) are
+    /// queued here, rather than in their response.
+    pub fn read_stream(&mut self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        while len < buf.len() {
+            match self.data_c.dequeue() {
+                Some(byte) => {
+                    buf[len] = byte;
+                    len += 1;
+                }
+                None => break,
+            }
+        }
+        len
     }
+}
 
-    #[derive(Clone, PartialEq, Serialize_repr, Deserialize_repr)]
-    #[repr(u8)]
-    pub enum Functionality {
-        Min = 0,
-        Full = 1,
-        APM = 4,
-        DM = 6,
+impl<Tx, T, C> DynClient for Client<Tx, T, C>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
+{
+    fn send_bytes(&mut self, cmd: &[u8], meta: &CmdMeta) -> nb::Result<ResponseGuard, Error> {
+        if !self.in_flight.is_empty() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.transmit_bytes(cmd, meta)?;
+        if !meta.expects_response && self.state == ClientState::Idle {
+            return Ok(ResponseGuard::empty());
+        }
+
+        match self.config.mode {
+            Mode::Blocking => Ok(block!(self.check_response_bytes())?),
+            Mode::NonBlocking => self.check_response_bytes(),
+            Mode::Timeout => {
+                self.timeout_ms = meta.timeout_ms;
+                self.timer.start(Milliseconds(self.timeout_ms));
+                Ok(block!(self.check_response_bytes())?)
+            }
+            Mode::NonBlockingTimeout => {
+                self.timeout_ms = meta.timeout_ms;
+                self.timer.start(Milliseconds(self.timeout_ms));
+                self.check_response_bytes()
+            }
+        }
     }
-    #[derive(Clone, PartialEq, Serialize_repr, Deserialize_repr)]
-    #[repr(u8)]
-    pub enum ResetMode {
-        DontReset = 0,
-        Reset = 1,
+
+    fn check_response_bytes(&mut self) -> nb::Result<ResponseGuard, Error> {
+        if !self.in_flight.is_empty() {
+            return Err(nb::Error::WouldBlock);
+        }
+        if let Some(result) = self.dequeue_res() {
+            return match result {
+                Ok(resp) if self.is_stale(&resp) => {
+                    #[cfg(feature = "logging")]
+                    log::warn!("Discarding stale response: {:?}", resp);
+                    self.stats.responses.record(DropCause::Stale);
+                    Err(nb::Error::WouldBlock)
+                }
+                Ok(resp) => {
+                    if let ClientState::AwaitingResponse = self.state {
+                        self.cooldown_timer.start(self.response_cooldown);
+                        self.cooldown_pending = true;
+                        self.state = ClientState::Idle;
+                        if core::mem::replace(&mut self.aborting, false) {
+                            return Err(nb::Error::Other(Error::Aborted));
+                        }
+                        Ok(resp)
+                    } else {
+                        self.stats.responses.record(DropCause::Stale);
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+                Err(Error::ModemReset) => {
+                    // Nothing in flight survives a reset of the device
+                    self.state = ClientState::Idle;
+                    self.aborting = false;
+                    Err(nb::Error::Other(Error::ModemReset))
+                }
+                Err(_) if self.aborting => {
+                    // The result code of the aborted command
+                    self.cooldown_timer.start(self.response_cooldown);
+                    self.cooldown_pending = true;
+                    self.state = ClientState::Idle;
+                    self.aborting = false;
+                    Err(nb::Error::Other(Error::Aborted))
+                }
+                Err(e) => Err(nb::Error::Other(e)),
+            };
+        } else if self.config.mode.has_timeout() && self.timer.wait().is_ok() {
+            self.cancel_response();
+            self.stats.timeouts = self.stats.timeouts.saturating_add(1);
+            return Err(nb::Error::Other(Error::Timeout));
+        }
+        Err(nb::Error::WouldBlock)
+    }
+
+    fn check_urc_bytes(&mut self, parse: &mut UrcParser<'_>) -> bool {
+        let (urc, stats) = match self.dequeue_urc() {
+            Some(urc) => urc,
+            None => return false,
+        };
+        match parse(&urc.line, urc.truncated_len) {
+            Ok(()) => {}
+            Err(Error::Overflow) => stats.record(DropCause::Overflow),
+            Err(_) => stats.record(DropCause::ParseFailed),
+        }
+
+        self.cooldown_timer.start(self.config.cmd_cooldown);
+        self.cooldown_pending = true;
+        true
+    }
+
+    fn peek_urc_bytes(&mut self, parse: &mut UrcParser<'_>) -> bool {
+        let priority_urc = self.priority_urc_c.as_ref().and_then(|c| c.peek());
+        let urc = match priority_urc {
+            Some(urc) => Some(urc),
+            None => self.urc_c.peek(),
+        };
+        match urc {
+            Some(urc) => {
+                parse(&urc.line, urc.truncated_len).ok();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn send_raw_bytes(&mut self, data: &[u8]) -> nb::Result<(), Error> {
+        if self.state == ClientState::AwaitingResponse || !self.in_flight.is_empty() {
+            return Err(nb::Error::WouldBlock);
+        }
+        block!(self.cooldown_timer.wait()).ok();
+        self.cooldown_pending = false;
+        #[cfg(feature = "logging")]
+        log::debug!("Sending {} raw bytes", data.len());
+        if self.config.payload_echo && self.enqueue_com(Command::SkipEcho(data.len())).is_err() {
+            // TODO: Consider how to act in this situation.
+            #[cfg(feature = "logging")]
+            log::error!("Failed to signal parser to skip the echo of the payload!");
+        }
+        Ok(self.write_chunked(data)?)
+    }
+
+    fn mode(&self) -> Mode {
+        self.config.mode
+    }
+}
+
+/// Handle to the raw serial streams while the device is in data mode,
+/// created by [`Client::data_mode`].
+pub struct DataMode<'a, Tx, T, C>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    C: CountDown,
+{
+    client: &'a mut Client<Tx, T, C>,
+}
+
+impl<'a, Tx, T, C> DataMode<'a, Tx, T, C>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
+{
+    /// Write raw bytes to the device.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        if let Some(handler) = self.client.trace_handler {
+            handler(TraceDirection::Tx, data);
+        }
+        let result = self.client.tx.write_all(data);
+        self.client.write_result(result)
+    }
+
+    /// Flush the underlying transport.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let result = self.client.tx.flush();
+        self.client.write_result(result)
+    }
+
+    /// Read the raw bytes received so far into `buf`, returning the number
+    /// of bytes read.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        self.client.read_stream(buf)
+    }
+
+    /// Split the raw serial streams into halves, that can be handed to e.g. a
+    /// PPP stack, receiving and transmitting independently.
+    ///
+    /// Once the halves are dropped, command mode is resumed by
+    /// [`exit`](#method.exit) if the call dropped, as reported by
+    /// [`DataRx::carrier_lost`], or by [`escape`](#method.escape) otherwise.
+    ///
+    /// Example:
+    /// ```
+    /// let Connect { .. } = client.send(&Dial { number: "*99#" })?;
+    /// let mut data = client.data_mode();
+    /// let (mut rx, mut tx) = data.split();
+    /// while !rx.carrier_lost() {
+    ///     ppp.poll(&mut rx, &mut tx);
+    /// }
+    /// data.exit();
+    /// ```
+    pub fn split(&mut self) -> (DataRx<'_>, DataTx<'_, Tx>) {
+        let client = &mut *self.client;
+        (
+            DataRx {
+                data_c: &mut client.data_c,
+                matched: 0,
+                carrier_lost: false,
+            },
+            DataTx {
+                tx: &mut client.tx,
+                write_error: &mut client.write_error,
+                trace_handler: client.trace_handler,
+            },
+        )
+    }
+
+    /// Return to command mode using the `+++` escape sequence, surrounded by
+    /// the configured guard times of silence, and await the `OK`.
+    ///
+    /// If the device does not confirm, the ingress manager is switched back
+    /// to data mode and the error is returned, so data mode can be resumed
+    /// through [`Client::data_mode`].
+    pub fn escape(self) -> Result<(), Error> {
+        let client = self.client;
+        let cmd = EscapeSequence;
+
+        // The cooldown awaited by `transmit` is the guard time before
+        client.cooldown_timer.start(client.config.escape_guard_time);
+        client.cooldown_pending = true;
+        client.transmit(&cmd)?;
+        client.timer.start(client.config.escape_guard_time);
+        block!(client.timer.wait()).ok();
+
+        match client.await_response(&cmd) {
+            Ok(_) => {
+                while client.data_c.dequeue().is_some() {}
+                Ok(())
+            }
+            Err(e) => {
+                client.state = ClientState::Idle;
+                if client
+                    .enqueue_com(Command::ForceState(State::DataMode))
+                    .is_err()
+                {
+                    // TODO: Consider how to act in this situation.
+                    #[cfg(feature = "logging")]
+                    log::error!("Failed to signal parser to resume data mode!");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Return to command mode, once the device has left data mode by other
+    /// means than [`escape`](#method.escape), e.g. by toggling DTR.
+    ///
+    /// Any data that has not been read is discarded, and the ingress manager
+    /// resumes parsing with a clean buffer.
+    pub fn exit(self) {
+        if self.client.enqueue_com(Command::ClearBuffer).is_err() {
+            // TODO: Consider how to act in this situation.
+            #[cfg(feature = "logging")]
+            log::error!("Failed to signal parser to leave data mode!");
+        }
+        while self.client.data_c.dequeue().is_some() {}
+        self.client.state = ClientState::Idle;
+        self.client
+            .cooldown_timer
+            .start(self.client.config.cmd_cooldown);
+        self.client.cooldown_pending = true;
+    }
+}
+
+/// Receiving half of the raw serial streams, created by [`DataMode::split`].
+pub struct DataRx<'a> {
+    data_c: &'a mut DataConsumer,
+    /// Number of bytes of `NO CARRIER` matched at the end of the data read
+    matched: usize,
+    carrier_lost: bool,
+}
+
+impl<'a> DataRx<'a> {
+    /// Read the raw bytes received so far into `buf`, returning the number
+    /// of bytes read.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        const NO_CARRIER: &[u8] = b"NO CARRIER";
+        let mut len = 0;
+        while len < buf.len() {
+            match self.data_c.dequeue() {
+                Some(byte) => {
+                    buf[len] = byte;
+                    len += 1;
+                    if byte == NO_CARRIER[self.matched] {
+                        self.matched += 1;
+                        if self.matched == NO_CARRIER.len() {
+                            self.carrier_lost = true;
+                            self.matched = 0;
+                        }
+                    } else {
+                        self.matched = if byte == NO_CARRIER[0] { 1 } else { 0 };
+                    }
+                }
+                None => break,
+            }
+        }
+        len
+    }
+
+    /// Whether the device reported the call dropping with `NO CARRIER`,
+    /// having returned to command mode.
+    pub fn carrier_lost(&self) -> bool {
+        self.carrier_lost
+    }
+}
+
+/// Transmitting half of the raw serial streams, created by
+/// [`DataMode::split`].
+pub struct DataTx<'a, Tx: AtTransport> {
+    tx: &'a mut Tx,
+    write_error: &'a mut Option<Tx::Error>,
+    trace_handler: Option<TraceHandler>,
+}
+
+impl<'a, Tx: AtTransport> DataTx<'a, Tx> {
+    /// Write raw bytes to the device.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        if let Some(handler) = self.trace_handler {
+            handler(TraceDirection::Tx, data);
+        }
+        let result = self.tx.write_all(data);
+        self.write_result(result)
+    }
+
+    /// Flush the underlying transport.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let result = self.tx.flush();
+        self.write_result(result)
+    }
+
+    fn write_result(&mut self, result: Result<(), Tx::Error>) -> Result<(), Error> {
+        result.map_err(|e| {
+            let error = Error::write(&e);
+            *self.write_error = Some(e);
+            error
+        })
+    }
+}
+
+/// Client handle enforcing an overall time budget, created by
+/// [`Client::with_deadline`].
+pub struct Deadline<'a, Tx, T, C, D>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    C: CountDown,
+    D: CountDown,
+{
+    client: &'a mut Client<Tx, T, C>,
+    timer: &'a mut D,
+    expired: bool,
+}
+
+impl<'a, Tx, T, C, D> Deadline<'a, Tx, T, C, D>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
+    D: CountDown,
+{
+    fn is_expired(&mut self) -> bool {
+        if !self.expired && self.timer.wait().is_ok() {
+            self.expired = true;
+        }
+        self.expired
+    }
+}
+
+impl<'a, Tx, T, C, D> DynClient for Deadline<'a, Tx, T, C, D>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
+    D: CountDown,
+{
+    fn send_bytes(&mut self, cmd: &[u8], meta: &CmdMeta) -> nb::Result<ResponseGuard, Error> {
+        if !self.client.in_flight.is_empty() {
+            return Err(nb::Error::WouldBlock);
+        }
+        if self.client.state == ClientState::Idle && self.is_expired() {
+            return Err(nb::Error::Other(Error::Timeout));
+        }
+        self.client.transmit_bytes(cmd, meta)?;
+        if !meta.expects_response && self.client.state == ClientState::Idle {
+            return Ok(ResponseGuard::empty());
+        }
+
+        match self.client.config.mode {
+            Mode::Blocking => Ok(block!(self.check_response_bytes())?),
+            Mode::NonBlocking => self.check_response_bytes(),
+            Mode::Timeout => {
+                self.client.timeout_ms = meta.timeout_ms;
+                self.client
+                    .timer
+                    .start(Milliseconds(self.client.timeout_ms));
+                Ok(block!(self.check_response_bytes())?)
+            }
+            Mode::NonBlockingTimeout => {
+                self.client.timeout_ms = meta.timeout_ms;
+                self.client
+                    .timer
+                    .start(Milliseconds(self.client.timeout_ms));
+                self.check_response_bytes()
+            }
+        }
+    }
+
+    fn check_response_bytes(&mut self) -> nb::Result<ResponseGuard, Error> {
+        match self.client.check_response_bytes() {
+            Err(nb::Error::WouldBlock)
+                if self.client.state == ClientState::AwaitingResponse && self.is_expired() =>
+            {
+                self.client.cancel_response();
+                self.client.stats.timeouts = self.client.stats.timeouts.saturating_add(1);
+                Err(nb::Error::Other(Error::Timeout))
+            }
+            r => r,
+        }
+    }
+
+    fn check_urc_bytes(&mut self, parse: &mut UrcParser<'_>) -> bool {
+        self.client.check_urc_bytes(parse)
+    }
+
+    fn peek_urc_bytes(&mut self, parse: &mut UrcParser<'_>) -> bool {
+        self.client.peek_urc_bytes(parse)
+    }
+
+    fn send_raw_bytes(&mut self, data: &[u8]) -> nb::Result<(), Error> {
+        if self.client.state == ClientState::Idle && self.is_expired() {
+            return Err(nb::Error::Other(Error::Timeout));
+        }
+        self.client.send_raw_bytes(data)
+    }
+
+    fn mode(&self) -> Mode {
+        self.client.mode()
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod test {
+    use super::*;
+    use crate as atat;
+    use crate::atat_derive::{AtatCmd, AtatEnum, AtatLen, AtatResp, AtatUrc};
+    use crate::error::ErrorContext;
+    use crate::queues;
+    use crate::queues::StaticQueue;
+    use crate::testing::VirtualClock;
+    use crate::traits::{AtatUrc as _, ParseBorrowed};
+    use crate::v25ter::Connect;
+    use crate::{KeyValues, Truncatable};
+    use embedded_hal::serial;
+    use heapless::{consts, String, Vec};
+    use nb;
+    use serde;
+    use serde_repr::{Deserialize_repr, Serialize_repr};
+    use void::Void;
+
+    struct CdMock {
+        time: u32,
+    }
+
+    impl CountDown for CdMock {
+        type Time = u32;
+        fn start<T>(&mut self, count: T)
+        where
+            T: Into<Self::Time>,
+        {
+            self.time = count.into();
+        }
+        fn wait(&mut self) -> nb::Result<(), Void> {
+            Ok(())
+        }
+    }
+
+    struct TxMock {
+        s: String<consts::U64>,
+    }
+
+    impl TxMock {
+        fn new(s: String<consts::U64>) -> Self {
+            TxMock { s }
+        }
+    }
+
+    impl serial::Write<u8> for TxMock {
+        type Error = ();
+
+        fn write(&mut self, c: u8) -> nb::Result<(), Self::Error> {
+            self.s.push(c as char).map_err(nb::Error::Other)
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CFUN", NoResponse, timeout_ms = 180000, catalog = true)]
+    pub struct SetModuleFunctionality {
+        #[at_arg(position = 0)]
+        pub fun: Functionality,
+        #[at_arg(position = 1)]
+        pub rst: Option<ResetMode>,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+FUN", NoResponse, timeout_ms = 180000)]
+    pub struct Test2Cmd {
+        #[at_arg(position = 1)]
+        pub fun: Functionality,
+        #[at_arg(position = 0)]
+        pub rst: Option<ResetMode>,
+    }
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CUN", TestResponseVec, timeout_ms = 180000)]
+    pub struct TestRespVecCmd {
+        #[at_arg(position = 0)]
+        pub fun: Functionality,
+        #[at_arg(position = 1)]
+        pub rst: Option<ResetMode>,
+    }
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CUN", TestResponseString, timeout_ms = 180000)]
+    pub struct TestRespStringCmd {
+        #[at_arg(position = 0)]
+        pub fun: Functionality,
+        #[at_arg(position = 1)]
+        pub rst: Option<ResetMode>,
+    }
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CUN", TestResponseStringMixed, timeout_ms = 180000)]
+    pub struct TestRespStringMixCmd {
+        #[at_arg(position = 1)]
+        pub fun: Functionality,
+        #[at_arg(position = 0)]
+        pub rst: Option<ResetMode>,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+USOCR", TestResponseProtocol)]
+    pub struct TestProtocolCmd {
+        #[at_arg(position = 0)]
+        pub protocol: Protocol,
+        #[at_arg(position = 1)]
+        pub mode: SocketMode,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatEnum)]
+    #[at_enum(rename_all = "UPPERCASE")]
+    pub enum Protocol {
+        Tcp,
+        Udp,
+        #[at_arg(rename = "TLS1.2")]
+        Tls,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatEnum)]
+    #[at_enum(rename_all = "SCREAMING_SNAKE_CASE")]
+    pub enum SocketMode {
+        NonBlocking,
+        Blocking,
+    }
+
+    #[derive(Clone, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+    #[repr(u8)]
+    pub enum Functionality {
+        Min = 0,
+        Full = 1,
+        APM = 4,
+        DM = 6,
+    }
+    #[derive(Clone, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+    #[repr(u8)]
+    pub enum ResetMode {
+        DontReset = 0,
+        Reset = 1,
     }
     #[derive(Clone, AtatResp, PartialEq, Debug)]
     pub struct NoResponse;
@@ -272,248 +1525,2099 @@ mod test {
         pub data: Vec<u8, consts::U256>,
     }
 
-    #[derive(Clone, AtatResp, PartialEq, Debug)]
-    pub struct TestResponseString {
-        #[at_arg(position = 0)]
-        pub socket: u8,
-        #[at_arg(position = 1)]
-        pub length: usize,
-        #[at_arg(position = 2)]
-        pub data: String<consts::U64>,
-    }
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct TestResponseString {
+        #[at_arg(position = 0)]
+        pub socket: u8,
+        #[at_arg(position = 1)]
+        pub length: usize,
+        #[at_arg(position = 2)]
+        pub data: String<consts::U64>,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct TestResponseStringMixed {
+        #[at_arg(position = 1)]
+        pub socket: u8,
+        #[at_arg(position = 2)]
+        pub length: usize,
+        #[at_arg(position = 0)]
+        pub data: String<consts::U64>,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct TestResponseProtocol {
+        #[at_arg(position = 0)]
+        pub protocol: Protocol,
+        #[at_arg(position = 1)]
+        pub fallback: Protocol,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("I", TextResponse, parse_raw = true)]
+    pub struct GetModel;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("I", KeyValues<consts::U4, consts::U16, consts::U32>, parse_text = true)]
+    pub struct GetInfo;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CGSN", SerialNumber, resp_prefix = "+CGSN")]
+    pub struct GetSerialNumber;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CPBR", Option<PhonebookEntry>)]
+    pub struct ReadPhonebook {
+        #[at_arg(position = 0)]
+        pub index: u8,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct PhonebookEntry {
+        #[at_arg(position = 0)]
+        pub index: u8,
+        #[at_arg(position = 1)]
+        pub number: String<consts::U32>,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct SerialNumber {
+        #[at_arg(position = 0)]
+        pub imei: u64,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct TextResponse {
+        #[at_arg(position = 0)]
+        pub text: String<consts::U32>,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd(
+        "",
+        NoResponse,
+        cmd_prefix = "",
+        value_sep = false,
+        termination = "\x1a"
+    )]
+    pub struct SmsBody<'a> {
+        #[at_arg(position = 0, len = 160)]
+        pub text: serde_at::ser::Bytes<'a>,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("", NoResponse, cmd_prefix = "", termination = "\x1b")]
+    pub struct AbortSms;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("D*99***", NoResponse, value_sep = false, termination = "#\r\n")]
+    pub struct DialContext {
+        #[at_arg(position = 0)]
+        pub cid: u8,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("SGACT", NoResponse, cmd_prefix = "AT#")]
+    pub struct ActivateContext {
+        #[at_arg(position = 0)]
+        pub cid: u8,
+        #[at_arg(position = 1)]
+        pub stat: u8,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("", NoResponse, termination = "\r")]
+    pub struct Probe;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+COPS=?", NoResponse, timeout_ms = 180000, abortable = true)]
+    pub struct ScanOperators;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CSGT", NoResponse, max_cmd_len = 24)]
+    pub struct SetGreetingText<'a> {
+        #[at_arg(position = 0, len = 32)]
+        pub text: &'a str,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("O", NoResponse, expects_response = false)]
+    pub struct EnterDataMode;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CGATT=1", NoResponse, cooldown_ms = 500)]
+    pub struct Attach;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CSQ", NoResponse, cooldown_ms = 0)]
+    pub struct SignalQuality;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+USOWR", NoResponse)]
+    pub struct WriteSocketData<N: heapless::ArrayLength<u8>> {
+        #[at_arg(position = 0)]
+        pub socket: u8,
+        #[at_arg(position = 1)]
+        pub data: String<N>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct ReadSocketData<N: heapless::ArrayLength<u8>> {
+        #[at_arg(position = 0)]
+        pub socket: u8,
+        #[at_arg(position = 1)]
+        pub data: String<N>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct BorrowedText<'a> {
+        #[at_arg(position = 0)]
+        pub text: &'a str,
+    }
+
+    /// Time of the real-time clock, formatted as `"yy/MM/dd,hh:mm:ss+zz"`
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Clock {
+        pub year: u8,
+        pub month: u8,
+        pub day: u8,
+        pub hour: u8,
+        pub minute: u8,
+        pub second: u8,
+        /// Time zone, in quarters of an hour
+        pub zone: i8,
+    }
+
+    fn serialize_clock<S: serde::Serializer>(
+        clock: &Clock,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use core::fmt::Write;
+        let mut s = String::<consts::U20>::new();
+        write!(
+            s,
+            "{:02}/{:02}/{:02},{:02}:{:02}:{:02}{:+03}",
+            clock.year, clock.month, clock.day, clock.hour, clock.minute, clock.second, clock.zone
+        )
+        .map_err(|_| serde::ser::Error::custom("clock too long"))?;
+        serializer.serialize_str(&s)
+    }
+
+    fn deserialize_clock<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Clock, D::Error> {
+        let s = <&str as serde::Deserialize>::deserialize(deserializer)?;
+        fn num<T: core::str::FromStr, E: serde::de::Error>(
+            s: &str,
+            range: core::ops::Range<usize>,
+        ) -> Result<T, E> {
+            s.get(range)
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| E::custom("invalid clock"))
+        }
+        Ok(Clock {
+            year: num(s, 0..2)?,
+            month: num(s, 3..5)?,
+            day: num(s, 6..8)?,
+            hour: num(s, 9..11)?,
+            minute: num(s, 12..14)?,
+            second: num(s, 15..17)?,
+            zone: num(s, 17..20)?,
+        })
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CCLK", NoResponse)]
+    pub struct SetClock {
+        #[at_arg(position = 0, serialize_with = "serialize_clock", len = 22)]
+        pub clock: Clock,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct ClockResponse {
+        #[at_arg(position = 0, deserialize_with = "deserialize_clock")]
+        pub clock: Clock,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+UDCONF", NoResponse)]
+    #[at_arg(value = 1, position = 0)]
+    pub struct SetHexMode {
+        #[at_arg(position = 1)]
+        pub hex_mode: u8,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+COPS", NoResponse)]
+    #[at_arg(skip, position = 1)]
+    pub struct SelectOperator<'a> {
+        #[at_arg(position = 0)]
+        pub mode: u8,
+        #[at_arg(position = 2, len = 16)]
+        pub operator: &'a str,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+XFLAG", NoResponse)]
+    pub struct SetFlags {
+        #[at_arg(position = 0, format = "0/1")]
+        pub enable: bool,
+        #[at_arg(position = 1, format = "ON/OFF")]
+        pub led: bool,
+        #[at_arg(position = 2, format = "presence")]
+        pub verbose: bool,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct Flags {
+        #[at_arg(position = 0, format = "0/1")]
+        pub enable: bool,
+        #[at_arg(position = 1, format = "ON/OFF")]
+        pub led: bool,
+        #[at_arg(position = 2, format = "presence")]
+        pub verbose: bool,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct Location {
+        #[at_arg(position = 0)]
+        pub lat: f32,
+        #[at_arg(position = 1)]
+        pub lon: f32,
+        #[at_arg(position = 2)]
+        pub alt: u16,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct GnssFix {
+        #[at_arg(position = 0)]
+        pub time: u32,
+        #[at_arg(position = 1, flatten)]
+        pub location: Location,
+        #[at_arg(position = 2)]
+        pub satellites: Option<u8>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub enum SimState {
+        #[at_arg(rename = "READY")]
+        Ready,
+        #[at_arg(rename = "SIM PIN")]
+        SimPin,
+        #[at_arg(rename = "SIM PUK")]
+        SimPuk,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CPIN?", SimState)]
+    pub struct GetSimState;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+USORD", SocketBytes, payload = "+USORD:")]
+    pub struct ReadSocket {
+        #[at_arg(position = 0)]
+        pub socket: u8,
+        #[at_arg(position = 1)]
+        pub length: usize,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct SocketBytes {
+        #[at_arg(position = 0)]
+        pub socket: u8,
+        #[at_arg(position = 1)]
+        pub length: usize,
+        #[at_arg(position = 2)]
+        pub data: String<consts::U16>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    #[at_enum(rename_all = "UPPERCASE")]
+    pub enum RadioState {
+        Idle,
+        Connected,
+    }
+
+    /// Status, long and short name, numeric code and access technology
+    pub type OperatorInfo = (
+        u8,
+        String<consts::U16>,
+        String<consts::U8>,
+        String<consts::U6>,
+        u8,
+    );
+
+    #[derive(Clone, Debug, PartialEq, AtatResp)]
+    pub struct OperatorList<'a> {
+        #[at_arg(position = 0)]
+        pub operators: Vec<OperatorInfo, consts::U4>,
+        #[at_arg(position = 1)]
+        pub modes: Option<&'a [u8]>,
+        #[at_arg(position = 2)]
+        pub formats: Option<&'a [u8]>,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+UPSD", NoResponse)]
+    pub enum SetPacketSwitchedConfig<'a> {
+        #[at_arg(value = 1, position = 1)]
+        Apn {
+            #[at_arg(position = 0)]
+            profile_id: u8,
+            #[at_arg(position = 2, len = 16)]
+            apn: &'a str,
+        },
+        #[at_arg(value = 7, position = 1)]
+        IpAddress {
+            #[at_arg(position = 0)]
+            profile_id: u8,
+            #[at_arg(position = 2, len = 15)]
+            ip: &'a str,
+        },
+        #[at_arg(value = 0)]
+        Protocol {
+            #[at_arg(position = 1)]
+            protocol: u8,
+        },
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("D*99#", Connect)]
+    pub struct DialPacketData;
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CREG?", RegistrationStatus)]
+    pub struct GetRegistrationStatus;
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct RegistrationStatus {
+        #[at_arg(position = 0)]
+        pub n: u8,
+        #[at_arg(position = 1)]
+        pub stat: u8,
+        #[at_arg(position = 2)]
+        pub lac: Option<String<consts::U4>>,
+        #[at_arg(position = 3)]
+        pub ci: Option<String<consts::U8>>,
+        #[at_arg(position = 4)]
+        pub act: Option<u8>,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+TEXT", EncodedText)]
+    pub struct SendEncodedText<'a> {
+        #[at_arg(position = 0, encoding = "ucs2-hex")]
+        pub text: String<consts::U8>,
+        #[at_arg(position = 1, encoding = "gsm7-hex", len = 8)]
+        pub ussd: &'a str,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct EncodedText {
+        #[at_arg(position = 0, encoding = "gsm7-hex")]
+        pub text: String<consts::U16>,
+        #[at_arg(position = 1)]
+        pub dcs: u8,
+    }
+
+    #[derive(Clone, AtatResp)]
+    pub struct MessageWaitingIndication {
+        #[at_arg(position = 0)]
+        pub status: u8,
+        #[at_arg(position = 1)]
+        pub code: u8,
+    }
+
+    #[derive(Clone, Debug, PartialEq, AtatEnum)]
+    #[at_enum(rename_all = "UPPERCASE")]
+    pub enum RingType {
+        Voice,
+        Fax,
+    }
+
+    #[derive(Clone, AtatUrc)]
+    pub enum Urc {
+        #[at_urc("+UMWI")]
+        MessageWaitingIndication(MessageWaitingIndication),
+        #[at_urc("+CRING", whole_line = true)]
+        Ring(RingType),
+        #[at_urc("#SHDN", whole_line = true)]
+        Shutdown(u8),
+        #[at_urc("+UUSORD", truncatable = true)]
+        SocketData(Truncatable<SocketData>),
+        #[at_urc("RING")]
+        IncomingCall,
+        #[at_urc("NORMAL POWER DOWN")]
+        PowerDown,
+        #[at_urc("+QIURC", discriminator = "recv")]
+        SocketReceive(SocketEvent),
+        #[at_urc("+QIURC", discriminator = "closed")]
+        SocketClosed(SocketEvent),
+        #[at_urc("+QIURC", discriminator = "pdpdeact")]
+        PdpDeactivated,
+        #[at_urc("+QIND", ci, discriminator = "csq")]
+        SignalQualityReport(SocketEvent),
+        #[at_urc("+UUPSDD", ci)]
+        PacketSwitchedDeactivated(SocketEvent),
+        #[at_urc("^SYSSTART", ci, whole_line)]
+        SystemStart(RingType),
+        #[at_urc("SMS READY", ci)]
+        SmsReady,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct SocketEvent {
+        #[at_arg(position = 0)]
+        pub socket: u8,
+    }
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct SocketData {
+        #[at_arg(position = 0)]
+        pub socket: u8,
+        #[at_arg(position = 1)]
+        pub len: usize,
+        #[at_arg(position = 2)]
+        pub data: Option<String<consts::U64>>,
+    }
+
+    macro_rules! setup {
+        ($config:expr) => {
+            setup!($config, CdMock { time: 0 }, CdMock { time: 0 })
+        };
+        ($config:expr, $timer:expr, $cooldown_timer:expr) => {{
+            static mut RES_Q: queues::ResQueue = queues::ResQueue::new();
+            let (res_p, res_c) = unsafe { RES_Q.split() };
+            static mut URC_Q: queues::UrcQueue = queues::UrcQueue::NEW;
+            let (urc_p, urc_c) = unsafe { URC_Q.split() };
+            static mut COM_Q: queues::ComQueue = queues::ComQueue::NEW;
+            let (com_p, com_c) = unsafe { COM_Q.split() };
+            static mut DATA_Q: queues::DataQueue = queues::DataQueue::NEW;
+            let (_data_p, data_c) = unsafe { DATA_Q.split() };
+
+            let tx_mock = TxMock::new(String::new());
+            let client: Client<TxMock, _> = Client::new(
+                tx_mock,
+                res_c,
+                urc_c,
+                com_p,
+                data_c,
+                $timer,
+                $cooldown_timer,
+                $config,
+            );
+            (client, queues::ResMock::new(res_p, com_c), urc_p)
+        }};
+    }
+
+    #[test]
+    fn string_sent() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.send(&cmd), Ok(NoResponse));
+        assert_eq!(client.state, ClientState::Idle);
+
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U32>::from("AT+CFUN=4,0\r\n"),
+            "Wrong encoding of string"
+        );
+
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+
+        let cmd = Test2Cmd {
+            fun: Functionality::DM,
+            rst: Some(ResetMode::Reset),
+        };
+        assert_eq!(client.send(&cmd), Ok(NoResponse));
+
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U32>::from("AT+CFUN=4,0\r\nAT+FUN=1,6\r\n"),
+            "Reverse order string did not match"
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn countdown() {
+        let (mut client, _, _) = setup!(Config::new(Mode::Timeout));
+
+        assert_eq!(client.state, ClientState::Idle);
+
+        let cmd = Test2Cmd {
+            fun: Functionality::DM,
+            rst: Some(ResetMode::Reset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::Other(Error::Timeout)));
+
+        // TODO: Test countdown is recived corretly
+        match client.config.mode {
+            Mode::Timeout => {} // assert_eq!(cd_mock.time, 180000),
+            _ => panic!("Wrong AT mode"),
+        }
+        assert_eq!(client.state, ClientState::Idle);
+    }
+
+    #[test]
+    fn blocking() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.send(&cmd), Ok(NoResponse));
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.tx.s, String::<consts::U32>::from("AT+CFUN=4,0\r\n"));
+    }
+
+    #[test]
+    fn non_blocking() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.state, ClientState::AwaitingResponse);
+
+        assert_eq!(client.check_response(&cmd), Err(nb::Error::WouldBlock));
+
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+
+        assert_eq!(client.state, ClientState::AwaitingResponse);
+
+        assert_eq!(client.check_response(&cmd), Ok(NoResponse));
+        assert_eq!(client.state, ClientState::Idle);
+    }
+
+    // Testing unsupported feature in form of vec deserialization
+    #[test]
+    #[ignore]
+    fn response_vec() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = TestRespVecCmd {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from(
+            "+CUN: 22,16,\"0123456789012345\"",
+        )))
+        .unwrap();
+
+        let res_vec: Vec<u8, consts::U256> =
+            "0123456789012345".as_bytes().iter().cloned().collect();
+
+        assert_eq!(client.state, ClientState::Idle);
+
+        assert_eq!(
+            client.send(&cmd),
+            Ok(TestResponseVec {
+                socket: 22,
+                length: 16,
+                data: res_vec
+            })
+        );
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.tx.s, String::<consts::U32>::from("AT+CFUN=4,0\r\n"));
+    }
+    // Test response containing string
+    #[test]
+    fn response_string() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        // String last
+        let cmd = TestRespStringCmd {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from(
+            "+CUN: 22,16,\"0123456789012345\"",
+        )))
+        .unwrap();
+
+        assert_eq!(client.state, ClientState::Idle);
+
+        assert_eq!(
+            client.send(&cmd),
+            Ok(TestResponseString {
+                socket: 22,
+                length: 16,
+                data: String::<consts::U64>::from("0123456789012345")
+            })
+        );
+        assert_eq!(client.state, ClientState::Idle);
+
+        // Mixed order for string
+        let cmd = TestRespStringMixCmd {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from(
+            "+CUN: \"0123456789012345\",22,16",
+        )))
+        .unwrap();
+
+        assert_eq!(
+            client.send(&cmd),
+            Ok(TestResponseStringMixed {
+                socket: 22,
+                length: 16,
+                data: String::<consts::U64>::from("0123456789012345")
+            })
+        );
+        assert_eq!(client.state, ClientState::Idle);
+    }
+
+    #[test]
+    fn textual_enum() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = TestProtocolCmd {
+            protocol: Protocol::Tls,
+            mode: SocketMode::NonBlocking,
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from("+USOCR: \"TLS1.2\",UDP")))
+            .unwrap();
+
+        assert_eq!(
+            client.send(&cmd),
+            Ok(TestResponseProtocol {
+                protocol: Protocol::Tls,
+                fallback: Protocol::Udp,
+            })
+        );
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U64>::from("AT+USOCR=\"TLS1.2\",\"NON_BLOCKING\"\r\n")
+        );
+    }
+
+    #[test]
+    fn missing_trailing_options() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        p.enqueue(Ok(String::<consts::U256>::from("+CREG: 0,1")))
+            .unwrap();
+        assert_eq!(
+            client.send(&GetRegistrationStatus),
+            Ok(RegistrationStatus {
+                n: 0,
+                stat: 1,
+                lac: None,
+                ci: None,
+                act: None,
+            })
+        );
+
+        p.enqueue(Ok(String::<consts::U256>::from(
+            "+CREG: 0,1,\"27BD\",\"0C139D0\",7",
+        )))
+        .unwrap();
+        assert_eq!(
+            client.send(&GetRegistrationStatus),
+            Ok(RegistrationStatus {
+                n: 0,
+                stat: 1,
+                lac: Some(String::from("27BD")),
+                ci: Some(String::from("0C139D0")),
+                act: Some(7),
+            })
+        );
+
+        // Missing non-optional parameters are still an error
+        p.enqueue(Ok(String::<consts::U256>::from("+CREG: 0")))
+            .unwrap();
+        assert_eq!(
+            client.send(&GetRegistrationStatus),
+            Err(nb::Error::Other(Error::ParseString(
+                ErrorContext::response("+CREG: 0")
+            )))
+        );
+    }
+
+    #[test]
+    fn custom_termination() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = SmsBody {
+            text: serde_at::ser::Bytes(b"Hello"),
+        };
+        assert_eq!(cmd.as_string().as_str(), "Hello\x1a");
+        assert_eq!(AbortSms.as_string().as_str(), "\x1b");
+
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        assert_eq!(client.send(&cmd), Ok(NoResponse));
+        assert_eq!(client.tx.s, String::<consts::U64>::from("Hello\x1a"));
+    }
+
+    #[test]
+    fn cmd_overrides() {
+        let cmd = DialContext { cid: 1 };
+        assert_eq!(cmd.as_string().as_str(), "ATD*99***1#\r\n");
+        assert_eq!(cmd.as_string().capacity(), "ATD*99***255#\r\n".len());
+
+        let cmd = ActivateContext { cid: 1, stat: 1 };
+        assert_eq!(cmd.as_string().as_str(), "AT#SGACT=1,1\r\n");
+        assert_eq!(cmd.as_string().capacity(), "AT#SGACT=255,255\r\n".len());
+
+        assert_eq!(Probe.as_string().as_str(), "AT\r");
+        assert_eq!(Probe.as_string().capacity(), 3);
+    }
+
+    #[test]
+    fn multi_stage() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = crate::sms::SendSms {
+            number: "+4512345678",
+            text: "Hello",
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        p.enqueue(Ok(String::<consts::U256>::from("Hello\x1a\r\n+CMGS: 12")))
+            .unwrap();
+
+        assert_eq!(
+            client.send_multi_stage(&cmd),
+            Ok(crate::sms::SmsReference { reference: 12 })
+        );
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U64>::from("AT+CMGS=\"+4512345678\"\rHello\x1a")
+        );
+    }
+
+    #[test]
+    fn cmd_too_long() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = SetGreetingText { text: "Hello" };
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        assert_eq!(client.send(&cmd), Ok(NoResponse));
+
+        let cmd = SetGreetingText {
+            text: "Hello, world! How are you?",
+        };
+        assert_eq!(
+            client.send(&cmd),
+            Err(nb::Error::Other(Error::CmdTooLong(38, 24)))
+        );
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U64>::from("AT+CSGT=\"Hello\"\r\n")
+        );
+
+        // The smaller of the configured and the per-command max applies
+        let (mut client, _, _) = setup!(Config::new(Mode::Blocking).max_cmd_len(12));
+        let cmd = SetGreetingText { text: "Hello" };
+        assert_eq!(
+            client.send(&cmd),
+            Err(nb::Error::Other(Error::CmdTooLong(17, 12)))
+        );
+
+        // Commands not even fitting their buffer are refused as well
+        let (mut client, _, _) = setup!(Config::new(Mode::Blocking));
+        let cmd = SetGreetingText {
+            text: "Hello, world! How are you doing today?",
+        };
+        assert_eq!(
+            client.send(&cmd),
+            Err(nb::Error::Other(Error::CmdTooLong(45, 44)))
+        );
+        assert_eq!(client.state, ClientState::Idle);
+    }
+
+    #[test]
+    fn data_mode() {
+        static mut DATA_Q: queues::DataQueue = queues::DataQueue::NEW;
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+        let (mut data_p, data_c) = unsafe { DATA_Q.split() };
+        client.data_c = data_c;
+
+        p.enqueue(Ok(String::<consts::U256>::from("CONNECT 115200")))
+            .unwrap();
+        assert_eq!(
+            client.send(&DialPacketData),
+            Ok(Connect { rate: Some(115200) })
+        );
+
+        let mut data = client.data_mode();
+        data.write(b"~\x7d\xff").unwrap();
+        for b in b"~\x7e" {
+            data_p.enqueue(*b).unwrap();
+        }
+        let mut buf = [0; 8];
+        assert_eq!(data.read(&mut buf), 2);
+        assert_eq!(&buf[..2], b"~\x7e");
+        assert_eq!(data.read(&mut buf), 0);
+
+        // Unread data is discarded on exit
+        data_p.enqueue(b'~').unwrap();
+        data.exit();
+        assert_eq!(client.data_c.dequeue(), None);
+        assert_eq!(client.state, ClientState::Idle);
+        assert!(client.cooldown_pending);
+        assert_eq!(client.tx.s.as_bytes(), &b"ATD*99#\r\n~\x7d\xc3\xbf"[..]);
+    }
+
+    #[test]
+    fn split_data_mode() {
+        static mut DATA_Q: queues::DataQueue = queues::DataQueue::NEW;
+        let (mut client, _, _) = setup!(Config::new(Mode::Blocking));
+        let (mut data_p, data_c) = unsafe { DATA_Q.split() };
+        client.data_c = data_c;
+
+        let mut data = client.data_mode();
+        {
+            let (mut rx, mut tx) = data.split();
+            tx.write(b"~\x7d").unwrap();
+            for b in b"~\x7e\r\nNO CARR" {
+                data_p.enqueue(*b).unwrap();
+            }
+            let mut buf = [0; 16];
+            assert_eq!(rx.read(&mut buf), 11);
+            assert!(!rx.carrier_lost());
+            for b in b"IER\r\n" {
+                data_p.enqueue(*b).unwrap();
+            }
+            assert_eq!(rx.read(&mut buf), 5);
+            assert!(rx.carrier_lost());
+        }
+        data.exit();
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.tx.s.as_bytes(), &b"~\x7d"[..]);
+    }
+
+    #[test]
+    fn escape() {
+        let (mut client, mut p, _) =
+            setup!(Config::new(Mode::Timeout).escape_guard_time(Milliseconds(500)));
+
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        assert_eq!(client.data_mode().escape(), Ok(()));
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.tx.s, String::<consts::U64>::from("+++"));
+
+        // Without confirmation, the device is still in data mode
+        assert_eq!(client.data_mode().escape(), Err(Error::Timeout));
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.tx.s, String::<consts::U64>::from("++++++"));
+    }
+
+    #[test]
+    fn escape_silent_device() {
+        // The wait for the confirmation is bounded without a timeout mode
+        let (mut client, _, _) = setup!(Config::new(Mode::Blocking));
+
+        assert_eq!(client.data_mode().escape(), Err(Error::Timeout));
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.stats.timeouts, 1);
+        assert_eq!(client.tx.s, String::<consts::U64>::from("+++"));
+    }
+
+    #[test]
+    fn handoff() {
+        static mut DATA_Q: queues::DataQueue = queues::DataQueue::NEW;
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Timeout));
+        let (mut data_p, data_c) = unsafe { DATA_Q.split() };
+        client.data_c = data_c;
+
+        data_p.enqueue(b'C').unwrap();
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        let received = client.handoff(|data| {
+            let mut buf = [0; 4];
+            let n = data.read(&mut buf);
+            data.write(b"\x01\x01\xfe").unwrap();
+            n
+        });
+        assert_eq!(received, Ok(1));
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.tx.s.as_bytes(), &b"\x01\x01\xc3\xbeAT\r\n"[..]);
+
+        // Resynchronizing gives up after a few attempts
+        assert_eq!(client.handoff(|_| ()), Err(Error::Timeout));
+        assert_eq!(&client.tx.s.as_bytes()[8..], &b"AT\r\nAT\r\nAT\r\n"[..]);
+    }
+
+    #[test]
+    fn handoff_silent_device() {
+        // The device never returns to command mode, and the resynchronization
+        // is bounded without a timeout mode
+        let (mut client, _, _) = setup!(Config::new(Mode::Blocking));
+
+        assert_eq!(client.handoff(|_| ()), Err(Error::Timeout));
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.stats.timeouts, 3);
+        assert_eq!(client.tx.s.as_bytes(), &b"AT\r\nAT\r\nAT\r\n"[..]);
+    }
+
+    #[test]
+    fn without_timer() {
+        let (mut client, _ingress) = atat::new_without_timer(
+            TxMock::new(String::new()),
+            atat::TimerlessMode::NonBlocking,
+            Config::new(Mode::Timeout),
+            None::<atat::NoopUrcMatcher>,
+        );
+        assert_eq!(client.get_mode(), Mode::NonBlocking);
+
+        // The cooldown completes immediately
+        assert_eq!(client.send(&EnterDataMode), Ok(NoResponse));
+        assert_eq!(client.send(&EnterDataMode), Ok(NoResponse));
+        assert_eq!(client.tx.s, String::<consts::U64>::from("ATO\r\nATO\r\n"));
+    }
+
+    #[test]
+    fn set_cooldown() {
+        let (mut client, _, _) = setup!(Config::new(Mode::Timeout).cmd_cooldown(Milliseconds(20)));
+        assert_eq!(client.cooldown(), Milliseconds(20));
+
+        client.set_cooldown(Milliseconds(5));
+        assert_eq!(client.cooldown(), Milliseconds(5));
+        assert_eq!(client.send(&EnterDataMode), Ok(NoResponse));
+        assert_eq!(client.cooldown_timer.time, 5);
+    }
+
+    #[test]
+    fn cooldown_override() {
+        let (mut client, mut p, _) =
+            setup!(Config::new(Mode::Timeout).cmd_cooldown(Milliseconds(20)));
+
+        p.enqueue(Ok(String::new())).unwrap();
+        assert_eq!(client.send(&SignalQuality), Ok(NoResponse));
+        assert_eq!(client.cooldown_timer.time, 0);
+
+        p.enqueue(Ok(String::new())).unwrap();
+        assert_eq!(client.send(&Attach), Ok(NoResponse));
+        assert_eq!(client.cooldown_timer.time, 500);
+
+        assert_eq!(client.send(&EnterDataMode), Ok(NoResponse));
+        assert_eq!(client.cooldown_timer.time, 20);
+    }
+
+    #[test]
+    fn runtime_config() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        // Initialized blocking, then polled in steady state
+        client.set_mode(Mode::NonBlocking);
+        let cmd = ReadPhonebook { index: 1 };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        p.enqueue(Ok(String::new())).unwrap();
+        assert_eq!(client.check_response(&cmd), Ok(None));
+
+        client.set_max_cmd_len(Some(8));
+        assert_eq!(
+            client.send(&cmd),
+            Err(nb::Error::Other(Error::CmdTooLong(11, 8)))
+        );
+
+        assert_eq!(
+            client.set_echo(false),
+            Err(nb::Error::Other(Error::Unsupported))
+        );
+
+        // Until the ingress manager takes the commands off the queue
+        for _ in 0..3 {
+            assert_eq!(client.set_echo(true), Ok(()));
+        }
+        assert_eq!(client.set_echo(true), Err(nb::Error::WouldBlock));
+    }
+
+    #[test]
+    fn send_with_timeout() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Timeout));
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        assert_eq!(
+            client.send_with_timeout(&cmd, Milliseconds(180_000)),
+            Ok(NoResponse)
+        );
+        assert_eq!(client.timeout_ms, 180_000);
+        assert_eq!(client.tx.s, String::<consts::U64>::from("AT+CFUN=4,0\r\n"));
+    }
+
+    #[test]
+    fn virtual_time() {
+        let clock = VirtualClock::new();
+        clock.set_auto_advance(true);
+        let (mut client, _, _) = setup!(
+            Config::new(Mode::Timeout).cmd_cooldown(Milliseconds(20)),
+            clock.timer(),
+            clock.timer()
+        );
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::Other(Error::Timeout)));
+        assert_eq!(clock.now(), 180_000);
+
+        assert_eq!(client.send(&EnterDataMode), Ok(NoResponse));
+        assert_eq!(client.send(&EnterDataMode), Ok(NoResponse));
+        assert_eq!(clock.now(), 180_020);
+        assert_eq!(clock.started(), [180_000, 20, 20]);
+    }
+
+    #[test]
+    fn tx_chunks() {
+        let clock = VirtualClock::new();
+        clock.set_auto_advance(true);
+        let (mut client, _, _) = setup!(
+            Config::new(Mode::NonBlocking).with_tx_chunks(5, Milliseconds(3)),
+            clock.timer(),
+            clock.timer()
+        );
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.tx.s, String::<consts::U64>::from("AT+CFUN=4,0\r\n"));
+        assert_eq!(clock.started(), [3, 3]);
+        assert_eq!(clock.now(), 6);
+    }
+
+    #[test]
+    fn trace_handler() {
+        std::thread_local! {
+            static TRACED: core::cell::RefCell<std::vec::Vec<std::vec::Vec<u8>>> =
+                Default::default();
+        }
+        fn handler(direction: TraceDirection, data: &[u8]) {
+            assert_eq!(direction, TraceDirection::Tx);
+            TRACED.with(|t| t.borrow_mut().push(data.to_vec()));
+        }
+
+        let (mut client, _, _) =
+            setup!(Config::new(Mode::NonBlocking).with_tx_chunks(5, Milliseconds(3)));
+        client.set_trace_handler(handler);
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        TRACED.with(|t| {
+            assert_eq!(
+                t.borrow().as_slice(),
+                [b"AT+CF".to_vec(), b"UN=4,".to_vec(), b"0\r\n".to_vec()]
+            )
+        });
+    }
+
+    #[test]
+    fn send_raw() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.send_raw(b"\x7e\xff\x7d"), Err(nb::Error::WouldBlock));
+
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        assert_eq!(client.check_response(&cmd), Ok(NoResponse));
+        client.tx.s.clear();
+        assert_eq!(client.send_raw(b"+++"), Ok(()));
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.tx.s, String::<consts::U64>::from("+++"));
+    }
+
+    #[test]
+    fn write_error() {
+        let (mut client, _, _) = setup!(Config::new(Mode::Blocking));
+
+        // The mock fails to write once its buffer is full
+        while client.tx.s.push('x').is_ok() {}
+        assert_eq!(client.take_write_error(), None);
+        assert_eq!(
+            client.send(&EnterDataMode),
+            Err(nb::Error::Other(Error::Write(String::from("()"))))
+        );
+        assert_eq!(client.take_write_error(), Some(()));
+        assert_eq!(client.take_write_error(), None);
+    }
+
+    #[test]
+    fn fire_and_forget() {
+        let (mut client, _, _) = setup!(Config::new(Mode::Timeout).cmd_cooldown(Milliseconds(20)));
+
+        assert_eq!(client.send(&EnterDataMode), Ok(NoResponse));
+        assert_eq!(client.state, ClientState::Idle);
+        assert!(client.cooldown_pending);
+        assert_eq!(client.cooldown_timer.time, 20);
+        assert_eq!(client.tx.s, String::<consts::U64>::from("ATO\r\n"));
+    }
+
+    #[test]
+    fn encoded_text() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        let cmd = SendEncodedText {
+            text: String::from("Hi€"),
+            ussd: "Hi",
+        };
+
+        p.enqueue(Ok(String::<consts::U256>::from("+TEXT: \"C834\",15")))
+            .unwrap();
+
+        assert_eq!(
+            client.send(&cmd),
+            Ok(EncodedText {
+                text: String::from("Hi"),
+                dcs: 15,
+            })
+        );
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U64>::from("AT+TEXT=\"0048006920AC\",\"C834\"\r\n")
+        );
+    }
+
+    #[test]
+    fn response_prefix_override() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        p.enqueue(Ok(String::<consts::U256>::from("Model: SARA-R410M")))
+            .unwrap();
+        assert_eq!(
+            client.send(&GetModel),
+            Ok(TextResponse {
+                text: String::from("Model: SARA-R410M")
+            })
+        );
+
+        p.enqueue(Ok(String::<consts::U256>::from(
+            "Manufacturer: u-blox\r\nModel: SARA-R410M",
+        )))
+        .unwrap();
+        let info = client.send(&GetInfo).unwrap();
+        assert_eq!(info.get("Model"), Some("SARA-R410M"));
+
+        p.enqueue(Ok(String::<consts::U256>::from("356938035643809")))
+            .unwrap();
+        assert_eq!(
+            client.send(&GetSerialNumber),
+            Ok(SerialNumber {
+                imei: 356938035643809
+            })
+        );
+
+        p.enqueue(Ok(String::<consts::U256>::from("+CGSN: 356938035643809")))
+            .unwrap();
+        assert_eq!(
+            client.send(&GetSerialNumber),
+            Ok(SerialNumber {
+                imei: 356938035643809
+            })
+        );
+
+        // The context names the prefix of the response
+        p.enqueue(Ok(String::<consts::U256>::from("+CSQ: 31,99")))
+            .unwrap();
+        assert_eq!(
+            client.send(&GetSerialNumber),
+            Err(nb::Error::Other(Error::ParseString(
+                ErrorContext::response("+CSQ: 31,99").expected("+CGSN")
+            )))
+        );
+    }
+
+    #[test]
+    fn optional_response() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+
+        p.enqueue(Ok(String::<consts::U256>::from("+CPBR: 1,\"+4512345678\"")))
+            .unwrap();
+        assert_eq!(
+            client.send(&ReadPhonebook { index: 1 }),
+            Ok(Some(PhonebookEntry {
+                index: 1,
+                number: String::from("+4512345678")
+            }))
+        );
+
+        // Empty entry, only `OK` arrived
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        assert_eq!(client.send(&ReadPhonebook { index: 2 }), Ok(None));
+    }
+
+    #[test]
+    fn urc() {
+        let (mut client, _, mut urc_p) = setup!(Config::new(Mode::NonBlocking));
+
+        urc_p
+            .enqueue(String::<consts::U256>::from("+UMWI: 0, 1").into())
+            .unwrap();
+
+        assert_eq!(client.state, ClientState::Idle);
+        assert!(client.check_urc::<Urc>().is_some());
+        assert_eq!(client.state, ClientState::Idle);
+    }
+
+    #[test]
+    fn urc_burst() {
+        let (mut client, _, mut urc_p) = setup!(Config::new(Mode::NonBlocking));
+
+        for urc in &["+UMWI: 0, 1", "+UNKNOWN: 1", "+UMWI: 0, 2"] {
+            urc_p
+                .enqueue(String::<consts::U256>::from(*urc).into())
+                .unwrap();
+        }
+
+        // Peeking leaves the URC in queue
+        for _ in 0..2 {
+            match client.peek_urc::<Urc>() {
+                Some(Urc::MessageWaitingIndication(MessageWaitingIndication {
+                    code: 1, ..
+                })) => {}
+                _ => panic!("Expected +UMWI: 0, 1"),
+            }
+        }
+
+        let mut codes: Vec<u8, consts::U4> = Vec::new();
+        let n = client.check_urc_all::<Urc, _>(|urc| {
+            if let Urc::MessageWaitingIndication(MessageWaitingIndication { code, .. }) = urc {
+                codes.push(code).unwrap();
+            }
+        });
+        assert_eq!(n, 2);
+        assert_eq!(codes, [1, 2]);
+        assert_eq!(client.stats().urcs.parse_failed, 1);
+        assert!(client.peek_urc::<Urc>().is_none());
+        assert_eq!(client.check_urc_all::<Urc, _>(|_| {}), 0);
+    }
+
+    #[test]
+    fn dyn_client() {
+        let (mut client, mut p, mut urc_p) = setup!(Config::new(Mode::Blocking));
+        let client: &mut dyn DynClient = &mut client;
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        assert_eq!(client.send(&cmd), Ok(NoResponse));
+
+        urc_p
+            .enqueue(String::<consts::U256>::from("+UMWI: 0, 1").into())
+            .unwrap();
+        assert!(client.check_urc::<Urc>().is_some());
+        assert!(client.check_urc::<Urc>().is_none());
+    }
+
+    #[test]
+    fn non_blocking_timeout() {
+        let clock = VirtualClock::new();
+        let (mut client, mut p, _) = setup!(
+            Config::new(Mode::NonBlockingTimeout),
+            clock.timer(),
+            clock.timer()
+        );
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        clock.advance(179_999);
+        assert_eq!(client.check_response(&cmd), Err(nb::Error::WouldBlock));
+        clock.advance(1);
+        assert_eq!(
+            client.check_response(&cmd),
+            Err(nb::Error::Other(Error::Timeout))
+        );
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.stats().timeouts, 1);
+
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        assert_eq!(client.check_response(&cmd), Ok(NoResponse));
+    }
+
+    #[test]
+    fn pipelining() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+
+        let first = client.pipeline(&ReadPhonebook { index: 1 }).unwrap();
+        let second = client.pipeline(&ReadPhonebook { index: 2 }).unwrap();
+        let third = client.pipeline(&GetSerialNumber).unwrap();
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U64>::from("AT+CPBR=1\r\nAT+CPBR=2\r\nAT+CGSN\r\n")
+        );
+        assert_eq!(
+            client.check_pipelined(first, &ReadPhonebook { index: 1 }),
+            Err(nb::Error::WouldBlock)
+        );
+        assert_eq!(client.send(&GetModel), Err(nb::Error::WouldBlock));
+
+        p.enqueue(Ok(String::<consts::U256>::from("+CPBR: 1,\"+4512345678\"")))
+            .unwrap();
+        p.enqueue(Ok(String::<consts::U256>::new())).unwrap();
+        p.enqueue(Ok(String::<consts::U256>::from("+CGSN: 356938035643809")))
+            .unwrap();
+
+        assert_eq!(
+            client.check_pipelined(third, &GetSerialNumber),
+            Ok(SerialNumber {
+                imei: 356938035643809
+            })
+        );
+        assert_eq!(
+            client.check_pipelined(second, &ReadPhonebook { index: 2 }),
+            Ok(None)
+        );
+        assert_eq!(
+            client.check_pipelined(first, &ReadPhonebook { index: 1 }),
+            Ok(Some(PhonebookEntry {
+                index: 1,
+                number: String::from("+4512345678")
+            }))
+        );
+        assert_eq!(
+            client.check_pipelined(first, &ReadPhonebook { index: 1 }),
+            Err(nb::Error::Other(Error::Aborted))
+        );
+        assert_eq!(client.state, ClientState::Idle);
+
+        // The number of uncollected responses is bounded
+        client.tx.s.clear();
+        for _ in 0..4 {
+            client.pipeline(&GetSerialNumber).unwrap();
+        }
+        assert_eq!(
+            client.pipeline(&GetSerialNumber),
+            Err(nb::Error::WouldBlock)
+        );
+
+        client.abort_current();
+        assert_eq!(client.state, ClientState::Idle);
+        assert!(client.pipeline(&GetSerialNumber).is_ok());
+    }
+
+    #[test]
+    fn pipelined_responses_fill_the_pool() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+
+        let seqs = [
+            client.pipeline(&GetSerialNumber).unwrap(),
+            client.pipeline(&GetSerialNumber).unwrap(),
+            client.pipeline(&GetSerialNumber).unwrap(),
+            client.pipeline(&GetSerialNumber).unwrap(),
+        ];
+        // Each response takes a buffer, until it is collected
+        for _ in &seqs {
+            p.enqueue(Ok(String::<consts::U256>::from("+CGSN: 356938035643809")))
+                .unwrap();
+        }
+        for seq in seqs.iter().rev() {
+            assert_eq!(
+                client.check_pipelined(*seq, &GetSerialNumber),
+                Ok(SerialNumber {
+                    imei: 356938035643809
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn urc_during_timeout() {
+        let clock = VirtualClock::new();
+        let (mut client, _, mut urc_p) = setup!(
+            Config::new(Mode::Timeout).cmd_cooldown(Milliseconds(20)),
+            clock.timer(),
+            clock.timer()
+        );
+        client.resume(SuspendedState {
+            awaiting_response: true,
+            cooldown_pending: false,
+            timeout_ms: 1000,
+            cooldown_ms: 20,
+        });
+
+        // The cooldown started by the URC leaves the timeout alone
+        urc_p
+            .enqueue(String::<consts::U256>::from("+UMWI: 0, 1").into())
+            .unwrap();
+        assert!(client.check_urc::<Urc>().is_some());
+        clock.advance(20);
+        assert_eq!(
+            client.check_response(&EnterDataMode),
+            Err(nb::Error::WouldBlock)
+        );
+
+        clock.advance(980);
+        assert_eq!(
+            client.check_response(&EnterDataMode),
+            Err(nb::Error::Other(Error::Timeout))
+        );
+    }
+
+    #[test]
+    fn whole_line_urc() {
+        match Urc::parse("+CRING: VOICE\r\n") {
+            Ok(Urc::Ring(RingType::Voice)) => {}
+            _ => panic!("Expected +CRING: VOICE"),
+        }
+        match Urc::parse("#SHDN 3") {
+            Ok(Urc::Shutdown(3)) => {}
+            _ => panic!("Expected #SHDN 3"),
+        }
+        assert!(Urc::parse("#SHDN now").is_err());
+        match Urc::parse("+UMWI: 0, 1") {
+            Ok(Urc::MessageWaitingIndication(_)) => {}
+            _ => panic!("Expected +UMWI"),
+        }
+    }
+
+    #[test]
+    fn unprefixed_and_discriminated_urc() {
+        match Urc::parse("RING") {
+            Ok(Urc::IncomingCall) => {}
+            _ => panic!("Expected RING"),
+        }
+        match Urc::parse("NORMAL POWER DOWN\r\n") {
+            Ok(Urc::PowerDown) => {}
+            _ => panic!("Expected NORMAL POWER DOWN"),
+        }
+        assert!(Urc::parse("RINGING").is_err());
+        match Urc::parse("+QIURC: \"recv\",2") {
+            Ok(Urc::SocketReceive(SocketEvent { socket: 2 })) => {}
+            _ => panic!("Expected +QIURC: \"recv\""),
+        }
+        match Urc::parse("+QIURC: \"closed\",1") {
+            Ok(Urc::SocketClosed(SocketEvent { socket: 1 })) => {}
+            _ => panic!("Expected +QIURC: \"closed\""),
+        }
+        match Urc::parse("+QIURC: \"pdpdeact\",1") {
+            Ok(Urc::PdpDeactivated) => {}
+            _ => panic!("Expected +QIURC: \"pdpdeact\""),
+        }
+        assert!(Urc::parse("+QIURC: \"incoming\",1,2").is_err());
+    }
+
+    #[test]
+    fn case_insensitive_urc() {
+        match Urc::parse("+qind: \"CSQ\",3") {
+            Ok(Urc::SignalQualityReport(SocketEvent { socket: 3 })) => {}
+            _ => panic!("Expected +QIND: \"csq\""),
+        }
+        match Urc::parse("+UUPSDD: 0") {
+            Ok(Urc::PacketSwitchedDeactivated(SocketEvent { socket: 0 })) => {}
+            _ => panic!("Expected +UUPSDD"),
+        }
+        match Urc::parse("+uupsdd: 1") {
+            Ok(Urc::PacketSwitchedDeactivated(SocketEvent { socket: 1 })) => {}
+            _ => panic!("Expected +uupsdd"),
+        }
+        match Urc::parse("^sysstart FAX") {
+            Ok(Urc::SystemStart(RingType::Fax)) => {}
+            _ => panic!("Expected ^SYSSTART"),
+        }
+        match Urc::parse("Sms Ready\r\n") {
+            Ok(Urc::SmsReady) => {}
+            _ => panic!("Expected SMS READY"),
+        }
+        // Codes without `ci` are still matched exactly
+        assert!(Urc::parse("ring").is_err());
+        assert!(Urc::parse("+umwi: 0, 1").is_err());
+    }
+
+    #[test]
+    fn truncated_urc() {
+        let (mut client, _, mut urc_p) = setup!(Config::new(Mode::NonBlocking));
+
+        urc_p
+            .enqueue(String::<consts::U256>::from("+UUSORD: 0,4,\"abcd\"").into())
+            .unwrap();
+        urc_p
+            .enqueue(queues::UrcFrame {
+                line: String::from("+UUSORD: 1,300,\"abcdefgh"),
+                truncated_len: Some(317),
+            })
+            .unwrap();
+        urc_p
+            .enqueue(queues::UrcFrame {
+                line: String::from("+UMWI: 0,1"),
+                truncated_len: Some(300),
+            })
+            .unwrap();
+
+        match client.check_urc::<Urc>() {
+            Some(Urc::SocketData(data)) => {
+                assert!(!data.is_truncated());
+                assert_eq!(data.value.data, Some(String::from("abcd")));
+            }
+            _ => panic!("Expected complete +UUSORD"),
+        }
+        match client.check_urc::<Urc>() {
+            Some(Urc::SocketData(data)) => assert_eq!(
+                data,
+                Truncatable {
+                    value: SocketData {
+                        socket: 1,
+                        len: 300,
+                        data: None
+                    },
+                    truncated_len: Some(317),
+                }
+            ),
+            _ => panic!("Expected truncated +UUSORD"),
+        }
+        // Variants that are not truncatable are dropped
+        assert!(client.check_urc::<Urc>().is_none());
+        assert_eq!(client.stats().urcs.overflow, 1);
+    }
+
+    #[test]
+    fn modem_reset() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.state, ClientState::AwaitingResponse);
+
+        p.enqueue(Err(Error::ModemReset)).unwrap();
+        assert_eq!(
+            client.check_response(&cmd),
+            Err(nb::Error::Other(Error::ModemReset))
+        );
+        assert_eq!(client.state, ClientState::Idle);
+    }
+
+    #[test]
+    fn response_validation() {
+        let config = Config::new(Mode::NonBlocking).with_response_validation(true);
+        let (mut client, mut p, _) = setup!(config);
+
+        assert_eq!(GetSerialNumber.response_prefix(), Some("+CGSN"));
+        assert_eq!(GetModel.response_prefix(), None);
+
+        let cmd = ReadPhonebook { index: 1 };
+        assert_eq!(cmd.response_prefix(), Some("+CPBR"));
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+
+        // The late response to a command that timed out
+        p.enqueue(Ok(String::<consts::U256>::from("+CGSN: 123456")))
+            .unwrap();
+        assert_eq!(client.check_response(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.stats().responses.stale, 1);
+        assert_eq!(client.state, ClientState::AwaitingResponse);
+
+        p.enqueue(Ok(String::<consts::U256>::from("+CPBR: 1,\"0123\"")))
+            .unwrap();
+        assert_eq!(
+            client.check_response(&cmd),
+            Ok(Some(PhonebookEntry {
+                index: 1,
+                number: String::from("0123")
+            }))
+        );
+    }
+
+    #[test]
+    fn late_response() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
 
-    #[derive(Clone, AtatResp, PartialEq, Debug)]
-    pub struct TestResponseStringMixed {
-        #[at_arg(position = 1)]
-        pub socket: u8,
-        #[at_arg(position = 2)]
-        pub length: usize,
-        #[at_arg(position = 0)]
-        pub data: String<consts::U64>,
-    }
+        let cmd = ReadPhonebook { index: 1 };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        client.abort_current();
 
-    #[derive(Clone, AtatResp)]
-    pub struct MessageWaitingIndication {
-        #[at_arg(position = 0)]
-        pub status: u8,
-        #[at_arg(position = 1)]
-        pub code: u8,
-    }
+        // Completed by the ingress manager before learning that the command
+        // was given up on
+        p.res_p.enqueue(Ok("+CPBR: 1,\"0123\""), 0).unwrap();
 
-    #[derive(Clone, AtatUrc)]
-    pub enum Urc {
-        #[at_urc("+UMWI")]
-        MessageWaitingIndication(MessageWaitingIndication),
+        let cmd = ReadPhonebook { index: 2 };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.check_response(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.stats().responses.stale, 1);
+
+        p.enqueue(Ok(String::from("+CPBR: 2,\"4567\""))).unwrap();
+        assert_eq!(
+            client.check_response(&cmd),
+            Ok(Some(PhonebookEntry {
+                index: 2,
+                number: String::from("4567")
+            }))
+        );
     }
 
-    macro_rules! setup {
-        ($config:expr) => {{
-            static mut RES_Q: queues::ResQueue = Queue(heapless::i::Queue::u8());
-            let (res_p, res_c) = unsafe { RES_Q.split() };
-            static mut URC_Q: queues::UrcQueue = Queue(heapless::i::Queue::u8());
-            let (urc_p, urc_c) = unsafe { URC_Q.split() };
-            static mut COM_Q: queues::ComQueue = Queue(heapless::i::Queue::u8());
-            let (com_p, _com_c) = unsafe { COM_Q.split() };
+    impl<'a> ParseBorrowed<'a> for ReadPhonebook {
+        type Borrowed = &'a str;
 
-            let timer = CdMock { time: 0 };
+        fn parse_borrowed(&self, resp: &'a [u8]) -> Result<&'a str, Error> {
+            core::str::from_utf8(resp)
+                .ok()
+                .and_then(|resp| resp.split('"').nth(1))
+                .ok_or_else(|| Error::ParseString(ErrorContext::new()))
+        }
+    }
 
-            let tx_mock = TxMock::new(String::new());
-            let client: Client<TxMock, CdMock> =
-                Client::new(tx_mock, res_c, urc_c, com_p, timer, $config);
-            (client, res_p, urc_p)
-        }};
+    #[test]
+    fn guarded_response() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+
+        let cmd = ReadPhonebook { index: 1 };
+        assert_eq!(client.send_guarded(&cmd), Err(nb::Error::WouldBlock));
+        p.enqueue(Ok(String::from("+CPBR: 1,\"0123\""))).unwrap();
+        let resp = client.check_response_bytes().unwrap();
+        assert_eq!(cmd.parse_borrowed(resp.as_bytes()), Ok("0123"));
     }
 
     #[test]
-    fn string_sent() {
-        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+    fn abort_and_reset() {
+        let (mut client, mut p, mut urc_p) = setup!(Config::new(Mode::NonBlocking));
 
         let cmd = SetModuleFunctionality {
             fun: Functionality::APM,
             rst: Some(ResetMode::DontReset),
         };
-
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
         p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
-
+        client.abort_current();
         assert_eq!(client.state, ClientState::Idle);
-        assert_eq!(client.send(&cmd), Ok(NoResponse));
+        assert_eq!(client.check_response(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.stats().responses.stale, 1);
+
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        p.enqueue(Err(Error::InvalidResponse(ErrorContext::new())))
+            .unwrap();
+        urc_p
+            .enqueue(String::<consts::U256>::from("+UMWI: 0, 1").into())
+            .unwrap();
+        client.reset();
         assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.check_response(&cmd), Err(nb::Error::WouldBlock));
+        assert!(client.check_urc::<Urc>().is_none());
+    }
+
+    #[test]
+    fn abort() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+
+        // Commands that cannot be aborted are left running
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        assert_eq!(client.abort(), Ok(false));
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        assert_eq!(client.check_response(&cmd), Ok(NoResponse));
 
+        client.tx.s.clear();
+        assert_eq!(client.send(&ScanOperators), Err(nb::Error::WouldBlock));
+        assert_eq!(client.abort(), Ok(true));
+        assert_eq!(client.abort(), Ok(true));
         assert_eq!(
             client.tx.s,
-            String::<consts::U32>::from("AT+CFUN=4,0\r\n"),
-            "Wrong encoding of string"
+            String::<consts::U64>::from("AT+COPS=?\r\n\x1b")
+        );
+        assert_eq!(
+            client.check_response(&ScanOperators),
+            Err(nb::Error::WouldBlock)
         );
-
         p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        assert_eq!(
+            client.check_response(&ScanOperators),
+            Err(nb::Error::Other(Error::Aborted))
+        );
+        assert_eq!(client.state, ClientState::Idle);
+        assert_eq!(client.abort(), Ok(false));
 
-        let cmd = Test2Cmd {
-            fun: Functionality::DM,
-            rst: Some(ResetMode::Reset),
-        };
-        assert_eq!(client.send(&cmd), Ok(NoResponse));
-
+        // The device may answer with an error result code instead
+        assert_eq!(client.send(&ScanOperators), Err(nb::Error::WouldBlock));
+        assert_eq!(client.abort(), Ok(true));
+        p.enqueue(Err(Error::Aborted)).unwrap();
         assert_eq!(
-            client.tx.s,
-            String::<consts::U32>::from("AT+CFUN=4,0\r\nAT+FUN=1,6\r\n"),
-            "Reverse order string did not match"
+            client.check_response(&ScanOperators),
+            Err(nb::Error::Other(Error::Aborted))
         );
+        assert_eq!(client.state, ClientState::Idle);
     }
 
     #[test]
-    #[ignore]
-    fn countdown() {
-        let (mut client, _, _) = setup!(Config::new(Mode::Timeout));
+    fn autobaud() {
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
 
+        let mut tried: Vec<u32, consts::U4> = Vec::new();
+        let baud = client.autobaud(Milliseconds(100), 2, &[9600, 115_200], |baud| {
+            tried.push(baud).unwrap();
+            if baud == 115_200 {
+                p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+            }
+        });
+        assert_eq!(baud, Ok(Some(115_200)));
+        assert_eq!(tried, [9600, 115_200]);
+        assert_eq!(
+            client.tx.s,
+            String::<consts::U64>::from("AT\r\nAT\r\nAT\r\n")
+        );
         assert_eq!(client.state, ClientState::Idle);
 
-        let cmd = Test2Cmd {
-            fun: Functionality::DM,
-            rst: Some(ResetMode::Reset),
-        };
-        assert_eq!(client.send(&cmd), Err(nb::Error::Other(Error::Timeout)));
-
-        // TODO: Test countdown is recived corretly
-        match client.config.mode {
-            Mode::Timeout => {} // assert_eq!(cd_mock.time, 180000),
-            _ => panic!("Wrong AT mode"),
-        }
+        assert_eq!(
+            client.autobaud(Milliseconds(100), 3, &[], |_| {}),
+            Err(Error::Timeout)
+        );
         assert_eq!(client.state, ClientState::Idle);
     }
 
     #[test]
-    fn blocking() {
-        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+    fn suspend_resume() {
+        let (mut client, _, _) = setup!(Config::new(Mode::NonBlocking));
 
         let cmd = SetModuleFunctionality {
             fun: Functionality::APM,
             rst: Some(ResetMode::DontReset),
         };
 
-        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+        let state = client.suspend();
+        assert_eq!(SuspendedState::from_bytes(state.to_bytes()), state);
 
+        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
         assert_eq!(client.state, ClientState::Idle);
-        assert_eq!(client.send(&cmd), Ok(NoResponse));
+        client.resume(state);
+        assert_eq!(client.state, ClientState::AwaitingResponse);
+
+        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        assert_eq!(client.check_response(&cmd), Ok(NoResponse));
         assert_eq!(client.state, ClientState::Idle);
-        assert_eq!(client.tx.s, String::<consts::U32>::from("AT+CFUN=4,0\r\n"));
+        assert!(client.suspend().cooldown_pending);
     }
 
     #[test]
-    fn non_blocking() {
-        let (mut client, mut p, _) = setup!(Config::new(Mode::NonBlocking));
+    fn suspend_resume_cooldown() {
+        let (mut client, mut p, _) =
+            setup!(Config::new(Mode::Timeout).cmd_cooldown(Milliseconds(20)));
+
+        // The pending cooldown of the command outlasts the suspension
+        p.enqueue(Ok(String::new())).unwrap();
+        assert_eq!(client.send(&Attach), Ok(NoResponse));
+        let state = SuspendedState::from_bytes(client.suspend().to_bytes());
+
+        let (mut client, _, _) = setup!(Config::new(Mode::Timeout).cmd_cooldown(Milliseconds(20)));
+        client.resume(state);
+        assert_eq!(client.cooldown_timer.time, 500);
+
+        // As does the cooldown to follow the response awaited
+        let (mut client, _, _) =
+            setup!(Config::new(Mode::NonBlocking).cmd_cooldown(Milliseconds(20)));
+        assert_eq!(client.send(&Attach), Err(nb::Error::WouldBlock));
+        let state = client.suspend();
+
+        let (mut client, mut p, _) =
+            setup!(Config::new(Mode::NonBlocking).cmd_cooldown(Milliseconds(20)));
+        client.resume(state);
+        p.enqueue(Ok(String::new())).unwrap();
+        assert_eq!(client.check_response(&Attach), Ok(NoResponse));
+        assert_eq!(client.cooldown_timer.time, 500);
+    }
+
+    #[test]
+    fn deadline_expired() {
+        let (mut client, _, _) = setup!(Config::new(Mode::Blocking));
+        let mut deadline = CdMock { time: 0 };
 
         let cmd = SetModuleFunctionality {
             fun: Functionality::APM,
             rst: Some(ResetMode::DontReset),
         };
 
+        let res = client.with_deadline(&mut deadline, Milliseconds(500), |c| c.send(&cmd));
+        assert_eq!(res, Err(nb::Error::Other(Error::Timeout)));
+        assert_eq!(deadline.time, 500);
         assert_eq!(client.state, ClientState::Idle);
-        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
-        assert_eq!(client.state, ClientState::AwaitingResponse);
+        assert_eq!(client.tx.s, String::<consts::U32>::from(""));
+    }
 
-        assert_eq!(client.check_response(&cmd), Err(nb::Error::WouldBlock));
+    #[test]
+    fn deadline_in_flight() {
+        let (mut client, _, _) = setup!(Config::new(Mode::NonBlocking));
+        let mut deadline = CdMock { time: 0 };
 
-        p.enqueue(Ok(String::<consts::U256>::from(""))).unwrap();
+        let cmd = SetModuleFunctionality {
+            fun: Functionality::APM,
+            rst: Some(ResetMode::DontReset),
+        };
 
+        assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
         assert_eq!(client.state, ClientState::AwaitingResponse);
 
-        assert_eq!(client.check_response(&cmd), Ok(NoResponse));
+        let res =
+            client.with_deadline(&mut deadline, Milliseconds(500), |c| c.check_response(&cmd));
+        assert_eq!(res, Err(nb::Error::Other(Error::Timeout)));
         assert_eq!(client.state, ClientState::Idle);
     }
 
-    // Testing unsupported feature in form of vec deserialization
     #[test]
-    #[ignore]
-    fn response_vec() {
-        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+    fn cmd_len() {
+        // "AT+CFUN=" + "4" + "," + "0" + "\r\n", with each argument at its maximum
+        let buf = String::<<SetModuleFunctionality as AtatCmd>::CommandLen>::new();
+        assert_eq!(buf.capacity(), 8 + 3 + 1 + 3 + 2);
 
-        let cmd = TestRespVecCmd {
-            fun: Functionality::APM,
-            rst: Some(ResetMode::DontReset),
+        // "AT+USOCR=" + "\"TLS1.2\"" + "," + "\"NON_BLOCKING\"" + "\r\n"
+        let buf = String::<<TestProtocolCmd as AtatCmd>::CommandLen>::new();
+        assert_eq!(buf.capacity(), 9 + 8 + 1 + 14 + 2);
+
+        let buf = String::<<GetRegistrationStatus as AtatCmd>::CommandLen>::new();
+        assert_eq!(buf.capacity(), "AT+CREG?\r\n".len());
+    }
+
+    #[test]
+    fn generic_derive() {
+        let cmd = WriteSocketData::<consts::U8> {
+            socket: 1,
+            data: String::from("hello"),
         };
+        assert_eq!(cmd.as_string().as_str(), "AT+USOWR=1,\"hello\"\r\n");
+        // "AT+USOWR=" + "1" + "," + "\"12345678\"" + "\r\n"
+        assert_eq!(cmd.as_string().capacity(), 9 + 3 + 1 + 10 + 2);
+        let cmd = WriteSocketData::<consts::U32> {
+            socket: 1,
+            data: String::from("hello"),
+        };
+        assert_eq!(cmd.as_string().capacity(), 9 + 3 + 1 + 34 + 2);
 
-        p.enqueue(Ok(String::<consts::U256>::from(
-            "+CUN: 22,16,\"0123456789012345\"",
-        )))
-        .unwrap();
+        let resp: ReadSocketData<consts::U8> = serde_at::from_str("+USORD: 1,\"hello\"").unwrap();
+        assert_eq!(resp.data, "hello");
 
-        let res_vec: Vec<u8, consts::U256> =
-            "0123456789012345".as_bytes().iter().cloned().collect();
+        let resp: BorrowedText = serde_at::from_str("+CSGT: \"hello\"").unwrap();
+        assert_eq!(resp.text, "hello");
+    }
 
-        assert_eq!(client.state, ClientState::Idle);
+    #[test]
+    fn custom_field_format() {
+        let clock = Clock {
+            year: 20,
+            month: 11,
+            day: 3,
+            hour: 9,
+            minute: 5,
+            second: 59,
+            zone: -8,
+        };
+        let cmd = SetClock {
+            clock: clock.clone(),
+        };
+        assert_eq!(
+            cmd.as_string().as_str(),
+            "AT+CCLK=\"20/11/03,09:05:59-08\"\r\n"
+        );
+
+        let resp: ClockResponse = serde_at::from_str("+CCLK: \"20/11/03,09:05:59-08\"").unwrap();
+        assert_eq!(resp.clock, clock);
+
+        // Errors of the custom serializer are no overlong commands
+        let cmd = SetClock {
+            clock: Clock { year: 100, ..clock },
+        };
+        assert_eq!(cmd.try_as_string(), Err(Error::Serialize));
+    }
 
+    #[test]
+    fn delimited_payload() {
+        let cmd = ReadSocket {
+            socket: 0,
+            length: 6,
+        };
+        assert_eq!(cmd.payload_prefix(), Some("+USORD:"));
         assert_eq!(
-            client.send(&cmd),
-            Ok(TestResponseVec {
-                socket: 22,
-                length: 16,
-                data: res_vec
+            cmd.parse("+USORD: 0,6,\"\r\nOK\r\n\""),
+            Ok(SocketBytes {
+                socket: 0,
+                length: 6,
+                data: String::from("\r\nOK\r\n"),
             })
         );
-        assert_eq!(client.state, ClientState::Idle);
-        assert_eq!(client.tx.s, String::<consts::U32>::from("AT+CFUN=4,0\r\n"));
     }
-    // Test response containing string
+
     #[test]
-    fn response_string() {
-        let (mut client, mut p, _) = setup!(Config::new(Mode::Blocking));
+    fn keyword_response() {
+        assert_eq!(GetSimState.parse("+CPIN: READY"), Ok(SimState::Ready));
+        assert_eq!(
+            GetSimState.parse("+CPIN: SIM PIN\r\n"),
+            Ok(SimState::SimPin)
+        );
+        assert_eq!(
+            GetSimState.parse("+CPIN: \"SIM PUK\""),
+            Ok(SimState::SimPuk)
+        );
+        assert!(GetSimState.parse("+CPIN: NOT READY").is_err());
 
-        // String last
-        let cmd = TestRespStringCmd {
-            fun: Functionality::APM,
-            rst: Some(ResetMode::DontReset),
-        };
+        assert_eq!(
+            serde_at::from_str::<RadioState>("+URADIO: CONNECTED"),
+            Ok(RadioState::Connected)
+        );
+    }
 
-        p.enqueue(Ok(String::<consts::U256>::from(
-            "+CUN: 22,16,\"0123456789012345\"",
-        )))
+    #[test]
+    fn repeated_groups() {
+        let resp: OperatorList<'_> = serde_at::from_str(
+            "+COPS: (2,\"Telia\",\"Telia\",\"24001\",7),(1,\"Tele2\",\"Tele2\",\"24007\",2),,(0-4),(0-2)",
+        )
         .unwrap();
+        assert_eq!(resp.operators.len(), 2);
+        assert_eq!(
+            resp.operators[1],
+            (
+                1,
+                String::from("Tele2"),
+                String::from("Tele2"),
+                String::from("24007"),
+                2
+            )
+        );
+        assert_eq!(resp.modes, Some(&b"(0-4)"[..]));
+        assert_eq!(resp.formats, Some(&b"(0-2)"[..]));
 
-        assert_eq!(client.state, ClientState::Idle);
+        let resp: OperatorList<'_> =
+            serde_at::from_str("+COPS: (2,\"Telia\",\"Telia\",\"24001\",7)").unwrap();
+        assert_eq!(resp.operators.len(), 1);
+        assert_eq!(resp.modes, None);
+    }
 
+    #[test]
+    fn flattened_response() {
+        let resp: GnssFix = serde_at::from_str("+QGPSLOC: 120553,59.33,18.06,31,7").unwrap();
         assert_eq!(
-            client.send(&cmd),
-            Ok(TestResponseString {
-                socket: 22,
-                length: 16,
-                data: String::<consts::U64>::from("0123456789012345")
-            })
+            resp,
+            GnssFix {
+                time: 120553,
+                location: Location {
+                    lat: 59.33,
+                    lon: 18.06,
+                    alt: 31,
+                },
+                satellites: Some(7),
+            }
         );
-        assert_eq!(client.state, ClientState::Idle);
 
-        // Mixed order for string
-        let cmd = TestRespStringMixCmd {
-            fun: Functionality::APM,
-            rst: Some(ResetMode::DontReset),
+        let resp: GnssFix = serde_at::from_str("+QGPSLOC: 120553,59.33,18.06,31").unwrap();
+        assert_eq!(resp.satellites, None);
+
+        assert!(serde_at::from_str::<GnssFix>("+QGPSLOC: 120553").is_err());
+    }
+
+    #[test]
+    fn constant_and_empty_args() {
+        let cmd = SetHexMode { hex_mode: 0 };
+        assert_eq!(cmd.as_string().as_str(), "AT+UDCONF=1,0\r\n");
+
+        let cmd = SelectOperator {
+            mode: 1,
+            operator: "Telia",
         };
+        assert_eq!(cmd.as_string().as_str(), "AT+COPS=1,,\"Telia\"\r\n");
+        // "AT+COPS=" + "255" + ",," + "\"0123456789abcdef\"" + "\r\n"
+        assert_eq!(cmd.as_string().capacity(), 8 + 3 + 2 + 18 + 2);
+    }
 
-        p.enqueue(Ok(String::<consts::U256>::from(
-            "+CUN: \"0123456789012345\",22,16",
-        )))
-        .unwrap();
+    #[test]
+    fn bool_formats() {
+        let cmd = SetFlags {
+            enable: true,
+            led: false,
+            verbose: true,
+        };
+        assert_eq!(cmd.as_string().as_str(), "AT+XFLAG=1,\"OFF\",1\r\n");
+        // "AT+XFLAG=" + "1" + "\"OFF\"" + "1" + ",," + "\r\n"
+        assert_eq!(cmd.as_string().capacity(), 9 + 1 + 5 + 1 + 2 + 2);
+        let cmd = SetFlags {
+            enable: false,
+            led: true,
+            verbose: false,
+        };
+        assert_eq!(cmd.as_string().as_str(), "AT+XFLAG=0,\"ON\"\r\n");
 
+        let resp: Flags = serde_at::from_str("+XFLAG: 1,ON,1").unwrap();
         assert_eq!(
-            client.send(&cmd),
-            Ok(TestResponseStringMixed {
-                socket: 22,
-                length: 16,
-                data: String::<consts::U64>::from("0123456789012345")
-            })
+            resp,
+            Flags {
+                enable: true,
+                led: true,
+                verbose: true
+            }
+        );
+        let resp: Flags = serde_at::from_str("+XFLAG: 0,\"off\"").unwrap();
+        assert_eq!(
+            resp,
+            Flags {
+                enable: false,
+                led: false,
+                verbose: false
+            }
         );
-        assert_eq!(client.state, ClientState::Idle);
     }
 
     #[test]
-    fn urc() {
-        let (mut client, _, mut urc_p) = setup!(Config::new(Mode::NonBlocking));
+    fn enum_cmd() {
+        let cmd = SetPacketSwitchedConfig::Apn {
+            profile_id: 0,
+            apn: "internet",
+        };
+        assert_eq!(cmd.as_string().as_str(), "AT+UPSD=0,1,\"internet\"\r\n");
+        let cmd = SetPacketSwitchedConfig::IpAddress {
+            profile_id: 2,
+            ip: "0.0.0.0",
+        };
+        assert_eq!(cmd.as_string().as_str(), "AT+UPSD=2,7,\"0.0.0.0\"\r\n");
+        let cmd = SetPacketSwitchedConfig::Protocol { protocol: 1 };
+        assert_eq!(cmd.as_string().as_str(), "AT+UPSD=0,1\r\n");
 
-        urc_p
-            .enqueue(String::<consts::U256>::from("+UMWI: 0, 1"))
-            .unwrap();
+        // The lengths of all variants add up to an upper bound
+        let buf = String::<<SetPacketSwitchedConfig as AtatCmd>::CommandLen>::new();
+        assert!(buf.capacity() >= "AT+UPSD=255,1,\"0123456789abcdef\"\r\n".len());
+    }
 
-        assert_eq!(client.state, ClientState::Idle);
-        assert!(client.check_urc::<Urc>().is_some());
-        assert_eq!(client.state, ClientState::Idle);
+    #[test]
+    fn cmd_info() {
+        assert_eq!(
+            SetModuleFunctionality::CMD_INFO,
+            atat::CmdInfo {
+                name: "SetModuleFunctionality",
+                syntax: "AT+CFUN=<fun>,<rst>",
+                timeout_ms: 180000,
+                response: "NoResponse",
+            }
+        );
     }
 
     #[test]
@@ -531,7 +3635,12 @@ mod test {
         p.enqueue(resp).unwrap();
 
         assert_eq!(client.state, ClientState::Idle);
-        assert_eq!(client.send(&cmd), Err(nb::Error::Other(Error::ParseString)));
+        match client.send(&cmd) {
+            Err(nb::Error::Other(Error::ParseString(context))) => {
+                assert_eq!(context, ErrorContext::response("+CUN: 22,16,22"));
+            }
+            r => panic!("Unexpected result: {:?}", r),
+        }
         assert_eq!(client.state, ClientState::Idle);
     }
 }
@@ -0,0 +1,130 @@
+//! Mobile termination control and status commands (3GPP TS 27.007 sections
+//! 8 and 9)
+
+use heapless::{consts, String};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use super::NoResponse;
+use crate as atat;
+use crate::atat_derive::{AtatCmd, AtatEnum, AtatLen, AtatResp};
+
+/// Level of functionality `<fun>` of `AT+CFUN`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+#[repr(u8)]
+pub enum Functionality {
+    Minimum = 0,
+    Full = 1,
+    /// Disable transmitting RF circuits only
+    DisableTx = 2,
+    /// Disable receiving RF circuits only
+    DisableRx = 3,
+    /// Disable both transmitting and receiving RF circuits
+    AirplaneMode = 4,
+}
+
+/// Whether to reset the device before setting the functionality, `<rst>` of
+/// `AT+CFUN`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+#[repr(u8)]
+pub enum ResetMode {
+    DontReset = 0,
+    Reset = 1,
+}
+
+/// Set phone functionality (`AT+CFUN`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CFUN", NoResponse, timeout_ms = 180000)]
+pub struct SetFunctionality {
+    #[at_arg(position = 0)]
+    pub fun: Functionality,
+    #[at_arg(position = 1)]
+    pub rst: Option<ResetMode>,
+}
+
+/// Read phone functionality (`AT+CFUN?`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CFUN?", FunctionalityResponse)]
+pub struct GetFunctionality;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct FunctionalityResponse {
+    #[at_arg(position = 0)]
+    pub fun: Functionality,
+}
+
+/// Status of the SIM, `<code>` of `+CPIN`
+#[derive(Debug, Clone, Copy, PartialEq, AtatEnum)]
+pub enum PinStatus {
+    #[at_arg(rename = "READY")]
+    Ready,
+    #[at_arg(rename = "SIM PIN")]
+    SimPin,
+    #[at_arg(rename = "SIM PUK")]
+    SimPuk,
+    #[at_arg(rename = "PH-SIM PIN")]
+    PhoneSimPin,
+    #[at_arg(rename = "SIM PIN2")]
+    SimPin2,
+    #[at_arg(rename = "SIM PUK2")]
+    SimPuk2,
+}
+
+/// Read the SIM status (`AT+CPIN?`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CPIN?", PinStatusResponse, timeout_ms = 5000)]
+pub struct GetPinStatus;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct PinStatusResponse {
+    #[at_arg(position = 0)]
+    pub code: PinStatus,
+}
+
+/// Enter the PIN (`AT+CPIN`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CPIN", NoResponse, timeout_ms = 5000)]
+pub struct EnterPin<'a> {
+    #[at_arg(position = 0, len = 8)]
+    pub pin: &'a str,
+}
+
+/// Format of the errors, `<n>` of `AT+CMEE`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+#[repr(u8)]
+pub enum ErrorReporting {
+    /// Plain `ERROR`
+    Disabled = 0,
+    /// `+CME ERROR: <err>`, with a numeric `<err>`
+    Numeric = 1,
+    /// `+CME ERROR: <err>`, with a verbose `<err>`
+    Verbose = 2,
+}
+
+/// Report mobile termination error (`AT+CMEE`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CMEE", NoResponse)]
+pub struct SetErrorReporting {
+    #[at_arg(position = 0)]
+    pub n: ErrorReporting,
+}
+
+/// Read the real-time clock (`AT+CCLK?`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CCLK?", Clock)]
+pub struct GetClock;
+
+/// Set the real-time clock (`AT+CCLK`), to `yy/MM/dd,hh:mm:ss±zz`
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CCLK", NoResponse)]
+pub struct SetClock<'a> {
+    #[at_arg(position = 0, len = 20)]
+    pub time: &'a str,
+}
+
+/// The time as `yy/MM/dd,hh:mm:ss±zz`, where `zz` is the time zone in
+/// quarters of an hour
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct Clock {
+    #[at_arg(position = 0)]
+    pub time: String<consts::U24>,
+}
@@ -0,0 +1,51 @@
+//! General commands (3GPP TS 27.007 section 5): identification of the device
+
+use heapless::{consts, String};
+
+use crate as atat;
+use crate::atat_derive::{AtatCmd, AtatResp};
+
+/// Request manufacturer identification (`AT+CGMI`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGMI", ManufacturerId, parse_raw = true)]
+pub struct GetManufacturerId;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct ManufacturerId {
+    #[at_arg(position = 0)]
+    pub id: String<consts::U64>,
+}
+
+/// Request model identification (`AT+CGMM`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGMM", ModelId, parse_raw = true)]
+pub struct GetModelId;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct ModelId {
+    #[at_arg(position = 0)]
+    pub id: String<consts::U64>,
+}
+
+/// Request revision identification (`AT+CGMR`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGMR", FirmwareVersion, parse_raw = true)]
+pub struct GetFirmwareVersion;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct FirmwareVersion {
+    #[at_arg(position = 0)]
+    pub version: String<consts::U64>,
+}
+
+/// Request product serial number identification (`AT+CGSN`), the IMEI of
+/// most devices. The `+CGSN: ` prefix is optional.
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGSN", Imei, resp_prefix = "+CGSN")]
+pub struct GetImei;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct Imei {
+    #[at_arg(position = 0)]
+    pub imei: u64,
+}
@@ -0,0 +1,192 @@
+//! Common commands of 3GPP TS 27.007 and 27.005, shared by most cellular
+//! modems
+//!
+//! Enabled by the `commands` feature. Vendor specific commands are best
+//! declared by the driver, next to these:
+//!
+//! ```ignore
+//! use atat::commands::{control, network};
+//!
+//! block!(client.send(&control::SetFunctionality {
+//!     fun: control::Functionality::Full,
+//!     rst: None,
+//! }))?;
+//! let network::SignalQuality { rssi, .. } = block!(client.send(&network::GetSignalQuality))?;
+//! ```
+//!
+//! The URCs of these commands are gathered in [`Urc`], to be matched before
+//! any vendor specific URCs.
+//!
+//! [`Urc`]: enum.Urc.html
+
+use crate as atat;
+use crate::atat_derive::{AtatResp, AtatUrc};
+
+pub mod control;
+pub mod general;
+pub mod network;
+pub mod packet;
+pub mod sms;
+
+/// Response of the commands answered with just `OK`
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct NoResponse;
+
+/// The URCs enabled by the commands of this module
+#[derive(Debug, Clone, PartialEq, AtatUrc)]
+pub enum Urc {
+    /// Enabled by [`SetNetworkRegistration`](network/struct.SetNetworkRegistration.html)
+    #[at_urc("+CREG")]
+    NetworkRegistration(network::RegistrationUrc),
+    /// Enabled by [`SetGprsRegistration`](packet/struct.SetGprsRegistration.html)
+    #[at_urc("+CGREG")]
+    GprsRegistration(network::RegistrationUrc),
+    /// Sent by most devices once the SIM is initialized
+    #[at_urc("+CPIN")]
+    PinStatus(control::PinStatusResponse),
+    /// Enabled by `AT+CNMI`
+    #[at_urc("+CMTI")]
+    NewMessage(sms::NewMessageIndication),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::traits::{AtatCmd, AtatUrc as _};
+    use heapless::{consts, String};
+
+    #[test]
+    fn urc() {
+        assert_eq!(
+            Urc::parse("+CREG: 5,\"4A2B\",\"00C3F2A1\""),
+            Ok(Urc::NetworkRegistration(network::RegistrationUrc {
+                stat: network::RegistrationStatus::Roaming,
+                lac: Some(String::from("4A2B")),
+                ci: Some(String::from("00C3F2A1")),
+            }))
+        );
+        assert_eq!(
+            Urc::parse("+CGREG: 2"),
+            Ok(Urc::GprsRegistration(network::RegistrationUrc {
+                stat: network::RegistrationStatus::Searching,
+                lac: None,
+                ci: None,
+            }))
+        );
+        assert_eq!(
+            Urc::parse("+CPIN: READY"),
+            Ok(Urc::PinStatus(control::PinStatusResponse {
+                code: control::PinStatus::Ready
+            }))
+        );
+        assert_eq!(
+            Urc::parse("+CMTI: \"SM\",3"),
+            Ok(Urc::NewMessage(sms::NewMessageIndication {
+                mem: String::from("SM"),
+                index: 3
+            }))
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            control::SetFunctionality {
+                fun: control::Functionality::Minimum,
+                rst: Some(control::ResetMode::Reset)
+            }
+            .as_string(),
+            String::<consts::U32>::from("AT+CFUN=0,1\r\n")
+        );
+        assert_eq!(
+            control::EnterPin { pin: "1234" }.as_string(),
+            String::<consts::U32>::from("AT+CPIN=\"1234\"\r\n")
+        );
+        assert_eq!(
+            network::SetNetworkRegistration {
+                n: network::RegistrationUrcMode::Location
+            }
+            .as_string(),
+            String::<consts::U32>::from("AT+CREG=2\r\n")
+        );
+        assert_eq!(
+            packet::SetAttach {
+                state: packet::AttachState::Attached
+            }
+            .as_string(),
+            String::<consts::U32>::from("AT+CGATT=1\r\n")
+        );
+        assert_eq!(
+            sms::SetMessageFormat {
+                mode: sms::MessageFormat::Text
+            }
+            .as_string(),
+            String::<consts::U32>::from("AT+CMGF=1\r\n")
+        );
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(
+            general::GetManufacturerId.parse("u-blox"),
+            Ok(general::ManufacturerId {
+                id: String::from("u-blox")
+            })
+        );
+        assert_eq!(
+            general::GetImei.parse("+CGSN: 356938035643809"),
+            Ok(general::Imei {
+                imei: 356938035643809
+            })
+        );
+        assert_eq!(
+            general::GetImei.parse("356938035643809"),
+            Ok(general::Imei {
+                imei: 356938035643809
+            })
+        );
+        assert_eq!(
+            control::GetFunctionality.parse("+CFUN: 4"),
+            Ok(control::FunctionalityResponse {
+                fun: control::Functionality::AirplaneMode
+            })
+        );
+        assert_eq!(
+            control::GetPinStatus.parse("+CPIN: SIM PIN"),
+            Ok(control::PinStatusResponse {
+                code: control::PinStatus::SimPin
+            })
+        );
+        assert_eq!(
+            control::GetClock.parse("+CCLK: \"20/10/16,12:34:56+08\""),
+            Ok(control::Clock {
+                time: String::from("20/10/16,12:34:56+08")
+            })
+        );
+        assert_eq!(
+            network::GetNetworkRegistration.parse("+CREG: 2,1,\"4A2B\",\"00C3F2A1\""),
+            Ok(network::NetworkRegistration {
+                n: network::RegistrationUrcMode::Location,
+                stat: network::RegistrationStatus::Home,
+                lac: Some(String::from("4A2B")),
+                ci: Some(String::from("00C3F2A1")),
+            })
+        );
+        assert_eq!(
+            network::GetSignalQuality.parse("+CSQ: 31,99"),
+            Ok(network::SignalQuality { rssi: 31, ber: 99 })
+        );
+        assert_eq!(
+            packet::GetAttach.parse("+CGATT: 0"),
+            Ok(packet::AttachResponse {
+                state: packet::AttachState::Detached
+            })
+        );
+        assert_eq!(
+            sms::GetMessageFormat.parse("+CMGF: 0"),
+            Ok(sms::MessageFormatResponse {
+                mode: sms::MessageFormat::Pdu
+            })
+        );
+    }
+}
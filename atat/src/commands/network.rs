@@ -0,0 +1,88 @@
+//! Network service commands (3GPP TS 27.007 section 7)
+
+use heapless::{consts, String};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use super::NoResponse;
+use crate as atat;
+use crate::atat_derive::{AtatCmd, AtatLen, AtatResp};
+
+/// Which registration URCs to send, `<n>` of `AT+CREG` and `AT+CGREG`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+#[repr(u8)]
+pub enum RegistrationUrcMode {
+    Disabled = 0,
+    /// `+CREG: <stat>`
+    Enabled = 1,
+    /// `+CREG: <stat>[,<lac>,<ci>]`
+    Location = 2,
+}
+
+/// Registration status `<stat>` of `+CREG` and `+CGREG`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+#[repr(u8)]
+pub enum RegistrationStatus {
+    NotRegistered = 0,
+    Home = 1,
+    Searching = 2,
+    Denied = 3,
+    Unknown = 4,
+    Roaming = 5,
+}
+
+/// Network registration (`AT+CREG`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CREG", NoResponse)]
+pub struct SetNetworkRegistration {
+    #[at_arg(position = 0)]
+    pub n: RegistrationUrcMode,
+}
+
+/// Read the network registration status (`AT+CREG?`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CREG?", NetworkRegistration)]
+pub struct GetNetworkRegistration;
+
+/// Response to `AT+CREG?` and `AT+CGREG?`
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct NetworkRegistration {
+    #[at_arg(position = 0)]
+    pub n: RegistrationUrcMode,
+    #[at_arg(position = 1)]
+    pub stat: RegistrationStatus,
+    /// Location area code, in hex
+    #[at_arg(position = 2)]
+    pub lac: Option<String<consts::U4>>,
+    /// Cell ID, in hex
+    #[at_arg(position = 3)]
+    pub ci: Option<String<consts::U8>>,
+}
+
+/// The `+CREG` and `+CGREG` URCs
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct RegistrationUrc {
+    #[at_arg(position = 0)]
+    pub stat: RegistrationStatus,
+    /// Location area code, in hex
+    #[at_arg(position = 1)]
+    pub lac: Option<String<consts::U4>>,
+    /// Cell ID, in hex
+    #[at_arg(position = 2)]
+    pub ci: Option<String<consts::U8>>,
+}
+
+/// Signal quality (`AT+CSQ`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CSQ", SignalQuality)]
+pub struct GetSignalQuality;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct SignalQuality {
+    /// Received signal strength, from 0 (-113 dBm or less) to 31 (-51 dBm or
+    /// more), or 99 if unknown
+    #[at_arg(position = 0)]
+    pub rssi: u8,
+    /// Channel bit error rate, from 0 to 7, or 99 if unknown
+    #[at_arg(position = 1)]
+    pub ber: u8,
+}
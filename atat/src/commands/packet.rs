@@ -0,0 +1,48 @@
+//! Packet domain commands (3GPP TS 27.007 section 10)
+
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use super::network::{NetworkRegistration, RegistrationUrcMode};
+use super::NoResponse;
+use crate as atat;
+use crate::atat_derive::{AtatCmd, AtatLen, AtatResp};
+
+/// Packet domain attachment state, `<state>` of `AT+CGATT`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+#[repr(u8)]
+pub enum AttachState {
+    Detached = 0,
+    Attached = 1,
+}
+
+/// Attach to or detach from the packet domain service (`AT+CGATT`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGATT", NoResponse, timeout_ms = 180000)]
+pub struct SetAttach {
+    #[at_arg(position = 0)]
+    pub state: AttachState,
+}
+
+/// Read the packet domain attachment state (`AT+CGATT?`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGATT?", AttachResponse)]
+pub struct GetAttach;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct AttachResponse {
+    #[at_arg(position = 0)]
+    pub state: AttachState,
+}
+
+/// GPRS network registration (`AT+CGREG`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGREG", NoResponse)]
+pub struct SetGprsRegistration {
+    #[at_arg(position = 0)]
+    pub n: RegistrationUrcMode,
+}
+
+/// Read the GPRS network registration status (`AT+CGREG?`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CGREG?", NetworkRegistration)]
+pub struct GetGprsRegistration;
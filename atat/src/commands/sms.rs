@@ -0,0 +1,46 @@
+//! SMS commands (3GPP TS 27.005 section 3)
+//!
+//! Sending messages is covered by the flows of [`atat::sms`](../../sms/index.html).
+
+use heapless::{consts, String};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use super::NoResponse;
+use crate as atat;
+use crate::atat_derive::{AtatCmd, AtatLen, AtatResp};
+
+/// Message format `<mode>` of `AT+CMGF`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+#[repr(u8)]
+pub enum MessageFormat {
+    Pdu = 0,
+    Text = 1,
+}
+
+/// Select the message format (`AT+CMGF`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CMGF", NoResponse)]
+pub struct SetMessageFormat {
+    #[at_arg(position = 0)]
+    pub mode: MessageFormat,
+}
+
+/// Read the message format (`AT+CMGF?`)
+#[derive(Debug, Clone, AtatCmd)]
+#[at_cmd("+CMGF?", MessageFormatResponse)]
+pub struct GetMessageFormat;
+
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct MessageFormatResponse {
+    #[at_arg(position = 0)]
+    pub mode: MessageFormat,
+}
+
+/// The `+CMTI` URC: a new message was stored at `index` of the memory `mem`
+#[derive(Debug, Clone, PartialEq, AtatResp)]
+pub struct NewMessageIndication {
+    #[at_arg(position = 0)]
+    pub mem: String<consts::U4>,
+    #[at_arg(position = 1)]
+    pub index: u16,
+}
@@ -0,0 +1,110 @@
+//! Queues guarded by critical sections, used in place of the lock-free
+//! `heapless::spsc` queues with the `critical-section` feature.
+//!
+//! Every access to a queue takes a critical section of the
+//! [`critical-section`] implementation of the target, so that the ingress
+//! manager and the client can run on different cores (e.g. ESP32, RP2040),
+//! or in different threads of an RTOS, without relying on the memory
+//! ordering of the lock-free queues.
+//!
+//! [`critical-section`]: https://crates.io/crates/critical-section
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use heapless::{spsc, ArrayLength};
+
+/// A queue with a capacity of `N` items, split into a [`Producer`] and a
+/// [`Consumer`] like a `heapless::spsc::Queue`.
+///
+/// [`Producer`]: struct.Producer.html
+/// [`Consumer`]: struct.Consumer.html
+pub struct Queue<T, N: ArrayLength<T>> {
+    inner: Mutex<RefCell<spsc::Queue<T, N>>>,
+}
+
+impl<T, N: ArrayLength<T>> Queue<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(spsc::Queue(heapless::i::Queue::new()))),
+        }
+    }
+
+    pub fn split(&'static mut self) -> (Producer<T, N>, Consumer<T, N>) {
+        let queue: &'static Self = self;
+        (Producer { queue }, Consumer { queue })
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut spsc::Queue<T, N>) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.inner.borrow(cs).borrow_mut()))
+    }
+}
+
+/// The end of a [`Queue`](struct.Queue.html) enqueueing items
+pub struct Producer<T: 'static, N: ArrayLength<T> + 'static> {
+    queue: &'static Queue<T, N>,
+}
+
+impl<T: 'static, N: ArrayLength<T> + 'static> Producer<T, N> {
+    /// Whether an item can be enqueued.
+    pub fn ready(&self) -> bool {
+        self.queue.with(|q| q.len() < q.capacity())
+    }
+
+    pub fn enqueue(&mut self, item: T) -> Result<(), T> {
+        self.queue.with(|q| q.enqueue(item))
+    }
+}
+
+/// The end of a [`Queue`](struct.Queue.html) dequeueing items
+pub struct Consumer<T: 'static, N: ArrayLength<T> + 'static> {
+    queue: &'static Queue<T, N>,
+}
+
+impl<T: 'static, N: ArrayLength<T> + 'static> Consumer<T, N> {
+    /// Whether an item can be dequeued.
+    pub fn ready(&self) -> bool {
+        self.queue.with(|q| !q.is_empty())
+    }
+
+    /// The oldest item, if any, without dequeueing it.
+    pub fn peek(&self) -> Option<&T> {
+        let item = self.queue.with(|q| q.peek().map(|item| item as *const T));
+        // Safety: Only this consumer removes items, which it cannot do while
+        // the item is borrowed, and the producer leaves the slots of queued
+        // items alone.
+        item.map(|item| unsafe { &*item })
+    }
+
+    pub fn dequeue(&mut self) -> Option<T> {
+        self.queue.with(|q| q.dequeue())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use heapless::consts;
+
+    #[test]
+    fn split_across_threads() {
+        static mut QUEUE: Queue<u32, consts::U4> = Queue::new();
+        let (mut p, mut c) = unsafe { QUEUE.split() };
+
+        let producer = std::thread::spawn(move || {
+            for i in 0..100 {
+                while p.enqueue(i).is_err() {}
+            }
+        });
+        let mut next = 0;
+        while next < 100 {
+            if let Some(&peeked) = c.peek() {
+                assert_eq!(c.dequeue(), Some(peeked));
+                assert_eq!(peeked, next);
+                next += 1;
+            }
+        }
+        producer.join().unwrap();
+        assert!(!c.ready());
+    }
+}
@@ -0,0 +1,146 @@
+//! Compile time selection of the AT dialect spoken by the device
+//!
+//! The ingress manager is generic over a [`Dialect`], defining the result
+//! codes, prompts and quirks of a device as associated consts. As these are
+//! known at compile time, the branches of dialects that are not in use are
+//! removed by the compiler.
+//!
+//! The line terminators are not part of the dialect, as they can be changed at
+//! runtime through `ATS3` and `ATS4`. See [`Config`](../struct.Config.html).
+//!
+//! A device deviating from V.25ter overrides the relevant consts:
+//!
+//! ```
+//! use atat::dialect::{Dialect, V25ter};
+//!
+//! struct MyModem;
+//!
+//! impl Dialect for MyModem {
+//!     const PROMPTS: &'static [&'static str] = &[">"];
+//!     const CONNECT_ENTERS_DATA_MODE: bool = false;
+//! }
+//! ```
+//!
+//! [`Dialect`]: trait.Dialect.html
+
+use crate::error::{Error, ErrorContext};
+
+/// An AT dialect, as spoken by a device.
+///
+/// All consts default to the V.25ter behaviour.
+pub trait Dialect {
+    /// Final result code of a successful command
+    const OK: &'static str = "OK";
+
+    /// Final result code of a failed command
+    const ERROR: &'static str = "ERROR";
+
+    /// Prompts requesting a payload, that are answered with an empty response
+    const PROMPTS: &'static [&'static str] = &[">", "@"];
+
+    /// Final result codes, besides [`ERROR`](#associatedconstant.ERROR), that
+    /// terminate a response with an error.
+    const ERROR_RESULT_CODES: &'static [(&'static str, Error)] = &[
+        ("NO CARRIER", Error::NoCarrier),
+        ("BUSY", Error::Busy),
+        ("NO DIALTONE", Error::NoDialtone),
+        ("NO ANSWER", Error::NoAnswer),
+        ("ABORTED", Error::Aborted),
+    ];
+
+    /// Result codes that are received outside of a response, and thus
+    /// handled as URCs.
+    const UNSOLICITED_RESULT_CODES: &'static [&'static str] = &["RING", "NO CARRIER"];
+
+    /// Banners sent by the device after booting, each forming a whole line.
+    /// A banner aborts the response in progress with `Error::ModemReset`, and
+    /// is passed on as a URC.
+    const BOOT_BANNERS: &'static [&'static str] = &["RDY", "+PBREADY", "^SYSSTART"];
+
+    /// Numeric result codes (`ATV0`) and their verbose counterparts, used if
+    /// enabled through `Config::with_numeric_result_codes`.
+    const NUMERIC_RESULT_CODES: &'static [(u8, &'static str)] = &[
+        (0, "OK"),
+        (1, "CONNECT"),
+        (2, "RING"),
+        (3, "NO CARRIER"),
+        (4, "ERROR"),
+        (6, "NO DIALTONE"),
+        (7, "BUSY"),
+        (8, "NO ANSWER"),
+    ];
+
+    /// Quirk: The device switches to data mode after the `CONNECT` result code.
+    /// If `false`, `CONNECT` is handled as a regular final result code.
+    const CONNECT_ENTERS_DATA_MODE: bool = true;
+
+    /// Quirk: A command requesting a payload is answered with `OK`, followed
+    /// by the prompt. The `OK` then completes the command, and the prompt is
+    /// discarded.
+    const PROMPT_AFTER_OK: bool = false;
+
+    /// Quirk: The length of a URC carrying binary data (e.g. `+IPD,<len>:`),
+    /// in received bytes including the header, if `buf` starts with the
+    /// complete header of one. Such URCs are passed on once complete,
+    /// regardless of any line terminators in the data.
+    fn binary_urc_len(_buf: &str) -> Option<usize> {
+        None
+    }
+}
+
+/// The dialect of ITU-T V.25ter, spoken by most devices.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct V25ter;
+
+impl Dialect for V25ter {}
+
+/// The dialect of the Espressif ESP-AT firmware for ESP8266 and ESP32.
+///
+/// Besides the V.25ter result codes this handles
+/// - the `ready` boot banner
+/// - the `WIFI ...` events, as URCs
+/// - `SEND FAIL` as `Error::InvalidResponse`
+/// - `busy p...` and `busy s...`, sent while a command is still processed,
+///   as `Error::Busy`
+/// - the `>` prompt following the `OK` of `AT+CIPSEND`
+/// - the `+IPD,[<link ID>,]<len>:<data>` URCs, carrying binary data
+///
+/// `CONNECT` reports a connection opened by `AT+CIPSTART` rather than data
+/// mode. As the URCs are limited to 256 bytes, receiving large amounts of
+/// data should use the passive receive mode (`AT+CIPRECVMODE=1`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EspAt;
+
+impl Dialect for EspAt {
+    const PROMPTS: &'static [&'static str] = &[">"];
+
+    const ERROR_RESULT_CODES: &'static [(&'static str, Error)] = &[
+        ("SEND FAIL", Error::InvalidResponse(ErrorContext::new())),
+        ("busy p", Error::Busy),
+        ("busy s", Error::Busy),
+    ];
+
+    const UNSOLICITED_RESULT_CODES: &'static [&'static str] =
+        &["WIFI CONNECTED", "WIFI GOT IP", "WIFI DISCONNECT"];
+
+    const BOOT_BANNERS: &'static [&'static str] = &["ready"];
+
+    const CONNECT_ENTERS_DATA_MODE: bool = false;
+
+    const PROMPT_AFTER_OK: bool = true;
+
+    fn binary_urc_len(buf: &str) -> Option<usize> {
+        if !buf.starts_with("+IPD,") {
+            return None;
+        }
+        let header_len = buf.find(':')? + 1;
+        let mut params = buf["+IPD,".len()..header_len - 1].split(',');
+        // `[<link ID>,]<len>[,<remote IP>,<remote port>]`, as of `AT+CIPMUX`
+        // and `AT+CIPDINFO`
+        let len = match buf[..header_len].matches(',').count() {
+            1 | 3 => params.next(),
+            _ => params.nth(1),
+        }?;
+        Some(header_len + len.parse::<usize>().ok()?)
+    }
+}
@@ -0,0 +1,234 @@
+//! Receiving through DMA into a ring buffer
+//!
+//! At high baud rates, moving every received byte through the Rx queue of
+//! [`new_split`](../fn.new_split.html) dominates the CPU time. Instead, the Rx
+//! DMA can write into a ring buffer in circular mode, which is consumed by a
+//! [`DmaIngress`] in place. The interrupts of the DMA only report how far it
+//! has written, through a [`DmaIndex`]:
+//!
+//! ```ignore
+//! use atat::dma::{DmaIndex, DmaIngress};
+//!
+//! static mut RX_BUF: [u8; 512] = [0; 512];
+//! static RX_INDEX: DmaIndex = DmaIndex::new();
+//!
+//! let (client, ingress) = atat::new(tx, timer, cooldown_timer, config, None);
+//! let mut ingress = DmaIngress::new(ingress, unsafe { &mut RX_BUF }, &RX_INDEX);
+//! let (ptr, len) = ingress.buffer();
+//! rx_dma.start_circular(ptr, len);
+//!
+//! // DMA half/full transfer interrupts
+//! RX_INDEX.half_complete(512);
+//! RX_INDEX.full_complete(512);
+//! // UART idle line interrupt
+//! RX_INDEX.set(512 - rx_dma.remaining());
+//!
+//! // Thread mode
+//! ingress.digest();
+//! ```
+//!
+//! The ring buffer must be digested at least once per half of its length,
+//! as bytes that are overwritten before being digested cannot be detected.
+//!
+//! [`DmaIngress`]: struct.DmaIngress.html
+//! [`DmaIndex`]: struct.DmaIndex.html
+
+use core::sync::atomic::{compiler_fence, AtomicUsize, Ordering};
+
+use heapless::consts;
+
+use crate::dialect::{Dialect, V25ter};
+use crate::ingress_manager::{IngressManager, UrcMatcher};
+use crate::stats::{QueueLevels, QueueStats};
+#[cfg(feature = "trace")]
+use crate::trace::TraceRing;
+
+/// The position up to which the DMA has written the ring buffer, updated from
+/// the interrupts of the DMA.
+#[derive(Debug, Default)]
+pub struct DmaIndex(AtomicUsize);
+
+impl DmaIndex {
+    pub const fn new() -> Self {
+        DmaIndex(AtomicUsize::new(0))
+    }
+
+    /// The DMA has written up to `index`, e.g. on an idle line interrupt,
+    /// where `index` is the buffer length minus the remaining transfers.
+    pub fn set(&self, index: usize) {
+        self.0.store(index, Ordering::Release);
+    }
+
+    /// The DMA has filled the first half of the buffer of length `len`.
+    pub fn half_complete(&self, len: usize) {
+        self.set(len / 2);
+    }
+
+    /// The DMA has filled the buffer of length `len`, and wraps around.
+    pub fn full_complete(&self, len: usize) {
+        self.set(len);
+    }
+
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// An ingress manager consuming a ring buffer written by DMA, see the
+/// [module documentation](index.html).
+pub struct DmaIngress<U, D = V25ter> {
+    ingress: IngressManager<U, D>,
+    buf: *const u8,
+    len: usize,
+    read: usize,
+    index: &'static DmaIndex,
+}
+
+// The buffer is only written by the DMA, and only read through this struct.
+unsafe impl<U: Send, D> Send for DmaIngress<U, D> {}
+
+impl<U, D> DmaIngress<U, D>
+where
+    U: UrcMatcher<MaxLen = consts::U256>,
+    D: Dialect,
+{
+    /// Consume the ring buffer `buf`, written by DMA up to `index`.
+    pub fn new(
+        ingress: IngressManager<U, D>,
+        buf: &'static mut [u8],
+        index: &'static DmaIndex,
+    ) -> Self {
+        index.set(0);
+        DmaIngress {
+            ingress,
+            buf: buf.as_ptr(),
+            len: buf.len(),
+            read: 0,
+            index,
+        }
+    }
+
+    /// Address and length of the ring buffer, to configure the DMA with.
+    pub fn buffer(&self) -> (*mut u8, usize) {
+        (self.buf as *mut u8, self.len)
+    }
+
+    /// The last frames digested. See
+    /// [`IngressManager::trace`](../struct.IngressManager.html#method.trace).
+    #[cfg(feature = "trace")]
+    pub fn trace(&self) -> &TraceRing {
+        self.ingress.trace()
+    }
+
+    /// Frames dropped on the way to the client. See
+    /// [`IngressManager::stats`](../struct.IngressManager.html#method.stats).
+    pub fn stats(&self) -> &QueueStats {
+        self.ingress.stats()
+    }
+
+    /// Fill levels of the queues to and from the client. See
+    /// [`IngressManager::queue_levels`](../struct.IngressManager.html#method.queue_levels).
+    pub fn queue_levels(&self) -> Option<QueueLevels> {
+        self.ingress.queue_levels()
+    }
+
+    /// Reset the counters of dropped frames.
+    pub fn clear_stats(&mut self) {
+        self.ingress.clear_stats();
+    }
+
+    /// Move the bytes written by the DMA since the last call into the receive
+    /// buffer, and process it. See
+    /// [`IngressManager::digest`](../struct.IngressManager.html#method.digest).
+    pub fn digest(&mut self) {
+        let write = core::cmp::min(self.index.get(), self.len);
+        compiler_fence(Ordering::Acquire);
+        if write < self.read {
+            self.consume(self.read, self.len);
+            self.consume(0, write);
+        } else {
+            self.consume(self.read, write);
+        }
+        self.read = write % self.len;
+        self.ingress.digest();
+    }
+
+    fn consume(&mut self, from: usize, to: usize) {
+        if from < to {
+            // The DMA has moved on past this part of the buffer
+            let data = unsafe { core::slice::from_raw_parts(self.buf.add(from), to - from) };
+            self.ingress.write_slice(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+    use crate::ingress_manager::NoopUrcMatcher;
+    use crate::queues::{ComQueue, DataQueue, ResQueue, StaticQueue, UrcQueue};
+    use crate::{Config, Mode};
+    use heapless::String;
+
+    #[test]
+    fn wrap_around() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        static mut RX_BUF: [u8; 16] = [0; 16];
+        static RX_INDEX: DmaIndex = DmaIndex::new();
+
+        let (res_p, mut res_c) = unsafe { RES_Q.split() };
+        let (urc_p, mut urc_c) = unsafe { URC_Q.split() };
+        let (_com_p, com_c) = unsafe { COM_Q.split() };
+        let (data_p, _data_c) = unsafe { DATA_Q.split() };
+        let ingress: IngressManager<NoopUrcMatcher> = IngressManager::new(
+            res_p,
+            urc_p,
+            com_c,
+            data_p,
+            Config::new(Mode::Timeout),
+            None,
+        );
+        let mut ingress = DmaIngress::new(ingress, unsafe { &mut RX_BUF }, &RX_INDEX);
+        let (ptr, len) = ingress.buffer();
+
+        // Plays the part of the DMA
+        let mut pos = 0;
+        let mut receive = |data: &[u8]| {
+            for byte in data {
+                unsafe { *ptr.add(pos) = *byte };
+                pos = (pos + 1) % len;
+            }
+        };
+
+        receive(b"AT+CSQ\r\r\n");
+        RX_INDEX.half_complete(len);
+        ingress.digest();
+        RX_INDEX.set(9);
+        ingress.digest();
+
+        receive(b"+CSQ: 3,");
+        RX_INDEX.full_complete(len);
+        ingress.digest();
+        receive(b"99\r\nOK\r\n");
+        RX_INDEX.set(9);
+        ingress.digest();
+        assert_eq!(
+            res_c.dequeue().map(|r| r.into_result()),
+            Some(Ok::<_, Error>(String::from("+CSQ: 3,99")))
+        );
+
+        receive(b"\r\n+UMWI: 0,1\r\n");
+        RX_INDEX.full_complete(len);
+        ingress.digest();
+        RX_INDEX.set(7);
+        ingress.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+UMWI: 0,1\r\n")
+        );
+    }
+}
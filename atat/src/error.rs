@@ -1,18 +1,172 @@
+use core::fmt::{self, Write as _};
+
+use heapless::{consts, String};
+
+/// What the device actually sent, attached to the errors about unexpected
+/// responses, to tell what went wrong in the field.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorContext {
+    /// The offending response, truncated to 48 bytes, if known
+    pub response: Option<String<consts::U48>>,
+    /// The prefix the response was expected to start with, if known,
+    /// truncated to 16 bytes
+    pub expected: Option<String<consts::U16>>,
+}
+
+impl ErrorContext {
+    /// A context that is not known, e.g. in the error result codes of a
+    /// [`Dialect`](dialect/trait.Dialect.html).
+    pub const fn new() -> Self {
+        ErrorContext {
+            response: None,
+            expected: None,
+        }
+    }
+
+    /// The context of the offending `response`.
+    pub fn response(response: &str) -> Self {
+        ErrorContext {
+            response: Some(truncated(response)),
+            expected: None,
+        }
+    }
+
+    /// The response was expected to start with `prefix`.
+    pub fn expected(mut self, prefix: &str) -> Self {
+        self.expected = Some(truncated(prefix));
+        self
+    }
+}
+
+/// `s`, truncated to the capacity of the returned string.
+fn truncated<N: heapless::ArrayLength<u8>>(s: &str) -> String<N> {
+    let mut t = Truncating(String::new());
+    t.write_str(s).ok();
+    t.0
+}
+
+/// Formats into the string, up to its capacity.
+struct Truncating<N: heapless::ArrayLength<u8>>(String<N>);
+
+impl<N: heapless::ArrayLength<u8>> fmt::Write for Truncating<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.0.push(c).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
 /// Errors returned by, or used within the crate
 #[derive(Clone, Debug, PartialEq)]
 pub enum Error {
     /// Serial read error
     Read,
-    /// Serial write error
-    Write,
+    /// Serial write error, with the `Debug` rendering of the error of the
+    /// transport, truncated to 32 bytes, e.g. `Overrun`. The error itself is
+    /// kept by the client, see
+    /// [`Client::take_write_error`](struct.Client.html#method.take_write_error).
+    Write(String<consts::U32>),
     /// Timed out while waiting for a response
     Timeout,
     /// Invalid response from module
-    InvalidResponse,
+    InvalidResponse(ErrorContext),
     /// Command was aborted
     Aborted,
     /// Buffer overflow
     Overflow,
     /// Failed to parse received response
-    ParseString,
+    ParseString(ErrorContext),
+    /// The connection was not established, or was lost (`NO CARRIER`)
+    NoCarrier,
+    /// The called party is busy (`BUSY`)
+    Busy,
+    /// No dial tone was detected (`NO DIALTONE`)
+    NoDialtone,
+    /// The called party did not answer (`NO ANSWER`)
+    NoAnswer,
+    /// The command is longer (first) than the maximum command length (second)
+    CmdTooLong(usize, usize),
+    /// An argument of the command could not be serialized, e.g. by a custom
+    /// serializer
+    Serialize,
+    /// The device was reset, as announced by a boot banner (e.g. `RDY`)
+    ModemReset,
+    /// The operation is not supported by the client in its current
+    /// configuration, e.g. in a mode without a timeout
+    Unsupported,
+}
+
+impl Error {
+    /// A serial write error, rendering the `error` of the transport.
+    pub(crate) fn write(error: &impl fmt::Debug) -> Self {
+        let mut s = Truncating(String::new());
+        write!(s, "{:?}", error).ok();
+        Error::Write(s.0)
+    }
+
+    /// Attach the offending `response` to the error, if it carries an
+    /// [`ErrorContext`](struct.ErrorContext.html) without one.
+    pub fn with_response(mut self, response: &str) -> Self {
+        if let Error::InvalidResponse(ref mut context) | Error::ParseString(ref mut context) = self
+        {
+            if context.response.is_none() {
+                context.response = Some(truncated(response));
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn context() {
+        let context = ErrorContext::response("+CME ERROR: 10").expected("+CCID");
+        assert_eq!(
+            context.response,
+            Some(String::<consts::U48>::from("+CME ERROR: 10"))
+        );
+        assert_eq!(context.expected, Some(String::<consts::U16>::from("+CCID")));
+        assert_ne!(context, ErrorContext::response("+CME ERROR: 10"));
+
+        let long = "+CPBR: 1,\"+4512345678\",145,\"A rather long contact name\"";
+        let context = ErrorContext::response(long).expected("+CPBR: 1,\"+4512345678\"");
+        assert_eq!(context.response.unwrap(), long[..48]);
+        assert_eq!(context.expected.unwrap(), "+CPBR: 1,\"+45123");
+    }
+
+    #[test]
+    fn with_response() {
+        assert_eq!(
+            Error::InvalidResponse(ErrorContext::new()).with_response("SEND FAIL"),
+            Error::InvalidResponse(ErrorContext::response("SEND FAIL"))
+        );
+        // A known response is kept
+        assert_eq!(
+            Error::ParseString(ErrorContext::response("+CSQ: 99")).with_response("OK"),
+            Error::ParseString(ErrorContext::response("+CSQ: 99"))
+        );
+        assert_eq!(Error::Busy.with_response("busy p..."), Error::Busy);
+    }
+
+    #[test]
+    fn write() {
+        #[derive(Debug)]
+        enum SerialError {
+            Framing,
+            Overrun { dropped: usize },
+        }
+
+        assert_eq!(
+            Error::write(&SerialError::Framing),
+            Error::Write(String::from("Framing"))
+        );
+        assert_eq!(
+            Error::write(&[SerialError::Overrun { dropped: 12 }, SerialError::Framing]),
+            Error::Write(String::from("[Overrun { dropped: 12 }, Framin"))
+        );
+    }
 }
@@ -0,0 +1,328 @@
+//! Fetching data announced by URCs
+//!
+//! Socket drivers are usually told about received data by a URC, e.g.
+//! `+UUSORD: 0,128`, and have to read it with a follow-up command, e.g.
+//! `AT+USORD=0,128`. An [`AutoFetch`] checks for URCs, issues the follow-up
+//! command for those announcing data, and queues the responses, which the
+//! driver can dequeue whenever convenient:
+//!
+//! ```ignore
+//! use atat::fetch::AutoFetch;
+//!
+//! let mut fetch: AutoFetch<ReadSocketData, consts::U4> = AutoFetch::new();
+//! loop {
+//!     match fetch.check_urc::<_, Urc, _>(&mut client, |urc| match urc {
+//!         Urc::SocketDataAvailable(ev) => Some(ReadSocketData {
+//!             socket: ev.socket,
+//!             length: ev.length,
+//!         }),
+//!         _ => None,
+//!     }) {
+//!         Some(Urc::SocketClosed(ev)) => close_socket(ev.socket),
+//!         _ => {}
+//!     }
+//!     while let Some(data) = fetch.dequeue() {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! [`AutoFetch`]: struct.AutoFetch.html
+
+use heapless::{spsc::Queue, ArrayLength};
+
+use crate::traits::{send_bounded, AtatClient, AtatCmd, AtatUrc};
+
+/// Issues the follow-up commands of URCs announcing data, and queues their
+/// responses, see the [module documentation](index.html).
+pub struct AutoFetch<A, N>
+where
+    A: AtatCmd,
+    N: ArrayLength<A::Response>,
+{
+    data: Queue<A::Response, N>,
+    dropped: u32,
+}
+
+impl<A, N> AutoFetch<A, N>
+where
+    A: AtatCmd,
+    N: ArrayLength<A::Response>,
+{
+    pub fn new() -> Self {
+        AutoFetch {
+            data: Queue::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Check for a URC of type `URC`, and issue the command returned by
+    /// `fetch` for it, if any, queueing its response.
+    ///
+    /// The command is awaited for at most its `max_timeout`, which is bounded
+    /// by the client, so it has to be in `Mode::Timeout` or
+    /// `Mode::NonBlockingTimeout`. In the modes without a timeout, the
+    /// command is not sent, and its response counted as
+    /// [`dropped`](#method.dropped). The URC is returned in any case, so that
+    /// the driver can act on it as well.
+    pub fn check_urc<C, URC, F>(&mut self, client: &mut C, fetch: F) -> Option<URC::Response>
+    where
+        C: AtatClient,
+        URC: AtatUrc,
+        F: FnOnce(&URC::Response) -> Option<A>,
+    {
+        let urc = client.check_urc::<URC>()?;
+        if let Some(cmd) = fetch(&urc) {
+            match send_bounded(client, &cmd) {
+                Ok(resp) => {
+                    if self.data.enqueue(resp).is_err() {
+                        #[cfg(feature = "logging")]
+                        log::warn!("Dropping fetched data, the data queue is full");
+                        self.dropped = self.dropped.saturating_add(1);
+                    }
+                }
+                Err(_e) => {
+                    #[cfg(feature = "logging")]
+                    log::warn!("Failed to fetch data announced by a URC: {:?}", _e);
+                    self.dropped = self.dropped.saturating_add(1);
+                }
+            }
+        }
+        Some(urc)
+    }
+
+    /// The oldest queued response, if any.
+    pub fn dequeue(&mut self) -> Option<A::Response> {
+        self.data.dequeue()
+    }
+
+    /// Number of queued responses.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Number of responses lost, as the data queue was full or the
+    /// follow-up command failed.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+impl<A, N> Default for AutoFetch<A, N>
+where
+    A: AtatCmd,
+    N: ArrayLength<A::Response>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+    use crate::queues::{ComQueue, DataQueue, ResQueue, StaticQueue, UrcQueue};
+    use crate::testing::{MockModem, VirtualClock};
+    use crate::traits::{AtTransport, AtatResp};
+    use crate::{Client, Config, IngressManager, Milliseconds, Mode, NoopUrcMatcher};
+    use core::cell::RefCell;
+    use core::fmt::Write;
+    use heapless::{consts, String};
+    use void::Void;
+
+    #[derive(Debug, PartialEq)]
+    enum Urc {
+        DataAvailable(u8),
+        Closed(u8),
+    }
+
+    impl AtatUrc for Urc {
+        type Response = Urc;
+
+        fn parse(resp: &str) -> Result<Self::Response, Error> {
+            let socket = resp[resp.len() - 1..].parse().unwrap();
+            if resp.starts_with("+UUSORD") {
+                Ok(Urc::DataAvailable(socket))
+            } else {
+                Ok(Urc::Closed(socket))
+            }
+        }
+    }
+
+    struct ReadData(u8);
+
+    #[derive(Debug, PartialEq)]
+    struct Data(String<consts::U16>);
+
+    impl AtatResp for Data {}
+
+    impl AtatCmd for ReadData {
+        type CommandLen = consts::U16;
+        type Response = Data;
+
+        fn as_string(&self) -> String<Self::CommandLen> {
+            let mut s = String::new();
+            write!(s, "AT+USORD={},16\r\n", self.0).unwrap();
+            s
+        }
+
+        fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+            Ok(Data(String::from(resp)))
+        }
+    }
+
+    /// Pumps the modem as soon as the client writes to it, standing in for
+    /// the receive interrupt while the client waits for the response
+    struct PumpedTransport<'m, 'c> {
+        modem: &'m MockModem<'c>,
+        ingress: &'m RefCell<IngressManager<NoopUrcMatcher>>,
+    }
+
+    impl<'m, 'c> AtTransport for PumpedTransport<'m, 'c> {
+        type Error = Void;
+
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+            self.modem.transport().write_all(buf)?;
+            self.modem.pump(&mut self.ingress.borrow_mut());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn fetch(urc: &Urc) -> Option<ReadData> {
+        match urc {
+            Urc::DataAvailable(socket) => Some(ReadData(*socket)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn fetch_announced_data() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        let (res_p, res_c) = unsafe { RES_Q.split() };
+        let (urc_p, urc_c) = unsafe { URC_Q.split() };
+        let (com_p, com_c) = unsafe { COM_Q.split() };
+        let (data_p, data_c) = unsafe { DATA_Q.split() };
+
+        let clock = VirtualClock::new();
+        let modem = MockModem::new(&clock);
+        modem
+            .expect("AT+USORD=0,16\r\n")
+            .reply("\r\n+USORD: 0,5\r\nOK\r\n")
+            .expect("AT+USORD=0,16\r\n")
+            .reply("\r\n+USORD: 0,6\r\nOK\r\n")
+            // Not answered
+            .expect("AT+USORD=0,16\r\n");
+
+        let config = Config::new(Mode::NonBlockingTimeout).cmd_cooldown(Milliseconds(0));
+        let ingress = RefCell::new(IngressManager::new(
+            res_p, urc_p, com_c, data_p, config, None,
+        ));
+        let mut client = Client::new(
+            PumpedTransport {
+                modem: &modem,
+                ingress: &ingress,
+            },
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            clock.timer(),
+            clock.timer(),
+            config,
+        );
+        let mut auto_fetch: AutoFetch<ReadData, consts::U1> = AutoFetch::new();
+
+        assert_eq!(auto_fetch.check_urc::<_, Urc, _>(&mut client, fetch), None);
+
+        modem.urc(0, "\r\n+UUSOCL: 1\r\n");
+        modem.pump(&mut ingress.borrow_mut());
+        assert_eq!(
+            auto_fetch.check_urc::<_, Urc, _>(&mut client, fetch),
+            Some(Urc::Closed(1))
+        );
+        assert!(auto_fetch.is_empty());
+
+        modem.urc(0, "\r\n+UUSORD: 0\r\n");
+        modem.pump(&mut ingress.borrow_mut());
+        assert_eq!(
+            auto_fetch.check_urc::<_, Urc, _>(&mut client, fetch),
+            Some(Urc::DataAvailable(0))
+        );
+        assert_eq!(auto_fetch.len(), 1);
+
+        // The queue is full
+        modem.urc(0, "\r\n+UUSORD: 0\r\n");
+        modem.pump(&mut ingress.borrow_mut());
+        auto_fetch.check_urc::<_, Urc, _>(&mut client, fetch);
+        assert_eq!(auto_fetch.dropped(), 1);
+
+        // The follow-up command times out
+        modem.urc(0, "\r\n+UUSORD: 0\r\n");
+        modem.pump(&mut ingress.borrow_mut());
+        clock.set_auto_advance(true);
+        assert_eq!(
+            auto_fetch.check_urc::<_, Urc, _>(&mut client, fetch),
+            Some(Urc::DataAvailable(0))
+        );
+        assert_eq!(clock.now(), ReadData(0).max_timeout().0);
+        assert_eq!(auto_fetch.dropped(), 2);
+        assert!(modem.is_done());
+        assert_eq!(modem.unexpected(), None);
+
+        assert_eq!(
+            auto_fetch.dequeue(),
+            Some(Data(String::from("+USORD: 0,5")))
+        );
+        assert_eq!(auto_fetch.dequeue(), None);
+    }
+
+    #[test]
+    fn mode_without_timeout() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        let (res_p, res_c) = unsafe { RES_Q.split() };
+        let (urc_p, urc_c) = unsafe { URC_Q.split() };
+        let (com_p, com_c) = unsafe { COM_Q.split() };
+        let (data_p, data_c) = unsafe { DATA_Q.split() };
+
+        let clock = VirtualClock::new();
+        let modem = MockModem::new(&clock);
+        let config = Config::new(Mode::NonBlocking);
+        let mut ingress: IngressManager<NoopUrcMatcher> =
+            IngressManager::new(res_p, urc_p, com_c, data_p, config, None);
+        let mut client = Client::new(
+            modem.transport(),
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            clock.timer(),
+            clock.timer(),
+            config,
+        );
+        let mut auto_fetch: AutoFetch<ReadData, consts::U1> = AutoFetch::new();
+
+        modem.urc(0, "\r\n+UUSORD: 0\r\n");
+        modem.pump(&mut ingress);
+        assert_eq!(
+            auto_fetch.check_urc::<_, Urc, _>(&mut client, fetch),
+            Some(Urc::DataAvailable(0))
+        );
+        assert_eq!(auto_fetch.dropped(), 1);
+        assert_eq!(modem.unexpected(), None);
+    }
+}
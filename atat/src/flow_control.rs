@@ -0,0 +1,312 @@
+//! Hardware flow control
+//!
+//! At high baud rates, large responses, e.g. to `+USORD`, can arrive faster
+//! than they are digested. A [`FlowControlledIngress`] asks the device to pause
+//! sending once the receive buffer fills up to a high watermark, and to resume
+//! once it is digested down to a low watermark, through an [`RxFlowControl`],
+//! e.g. driving the RTS line. Likewise, a [`FlowControlledTx`] holds off
+//! transmitting commands while the device is not ready to receive, as told by
+//! a [`TxFlowControl`], e.g. reading the CTS line:
+//!
+//! ```ignore
+//! use atat::flow_control::{
+//!     FlowControlledIngress, FlowControlledTx, RxFlowControl, TxFlowControl,
+//! };
+//!
+//! struct Rts(PA12<Output<PushPull>>);
+//!
+//! impl RxFlowControl for Rts {
+//!     fn pause(&mut self) {
+//!         self.0.set_high().ok();
+//!     }
+//!
+//!     fn resume(&mut self) {
+//!         self.0.set_low().ok();
+//!     }
+//! }
+//!
+//! struct Cts(PA11<Input<Floating>>);
+//!
+//! impl TxFlowControl for Cts {
+//!     fn clear_to_send(&mut self) -> bool {
+//!         self.0.is_low().unwrap_or(true)
+//!     }
+//! }
+//!
+//! let tx = FlowControlledTx::new(tx, Cts(cts));
+//! let (client, ingress) = atat::new(tx, timer, cooldown_timer, config, None);
+//! let mut ingress = FlowControlledIngress::new(ingress, Rts(rts), 224, 64);
+//! ```
+//!
+//! Software flow control works the same way, with `pause` and `resume`
+//! sending XOFF and XON.
+//!
+//! The device stops sending mid-response once paused, so the high watermark
+//! must leave room for the longest expected response, or the response only
+//! completes by timing out.
+//!
+//! [`FlowControlledIngress`]: struct.FlowControlledIngress.html
+//! [`RxFlowControl`]: trait.RxFlowControl.html
+//! [`FlowControlledTx`]: struct.FlowControlledTx.html
+//! [`TxFlowControl`]: trait.TxFlowControl.html
+
+use heapless::consts;
+
+use crate::dialect::{Dialect, V25ter};
+use crate::ingress_manager::{IngressManager, UrcMatcher};
+use crate::stats::{QueueLevels, QueueStats};
+#[cfg(feature = "trace")]
+use crate::trace::TraceRing;
+use crate::traits::AtTransport;
+
+/// Pausing the device sending to us, e.g. by deasserting RTS, or sending XOFF
+pub trait RxFlowControl {
+    /// Ask the device to pause sending.
+    fn pause(&mut self);
+
+    /// Let the device resume sending.
+    fn resume(&mut self);
+}
+
+/// Checking whether the device is ready to receive, e.g. by CTS being
+/// asserted
+pub trait TxFlowControl {
+    fn clear_to_send(&mut self) -> bool;
+}
+
+/// An ingress manager pausing the device while its receive buffer is filled up,
+/// see the [module documentation](index.html).
+pub struct FlowControlledIngress<U, F, D = V25ter> {
+    ingress: IngressManager<U, D>,
+    flow_control: F,
+    high: usize,
+    low: usize,
+    paused: bool,
+}
+
+impl<U, F, D> FlowControlledIngress<U, F, D>
+where
+    U: UrcMatcher<MaxLen = consts::U256>,
+    F: RxFlowControl,
+    D: Dialect,
+{
+    /// Pause the device once `high` bytes are buffered, and resume it once
+    /// they are digested down to `low` bytes.
+    pub fn new(ingress: IngressManager<U, D>, flow_control: F, high: usize, low: usize) -> Self {
+        FlowControlledIngress {
+            ingress,
+            flow_control,
+            high,
+            low,
+            paused: false,
+        }
+    }
+
+    /// Whether the device is currently asked to pause sending.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The last frames digested. See
+    /// [`IngressManager::trace`](../struct.IngressManager.html#method.trace).
+    #[cfg(feature = "trace")]
+    pub fn trace(&self) -> &TraceRing {
+        self.ingress.trace()
+    }
+
+    /// Frames dropped on the way to the client. See
+    /// [`IngressManager::stats`](../struct.IngressManager.html#method.stats).
+    pub fn stats(&self) -> &QueueStats {
+        self.ingress.stats()
+    }
+
+    /// Fill levels of the queues to and from the client. See
+    /// [`IngressManager::queue_levels`](../struct.IngressManager.html#method.queue_levels).
+    pub fn queue_levels(&self) -> Option<QueueLevels> {
+        self.ingress.queue_levels()
+    }
+
+    /// Reset the counters of dropped frames.
+    pub fn clear_stats(&mut self) {
+        self.ingress.clear_stats();
+    }
+
+    /// See [`IngressManager::write`](../struct.IngressManager.html#method.write).
+    pub fn write(&mut self, data: &[u8]) {
+        self.ingress.write(data);
+        self.update();
+    }
+
+    /// See
+    /// [`IngressManager::write_slice`](../struct.IngressManager.html#method.write_slice).
+    pub fn write_slice(&mut self, data: &[u8]) {
+        self.ingress.write_slice(data);
+        self.update();
+    }
+
+    /// See [`IngressManager::digest`](../struct.IngressManager.html#method.digest).
+    pub fn digest(&mut self) {
+        self.ingress.digest();
+        self.update();
+    }
+
+    /// Pause or resume the device, as the buffer crosses the watermarks.
+    fn update(&mut self) {
+        let buffered = self.ingress.buffered();
+        if !self.paused && buffered >= self.high {
+            #[cfg(feature = "logging")]
+            log::trace!("Pausing the device at {} buffered bytes", buffered);
+            self.flow_control.pause();
+            self.paused = true;
+        } else if self.paused && buffered <= self.low {
+            #[cfg(feature = "logging")]
+            log::trace!("Resuming the device at {} buffered bytes", buffered);
+            self.flow_control.resume();
+            self.paused = false;
+        }
+    }
+}
+
+/// A transport holding off each byte until the device is ready to receive it,
+/// see the [module documentation](index.html).
+pub struct FlowControlledTx<Tx, F> {
+    tx: Tx,
+    flow_control: F,
+}
+
+impl<Tx, F> FlowControlledTx<Tx, F>
+where
+    Tx: AtTransport,
+    F: TxFlowControl,
+{
+    pub fn new(tx: Tx, flow_control: F) -> Self {
+        FlowControlledTx { tx, flow_control }
+    }
+
+    pub fn release(self) -> (Tx, F) {
+        (self.tx, self.flow_control)
+    }
+}
+
+impl<Tx, F> AtTransport for FlowControlledTx<Tx, F>
+where
+    Tx: AtTransport,
+    F: TxFlowControl,
+{
+    type Error = Tx::Error;
+
+    /// Write all of `buf`, blocking as long as the device is not ready to
+    /// receive.
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        for c in buf {
+            while !self.flow_control.clear_to_send() {}
+            self.tx.write_all(core::slice::from_ref(c))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.tx.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+    use crate::ingress_manager::NoopUrcMatcher;
+    use crate::queues::{ComQueue, DataQueue, ResQueue, StaticQueue, UrcQueue};
+    use crate::{Config, Mode};
+    use core::cell::{Cell, RefCell};
+    use heapless::{String, Vec};
+
+    /// The lines between us and the device
+    #[derive(Default)]
+    struct Device {
+        paused: RefCell<Vec<bool, consts::U8>>,
+        /// Polls of CTS until the last byte written is received
+        busy: Cell<usize>,
+        received: RefCell<String<consts::U16>>,
+    }
+
+    impl RxFlowControl for &Device {
+        fn pause(&mut self) {
+            self.paused.borrow_mut().push(true).unwrap();
+        }
+
+        fn resume(&mut self) {
+            self.paused.borrow_mut().push(false).unwrap();
+        }
+    }
+
+    impl TxFlowControl for &Device {
+        fn clear_to_send(&mut self) -> bool {
+            self.busy.set(self.busy.get().saturating_sub(1));
+            self.busy.get() == 0
+        }
+    }
+
+    impl AtTransport for &Device {
+        type Error = ();
+
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+            assert_eq!(self.busy.get(), 0);
+            self.busy.set(3);
+            for c in buf {
+                self.received.borrow_mut().push(*c as char)?;
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn watermarks() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+
+        let (res_p, mut res_c) = unsafe { RES_Q.split() };
+        let (urc_p, _urc_c) = unsafe { URC_Q.split() };
+        let (_com_p, com_c) = unsafe { COM_Q.split() };
+        let (data_p, _data_c) = unsafe { DATA_Q.split() };
+        let ingress: IngressManager<NoopUrcMatcher> = IngressManager::new(
+            res_p,
+            urc_p,
+            com_c,
+            data_p,
+            Config::new(Mode::Timeout),
+            None,
+        );
+        let device = Device::default();
+        let mut ingress = FlowControlledIngress::new(ingress, &device, 64, 16);
+
+        ingress.write(b"AT+USORD=0,64\r\r\n");
+        ingress.digest();
+        ingress.write(b"+USORD: 0,64,\"");
+        ingress.write(&[b'a'; 64]);
+        assert!(ingress.is_paused());
+        ingress.digest();
+        ingress.write(b"\"\r\nOK\r\n");
+        assert!(ingress.is_paused());
+        ingress.digest();
+        assert!(!ingress.is_paused());
+        assert!(matches!(
+            res_c.dequeue().map(|r| r.into_result()),
+            Some(Ok::<_, Error>(_))
+        ));
+        assert_eq!(*device.paused.borrow(), [true, false]);
+    }
+
+    #[test]
+    fn clear_to_send() {
+        let device = Device::default();
+        let mut tx = FlowControlledTx::new(&device, &device);
+        tx.write_all(b"AT\r\n").unwrap();
+        assert_eq!(*device.received.borrow(), "AT\r\n");
+    }
+}
@@ -1,13 +1,23 @@
 use heapless::{consts, ArrayLength, String};
 
-use crate::error::Error;
-use crate::queues::{ComConsumer, ResProducer, UrcProducer};
-use crate::{Command, Config};
+use crate::error::{Error, ErrorContext};
+use core::marker::PhantomData;
+
+use crate::dialect::{Dialect, V25ter};
+use crate::queues::{
+    ComConsumer, DataProducer, PriorityUrcProducer, ResProducer, RxConsumer, RxProducer, RxQueue,
+    UrcFrame, UrcProducer,
+};
+use crate::stats::{DropCause, DropCounter, QueueCounters, QueueLevels, QueueStats};
+#[cfg(feature = "trace")]
+use crate::trace::TraceRing;
+use crate::trace::{FrameKind, TraceDirection, TraceHandler};
+use crate::{Command, Config, OverflowPolicy};
 
 /// Helper function to take a subsection from `buf`.
 ///
-/// It searches for `needle`, either from the beginning of buf, or the end,
-/// depending on `reverse`. If the search finds a match, it continues forward as
+/// It searches for `needle` from the index `from` of buf on, skipping e.g. the
+/// payload of a response. If the search finds a match, it continues forward as
 /// long as the next characters matches `line_term_char` or `format_char`. It
 /// then returns a substring, trimming it for whitespaces if `trim_response` is
 /// true, and leaves the remainder in `buf`.
@@ -15,50 +25,99 @@ use crate::{Command, Config};
 /// Example:
 /// ```
 /// let mut buf = heapless::String::from("+USORD: 3,16,\"16 bytes of data\"\r\nOK\r\nAT+GMR\r\r\n");
-/// let response: heapless::String<heapless::consts::U64> = get_line(&mut buf, "OK", b'\r', b'\n', false, false);
+/// let response: heapless::String<heapless::consts::U64> = get_line(&mut buf, 0, "OK", b'\r', b'\n', false);
 /// assert_eq!(response, heapless::String::from("+USORD: 3,16,\"16 bytes of data\"\r\nOK\r\n"));
 /// assert_eq!(buf, heapless::String::from("AT+GMR\r\r\n"));
 /// ```
 pub(crate) fn get_line<L: ArrayLength<u8>, I: ArrayLength<u8>>(
     buf: &mut String<I>,
+    from: usize,
     needle: &str,
     line_term_char: u8,
     format_char: u8,
     trim_response: bool,
+) -> Option<String<L>> {
+    buf[from..].find(needle).map(|index| {
+        split_line(
+            buf,
+            from + index + needle.len(),
+            line_term_char,
+            format_char,
+            trim_response,
+        )
+    })
+}
+
+/// Helper function to take the first (or last, depending on `reverse`) line
+/// from `buf`, like [`get_line`], ending at either `line_term_char` or
+/// `format_char`.
+///
+/// This tolerates devices terminating lines with a bare `\r` or a bare `\n`.
+///
+/// [`get_line`]: fn.get_line.html
+pub(crate) fn get_terminated_line<L: ArrayLength<u8>, I: ArrayLength<u8>>(
+    buf: &mut String<I>,
+    line_term_char: u8,
+    format_char: u8,
+    trim_response: bool,
     reverse: bool,
 ) -> Option<String<L>> {
+    let is_term = |c: char| c == line_term_char as char || c == format_char as char;
     let ind = if reverse {
-        buf.rmatch_indices(needle).next()
+        buf.rfind(is_term)
     } else {
-        buf.match_indices(needle).next()
+        buf.find(is_term)
     };
-    match ind {
-        Some((mut index, _)) => {
-            index += needle.len();
-            while match buf.get(index..=index) {
-                Some(c) => c.as_bytes()[0] == line_term_char || c.as_bytes()[0] == format_char,
-                _ => false,
-            } {
-                index += 1;
-            }
-
-            let return_string = {
-                let part = unsafe { buf.get_unchecked(0..index) };
-                String::from(if trim_response { part.trim() } else { part })
-            };
-            *buf = String::from(unsafe { buf.get_unchecked(index..buf.len()) });
-            Some(return_string)
-        }
-        None => None,
+    ind.map(|index| split_line(buf, index + 1, line_term_char, format_char, trim_response))
+}
+
+/// Split `buf` after `index`, and any line terminators following it,
+/// returning the first part and leaving the remainder in `buf`.
+fn split_line<L: ArrayLength<u8>, I: ArrayLength<u8>>(
+    buf: &mut String<I>,
+    mut index: usize,
+    line_term_char: u8,
+    format_char: u8,
+    trim_response: bool,
+) -> String<L> {
+    while match buf.get(index..=index) {
+        Some(c) => c.as_bytes()[0] == line_term_char || c.as_bytes()[0] == format_char,
+        _ => false,
+    } {
+        index += 1;
     }
+
+    let return_string = {
+        let part = unsafe { buf.get_unchecked(0..index) };
+        String::from(if trim_response {
+            part.trim_matches(|c: char| {
+                c.is_whitespace() || c == line_term_char as char || c == format_char as char
+            })
+        } else {
+            part
+        })
+    };
+    *buf = String::from(unsafe { buf.get_unchecked(index..buf.len()) });
+    return_string
 }
 
+/// Length of the final result code and the surrounding line terminators
+/// (`\r\nERROR\r\n`), which is allowed on top of the max response length
+/// before a response is considered overflowing.
+const RESULT_CODE_LEN: usize = 9;
+
 /// State of the IngressManager, used to distiguish URCs from solicited
 /// responses
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum State {
     Idle,
     ReceivingResponse,
+    /// Received bytes are passed through to the application untouched, after
+    /// a `CONNECT` result code
+    DataMode,
+    /// The payload of a size delimited response is passed on to the stream
+    /// sink, with the given number of bytes left
+    Streaming(usize),
 }
 
 /// The type returned from a custom URC matcher.
@@ -66,6 +125,10 @@ pub enum UrcMatcherResult<L: ArrayLength<u8>> {
     NotHandled,
     Incomplete,
     Complete(String<L>),
+    /// The buffer starts with a URC of this many received bytes, including
+    /// any binary tail, which is taken off the buffer once received
+    /// completely.
+    Length(usize),
 }
 
 /// A user-defined URC matcher
@@ -74,6 +137,12 @@ pub enum UrcMatcherResult<L: ArrayLength<u8>> {
 /// standard response codes like "OK". An example could be an URC that returns
 /// length-value (LV) encoded data without a terminator.
 ///
+/// The matcher is consulted for every buffer received outside of a
+/// response, before the URCs of the [`Dialect`](dialect/trait.Dialect.html),
+/// so it can also handle URCs that do not start with `+`. Rather than
+/// consuming the URC from the buffer itself, it can return its
+/// [`Length`](enum.UrcMatcherResult.html#variant.Length).
+///
 /// Note that you should only detect and consume but not process URCs.
 /// Processing should be done by an [`AtatUrc`](trait.AtatUrc.html)
 /// implementation.
@@ -121,8 +190,11 @@ pub trait UrcMatcher {
     /// - If the buffer contains a full URC, remove these bytes from the buffer
     ///   and return [`Complete`] with the matched data.
     /// - If it contains an incomplete URC, return [`Incomplete`].
+    /// - If it starts with a URC of known length, return [`Length`], leaving
+    ///   the buffer untouched.
     /// - Otherwise, return [`NotHandled`].
     ///
+    /// [`Length`]: enum.UrcMatcherResult.html#variant.Length
     /// [`Complete`]: enum.UrcMatcherResult.html#variant.Complete
     /// [`Incomplete`]: enum.UrcMatcherResult.html#variant.Incomplete
     /// [`NotHandled`]: enum.UrcMatcherResult.html#variant.NotHandled
@@ -141,7 +213,7 @@ impl UrcMatcher for NoopUrcMatcher {
     }
 }
 
-pub struct IngressManager<U> {
+pub struct IngressManager<U, D = V25ter> {
     /// Buffer holding incoming bytes.
     buf: String<consts::U256>,
     /// A flag that is set to `true` when the buffer is cleared
@@ -152,22 +224,68 @@ pub struct IngressManager<U> {
     res_p: ResProducer,
     /// The URC producer sends URCs to the client
     urc_p: UrcProducer,
+    /// Sends the URCs matching `priority_urcs` to the client, if set
+    priority_urc_p: Option<PriorityUrcProducer>,
     /// The command consumer receives commands from the client
     com_c: ComConsumer,
+    /// The data producer passes raw bytes to the client while in data mode
+    data_p: DataProducer,
 
     /// Current processing state.
     state: State,
+    /// Generation of the client, that responses are tagged with
+    generation: u8,
     /// Command line termination character S3 (Default = '\r' ASCII: \[013\])
     line_term_char: u8,
     /// Response formatting character S4 (Default = '\n' ASCII: \[010\])
     format_char: u8,
     echo_enabled: bool,
+    /// Interpret numeric result codes (`ATV0`)
+    numeric_result_codes: bool,
+    /// What to do with received bytes that do not fit the buffer
+    overflow_policy: OverflowPolicy,
+    /// Prefixes of the URCs passed through the high priority queue
+    priority_urcs: &'static [&'static str],
+    /// Skip lines holding garbage, rather than passing them on
+    resilient_digest: bool,
+    /// Normalize whitespace and the case of result codes
+    tolerant_parsing: bool,
+    /// Max length of the response currently being received, if known
+    max_response_len: Option<usize>,
+    /// Length of the URC currently being received, if it overflowed the
+    /// buffer and is being truncated
+    truncated_urc_len: Option<usize>,
+    /// Length of the buffer that has been scanned for the end of the response
+    /// being received, without finding it
+    scanned: usize,
+    /// Prefix of the size delimited response whose payload is streamed, see
+    /// [`AtatCmd::stream_prefix`](trait.AtatCmd.html#method.stream_prefix)
+    stream_prefix: Option<&'static str>,
+    /// Prefix of the size delimited response whose payload is buffered, see
+    /// [`AtatCmd::payload_prefix`](trait.AtatCmd.html#method.payload_prefix)
+    payload_prefix: Option<&'static str>,
+    /// Receives streamed payloads, rather than the data queue
+    stream_sink: Option<fn(&[u8])>,
+    /// Number of bytes of the echo of a payload left to be discarded
+    echo_skip: usize,
+    /// Receives all bytes written, before any parsing
+    trace_handler: Option<TraceHandler>,
 
     /// Custom URC matcher.
     custom_urc_matcher: Option<U>,
+
+    /// The last frames digested
+    #[cfg(feature = "trace")]
+    trace: TraceRing,
+    /// Frames dropped on the way to the client
+    stats: QueueStats,
+    /// Fill levels of the queues, shared with the client
+    counters: Option<&'static QueueCounters>,
+
+    dialect: PhantomData<D>,
 }
 
-impl<U> IngressManager<U>
+impl<U> IngressManager<U, V25ter>
 where
     U: UrcMatcher<MaxLen = consts::U256>,
 {
@@ -175,23 +293,119 @@ where
         res_p: ResProducer,
         urc_p: UrcProducer,
         com_c: ComConsumer,
+        data_p: DataProducer,
+        config: Config,
+        custom_urc_matcher: Option<U>,
+    ) -> Self {
+        Self::with_dialect(res_p, urc_p, com_c, data_p, config, custom_urc_matcher)
+    }
+}
+
+impl<U, D> IngressManager<U, D>
+where
+    U: UrcMatcher<MaxLen = consts::U256>,
+    D: Dialect,
+{
+    /// Create an ingress manager for a device speaking the [`Dialect`] `D`.
+    ///
+    /// [`Dialect`]: dialect/trait.Dialect.html
+    pub fn with_dialect(
+        res_p: ResProducer,
+        urc_p: UrcProducer,
+        com_c: ComConsumer,
+        data_p: DataProducer,
         config: Config,
         custom_urc_matcher: Option<U>,
     ) -> Self {
         Self {
             state: State::Idle,
+            generation: 0,
             buf: String::new(),
             buf_incomplete: false,
             res_p,
             urc_p,
+            priority_urc_p: None,
             com_c,
+            data_p,
             line_term_char: config.line_term_char,
             format_char: config.format_char,
             echo_enabled: config.at_echo_enabled,
+            numeric_result_codes: config.numeric_result_codes,
+            overflow_policy: config.overflow_policy,
+            priority_urcs: config.priority_urcs,
+            resilient_digest: config.resilient_digest,
+            tolerant_parsing: config.tolerant_parsing,
+            max_response_len: None,
+            truncated_urc_len: None,
+            scanned: 0,
+            stream_prefix: None,
+            payload_prefix: None,
+            stream_sink: None,
+            echo_skip: 0,
+            trace_handler: None,
             custom_urc_matcher,
+            #[cfg(feature = "trace")]
+            trace: TraceRing::new(),
+            stats: QueueStats::new(),
+            counters: None,
+            dialect: PhantomData,
         }
     }
 
+    /// The last frames digested, to be dumped e.g. when a response fails to
+    /// parse.
+    #[cfg(feature = "trace")]
+    pub fn trace(&self) -> &TraceRing {
+        &self.trace
+    }
+
+    /// Frames dropped on the way to the client, by cause.
+    pub fn stats(&self) -> &QueueStats {
+        &self.stats
+    }
+
+    /// Reset the counters of dropped frames.
+    pub fn clear_stats(&mut self) {
+        self.stats.clear();
+    }
+
+    /// Current and high-watermark fill levels of the queues to and from the
+    /// client, if they are tracked, i.e. if the ingress manager was created
+    /// by [`atat::new`](fn.new.html).
+    pub fn queue_levels(&self) -> Option<QueueLevels> {
+        self.counters.map(QueueCounters::levels)
+    }
+
+    /// Pass the payloads of streamed responses to `sink`, as they are
+    /// received, rather than to the data queue read by
+    /// [`Client::read_stream`](struct.Client.html#method.read_stream).
+    ///
+    /// The sink is called from wherever [`write`](#method.write) is called,
+    /// possibly from interrupt context.
+    pub fn set_stream_sink(&mut self, sink: fn(&[u8])) {
+        self.stream_sink = Some(sink);
+    }
+
+    /// Pass all received bytes to `handler`, before any parsing.
+    ///
+    /// The handler is called from wherever [`write`](#method.write) is
+    /// called, possibly from interrupt context.
+    pub fn set_trace_handler(&mut self, handler: TraceHandler) {
+        self.trace_handler = Some(handler);
+    }
+
+    /// Pass the URCs matching
+    /// [`Config::with_priority_urcs`](struct.Config.html#method.with_priority_urcs)
+    /// through `priority_urc_p`.
+    pub(crate) fn set_priority_urc_queue(&mut self, priority_urc_p: PriorityUrcProducer) {
+        self.priority_urc_p = Some(priority_urc_p);
+    }
+
+    /// Track the fill levels of the queues in `counters`.
+    pub(crate) fn set_queue_counters(&mut self, counters: &'static QueueCounters) {
+        self.counters = Some(counters);
+    }
+
     /// Write data into the internal buffer raw bytes being the core type allows
     /// the ingress manager to be abstracted over the communication medium.
     ///
@@ -201,23 +415,342 @@ where
     pub fn write(&mut self, data: &[u8]) {
         #[cfg(feature = "logging")]
         log::trace!("Receiving {} bytes", data.len());
-        for byte in data {
+        if let Some(handler) = self.trace_handler {
+            handler(TraceDirection::Rx, data);
+        }
+        self.write_untraced(data);
+    }
+
+    /// Write `data` like [`write`](#method.write), without passing it to the
+    /// trace handler, which the caller has done already.
+    fn write_untraced(&mut self, mut data: &[u8]) {
+        if let State::DataMode = self.state {
+            self.write_data(data);
+            return;
+        }
+        if let State::Streaming(remaining) = self.state {
+            let len = core::cmp::min(remaining, data.len());
+            self.stream(&data[..len]);
+            self.state = if len == remaining {
+                State::ReceivingResponse
+            } else {
+                State::Streaming(remaining - len)
+            };
+            data = &data[len..];
+        }
+        if self.echo_skip > 0 {
+            let len = core::cmp::min(self.echo_skip, data.len());
+            self.echo_skip -= len;
+            data = &data[len..];
+        }
+        for (i, byte) in data.iter().enumerate() {
+            // Discard the rest of a truncated URC, counting its length
+            if let Some(len) = self.truncated_urc_len {
+                if *byte != self.line_term_char && *byte != self.format_char {
+                    self.truncated_urc_len = Some(len + 1);
+                    self.stats.dropped_bytes = self.stats.dropped_bytes.saturating_add(1);
+                    continue;
+                }
+                self.notify_truncated_urc(len);
+            }
             match self.buf.push(*byte as char) {
                 Ok(_) => {}
-                Err(_) => self.notify_response(Err(Error::Overflow)),
+                Err(_) => match (self.receiving_urc(), self.overflow_policy) {
+                    (Some(len), _) => {
+                        #[cfg(feature = "logging")]
+                        log::warn!("URC exceeds the buffer, truncating it");
+                        self.truncated_urc_len = Some(len + 1);
+                    }
+                    (None, OverflowPolicy::DropNewest) => {
+                        #[cfg(feature = "logging")]
+                        log::warn!("Receive buffer is full, dropping received bytes!");
+                        self.stats.responses.record(DropCause::Overflow);
+                        self.stats.dropped_bytes = self
+                            .stats
+                            .dropped_bytes
+                            .saturating_add((data.len() - i) as u32);
+                        break;
+                    }
+                    (None, OverflowPolicy::DropOldest) => {
+                        #[cfg(feature = "logging")]
+                        log::warn!("Receive buffer is full, dropping the oldest line!");
+                        self.stats.responses.record(DropCause::Overflow);
+                        self.clear_buf(false);
+                        self.scanned = 0;
+                        self.buf.push(*byte as char).ok();
+                    }
+                    (None, OverflowPolicy::Error) => {
+                        #[cfg(feature = "trace")]
+                        self.trace
+                            .record(FrameKind::Error(Error::Overflow), &self.buf);
+                        self.stats.responses.record(DropCause::Overflow);
+                        self.notify_response(Err(Error::Overflow))
+                    }
+                },
+            }
+        }
+    }
+
+    /// Whether the receive buffer is full, so that further received bytes are
+    /// handled according to the [`OverflowPolicy`] until it is digested.
+    ///
+    /// This lets the UART driver pause the DMA, or deassert RTS, rather than
+    /// losing data.
+    ///
+    /// [`OverflowPolicy`]: enum.OverflowPolicy.html
+    pub fn is_full(&self) -> bool {
+        self.buf.len() == self.buf.capacity()
+    }
+
+    /// Number of received bytes in the receive buffer, out of 256.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Write a chunk of received bytes into the internal buffer, e.g. from a
+    /// DMA idle-line interrupt, or when draining a ring buffer.
+    ///
+    /// This behaves like [`write`](#method.write), but copies runs of ASCII
+    /// bytes into the buffer in one go, rather than byte by byte.
+    ///
+    /// It is safe to call from interrupt context, as long as the ingress
+    /// manager is not accessed from another context meanwhile: it does not
+    /// allocate, never blocks, and does work proportional to `data.len()`.
+    /// Frames are only handed to the client by [`digest`](#method.digest).
+    pub fn write_slice(&mut self, data: &[u8]) {
+        #[cfg(feature = "logging")]
+        log::trace!("Receiving {} bytes", data.len());
+        if let Some(handler) = self.trace_handler {
+            handler(TraceDirection::Rx, data);
+        }
+        if let State::DataMode = self.state {
+            self.write_data(data);
+            return;
+        }
+        let mut rest = data;
+        if let State::Streaming(remaining) = self.state {
+            let len = core::cmp::min(remaining, rest.len());
+            self.write_untraced(&rest[..len]);
+            rest = &rest[len..];
+        }
+        if self.echo_skip > 0 {
+            let len = core::cmp::min(self.echo_skip, rest.len());
+            self.write_untraced(&rest[..len]);
+            rest = &rest[len..];
+        }
+        while !rest.is_empty() {
+            let room = self.buf.capacity() - self.buf.len();
+            if room == 0 || self.truncated_urc_len.is_some() {
+                // Truncation and overflow are handled by `write_untraced`
+                self.write_untraced(rest);
+                return;
+            }
+            let ascii = rest.iter().take(room).take_while(|b| b.is_ascii()).count();
+            if ascii == 0 {
+                // As are non-ASCII bytes
+                self.write_untraced(&rest[..1]);
+                rest = &rest[1..];
+                continue;
+            }
+            // ASCII is valid UTF-8, and fits in the remaining capacity
+            let chunk = unsafe { core::str::from_utf8_unchecked(&rest[..ascii]) };
+            self.buf.push_str(chunk).ok();
+            rest = &rest[ascii..];
+        }
+    }
+
+    /// The length of the URC line in the buffer, if it holds nothing but the
+    /// start of one, which can be truncated if it does not fit.
+    fn receiving_urc(&self) -> Option<usize> {
+        let (term, format) = (self.line_term_char as char, self.format_char as char);
+        let line = self
+            .buf
+            .trim_start_matches(|c: char| c.is_whitespace() || c == term || c == format);
+        if self.state == State::Idle
+            && !self.buf_incomplete
+            && line.starts_with('+')
+            && !line.contains([term, format])
+            && D::binary_urc_len(line).is_none()
+        {
+            Some(line.chars().count())
+        } else {
+            None
+        }
+    }
+
+    /// Switch to data mode, passing anything left in the buffer on to the
+    /// client as data.
+    fn enter_data_mode(&mut self) {
+        self.state = State::DataMode;
+        self.max_response_len = None;
+        self.buf_incomplete = false;
+        let buf = core::mem::replace(&mut self.buf, String::new());
+        let data: heapless::Vec<u8, consts::U256> = buf.chars().map(|c| c as u8).collect();
+        self.write_data(&data);
+    }
+
+    /// Pass raw bytes received in data mode on to the client.
+    fn write_data(&mut self, data: &[u8]) {
+        for byte in data {
+            if self.data_p.enqueue(*byte).is_err() {
+                // TODO: Consider how to act in this situation.
+                #[cfg(feature = "logging")]
+                log::error!("Data queue is full, dropping received bytes!");
+                self.stats.data.record(DropCause::QueueFull);
+                break;
+            }
+        }
+    }
+
+    /// Pass part of a streamed payload on to the stream sink, or the client.
+    fn stream(&mut self, data: &[u8]) {
+        match self.stream_sink {
+            Some(sink) => sink(data),
+            None => self.write_data(data),
+        }
+    }
+
+    /// Find the payload of the size delimited response starting with `prefix`
+    /// once its header is complete, returning the start and length of the
+    /// payload.
+    ///
+    /// The length is the number preceding the payload, which is either quoted,
+    /// as in `+URDFILE: "f.txt",5,"hello"`, or on the next line, as in
+    /// `+HTTPREAD: 5\r\nhello`.
+    fn find_payload(&self, prefix: &str) -> Option<(usize, usize)> {
+        let (term, format) = (self.line_term_char, self.format_char);
+        let start = self.buf.find(prefix)? + prefix.len();
+        let bytes = &self.buf.as_bytes()[start..];
+        let mut i = 0;
+        while i < bytes.len() {
+            let digits = bytes[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+            if digits == 0 {
+                if bytes[i] == term || bytes[i] == format {
+                    // The header ends without announcing a payload
+                    return None;
+                }
+                i += 1;
+                continue;
             }
+            let end = i + digits;
+            let payload = match bytes.get(end..end + 2) {
+                Some(&[b',', b'"']) => end + 2,
+                Some(&[c, f]) if c == term && f == format => end + 2,
+                Some(&[c, _]) if c == term || c == format => end + 1,
+                _ => {
+                    i = end;
+                    continue;
+                }
+            };
+            let len = self.buf[start + i..start + end].parse().ok()?;
+            return Some((start + payload, len));
+        }
+        None
+    }
+
+    /// The index following the payload of the response being received, if
+    /// delimited by [`payload_prefix`](#structfield.payload_prefix), `0` if
+    /// there is none, or `None` while it is being received.
+    fn payload_end(&self) -> Option<usize> {
+        let (start, len) = match self.payload_prefix.and_then(|p| self.find_payload(p)) {
+            Some(payload) => payload,
+            None => return Some(0),
+        };
+        // Each received byte is pushed into the buffer as a char
+        let mut chars = self.buf[start..].char_indices().skip(len);
+        match chars.next() {
+            Some((i, _)) => Some(start + i),
+            None if self.buf[start..].chars().count() == len => Some(self.buf.len()),
+            None => None,
+        }
+    }
+
+    /// Pass the payload of the streamed response on, once its header is
+    /// complete, leaving the header and the final result code in the buffer.
+    fn start_stream(&mut self) {
+        let (start, len) = match self.stream_prefix.and_then(|p| self.find_payload(p)) {
+            Some(payload) => payload,
+            None => return,
+        };
+        self.stream_prefix = None;
+
+        // Each received byte is pushed into the buffer as a char
+        let mut end = start;
+        let mut payload = heapless::Vec::<u8, consts::U256>::new();
+        for c in self.buf[start..].chars().take(len) {
+            payload.push(c as u8).ok();
+            end += c.len_utf8();
+        }
+        #[cfg(feature = "logging")]
+        log::trace!("Streaming a payload of {} bytes", len);
+        self.stream(&payload);
+
+        let mut buf = String::from(&self.buf[..start]);
+        buf.push_str(&self.buf[end..]).ok();
+        self.buf = buf;
+        if payload.len() < len {
+            self.state = State::Streaming(len - payload.len());
         }
     }
 
+    /// Record a raw frame in the trace ring, if enabled.
+    #[allow(unused_variables)]
+    fn record(&mut self, kind: FrameKind, data: &str) {
+        #[cfg(feature = "trace")]
+        self.trace.record(kind, data);
+    }
+
     /// Notify the client that an appropriate response code, or error has been
     /// received
     fn notify_response(&mut self, resp: Result<String<consts::U256>, Error>) {
         #[cfg(feature = "logging")]
         log::debug!("Received response: {:?}", &resp);
-        if self.res_p.ready() {
-            self.res_p.enqueue(resp).ok();
-        } else {
+        if let Ok(ref r) = resp {
+            self.record(FrameKind::Response, r);
+        }
+        let result = resp.as_deref().map_err(Clone::clone);
+        match self.res_p.enqueue(result, self.generation) {
+            Ok(()) => {
+                if let Some(counters) = self.counters {
+                    counters.responses.enqueued();
+                }
+            }
             // FIXME: Handle queue not being ready
+            Err(cause) => self.stats.responses.record(cause),
+        }
+    }
+
+    /// Apply the custom URC matcher to the buffer, returning whether it
+    /// handled the buffer, possibly waiting for the rest of a URC.
+    fn match_custom_urc(&mut self) -> bool {
+        let result = match self.custom_urc_matcher {
+            Some(ref mut matcher) => matcher.process(&mut self.buf),
+            None => return false,
+        };
+        match result {
+            UrcMatcherResult::NotHandled => false,
+            UrcMatcherResult::Incomplete => true,
+            UrcMatcherResult::Complete(urc) => {
+                self.notify_urc(urc);
+                true
+            }
+            UrcMatcherResult::Length(len) => {
+                self.take_urc(len);
+                true
+            }
+        }
+    }
+
+    /// Pass on the URC of `len` received bytes at the start of the buffer,
+    /// once received completely.
+    fn take_urc(&mut self, len: usize) {
+        if let Some((end, _)) = self.buf.char_indices().nth(len) {
+            let urc = String::from(&self.buf[..end]);
+            self.buf = String::from(&self.buf[end..]);
+            self.notify_urc(urc);
+        } else if self.buf.chars().count() == len {
+            let urc = core::mem::replace(&mut self.buf, String::new());
+            self.notify_urc(urc);
         }
     }
 
@@ -226,22 +759,102 @@ where
     fn notify_urc(&mut self, resp: String<consts::U256>) {
         #[cfg(feature = "logging")]
         log::debug!("Received URC: {:?}", &resp);
-        if self.urc_p.ready() {
-            self.urc_p.enqueue(resp).ok();
-        } else {
-            // FIXME: Handle queue not being ready
+        self.record(FrameKind::Urc, &resp);
+        self.enqueue_urc(resp.into());
+    }
+
+    /// Notify the client of a URC that was truncated to fit the buffer, having
+    /// an original length of `len` bytes.
+    fn notify_truncated_urc(&mut self, len: usize) {
+        let (term, format) = (self.line_term_char as char, self.format_char as char);
+        let line = String::from(
+            self.buf
+                .trim_start_matches(|c: char| c.is_whitespace() || c == term || c == format),
+        );
+        self.buf.clear();
+        self.truncated_urc_len = None;
+        #[cfg(feature = "logging")]
+        log::debug!("Received truncated URC ({} bytes): {:?}", len, &line);
+        self.record(FrameKind::Urc, &line);
+        self.enqueue_urc(UrcFrame {
+            line,
+            truncated_len: Some(len),
+        });
+    }
+
+    /// Pass `urc` to the client, through the high priority queue if it
+    /// matches one of the `priority_urcs`.
+    fn enqueue_urc(&mut self, urc: UrcFrame) {
+        let priority = self
+            .priority_urcs
+            .iter()
+            .any(|prefix| urc.line.starts_with(prefix));
+        match self.priority_urc_p {
+            Some(ref mut priority_urc_p) if priority => {
+                if priority_urc_p.enqueue(urc).is_ok() {
+                    if let Some(counters) = self.counters {
+                        counters.priority_urcs.enqueued();
+                    }
+                } else {
+                    // FIXME: Handle queue not being ready
+                    self.stats.priority_urcs.record(DropCause::QueueFull);
+                }
+            }
+            _ => {
+                if self.urc_p.enqueue(urc).is_ok() {
+                    if let Some(counters) = self.counters {
+                        counters.urcs.enqueued();
+                    }
+                } else {
+                    // FIXME: Handle queue not being ready
+                    self.stats.urcs.record(DropCause::QueueFull);
+                }
+            }
+        }
+    }
+
+    /// Return to the idle state after the device announced a reset through
+    /// `banner`, aborting the response in progress, if any.
+    fn handle_reset(&mut self, banner: String<consts::U256>) {
+        #[cfg(feature = "logging")]
+        log::warn!("Device reset detected: {:?}", banner);
+        let pending = self.state == State::ReceivingResponse;
+        self.state = State::Idle;
+        self.max_response_len = None;
+        self.buf_incomplete = false;
+        self.notify_urc(banner);
+        // Without a response in progress, the error would be taken as the
+        // response to the next command
+        if pending {
+            self.notify_response(Err(Error::ModemReset));
         }
     }
 
     /// Handle receiving internal config commands from the client.
     fn handle_com(&mut self) {
         if let Some(com) = self.com_c.dequeue() {
+            if let Some(counters) = self.counters {
+                counters.commands.dequeued();
+            }
+            // The buffer may need to be scanned differently
+            self.scanned = 0;
             match com {
                 Command::ClearBuffer => {
                     self.state = State::Idle;
                     #[cfg(feature = "logging")]
                     log::debug!("Clearing buffer on timeout / {:?}", self.buf);
                     self.clear_buf(true);
+                    self.buf_incomplete = false;
+                    self.max_response_len = None;
+                    self.truncated_urc_len = None;
+                    self.stream_prefix = None;
+                    self.payload_prefix = None;
+                    self.echo_skip = 0;
+                }
+                Command::ForceState(State::DataMode) => {
+                    #[cfg(feature = "logging")]
+                    log::trace!("Switching to state DataMode");
+                    self.enter_data_mode();
                 }
                 Command::ForceState(state) => {
                     #[cfg(feature = "logging")]
@@ -251,30 +864,63 @@ where
                 Command::SetEcho(e) => {
                     self.echo_enabled = e;
                 }
+                Command::SetNumericResultCodes(n) => {
+                    self.numeric_result_codes = n;
+                }
                 Command::SetFormat(c) => {
                     self.format_char = c;
                 }
                 Command::SetLineTerm(c) => {
                     self.line_term_char = c;
                 }
+                Command::SetMaxResponseLen(len) => {
+                    self.max_response_len = Some(len);
+                }
+                Command::StreamPayload(prefix) => {
+                    self.stream_prefix = Some(prefix);
+                }
+                Command::DelimitPayload(prefix) => {
+                    self.payload_prefix = Some(prefix);
+                }
+                Command::SetGeneration(generation) => {
+                    self.generation = generation;
+                }
+                Command::SkipEcho(len) => {
+                    self.skip_echo(len);
+                }
             }
         }
     }
 
+    /// Discard the echo of a payload of `len` bytes, as far as it has been
+    /// received already, and the rest as it is received.
+    fn skip_echo(&mut self, len: usize) {
+        // Received bytes are buffered as chars
+        let index = match self.buf.char_indices().nth(len) {
+            Some((i, _)) => i,
+            None => self.buf.len(),
+        };
+        let skipped = self.buf[..index].chars().count();
+        #[cfg(feature = "logging")]
+        log::trace!("Skipping the echo of {} bytes", len);
+        self.buf = String::from(&self.buf[index..]);
+        self.echo_skip = len - skipped;
+    }
+
     /// Clear the buffer.
     ///
     /// If `complete` is `true`, clear the entire buffer. Otherwise, only
     /// remove data until (and including) the first newline (or the entire
     /// buffer if no newline is present).
     fn clear_buf(&mut self, complete: bool) {
+        let len = self.buf.len();
         if complete {
             self.buf.clear();
             #[cfg(feature = "logging")]
             log::trace!("Cleared complete buffer");
         } else {
-            let removed = get_line::<consts::U128, _>(
+            let removed = get_terminated_line::<consts::U128, _>(
                 &mut self.buf,
-                unsafe { core::str::from_utf8_unchecked(&[self.line_term_char]) },
                 self.line_term_char,
                 self.format_char,
                 false,
@@ -293,27 +939,354 @@ where
                 }
             }
         }
+        let dropped = (len - self.buf.len()) as u32;
+        self.stats.dropped_bytes = self.stats.dropped_bytes.saturating_add(dropped);
+    }
+
+    /// Scrub the terminated lines of the buffer, only considering the first
+    /// non-empty line unless `all_lines`.
+    ///
+    /// With a resilient digest, lines holding control characters or non-ASCII
+    /// bytes are removed. With tolerant parsing, trailing whitespace is
+    /// removed, result codes are spelled as by the dialect, e.g. `ok` as `OK`,
+    /// and a space is inserted after the colon of a prefix, e.g. `+CSQ:31,99`
+    /// becomes `+CSQ: 31,99`.
+    fn scrub_lines(&mut self, all_lines: bool) {
+        let (term, format) = (self.line_term_char as char, self.format_char as char);
+        let is_garbage = |c: char| {
+            !c.is_ascii() || (c.is_ascii_control() && c != '\t' && c != term && c != format)
+        };
+        let codes = [D::OK, D::ERROR, "CONNECT"]
+            .iter()
+            .copied()
+            .chain(D::ERROR_RESULT_CODES.iter().map(|(code, _)| *code))
+            .chain(D::UNSOLICITED_RESULT_CODES.iter().copied());
+        let mut kept = String::<consts::U256>::new();
+        let mut rest = self.buf.as_str();
+        let mut changed = false;
+        let mut skipped = 0;
+        let mut dropped = 0;
+        while let Some(end) = rest.find([term, format]) {
+            let (original, line, terminator) = (&rest[..=end], &rest[..end], &rest[end..=end]);
+            rest = &rest[end + 1..];
+            if self.resilient_digest && line.chars().any(is_garbage) {
+                #[cfg(feature = "logging")]
+                log::warn!("Skipping garbage line: {:?}", line);
+                changed = true;
+                skipped += 1;
+                // Every received byte was pushed as a single char
+                dropped += line.chars().count() as u32 + 1;
+            } else if self.tolerant_parsing {
+                let before = kept.len();
+                let mut line = line.trim_end_matches([' ', '\t']);
+                let code = codes.clone().find(|code| match line.get(..code.len()) {
+                    Some(start) => {
+                        start.eq_ignore_ascii_case(code)
+                            && (line.len() == code.len() || line[code.len()..].starts_with(' '))
+                    }
+                    None => false,
+                });
+                if let Some(code) = code {
+                    kept.push_str(code).ok();
+                    line = &line[code.len()..];
+                }
+                // The rest of the buffer follows, so this may only grow it
+                // as long as it is not full
+                let room = kept.len() + line.len() + rest.len() + 1 < self.buf.capacity();
+                match line.find(':') {
+                    Some(colon) if line.starts_with('+') && room => {
+                        let (prefix, args) = line.split_at(colon + 1);
+                        kept.push_str(prefix).ok();
+                        if !args.is_empty() && !args.starts_with(' ') {
+                            kept.push(' ').ok();
+                        }
+                        kept.push_str(args).ok();
+                    }
+                    _ => {
+                        kept.push_str(line).ok();
+                    }
+                }
+                kept.push_str(terminator).ok();
+                changed |= &kept[before..] != original;
+            } else {
+                kept.push_str(original).ok();
+            }
+            if !all_lines && !line.is_empty() {
+                break;
+            }
+        }
+        if !changed {
+            return;
+        }
+        kept.push_str(rest).ok();
+        self.buf = kept;
+        self.stats.garbage_lines = self.stats.garbage_lines.saturating_add(skipped);
+        self.stats.dropped_bytes = self.stats.dropped_bytes.saturating_add(dropped);
+    }
+
+    /// Replace numeric result codes (`ATV0`) in the buffer by their verbose
+    /// counterparts, so they are handled like verbose result codes.
+    ///
+    /// A numeric result code is a line of digits, terminated by
+    /// `line_term_char` only. Information text is terminated by
+    /// `line_term_char` and `format_char`, which tells a line of digits (e.g.
+    /// an IMEI) apart, unless the `format_char` has not been received yet.
+    fn expand_numeric_result_codes(&mut self) {
+        let (term, format) = (self.line_term_char, self.format_char);
+        let bytes = self.buf.as_bytes();
+        let mut expanded = String::<consts::U256>::new();
+        let mut last = 0;
+
+        let line_starts = core::iter::once(0).chain(
+            bytes
+                .iter()
+                .enumerate()
+                .filter(|(_, &c)| c == term || c == format)
+                .map(|(i, _)| i + 1),
+        );
+        for start in line_starts {
+            let digits = bytes[start..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            if digits == 0
+                || digits > 3
+                || bytes.get(start + digits) != Some(&term)
+                || bytes.get(start + digits + 1) == Some(&format)
+            {
+                continue;
+            }
+            let verbose = self.buf[start..start + digits]
+                .parse::<u8>()
+                .ok()
+                .and_then(|code| D::NUMERIC_RESULT_CODES.iter().find(|(c, _)| *c == code))
+                .map(|(_, verbose)| verbose);
+            if let Some(verbose) = verbose {
+                if expanded.push_str(&self.buf[last..start]).is_err()
+                    || expanded.push_str(verbose).is_err()
+                    || expanded.push(term as char).is_err()
+                    || expanded.push(format as char).is_err()
+                {
+                    // TODO: Consider how to act in this situation.
+                    #[cfg(feature = "logging")]
+                    log::error!("No room to expand numeric result code!");
+                    return;
+                }
+                last = start + digits + 1;
+            }
+        }
+
+        if last > 0 {
+            if expanded.push_str(&self.buf[last..]).is_err() {
+                // TODO: Consider how to act in this situation.
+                #[cfg(feature = "logging")]
+                log::error!("No room to expand numeric result code!");
+                return;
+            }
+            self.buf = expanded;
+        }
+    }
+
+    /// Take the first complete line starting with the result code `code`,
+    /// discarding everything before it.
+    fn take_result_code(&mut self, code: &str) -> Option<String<consts::U256>> {
+        self.take_result_code_after(code, 0)
+    }
+
+    /// Take the line starting with the result code `code`, like
+    /// [`take_result_code`](#method.take_result_code), looking for it from
+    /// the index `from` on only.
+    fn take_result_code_after(&mut self, code: &str, from: usize) -> Option<String<consts::U256>> {
+        let is_term = |c: u8| c == self.line_term_char || c == self.format_char;
+        let bytes = self.buf.as_bytes();
+        let start = self.buf[from..]
+            .match_indices(code)
+            .map(|(i, _)| from + i)
+            .find(|&i| i == 0 || is_term(bytes[i - 1]))?;
+        let end = start + bytes[start..].iter().position(|&c| is_term(c))?;
+        let rest = end
+            + bytes[end..]
+                .iter()
+                .position(|&c| !is_term(c))
+                .unwrap_or(bytes.len() - end);
+
+        let line = String::from(&self.buf[start..end]);
+        self.buf = String::from(&self.buf[rest..]);
+        Some(line)
+    }
+
+    /// The last line of `text`, without its line terminators.
+    fn last_line<'t>(&self, text: &'t str) -> &'t str {
+        let is_term = |c: char| c == self.line_term_char as char || c == self.format_char as char;
+        let text = text.trim_end_matches(is_term);
+        text.rsplit(is_term).next().unwrap_or(text)
+    }
+
+    /// Take the first line from the index `from` on that is one of the boot
+    /// banners of the dialect as a whole, discarding everything before it.
+    ///
+    /// With `at_start`, only the line starting at `from` is considered.
+    fn take_boot_banner(&mut self, from: usize, at_start: bool) -> Option<String<consts::U256>> {
+        let is_term = |c: u8| c == self.line_term_char || c == self.format_char;
+        let bytes = self.buf.as_bytes();
+        let mut start = from;
+        loop {
+            let end = start + bytes.get(start..)?.iter().position(|&c| is_term(c))?;
+            let line = &self.buf[start..end];
+            if D::BOOT_BANNERS.iter().any(|banner| *banner == line) {
+                let rest = end
+                    + bytes[end..]
+                        .iter()
+                        .position(|&c| !is_term(c))
+                        .unwrap_or(bytes.len() - end);
+                let banner = String::from(line);
+                self.buf = String::from(&self.buf[rest..]);
+                return Some(banner);
+            }
+            if at_start {
+                return None;
+            }
+            start = end + bytes[end..].iter().position(|&c| !is_term(c))?;
+        }
+    }
+
+    /// Split the ingress manager into the [`IsrProducer`], feeding received
+    /// bytes, and the [`ThreadConsumer`], digesting them, connected through
+    /// `queue`. The bytes the producer has no room for are counted in
+    /// `dropped`.
+    ///
+    /// [`IsrProducer`]: struct.IsrProducer.html
+    /// [`ThreadConsumer`]: struct.ThreadConsumer.html
+    pub(crate) fn split(
+        self,
+        queue: &'static mut RxQueue,
+        dropped: &'static DropCounter,
+    ) -> (IsrProducer, ThreadConsumer<U, D>) {
+        let (rx_p, rx_c) = queue.split();
+        (
+            IsrProducer { rx_p, dropped },
+            ThreadConsumer {
+                ingress: self,
+                rx_c,
+                dropped,
+                dropped_seen: dropped.count(),
+                _not_send: PhantomData,
+            },
+        )
     }
 
     /// Process the receive buffer, checking for AT responses, URC's or errors
     ///
     /// This function should be called regularly for the ingress manager to work
     pub fn digest(&mut self) {
+        // A single read may contain several frames, e.g. a whole response, or
+        // the responses to chained commands, so digest until nothing changes.
+        loop {
+            let before = (self.state, self.buf.len(), self.buf_incomplete);
+            self.digest_frame();
+            if (self.state, self.buf.len(), self.buf_incomplete) == before {
+                break;
+            }
+        }
+        self.scanned = match self.state {
+            State::ReceivingResponse => self.buf.len(),
+            _ => 0,
+        };
+    }
+
+    /// Whether the buffer exceeds the max length of the response being
+    /// received, and the final result code following it.
+    fn response_overflowing(&self) -> bool {
+        self.buf.len()
+            > self
+                .max_response_len
+                .unwrap_or(usize::max_value() - RESULT_CODE_LEN)
+                + RESULT_CODE_LEN
+    }
+
+    /// Whether the bytes received since the buffer was last scanned cannot
+    /// complete the response being received, so scanning it again can be
+    /// skipped.
+    ///
+    /// Apart from prompts, a response is only completed by a line terminator,
+    /// so this keeps receiving a long response from rescanning the whole
+    /// buffer on every byte.
+    fn unchanged_since_scan(&self) -> bool {
+        let received = match self.buf.as_bytes().get(self.scanned..) {
+            Some(received) if self.scanned > 0 && self.stream_prefix.is_none() => received,
+            _ => return false,
+        };
+        let prompted = !D::PROMPT_AFTER_OK
+            && D::PROMPTS.iter().any(|prompt| {
+                let start = self.scanned.saturating_sub(prompt.len() - 1);
+                match self.buf.get(start..) {
+                    Some(s) => s.contains(prompt),
+                    None => true,
+                }
+            });
+        !self.response_overflowing()
+            && !prompted
+            && !received
+                .iter()
+                .any(|&c| c == self.line_term_char || c == self.format_char)
+    }
+
+    /// Digest the next frame in the receive buffer, if complete.
+    fn digest_frame(&mut self) {
         // Handle commands
         self.handle_com();
 
+        // Nothing to parse, bytes are passed through by `write`
+        if let State::DataMode | State::Streaming(_) = self.state {
+            return;
+        }
+
+        if self.state == State::ReceivingResponse && self.unchanged_since_scan() {
+            return;
+        }
+
+        if self.numeric_result_codes {
+            self.expand_numeric_result_codes();
+        }
+
+        if self.resilient_digest || self.tolerant_parsing {
+            match self.state {
+                // Binary URCs are framed by their length rather than lines
+                State::Idle if !self.buf_incomplete && D::binary_urc_len(&self.buf).is_none() => {
+                    self.scrub_lines(false)
+                }
+                State::ReceivingResponse
+                    if self.stream_prefix.is_none() && self.payload_prefix.is_none() =>
+                {
+                    self.scrub_lines(true)
+                }
+                _ => {}
+            }
+        }
+
         // Trim leading whitespace
         if self.buf.starts_with(self.line_term_char as char)
             || self.buf.starts_with(self.format_char as char)
         {
-            // TODO: Custom trim_start, that trims based on line_term_char and format_char
-            self.buf = String::from(self.buf.trim_start());
+            let (term, format) = (self.line_term_char as char, self.format_char as char);
+            self.buf = String::from(
+                self.buf
+                    .trim_start_matches(|c: char| c.is_whitespace() || c == term || c == format),
+            );
+        }
+
+        // The prompt following `OK` carries no information
+        if D::PROMPT_AFTER_OK {
+            if let Some(prompt) = D::PROMPTS.iter().find(|p| self.buf.starts_with(*p)) {
+                self.buf = String::from(self.buf[prompt.len()..].trim_start_matches(' '));
+            }
         }
 
         #[cfg(feature = "logging")]
         log::trace!("Digest / {:?} / {:?}", self.state, self.buf);
 
         match self.state {
+            State::DataMode | State::Streaming(_) => {}
             State::Idle => {
                 // The minimal buffer length that is required to identify all
                 // types of responses (e.g. `AT` and `+`).
@@ -324,11 +1297,18 @@ where
                     unimplemented!("Disabling AT echo is currently unsupported");
                 }
 
+                // A boot banner means the device has been reset
+                if !self.buf_incomplete {
+                    if let Some(banner) = self.take_boot_banner(0, true) {
+                        self.handle_reset(banner);
+                        return;
+                    }
+                }
+
                 // Handle AT echo responses
                 if !self.buf_incomplete && self.echo_enabled && self.buf.starts_with("AT") {
-                    if get_line::<consts::U256, _>(
+                    if get_terminated_line::<consts::U256, _>(
                         &mut self.buf,
-                        unsafe { core::str::from_utf8_unchecked(&[self.line_term_char]) },
                         self.line_term_char,
                         self.format_char,
                         false,
@@ -342,25 +1322,22 @@ where
                         log::trace!("Switching to state ReceivingResponse");
                     }
 
-                // Handle URCs
+                // Apply the custom URC matcher, which takes precedence over
+                // the URCs of the dialect
+                } else if !self.buf_incomplete && self.match_custom_urc() {
+
+                    // Handle URCs
                 } else if !self.buf_incomplete && self.buf.starts_with('+') {
-                    // Try to apply the custom URC matcher
-                    let handled = if let Some(ref mut matcher) = self.custom_urc_matcher {
-                        match matcher.process(&mut self.buf) {
-                            UrcMatcherResult::NotHandled => false,
-                            UrcMatcherResult::Incomplete => true,
-                            UrcMatcherResult::Complete(urc) => {
-                                self.notify_urc(urc);
-                                true
-                            }
-                        }
+                    // URCs carrying binary data are framed by their length
+                    let handled = if let Some(len) = D::binary_urc_len(&self.buf) {
+                        self.take_urc(len);
+                        true
                     } else {
                         false
                     };
                     if !handled {
-                        if let Some(line) = get_line(
+                        if let Some(line) = get_terminated_line(
                             &mut self.buf,
-                            unsafe { core::str::from_utf8_unchecked(&[self.line_term_char]) },
                             self.line_term_char,
                             self.format_char,
                             false,
@@ -371,6 +1348,21 @@ where
                         }
                     }
 
+                // Handle result codes received outside of a response, e.g. `RING`
+                } else if !self.buf_incomplete
+                    && D::UNSOLICITED_RESULT_CODES
+                        .iter()
+                        .any(|c| self.buf.starts_with(c) || c.starts_with(self.buf.as_str()))
+                {
+                    if let Some(code) = D::UNSOLICITED_RESULT_CODES
+                        .iter()
+                        .find(|c| self.buf.starts_with(*c))
+                    {
+                        if let Some(line) = self.take_result_code(code) {
+                            self.notify_urc(line);
+                        }
+                    }
+
                 // Text sent by the device that is not a valid response type (e.g. starting
                 // with "AT" or "+") can be ignored. Clear the buffer, but only if we can
                 // ensure that we don't accidentally break a valid response.
@@ -381,6 +1373,7 @@ where
                         self.buf_incomplete,
                         self.buf.len(),
                     );
+                    self.stats.urcs.record(DropCause::Filtered);
                     self.buf_incomplete = !self.buf.ends_with(self.line_term_char as char)
                         && !self.buf.ends_with(self.format_char as char);
                     self.clear_buf(false);
@@ -394,58 +1387,127 @@ where
                 }
             }
             State::ReceivingResponse => {
-                let resp = if let Some(mut line) = get_line::<consts::U256, _>(
-                    &mut self.buf,
-                    "OK",
-                    self.line_term_char,
-                    self.format_char,
-                    true,
-                    false,
-                ) {
-                    Ok(get_line(
-                        &mut line,
-                        unsafe { core::str::from_utf8_unchecked(&[self.line_term_char]) },
+                // The payload of a streamed response is not buffered
+                if self.stream_prefix.is_some() {
+                    self.start_stream();
+                    if let State::Streaming(_) = self.state {
+                        return;
+                    }
+                }
+
+                // The payload of a size delimited response may hold anything,
+                // e.g. `OK`, so result codes are only looked for after it
+                let from = match self.payload_end() {
+                    Some(from) => from,
+                    None => return,
+                };
+
+                // With several responses in the buffer, the first final result
+                // code completes this one
+                let error_first = match (
+                    self.buf[from..].find(D::OK),
+                    self.buf[from..].find(D::ERROR),
+                ) {
+                    (Some(ok), Some(error)) => error < ok,
+                    (ok, _) => ok.is_none(),
+                };
+                let ok_line = if error_first {
+                    None
+                } else {
+                    get_line::<consts::U256, _>(
+                        &mut self.buf,
+                        from,
+                        D::OK,
+                        self.line_term_char,
+                        self.format_char,
+                        true,
+                    )
+                };
+                let resp = if let Some(mut line) = ok_line {
+                    Ok(get_terminated_line(
+                        &mut line,
                         self.line_term_char,
                         self.format_char,
                         true,
                         true,
                     )
                     .unwrap_or_else(String::new))
-                } else if get_line::<consts::U256, _>(
-                    &mut self.buf,
-                    "ERROR",
-                    self.line_term_char,
-                    self.format_char,
-                    false,
-                    false,
-                )
-                .is_some()
-                {
-                    Err(Error::InvalidResponse)
-                } else if get_line::<consts::U256, _>(
+                } else if let Some(line) = get_line::<consts::U256, _>(
                     &mut self.buf,
-                    ">",
+                    from,
+                    D::ERROR,
                     self.line_term_char,
                     self.format_char,
                     false,
-                    false,
-                )
-                .is_some()
-                    || get_line::<consts::U256, _>(
-                        &mut self.buf,
-                        "@",
-                        self.line_term_char,
-                        self.format_char,
-                        false,
-                        false,
-                    )
-                    .is_some()
+                ) {
+                    let e = Error::InvalidResponse(ErrorContext::response(self.last_line(&line)));
+                    self.record(FrameKind::Error(e.clone()), &line);
+                    Err(e)
+                } else if !D::PROMPT_AFTER_OK
+                    && D::PROMPTS.iter().any(|prompt| {
+                        get_line::<consts::U256, _>(
+                            &mut self.buf,
+                            from,
+                            prompt,
+                            self.line_term_char,
+                            self.format_char,
+                            false,
+                        )
+                        .is_some()
+                    })
                 {
+                    // As well as the space following it, e.g. `> `
+                    self.buf = String::from(self.buf.trim_start_matches(' '));
                     Ok(String::from(""))
+                } else if let Some(line) = self.take_result_code_after("CONNECT", from) {
+                    if !D::CONNECT_ENTERS_DATA_MODE {
+                        Ok(line)
+                    } else {
+                        // `CONNECT [<rate>]` is the final result code of e.g. `ATD`,
+                        // after which the device switches to data mode. Anything
+                        // following it is already data.
+                        self.notify_response(Ok(line));
+                        #[cfg(feature = "logging")]
+                        log::trace!("Switching to state DataMode");
+                        self.enter_data_mode();
+                        return;
+                    }
+                } else if let Some((e, line)) =
+                    D::ERROR_RESULT_CODES.iter().find_map(|(code, e)| {
+                        self.take_result_code_after(code, from)
+                            .map(|line| (e.clone().with_response(&line), line))
+                    })
+                {
+                    self.record(FrameKind::Error(e.clone()), &line);
+                    Err(e)
+                } else if let Some(banner) = self.take_boot_banner(from, false) {
+                    // The device has been reset, dropping the response in
+                    // progress
+                    self.handle_reset(banner);
+                    return;
+                } else if self.response_overflowing() {
+                    // Fail fast, rather than waiting for the buffer to fill up
+                    #[cfg(feature = "trace")]
+                    self.trace
+                        .record(FrameKind::Error(Error::Overflow), &self.buf);
+                    self.stats.responses.record(DropCause::Overflow);
+                    self.clear_buf(true);
+                    Err(Error::Overflow)
                 } else {
                     return;
                 };
 
+                self.stream_prefix = None;
+                self.payload_prefix = None;
+                let resp = match (resp, self.max_response_len.take()) {
+                    (Ok(ref r), Some(max)) if r.len() > max => {
+                        self.record(FrameKind::Error(Error::Overflow), r);
+                        self.stats.responses.record(DropCause::Overflow);
+                        Err(Error::Overflow)
+                    }
+                    (resp, _) => resp,
+                };
+
                 self.notify_response(resp);
                 #[cfg(feature = "logging")]
                 log::trace!("Switching to state Idle");
@@ -455,26 +1517,143 @@ where
     }
 }
 
+/// The receiving half of a split ingress manager, feeding received bytes.
+///
+/// This is meant to be owned by the UART Rx interrupt (or DMA interrupt),
+/// being the only context that feeds bytes. It does no processing, so it is
+/// cheap to call from interrupt context.
+pub struct IsrProducer {
+    rx_p: RxProducer,
+    dropped: &'static DropCounter,
+}
+
+impl IsrProducer {
+    /// Feed received bytes to the [`ThreadConsumer`](struct.ThreadConsumer.html).
+    ///
+    /// Bytes that do not fit in the queue are dropped, and counted in the
+    /// `dropped_bytes` of its [`stats`](struct.ThreadConsumer.html#method.stats).
+    pub fn write(&mut self, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            if self.rx_p.enqueue(*byte).is_err() {
+                #[cfg(feature = "logging")]
+                log::error!("Rx queue is full, dropping received bytes!");
+                self.dropped.dropped(data.len() - i);
+                break;
+            }
+        }
+    }
+
+    /// Whether the queue to the [`ThreadConsumer`] is full, so that further
+    /// received bytes are dropped until it is digested.
+    ///
+    /// [`ThreadConsumer`]: struct.ThreadConsumer.html
+    pub fn is_full(&self) -> bool {
+        !self.rx_p.ready()
+    }
+}
+
+/// The processing half of a split ingress manager, digesting the bytes
+/// received by the [`IsrProducer`](struct.IsrProducer.html).
+///
+/// This is meant to be owned by thread mode, and is neither `Send` nor `Sync`,
+/// so it cannot be handed to an interrupt.
+pub struct ThreadConsumer<U, D = V25ter> {
+    ingress: IngressManager<U, D>,
+    rx_c: RxConsumer,
+    dropped: &'static DropCounter,
+    /// The count of `dropped` already recorded in the stats
+    dropped_seen: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<U, D> ThreadConsumer<U, D>
+where
+    U: UrcMatcher<MaxLen = consts::U256>,
+    D: Dialect,
+{
+    /// The last frames digested. See
+    /// [`IngressManager::trace`](struct.IngressManager.html#method.trace).
+    #[cfg(feature = "trace")]
+    pub fn trace(&self) -> &TraceRing {
+        self.ingress.trace()
+    }
+
+    /// Frames dropped on the way to the client. See
+    /// [`IngressManager::stats`](struct.IngressManager.html#method.stats).
+    pub fn stats(&self) -> &QueueStats {
+        self.ingress.stats()
+    }
+
+    /// Fill levels of the queues to and from the client. See
+    /// [`IngressManager::queue_levels`](struct.IngressManager.html#method.queue_levels).
+    pub fn queue_levels(&self) -> Option<QueueLevels> {
+        self.ingress.queue_levels()
+    }
+
+    /// Reset the counters of dropped frames.
+    pub fn clear_stats(&mut self) {
+        self.ingress.clear_stats();
+    }
+
+    /// Move the bytes fed by the [`IsrProducer`](struct.IsrProducer.html) into
+    /// the receive buffer, and process it. See
+    /// [`IngressManager::digest`](struct.IngressManager.html#method.digest).
+    pub fn digest(&mut self) {
+        let dropped = self.dropped.count();
+        let new_drops = dropped.wrapping_sub(core::mem::replace(&mut self.dropped_seen, dropped));
+        self.ingress.stats.dropped_bytes = self
+            .ingress
+            .stats
+            .dropped_bytes
+            .saturating_add(new_drops as u32);
+
+        let mut buf = [0; 32];
+        loop {
+            let mut len = 0;
+            while len < buf.len() {
+                match self.rx_c.dequeue() {
+                    Some(byte) => {
+                        buf[len] = byte;
+                        len += 1;
+                    }
+                    None => break,
+                }
+            }
+            if len > 0 {
+                self.ingress.write_slice(&buf[..len]);
+            }
+            // Chunk by chunk, as the bytes queued may exceed the receive buffer
+            self.ingress.digest();
+            if len < buf.len() {
+                break;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 #[cfg_attr(tarpaulin, skip)]
 mod test {
     use super::*;
     use crate as atat;
+    use crate::queues::{ComQueue, DataQueue, PriorityUrcQueue, ResQueue, StaticQueue, UrcQueue};
+    use crate::stats::DropStats;
+    use crate::stats::FillLevel;
     use atat::Mode;
-    use heapless::{consts, spsc::Queue, String};
+    use heapless::{consts, String};
 
     macro_rules! setup {
         ($config:expr, $urch:expr) => {{
-            static mut REQ_Q: Queue<Result<String<consts::U256>, Error>, consts::U5, u8> =
-                Queue(heapless::i::Queue::u8());
+            static mut REQ_Q: ResQueue = ResQueue::new();
             let (req_p, req_c) = unsafe { REQ_Q.split() };
-            static mut URC_Q: Queue<String<consts::U256>, consts::U10, u8> =
-                Queue(heapless::i::Queue::u8());
+            static mut URC_Q: UrcQueue = UrcQueue::NEW;
             let (urc_p, urc_c) = unsafe { URC_Q.split() };
-            static mut COM_Q: Queue<Command, consts::U3, u8> = Queue(heapless::i::Queue::u8());
+            static mut COM_Q: ComQueue = ComQueue::NEW;
             let (_com_p, com_c) = unsafe { COM_Q.split() };
+            static mut DATA_Q: DataQueue = DataQueue::NEW;
+            let (data_p, _data_c) = unsafe { DATA_Q.split() };
             (
-                IngressManager::new(req_p, urc_p, com_c, $config, $urch),
+                IngressManager::new(req_p, urc_p, com_c, data_p, $config, $urch),
                 req_c,
                 urc_c,
             )
@@ -483,103 +1662,937 @@ mod test {
             let val: (IngressManager<NoopUrcMatcher>, _, _) = setup!($config, None);
             val
         }};
+        (@dialect $dialect:ty, $config:expr) => {{
+            static mut REQ_Q: ResQueue = ResQueue::new();
+            let (req_p, req_c) = unsafe { REQ_Q.split() };
+            static mut URC_Q: UrcQueue = UrcQueue::NEW;
+            let (urc_p, urc_c) = unsafe { URC_Q.split() };
+            static mut COM_Q: ComQueue = ComQueue::NEW;
+            let (_com_p, com_c) = unsafe { COM_Q.split() };
+            static mut DATA_Q: DataQueue = DataQueue::NEW;
+            let (data_p, _data_c) = unsafe { DATA_Q.split() };
+            let at_pars: IngressManager<NoopUrcMatcher, $dialect> =
+                IngressManager::with_dialect(req_p, urc_p, com_c, data_p, $config, None);
+            (at_pars, req_c, urc_c)
+        }};
+    }
+
+    #[test]
+    fn no_response() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        assert_eq!(at_pars.state, State::Idle);
+        at_pars.write("AT\r\r\n\r\n".as_bytes());
+        at_pars.digest();
+
+        assert_eq!(at_pars.state, State::ReceivingResponse);
+
+        at_pars.write("OK\r\n".as_bytes());
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from(""))
+        );
+    }
+
+    #[test]
+    fn response() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        assert_eq!(at_pars.state, State::Idle);
+        at_pars.write("AT+USORD=3,16\r\n".as_bytes());
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::ReceivingResponse);
+
+        at_pars.write("+USORD: 3,16,\"16 bytes of data\"\r\n".as_bytes());
+        at_pars.digest();
+
+        assert_eq!(
+            at_pars.buf,
+            String::<consts::U256>::from("+USORD: 3,16,\"16 bytes of data\"\r\n")
+        );
+
+        at_pars.write("OK\r\n".as_bytes());
+        assert_eq!(
+            at_pars.buf,
+            String::<consts::U256>::from("+USORD: 3,16,\"16 bytes of data\"\r\nOK\r\n")
+        );
+        at_pars.digest();
+        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from(
+                "+USORD: 3,16,\"16 bytes of data\""
+            ))
+        );
+    }
+
+    #[test]
+    fn multi_line_response() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        assert_eq!(at_pars.state, State::Idle);
+        at_pars.write("AT+GMR\r\r\n".as_bytes());
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::ReceivingResponse);
+
+        at_pars.write("AT version:1.1.0.0(May 11 2016 18:09:56)\r\nSDK version:1.5.4(baaeaebb)\r\ncompile time:May 20 2016 15:08:19\r\nOK\r\n".as_bytes());
+        at_pars.digest();
+
+        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(req_c.dequeue().unwrap().into_result(), Ok(String::<consts::U256>::from("AT version:1.1.0.0(May 11 2016 18:09:56)\r\nSDK version:1.5.4(baaeaebb)\r\ncompile time:May 20 2016 15:08:19")));
+    }
+
+    #[test]
+    fn urc() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, _req_c, _urc_c) = setup!(conf);
+
+        assert_eq!(at_pars.state, State::Idle);
+
+        at_pars.write("+UUSORD: 3,16,\"16 bytes of data\"\r\n".as_bytes());
+        at_pars.digest();
+        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+        assert_eq!(at_pars.state, State::Idle);
+    }
+
+    #[test]
+    fn overflow() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        for _ in 0..266 {
+            at_pars.write(b"s");
+        }
+        at_pars.digest();
+        assert_eq!(req_c.dequeue().unwrap().into_result(), Err(Error::Overflow));
+    }
+
+    #[test]
+    fn overflow_policy() {
+        let conf = Config::new(Mode::Timeout).with_overflow_policy(OverflowPolicy::DropNewest);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CGDCONT?\r\r\n");
+        at_pars.digest();
+        for _ in 0..26 {
+            at_pars.write(b"+CGDCONT: 1,\"IP\",\"inet\"\r\n");
+        }
+        assert!(at_pars.is_full());
+        at_pars.write(b"OK\r\n");
+        at_pars.digest();
+        assert_eq!(req_c.dequeue(), None);
+        assert_eq!(at_pars.stats().responses.overflow, 17);
+
+        let conf = Config::new(Mode::Timeout).with_overflow_policy(OverflowPolicy::DropOldest);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CGDCONT?\r\r\n");
+        at_pars.digest();
+        for cid in 0..26 {
+            at_pars.write(format!("+CGDCONT: {},\"IP\",\"inet\"\r\n", cid % 10).as_bytes());
+        }
+        assert!(!at_pars.is_full());
+        at_pars.write(b"OK\r\n");
+        at_pars.digest();
+        let resp = req_c.dequeue().unwrap().into_result().unwrap();
+        assert!(resp.starts_with("+CGDCONT: 6,"));
+        assert!(resp.ends_with("+CGDCONT: 5,\"IP\",\"inet\""));
+    }
+
+    #[test]
+    fn queue_levels() {
+        static COUNTERS: QueueCounters = QueueCounters::new();
+        let conf = Config::new(Mode::Timeout).with_overflow_policy(OverflowPolicy::DropNewest);
+        let (mut at_pars, _req_c, mut urc_c) = setup!(conf);
+        assert_eq!(at_pars.queue_levels(), None);
+        at_pars.set_queue_counters(&COUNTERS);
+
+        for _ in 0..3 {
+            at_pars.write(b"+CSQ: 20,99\r\n");
+            at_pars.digest();
+        }
+        urc_c.dequeue().unwrap();
+        COUNTERS.urcs.dequeued();
+        let levels = at_pars.queue_levels().unwrap();
+        assert_eq!(
+            levels.urcs,
+            FillLevel {
+                len: 2,
+                high_watermark: 3,
+                capacity: 10
+            }
+        );
+        assert_eq!(levels.responses.high_watermark, 0);
+        assert_eq!(levels.commands.capacity, 3);
+
+        at_pars.write(&[b'x'; 300]);
+        assert_eq!(at_pars.stats().dropped_bytes, 44);
+    }
+
+    #[test]
+    fn trace_handler() {
+        std::thread_local! {
+            static TRACED: core::cell::RefCell<std::vec::Vec<u8>> = Default::default();
+        }
+        fn handler(direction: TraceDirection, data: &[u8]) {
+            assert_eq!(direction, TraceDirection::Rx);
+            TRACED.with(|t| t.borrow_mut().extend_from_slice(data));
+        }
+
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, _req_c, _urc_c) = setup!(conf);
+        at_pars.set_trace_handler(handler);
+
+        at_pars.write(b"\r\n+CSQ: 20");
+        at_pars.write(b",99\r\n");
+        at_pars.digest();
+        TRACED.with(|t| assert_eq!(t.borrow().as_slice(), b"\r\n+CSQ: 20,99\r\n"));
+
+        // Chunks are traced as a whole, in order, whichever way they are
+        // buffered
+        TRACED.with(|t| t.borrow_mut().clear());
+        at_pars.write_slice(b"+UMWI: \xb5\r\n");
+        at_pars.enter_data_mode();
+        at_pars.write_slice(b"\x7e\xff\x7d");
+        TRACED.with(|t| assert_eq!(t.borrow().as_slice(), b"+UMWI: \xb5\r\n\x7e\xff\x7d"));
+    }
+
+    #[test]
+    fn delimited_payload() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+USORD=0,6\r\r\n");
+        at_pars.digest();
+        at_pars.payload_prefix = Some("+USORD:");
+        at_pars.write(b"+USORD: 0,6,\"\r\nOK");
+        at_pars.digest();
+        at_pars.write(b"\r\n");
+        at_pars.digest();
+        assert!(req_c.dequeue().is_none());
+        at_pars.write(b"\"\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().map(|r| r.into_result()),
+            Some(Ok(String::<consts::U256>::from(
+                "+USORD: 0,6,\"\r\nOK\r\n\""
+            )))
+        );
+        assert_eq!(at_pars.payload_prefix, None);
+        assert_eq!(at_pars.state, State::Idle);
+
+        // Errors are not delimited
+        at_pars.write(b"AT+USORD=1,6\r\r\n");
+        at_pars.digest();
+        at_pars.payload_prefix = Some("+USORD:");
+        at_pars.write(b"+CME ERROR: 8\r\n");
+        at_pars.digest();
+        assert!(req_c.dequeue().unwrap().into_result().is_err());
+        assert_eq!(at_pars.state, State::Idle);
+    }
+
+    #[test]
+    fn skip_payload_echo() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CIPSEND=0,8\r\r\n> ");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().map(|r| r.into_result()),
+            Some(Ok(String::<consts::U256>::from("")))
+        );
+        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+
+        // The echo starts arriving before the command to skip it is handled
+        at_pars.write(b"\r\nOK");
+        at_pars.skip_echo(8);
+        assert_eq!(at_pars.echo_skip, 4);
+        at_pars.write_slice(b"\r\n\xffE");
+        assert_eq!(at_pars.echo_skip, 0);
+        at_pars.state = State::ReceivingResponse;
+        at_pars.write(b"\r\n+CIPSEND: 0,8\r\n\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().map(|r| r.into_result()),
+            Some(Ok(String::<consts::U256>::from("+CIPSEND: 0,8")))
+        );
+        assert_eq!(at_pars.state, State::Idle);
+    }
+
+    #[test]
+    fn stream_payload() {
+        std::thread_local! {
+            static STREAMED: core::cell::RefCell<std::vec::Vec<u8>> = Default::default();
+        }
+        fn sink(data: &[u8]) {
+            STREAMED.with(|s| s.borrow_mut().extend_from_slice(data));
+        }
+
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+        at_pars.set_stream_sink(sink);
+
+        // A quoted payload, much larger than the buffer
+        let mut payload = std::vec::Vec::new();
+        for i in 0..300 {
+            payload.push(match i % 50 {
+                0 => b'\r',
+                1 => b'\n',
+                2 => b'O',
+                3 => b'K',
+                _ => (i % 256) as u8,
+            });
+        }
+        at_pars.write(b"AT+URDFILE=\"f.txt\"\r\r\n");
+        at_pars.digest();
+        at_pars.stream_prefix = Some("+URDFILE:");
+        at_pars.write(b"+URDFILE: \"f.txt\",300,\"");
+        for chunk in payload.chunks(64) {
+            at_pars.write(chunk);
+            at_pars.digest();
+        }
+        assert_eq!(at_pars.state, State::ReceivingResponse);
+        at_pars.write(b"\"\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().map(|r| r.into_result()),
+            Some(Ok(String::<consts::U256>::from(
+                "+URDFILE: \"f.txt\",300,\"\""
+            )))
+        );
+        assert_eq!(STREAMED.with(|s| s.replace(vec![])), payload);
+
+        // A payload on the line following the header
+        at_pars.write(b"AT+HTTPREAD=0,5\r\r\n");
+        at_pars.digest();
+        at_pars.stream_prefix = Some("+HTTPREAD:");
+        at_pars.write_slice(b"+HTTPREAD: 5\r\nOK\r\n!\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(
+            req_c.dequeue().map(|r| r.into_result()),
+            Some(Ok(String::<consts::U256>::from("+HTTPREAD: 5")))
+        );
+        assert_eq!(STREAMED.with(|s| s.replace(vec![])), b"OK\r\n!");
+    }
+
+    #[test]
+    fn result_codes() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(conf);
+
+        at_pars.write(b"ATD*99#\r\r\nCONNECT 115200\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("CONNECT 115200"))
+        );
+        assert_eq!(at_pars.state, State::DataMode);
+        at_pars.state = State::Idle;
+
+        at_pars.write(b"ATD+4512345678;\r\r\nBUSY\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(req_c.dequeue().unwrap().into_result(), Err(Error::Busy));
+
+        // A result code has to start a line
+        at_pars.write(b"AT+UCONNECT?\r\r\n+UCONNECT: 1\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(req_c.dequeue(), None);
+        at_pars.write(b"OK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+UCONNECT: 1"))
+        );
+
+        // Unsolicited result codes, also when received in parts
+        at_pars.write(b"\r\nRI");
+        at_pars.digest();
+        at_pars.write(b"NG\r\n");
+        at_pars.digest();
+        at_pars.write(b"\r\nNO CARRIER\r\n");
+        at_pars.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("RING")
+        );
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("NO CARRIER")
+        );
+        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+    }
+
+    #[test]
+    fn data_mode() {
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(conf);
+        let (data_p, mut data_c) = unsafe { DATA_Q.split() };
+        at_pars.data_p = data_p;
+
+        // Data following `CONNECT` in the same chunk is passed through as well
+        at_pars.write(b"ATD*99#\r\r\nCONNECT 115200\r\n~\x7d\xff");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("CONNECT 115200"))
+        );
+        assert_eq!(at_pars.state, State::DataMode);
+
+        at_pars.write(b"\r\nOK\r\n+CSQ: 31,99\r\n");
+        at_pars.digest();
+        assert_eq!(req_c.dequeue(), None);
+        assert_eq!(urc_c.dequeue(), None);
+        let mut data: heapless::Vec<u8, consts::U32> = heapless::Vec::new();
+        while let Some(b) = data_c.dequeue() {
+            data.push(b).unwrap();
+        }
+        assert_eq!(&data[..], b"~\x7d\xff\r\nOK\r\n+CSQ: 31,99\r\n");
+
+        // Back to command mode
+        at_pars.state = State::Idle;
+        at_pars.write(b"AT+CSQ\r\n+CSQ: 31,99\r\nOK\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "trace")]
+    fn trace() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CSQ\r\n+CSQ: 31,99\r\nOK\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        at_pars.write(b"+UMWI: 0,1\r\n");
+        at_pars.digest();
+        at_pars.write(b"AT+CCID\r\n+CME ERROR: 10\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        at_pars.write(b"+UMWI: 0,2\r\n+UMWI: 0,3\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        at_pars.digest();
+        while req_c.dequeue().is_some() {}
+        while urc_c.dequeue().is_some() {}
+
+        // Only the last 4 frames are kept, oldest first
+        let frames: heapless::Vec<_, consts::U4> = at_pars
+            .trace()
+            .iter()
+            .map(|f| (f.kind.clone(), f.data.as_str()))
+            .collect();
+        assert_eq!(
+            &frames[..],
+            &[
+                (FrameKind::Urc, "+UMWI: 0,1\r\n"),
+                (
+                    FrameKind::Error(Error::InvalidResponse(ErrorContext::response("+CME ERROR"))),
+                    "+CME ERROR"
+                ),
+                (FrameKind::Urc, "+UMWI: 0,2\r\n"),
+                (FrameKind::Urc, "+UMWI: 0,3\r\n"),
+            ][..]
+        );
+    }
+
+    #[test]
+    fn dialect() {
+        struct Quirky;
+
+        impl Dialect for Quirky {
+            const PROMPTS: &'static [&'static str] = &[">"];
+            const UNSOLICITED_RESULT_CODES: &'static [&'static str] = &["RING"];
+            const CONNECT_ENTERS_DATA_MODE: bool = false;
+        }
+
+        let (mut at_pars, mut req_c, mut urc_c) =
+            setup!(@dialect Quirky, Config::new(Mode::Timeout));
+
+        at_pars.write(b"AT+USOCO=0,\"1.2.3.4\",80\r\r\nCONNECT\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("CONNECT"))
+        );
+        assert_eq!(at_pars.state, State::Idle);
+
+        // `@` is not a prompt
+        at_pars.write(b"AT+CPWD?\r\r\n+CPWD: \"@\"\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(req_c.dequeue(), None);
+        at_pars.write(b"OK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CPWD: \"@\""))
+        );
+
+        at_pars.write(b"\r\nNO CARRIER\r\n\r\nRING\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("RING")
+        );
+        assert_eq!(urc_c.dequeue(), None);
+    }
+
+    #[test]
+    fn numeric_result_codes() {
+        let conf = Config::new(Mode::Timeout).with_numeric_result_codes(true);
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CSQ\r+CSQ: 31,99\r\n0\r");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+
+        // A line of digits is information text
+        at_pars.write(b"AT+CGSN\r356938035643809\r\n0\r");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("356938035643809"))
+        );
+
+        at_pars.write(b"AT+CMEE=5\r4\r");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Err(Error::InvalidResponse(ErrorContext::response("ERROR")))
+        );
+
+        at_pars.write(b"2\r");
+        at_pars.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("RING")
+        );
+        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+    }
+
+    #[test]
+    fn bare_line_terminators() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CSQ\n+CSQ: 31,99\nOK\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+
+        at_pars.write(b"+UMWI: 0,1\n");
+        at_pars.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+UMWI: 0,1\n")
+        );
+
+        at_pars.write(b"AT+CSQ\r+CSQ: 31,99\rOK\r");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+
+        // Non-standard terminators
+        let conf = Config::new(Mode::Timeout)
+            .with_line_term(b';')
+            .with_format_char(b'|');
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+        at_pars.write(b"AT+CSQ;|+CSQ: 31,99;|OK;|");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
     }
 
     #[test]
-    fn no_response() {
-        let conf = Config::new(Mode::Timeout);
-        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+    fn truncated_urc() {
+        let (mut at_pars, _req_c, mut urc_c) = setup!(Config::new(Mode::Timeout));
 
-        assert_eq!(at_pars.state, State::Idle);
-        at_pars.write("AT\r\r\n\r\n".as_bytes());
+        at_pars.write(b"\r\n+UUSORD: 0,300,\"");
+        for _ in 0..30 {
+            at_pars.write(b"0123456789");
+        }
+        at_pars.write(b"\"\r\n+UMWI: 0,1\r\n");
+        at_pars.digest();
         at_pars.digest();
 
-        assert_eq!(at_pars.state, State::ReceivingResponse);
+        let urc = urc_c.dequeue().unwrap();
+        assert_eq!(urc.truncated_len, Some(317));
+        assert_eq!(urc.line.len(), 254);
+        assert!(urc.line.starts_with("+UUSORD: 0,300,\"0123"));
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+UMWI: 0,1\r\n")
+        );
+        assert_eq!(urc_c.dequeue(), None);
+    }
 
-        at_pars.write("OK\r\n".as_bytes());
+    #[test]
+    fn write_slice() {
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(Config::new(Mode::Timeout));
+
+        let mut data: heapless::Vec<u8, consts::U512> = heapless::Vec::new();
+        data.extend_from_slice(b"\r\n+UUSORD: 0,300,\"").unwrap();
+        for _ in 0..30 {
+            data.extend_from_slice(b"0123456789").unwrap();
+        }
+        data.extend_from_slice(b"\"\r\nAT+CGMI\r\r\n\xb5-blox\r\nOK\r\n")
+            .unwrap();
+        at_pars.write_slice(&data);
         at_pars.digest();
-        assert_eq!(at_pars.state, State::Idle);
+
+        let urc = urc_c.dequeue().unwrap();
+        assert_eq!(urc.truncated_len, Some(317));
+        assert_eq!(urc.line.len(), 254);
+        assert_eq!(urc_c.dequeue(), None);
         assert_eq!(
-            req_c.dequeue().unwrap(),
-            Ok(String::<consts::U256>::from(""))
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("\u{b5}-blox"))
         );
+        assert_eq!(at_pars.state, State::Idle);
     }
 
     #[test]
-    fn response() {
-        let conf = Config::new(Mode::Timeout);
-        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+    fn modem_reset() {
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(Config::new(Mode::Timeout));
 
-        assert_eq!(at_pars.state, State::Idle);
-        at_pars.write("AT+USORD=3,16\r\n".as_bytes());
+        at_pars.write(b"AT+CSQ\r\r\n+CSQ: 3");
+        at_pars.digest();
         at_pars.digest();
         assert_eq!(at_pars.state, State::ReceivingResponse);
 
-        at_pars.write("+USORD: 3,16,\"16 bytes of data\"\r\n".as_bytes());
+        at_pars.write(b"\r\nRDY\r\n\r\n+CFUN: 1\r\n");
         at_pars.digest();
-
+        assert_eq!(at_pars.state, State::Idle);
         assert_eq!(
-            at_pars.buf,
-            String::<consts::U256>::from("+USORD: 3,16,\"16 bytes of data\"\r\n")
+            req_c.dequeue().unwrap().into_result(),
+            Err(Error::ModemReset)
+        );
+        assert_eq!(req_c.dequeue(), None);
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("RDY")
         );
 
-        at_pars.write("OK\r\n".as_bytes());
+        // The rest of the buffer is digested as usual
+        at_pars.digest();
         assert_eq!(
-            at_pars.buf,
-            String::<consts::U256>::from("+USORD: 3,16,\"16 bytes of data\"\r\nOK\r\n")
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+CFUN: 1\r\n")
         );
+    }
+
+    #[test]
+    fn boot_banner_whole_line() {
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(Config::new(Mode::Timeout));
+
+        // Frames preceding the banner in the same read are digested first
+        at_pars.write(b"AT+CSQ\r\r\n+CSQ: 31,99\r\n\r\nOK\r\n+UMWI: 0,1\r\nRDY\r\n");
         at_pars.digest();
-        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+        assert_eq!(req_c.dequeue(), None);
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+UMWI: 0,1\r\n")
+        );
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("RDY")
+        );
         assert_eq!(at_pars.state, State::Idle);
+
+        // A banner within a parameter is no banner
+        at_pars.write(b"AT+COPS?\r\r\n+COPS: 0,0,\"RDY\"\r\n\r\nOK\r\n");
+        at_pars.digest();
         assert_eq!(
-            req_c.dequeue().unwrap(),
-            Ok(String::<consts::U256>::from(
-                "+USORD: 3,16,\"16 bytes of data\""
-            ))
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+COPS: 0,0,\"RDY\""))
+        );
+        assert_eq!(urc_c.dequeue(), None);
+
+        // Nor is the SIM not being ready yet
+        at_pars.write(b"AT+CPIN?\r\r\n+CPIN: NOT READY\r\n\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CPIN: NOT READY"))
         );
+        assert_eq!(urc_c.dequeue(), None);
     }
 
     #[test]
-    fn multi_line_response() {
-        let conf = Config::new(Mode::Timeout);
-        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+    fn drop_stats() {
+        let (mut at_pars, _req_c, _urc_c) = setup!(Config::new(Mode::Timeout));
 
-        assert_eq!(at_pars.state, State::Idle);
-        at_pars.write("AT+GMR\r\r\n".as_bytes());
+        // Garbage
+        at_pars.write(b"garbage\r\n");
         at_pars.digest();
-        assert_eq!(at_pars.state, State::ReceivingResponse);
+        assert_eq!(at_pars.stats().urcs.filtered, 1);
 
-        at_pars.write("AT version:1.1.0.0(May 11 2016 18:09:56)\r\nSDK version:1.5.4(baaeaebb)\r\ncompile time:May 20 2016 15:08:19\r\nOK\r\n".as_bytes());
+        // The URC queue fills up, as the client does not poll it
+        for _ in 0..12 {
+            at_pars.write(b"+UMWI: 0,1\r\n");
+            at_pars.digest();
+        }
+        assert_eq!(at_pars.stats().urcs.queue_full, 2);
+        assert_eq!(at_pars.stats().urcs.total(), 3);
+        assert_eq!(at_pars.stats().responses, DropStats::default());
+
+        at_pars.clear_stats();
+        assert_eq!(at_pars.stats().urcs.total(), 0);
+    }
+
+    #[test]
+    fn squeezed_frames() {
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(Config::new(Mode::Timeout));
+
+        // A whole response in a single read
+        at_pars.write(b"AT+CSQ\r\r\n+CSQ: 31,99\r\n\r\nOK\r\n");
         at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+        assert_eq!(at_pars.state, State::Idle);
 
-        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+        // The responses to chained commands, and a URC, in a single read
+        at_pars.write(b"ATE1\r\r\nOK\r\nAT+CGMI\r\r\nu-blox\r\n\r\nOK\r\n+UMWI: 0,1\r\n");
+        at_pars.write(b"AT+CMEE=2\r\r\nERROR\r\nAT\r\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from(""))
+        );
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("u-blox"))
+        );
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Err(Error::InvalidResponse(ErrorContext::response("ERROR")))
+        );
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from(""))
+        );
+        assert_eq!(req_c.dequeue(), None);
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+UMWI: 0,1\r\n")
+        );
         assert_eq!(at_pars.state, State::Idle);
-        assert_eq!(req_c.dequeue().unwrap(), Ok(String::<consts::U256>::from("AT version:1.1.0.0(May 11 2016 18:09:56)\r\nSDK version:1.5.4(baaeaebb)\r\ncompile time:May 20 2016 15:08:19")));
+        assert_eq!(at_pars.buf, "");
     }
 
     #[test]
-    fn urc() {
+    fn esp_at() {
+        use crate::dialect::EspAt;
+
+        let (mut at_pars, mut req_c, mut urc_c) =
+            setup!(@dialect EspAt, Config::new(Mode::Timeout));
+
+        at_pars.write(b"\r\nready\r\nWIFI CONNECTED\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(req_c.dequeue(), None);
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("ready")
+        );
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("WIFI CONNECTED")
+        );
+
+        at_pars.write(b"AT+CIPSEND=0,5\r\r\nOK\r\n> ");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::new())
+        );
+        at_pars.state = State::ReceivingResponse;
+        at_pars.write(b"\r\nRecv 5 bytes\r\n\r\nSEND FAIL\r\n");
+        at_pars.digest();
+        // The error result code of the dialect, with the line it was found in
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Err(Error::InvalidResponse(ErrorContext::response("SEND FAIL")))
+        );
+        assert_eq!(req_c.dequeue(), None);
+
+        at_pars.write(b"AT+CWJAP?\r\r\nbusy p...\r\n");
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(req_c.dequeue().unwrap().into_result(), Err(Error::Busy));
+
+        // Binary data, received in parts
+        at_pars.write(b"+IPD,0,6:\r\nOK");
+        at_pars.digest();
+        assert_eq!(urc_c.dequeue(), None);
+        at_pars.write(b"\r\n+IPD,3:abc+IPD,2,\"192.168.1.1\",80:hi");
+        at_pars.digest();
+        at_pars.digest();
+        at_pars.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+IPD,0,6:\r\nOK\r\n")
+        );
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+IPD,3:abc")
+        );
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+IPD,2,\"192.168.1.1\",80:hi")
+        );
+        assert_eq!(at_pars.buf, String::<consts::U256>::new());
+    }
+
+    #[test]
+    fn split() {
+        static mut RX_Q: RxQueue = RxQueue::NEW;
+        static RX_DROPPED: DropCounter = DropCounter::new();
         let conf = Config::new(Mode::Timeout);
-        let (mut at_pars, _req_c, _urc_c) = setup!(conf);
+        let (at_pars, mut req_c, mut urc_c) = setup!(conf);
+        let (mut isr, mut thread) = at_pars.split(unsafe { &mut RX_Q }, &RX_DROPPED);
 
-        assert_eq!(at_pars.state, State::Idle);
+        isr.write(b"AT+CSQ\r\n+CSQ: 31,99\r\nOK\r\n");
+        thread.digest();
+        thread.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+        assert_eq!(thread.ingress.state, State::Idle);
+
+        // A backlog exceeding the receive buffer, along with what it holds
+        // already, is digested without loss
+        isr.write(b"+UMWI: 0,");
+        thread.digest();
+        isr.write(b"1\r\n");
+        for _ in 0..20 {
+            isr.write(b"garbage...\r\n");
+        }
+        isr.write(b"+UMWI: 0,2\r\n");
+        thread.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+UMWI: 0,1\r\n")
+        );
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+UMWI: 0,2\r\n")
+        );
+        assert_eq!(RX_DROPPED.count(), 0);
+
+        // Bytes the queue has no room for are counted along with the bytes
+        // discarded as garbage
+        let dropped = thread.stats().dropped_bytes;
+        isr.write(&[b'x'; 300]);
+        assert_eq!(RX_DROPPED.count(), 44);
+        thread.digest();
+        assert_eq!(thread.stats().dropped_bytes - dropped, 300);
+    }
 
-        at_pars.write("+UUSORD: 3,16,\"16 bytes of data\"\r\n".as_bytes());
+    #[test]
+    fn multi_stage_prompt() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, mut urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CMGS=\"+4512345678\"\r\r\n> ");
         at_pars.digest();
-        assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::new())
+        );
         assert_eq!(at_pars.state, State::Idle);
+
+        // The echo of the body has no line terminator
+        at_pars.state = State::ReceivingResponse;
+        at_pars.write(b"Hello\x1a\r\n+CMGS: 12\r\n\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("Hello\x1a\r\n+CMGS: 12"))
+        );
+        assert_eq!(urc_c.dequeue(), None);
     }
 
     #[test]
-    fn overflow() {
+    fn max_response_len() {
         let conf = Config::new(Mode::Timeout);
         let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
 
-        for _ in 0..266 {
-            at_pars.write(b"s");
-        }
+        // Response within limits
+        at_pars.max_response_len = Some(16);
+        at_pars.write(b"AT+CSQ\r\n");
+        at_pars.digest();
+        at_pars.write(b"+CSQ: 31,99\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from("+CSQ: 31,99"))
+        );
+        assert_eq!(at_pars.max_response_len, None);
+
+        // Complete response exceeding the limit
+        at_pars.max_response_len = Some(8);
+        at_pars.write(b"AT+CSQ\r\n");
+        at_pars.digest();
+        at_pars.write(b"+CSQ: 31,99\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(req_c.dequeue().unwrap().into_result(), Err(Error::Overflow));
+        assert_eq!(at_pars.state, State::Idle);
+
+        // Incomplete response exceeding the limit fails fast
+        at_pars.max_response_len = Some(8);
+        at_pars.write(b"AT+CSQ\r\n");
+        at_pars.digest();
+        at_pars.write(b"+CSQ: 31,99,1,2,3,4,5");
         at_pars.digest();
-        assert_eq!(req_c.dequeue().unwrap(), Err(Error::Overflow));
+        assert_eq!(req_c.dequeue().unwrap().into_result(), Err(Error::Overflow));
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(at_pars.buf.as_str(), "");
     }
 
     #[test]
@@ -615,7 +2628,11 @@ mod test {
 
         assert_eq!(at_pars.state, State::Idle);
         assert_eq!(at_pars.buf, String::<consts::U256>::from(""));
-        assert_eq!(req_c.dequeue().unwrap(), Err(Error::InvalidResponse));
+        // Only the line of the result code, not the information text before it
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Err(Error::InvalidResponse(ErrorContext::response("ERROR")))
+        );
     }
 
     /// By breaking up non-AT-commands into chunks, it's possible that
@@ -673,6 +2690,65 @@ mod test {
         assert_eq!(at_pars.state, State::ReceivingResponse);
     }
 
+    #[test]
+    fn resilient_digest() {
+        let conf = Config::new(Mode::Timeout).with_resilient_digest(true);
+        let (mut at_pars, mut res_c, mut urc_c) = setup!(conf);
+
+        // Baud rate mismatch
+        at_pars.write(b"\x00\xf0\x13x\xfe\r\n+CSQ: 20,99\r\n");
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+CSQ: 20,99\r\n")
+        );
+
+        // Corrupted line within a response
+        at_pars.write(b"AT+CSQ\r\r\n+CSQ: 20,99\r\n\xff\xfe\x01\r\n");
+        at_pars.digest();
+        at_pars.write(b"\r\nOK\r\n");
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(
+            res_c.dequeue().map(|r| r.into_result()),
+            Some(Ok(String::from("+CSQ: 20,99")))
+        );
+
+        assert_eq!(at_pars.stats().garbage_lines, 2);
+        assert_eq!(at_pars.stats().dropped_bytes, 10);
+    }
+
+    #[test]
+    fn tolerant_parsing() {
+        let conf = Config::new(Mode::Timeout).with_tolerant_parsing(true);
+        let (mut at_pars, mut res_c, mut urc_c) = setup!(conf);
+
+        at_pars.write(b"AT+CSQ\r\r\n+CSQ:31,99  \r\n\r\nok\r\n");
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::Idle);
+        assert_eq!(
+            res_c.dequeue().map(|r| r.into_result()),
+            Some(Ok(String::from("+CSQ: 31,99")))
+        );
+
+        at_pars.write(b"AT+CMGF=2\r\r\n\r\nError \r\n");
+        at_pars.digest();
+        assert_eq!(at_pars.state, State::Idle);
+        assert!(res_c.dequeue().unwrap().into_result().is_err());
+
+        at_pars.write(b"\r\n+CREG:1\t\r\n\r\nring\r\n");
+        at_pars.digest();
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("+CREG: 1\r\n\r\n")
+        );
+        assert_eq!(
+            urc_c.dequeue().unwrap().line,
+            String::<consts::U256>::from("RING")
+        );
+    }
+
     /// If a valid response follows an invalid response, the buffer should not
     /// be cleared in between.
     #[test]
@@ -736,7 +2812,10 @@ mod test {
         struct MyUrcMatcher {}
         impl UrcMatcher for MyUrcMatcher {
             type MaxLen = consts::U256;
-            fn process(&mut self, buf: &mut String<consts::U256>) -> UrcMatcherResult<Self::MaxLen> {
+            fn process(
+                &mut self,
+                buf: &mut String<consts::U256>,
+            ) -> UrcMatcherResult<Self::MaxLen> {
                 if buf.starts_with("+match") {
                     let data = buf.clone();
                     buf.truncate(0);
@@ -760,7 +2839,10 @@ mod test {
         ingress.write(b"+default-behavior\r\n");
         ingress.digest();
         assert_eq!(ingress.state, State::Idle);
-        assert_eq!(urc_c.dequeue().unwrap().as_str(), "+default-behavior\r\n");
+        assert_eq!(
+            urc_c.dequeue().unwrap().line.as_str(),
+            "+default-behavior\r\n"
+        );
 
         // Check an URC that is generally handled by MyUrcMatcher but
         // considered incomplete (not enough data). This will not yet result in
@@ -774,6 +2856,117 @@ mod test {
         ingress.write(b"ch"); // Still no newlines, but this will still be picked up!
         ingress.digest();
         assert_eq!(ingress.state, State::Idle);
-        assert_eq!(urc_c.dequeue().unwrap().as_str(), "+match");
+        assert_eq!(urc_c.dequeue().unwrap().line.as_str(), "+match");
+    }
+
+    #[test]
+    fn custom_urc_matcher_length() {
+        let conf = Config::new(Mode::Timeout);
+
+        /// Matches `#BIN,<n>:<n bytes>`, without a line terminator
+        struct BinUrcMatcher {}
+        impl UrcMatcher for BinUrcMatcher {
+            type MaxLen = consts::U256;
+            fn process(
+                &mut self,
+                buf: &mut String<consts::U256>,
+            ) -> UrcMatcherResult<Self::MaxLen> {
+                if !buf.starts_with("#BIN,") {
+                    return UrcMatcherResult::NotHandled;
+                }
+                match buf.find(':') {
+                    Some(i) => match buf[5..i].parse::<usize>() {
+                        Ok(n) => UrcMatcherResult::Length(i + 1 + n),
+                        Err(_) => UrcMatcherResult::NotHandled,
+                    },
+                    None => UrcMatcherResult::Incomplete,
+                }
+            }
+        }
+
+        let (mut ingress, _req_c, mut urc_c) = setup!(conf, Some(BinUrcMatcher {}));
+
+        ingress.write(b"#BIN,");
+        ingress.digest();
+        assert_eq!(urc_c.dequeue(), None);
+
+        ingress.write(b"6:ab\r\n");
+        ingress.digest();
+        assert_eq!(urc_c.dequeue(), None);
+
+        ingress.write(b"cd+UMWI: 0,1\r\n");
+        ingress.digest();
+        assert_eq!(urc_c.dequeue().unwrap().line.as_str(), "#BIN,6:ab\r\ncd");
+        ingress.digest();
+        assert_eq!(urc_c.dequeue().unwrap().line.as_str(), "+UMWI: 0,1\r\n");
+        assert_eq!(ingress.state, State::Idle);
+    }
+
+    #[test]
+    fn priority_urcs() {
+        static mut PRIORITY_URC_Q: PriorityUrcQueue = PriorityUrcQueue::NEW;
+        let conf =
+            Config::new(Mode::Timeout).with_priority_urcs(&["NO CARRIER", "+QIURC: \"closed\""]);
+        let (mut ingress, _req_c, mut urc_c) = setup!(conf);
+        let (priority_urc_p, mut priority_urc_c) = unsafe { PRIORITY_URC_Q.split() };
+        ingress.set_priority_urc_queue(priority_urc_p);
+
+        // Signal quality reports fill up the URC queue
+        for _ in 0..12 {
+            ingress.write(b"+CSQ: 20,99\r\n");
+            ingress.digest();
+        }
+        assert_eq!(ingress.stats().urcs.queue_full, 2);
+
+        ingress.write(b"+QIURC: \"closed\",1\r\n");
+        ingress.digest();
+        ingress.write(b"NO CARRIER\r\n");
+        ingress.digest();
+        assert_eq!(ingress.stats().urcs.queue_full, 2);
+        assert_eq!(
+            priority_urc_c.dequeue().unwrap().line.as_str(),
+            "+QIURC: \"closed\",1\r\n"
+        );
+        assert_eq!(
+            priority_urc_c.dequeue().unwrap().line.as_str(),
+            "NO CARRIER"
+        );
+        assert_eq!(urc_c.dequeue().unwrap().line.as_str(), "+CSQ: 20,99\r\n");
+
+        // The priority queue overflows on its own
+        for _ in 0..5 {
+            ingress.write(b"NO CARRIER\r\n");
+            ingress.digest();
+        }
+        assert_eq!(ingress.stats().priority_urcs.queue_full, 1);
+        assert_eq!(ingress.stats().urcs.queue_full, 2);
+    }
+
+    #[test]
+    fn response_byte_by_byte() {
+        let conf = Config::new(Mode::Timeout);
+        let (mut at_pars, mut req_c, _urc_c) = setup!(conf);
+
+        for byte in "AT+CGDCONT?\r\r\n+CGDCONT: 1,\"IP\",\"internet\"\r\n\r\nOK\r\n".bytes() {
+            at_pars.write(&[byte]);
+            at_pars.digest();
+        }
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from(
+                "+CGDCONT: 1,\"IP\",\"internet\""
+            ))
+        );
+
+        // Only the unscanned part is checked for a prompt
+        for byte in "AT+USOST=0,\"1.2.3.4\",80,4\r\r\n>".bytes() {
+            at_pars.write(&[byte]);
+            at_pars.digest();
+        }
+        assert_eq!(
+            req_c.dequeue().unwrap().into_result(),
+            Ok(String::<consts::U256>::from(""))
+        );
+        assert_eq!(at_pars.state, State::Idle);
     }
 }
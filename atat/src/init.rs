@@ -0,0 +1,263 @@
+//! Running the initialization sequence of a device
+//!
+//! Most drivers start by sending a fixed list of commands, e.g. to check that
+//! the device is responsive, enable verbose errors and apply vendor settings.
+//! Rather than writing a command type for each of them, the sequence is
+//! described by a list of [`InitStep`]s, and run by [`run`]:
+//!
+//! ```ignore
+//! use atat::init::{self, InitStep};
+//!
+//! const INIT: &[InitStep] = &[
+//!     // The first commands may be lost while the device autobauds
+//!     InitStep::new("AT").retries(5).timeout(Milliseconds(200)),
+//!     InitStep::new("AT+CMEE=1"),
+//!     InitStep::new("AT+CPIN?").expect("READY").retries(3),
+//! ];
+//!
+//! init::run(&mut client, INIT)?;
+//! ```
+//!
+//! [`InitStep`]: struct.InitStep.html
+//! [`run`]: fn.run.html
+
+use heapless::{consts, String};
+
+use crate::error::{Error, ErrorContext};
+use crate::timer::Milliseconds;
+use crate::traits::{send_bounded, AtatClient, AtatCmd, AtatResp};
+
+/// Max length of the command of an [`InitStep`](struct.InitStep.html),
+/// including the `\r\n` termination
+type StepCmdLen = consts::U64;
+
+/// A command of an initialization sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InitStep<'a> {
+    /// The command line, including the `AT` prefix, e.g. `AT+CMEE=1`
+    pub cmd: &'a str,
+    /// Text the response has to contain, if any
+    pub expect: Option<&'a str>,
+    /// Number of times the command is repeated, if it fails
+    pub retries: u8,
+    /// Response timeout
+    pub timeout: Milliseconds,
+}
+
+impl<'a> InitStep<'a> {
+    /// A step expecting a successful response, without retries.
+    pub const fn new(cmd: &'a str) -> Self {
+        InitStep {
+            cmd,
+            expect: None,
+            retries: 0,
+            timeout: Milliseconds(1000),
+        }
+    }
+
+    /// Fail the step, unless the response contains `text`.
+    pub const fn expect(mut self, text: &'a str) -> Self {
+        self.expect = Some(text);
+        self
+    }
+
+    /// Repeat the command up to `retries` times, if it fails.
+    pub const fn retries(mut self, retries: u8) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Give up waiting for the response after `timeout`.
+    pub const fn timeout(mut self, timeout: Milliseconds) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// The initialization sequence failed on step `step` (counting from zero),
+/// with the `error` of its last attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitError {
+    pub step: usize,
+    pub error: Error,
+}
+
+/// The response to an [`InitStep`](struct.InitStep.html), that has been
+/// checked against its expected text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepDone;
+
+impl AtatResp for StepDone {}
+
+impl<'a> AtatCmd for InitStep<'a> {
+    type CommandLen = StepCmdLen;
+    type Response = StepDone;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        let mut s = String::new();
+        s.push_str(self.cmd).ok();
+        s.push_str("\r\n").ok();
+        s
+    }
+
+    fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+        match self.expect {
+            Some(expect) if !resp.contains(expect) => Err(Error::InvalidResponse(
+                ErrorContext::response(resp).expected(expect),
+            )),
+            _ => Ok(StepDone),
+        }
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        self.timeout
+    }
+}
+
+/// Run the initialization sequence `steps` in order, stopping at the first
+/// step that still fails after its retries.
+///
+/// Each attempt is awaited for at most the `timeout` of its step, so the
+/// client has to be in `Mode::Timeout` or `Mode::NonBlockingTimeout`. In the
+/// modes without a timeout, the first step fails with `Error::Unsupported`
+/// before anything is sent.
+pub fn run<C: AtatClient>(client: &mut C, steps: &[InitStep<'_>]) -> Result<(), InitError> {
+    if !steps.is_empty() && !client.get_mode().has_timeout() {
+        return Err(InitError {
+            step: 0,
+            error: Error::Unsupported,
+        });
+    }
+    for (i, step) in steps.iter().enumerate() {
+        let max_len = String::<StepCmdLen>::new().capacity();
+        if step.cmd.len() + 2 > max_len {
+            return Err(InitError {
+                step: i,
+                error: Error::CmdTooLong(step.cmd.len() + 2, max_len),
+            });
+        }
+
+        let mut attempts = 0;
+        loop {
+            match send_bounded(client, step) {
+                Ok(StepDone) => break,
+                Err(_) if attempts < step.retries => {
+                    attempts += 1;
+                    #[cfg(feature = "logging")]
+                    log::debug!("Retrying init step {:?} ({})", step.cmd, attempts);
+                }
+                Err(error) => return Err(InitError { step: i, error }),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::traits::AtatUrc;
+    use crate::Mode;
+    use heapless::Vec;
+
+    /// Answers the commands with the given responses, in order
+    struct ClientMock {
+        mode: Mode,
+        sent: Vec<String<consts::U64>, consts::U8>,
+        responses: &'static [Result<&'static str, Error>],
+    }
+
+    impl AtatClient for ClientMock {
+        fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            self.sent.push(String::from(cmd.as_string().as_str())).ok();
+            let (resp, rest) = self.responses.split_first().unwrap();
+            self.responses = rest;
+            Ok(cmd.parse(resp.clone()?)?)
+        }
+
+        fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+            None
+        }
+
+        fn check_response<A: AtatCmd>(&mut self, _cmd: &A) -> nb::Result<A::Response, Error> {
+            Err(nb::Error::WouldBlock)
+        }
+
+        fn send_raw(&mut self, _data: &[u8]) -> nb::Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_mode(&self) -> Mode {
+            self.mode
+        }
+    }
+
+    const INIT: &[InitStep] = &[
+        InitStep::new("AT").retries(2).timeout(Milliseconds(200)),
+        InitStep::new("AT+CPIN?").expect("READY"),
+    ];
+
+    #[test]
+    fn run_sequence() {
+        let mut client = ClientMock {
+            mode: Mode::Timeout,
+            sent: Vec::new(),
+            responses: &[Err(Error::Timeout), Ok(""), Ok("+CPIN: READY")],
+        };
+        assert_eq!(run(&mut client, INIT), Ok(()));
+        assert_eq!(client.sent, ["AT\r\n", "AT\r\n", "AT+CPIN?\r\n"]);
+    }
+
+    #[test]
+    fn failing_step() {
+        let mut client = ClientMock {
+            mode: Mode::Timeout,
+            sent: Vec::new(),
+            responses: &[Ok(""), Ok("+CPIN: SIM PIN")],
+        };
+        assert_eq!(
+            run(&mut client, INIT),
+            Err(InitError {
+                step: 1,
+                error: Error::InvalidResponse(
+                    ErrorContext::response("+CPIN: SIM PIN").expected("READY")
+                )
+            })
+        );
+
+        let mut client = ClientMock {
+            mode: Mode::Timeout,
+            sent: Vec::new(),
+            responses: &[
+                Err(Error::Timeout),
+                Err(Error::Timeout),
+                Err(Error::Timeout),
+            ],
+        };
+        assert_eq!(
+            run(&mut client, INIT),
+            Err(InitError {
+                step: 0,
+                error: Error::Timeout
+            })
+        );
+        assert_eq!(client.sent.len(), 3);
+    }
+    #[test]
+    fn mode_without_timeout() {
+        let mut client = ClientMock {
+            mode: Mode::Blocking,
+            sent: Vec::new(),
+            responses: &[],
+        };
+        assert_eq!(
+            run(&mut client, INIT),
+            Err(InitError {
+                step: 0,
+                error: Error::Unsupported
+            })
+        );
+        assert!(client.sent.is_empty());
+        assert_eq!(run(&mut client, &[]), Ok(()));
+    }
+}
@@ -0,0 +1,284 @@
+//! Supervision of the link to the device
+//!
+//! A crashed or powered down device is only noticed once a command times out,
+//! which may be long after the fact if the application is mostly waiting for
+//! URCs. A [`Keepalive`] probes the link with `AT` whenever it has been idle
+//! for a while, and reports [`LinkEvent::LinkDown`] after a number of
+//! consecutive failed probes:
+//!
+//! ```ignore
+//! let mut keepalive = Keepalive::new(timer, Milliseconds(30_000), 3);
+//! loop {
+//!     if let Some(urc) = client.check_urc::<Urc>() {
+//!         keepalive.feed();
+//!         // ...
+//!     }
+//!     match keepalive.poll(&mut client) {
+//!         Some(LinkEvent::LinkDown) => power_cycle_modem(),
+//!         Some(LinkEvent::LinkUp) | None => {}
+//!     }
+//! }
+//! ```
+//!
+//! [`Keepalive`]: struct.Keepalive.html
+//! [`LinkEvent::LinkDown`]: enum.LinkEvent.html#variant.LinkDown
+
+use embedded_hal::timer::CountDown;
+
+use crate::error::Error;
+use crate::timer::Milliseconds;
+use crate::traits::{AtatClient, AtatCmd};
+use crate::v25ter::Attention;
+
+/// Change of the link state, reported by [`Keepalive::poll`].
+///
+/// [`Keepalive::poll`]: struct.Keepalive.html#method.poll
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum LinkEvent {
+    /// The device failed to answer the configured number of consecutive probes
+    LinkDown,
+    /// The device answered a probe again, after the link went down
+    LinkUp,
+}
+
+/// Probes the link when it has been idle for `idle`, see the
+/// [module documentation](index.html).
+pub struct Keepalive<T: CountDown> {
+    timer: T,
+    idle: Milliseconds,
+    max_failures: u8,
+    failures: u8,
+}
+
+impl<T> Keepalive<T>
+where
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Create a new keepalive, probing the link after `idle` of inactivity,
+    /// and reporting it down after `max_failures` consecutive failed probes.
+    pub fn new(mut timer: T, idle: Milliseconds, max_failures: u8) -> Self {
+        timer.start(idle);
+        Keepalive {
+            timer,
+            idle,
+            max_failures,
+            failures: 0,
+        }
+    }
+
+    /// Whether the link is considered down.
+    pub fn is_link_down(&self) -> bool {
+        self.failures >= self.max_failures
+    }
+
+    /// Note activity on the link, e.g. a response or URC, restarting the idle
+    /// period.
+    pub fn feed(&mut self) {
+        self.timer.start(self.idle);
+    }
+
+    /// Probe the link with `AT`, if it has been idle. See
+    /// [`poll_with`](#method.poll_with).
+    pub fn poll<C: AtatClient>(&mut self, client: &mut C) -> Option<LinkEvent> {
+        self.poll_with(client, &Attention)
+    }
+
+    /// Probe the link with the command `probe`, if it has been idle.
+    ///
+    /// The probe is awaited for at most its `max_timeout`, on the timer of the
+    /// keepalive, so it is bounded in `Mode::NonBlocking` as well. A probe
+    /// that is not answered in time counts as failed, while the client keeps
+    /// awaiting its response. In `Mode::Blocking`, the client waits for the
+    /// response itself, without a timeout, so the device is only reported
+    /// down if it answers with an error. Returns the change of the link
+    /// state, if any.
+    pub fn poll_with<C, A>(&mut self, client: &mut C, probe: &A) -> Option<LinkEvent>
+    where
+        C: AtatClient,
+        A: AtatCmd,
+    {
+        if self.timer.wait().is_err() {
+            return None;
+        }
+
+        let was_down = self.is_link_down();
+        self.timer.start(probe.max_timeout());
+        let result = match client.send(probe) {
+            Err(nb::Error::WouldBlock) => loop {
+                match client.check_response(probe) {
+                    Err(nb::Error::WouldBlock) if self.timer.wait().is_ok() => {
+                        break Err(Error::Timeout)
+                    }
+                    Err(nb::Error::WouldBlock) => {}
+                    Err(nb::Error::Other(e)) => break Err(e),
+                    Ok(resp) => break Ok(resp),
+                }
+            },
+            Err(nb::Error::Other(e)) => Err(e),
+            Ok(resp) => Ok(resp),
+        };
+        let event = match result {
+            Ok(_) => {
+                self.failures = 0;
+                if was_down {
+                    Some(LinkEvent::LinkUp)
+                } else {
+                    None
+                }
+            }
+            Err(_e) => {
+                #[cfg(feature = "logging")]
+                log::warn!("Keepalive probe failed: {:?}", _e);
+                self.failures = self.failures.saturating_add(1);
+                if !was_down && self.is_link_down() {
+                    Some(LinkEvent::LinkDown)
+                } else {
+                    None
+                }
+            }
+        };
+        self.timer.start(self.idle);
+        event
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::queues::{ComQueue, DataQueue, ResQueue, StaticQueue, UrcQueue};
+    use crate::testing::{MockModem, VirtualClock};
+    use crate::traits::AtatUrc;
+    use crate::{Client, Config, IngressManager, Mode, NoopUrcMatcher};
+    use void::Void;
+
+    struct CdMock {
+        expired: bool,
+    }
+
+    impl CountDown for CdMock {
+        type Time = u32;
+        fn start<T>(&mut self, _count: T)
+        where
+            T: Into<Self::Time>,
+        {
+            self.expired = false;
+        }
+        fn wait(&mut self) -> nb::Result<(), Void> {
+            if self.expired {
+                Ok(())
+            } else {
+                Err(nb::Error::WouldBlock)
+            }
+        }
+    }
+
+    struct ClientMock {
+        sent: usize,
+        answering: bool,
+    }
+
+    impl AtatClient for ClientMock {
+        fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            self.sent += 1;
+            if self.answering {
+                Ok(cmd.parse("")?)
+            } else {
+                Err(nb::Error::Other(Error::Timeout))
+            }
+        }
+
+        fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+            None
+        }
+
+        fn check_response<A: AtatCmd>(&mut self, _cmd: &A) -> nb::Result<A::Response, Error> {
+            Err(nb::Error::WouldBlock)
+        }
+
+        fn send_raw(&mut self, _data: &[u8]) -> nb::Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_mode(&self) -> Mode {
+            Mode::Timeout
+        }
+    }
+
+    #[test]
+    fn link_down_and_up() {
+        let mut client = ClientMock {
+            sent: 0,
+            answering: true,
+        };
+        let mut keepalive = Keepalive::new(CdMock { expired: false }, Milliseconds(1000), 2);
+
+        // Not idle yet
+        assert_eq!(keepalive.poll(&mut client), None);
+        assert_eq!(client.sent, 0);
+
+        keepalive.timer.expired = true;
+        assert_eq!(keepalive.poll(&mut client), None);
+        assert_eq!(client.sent, 1);
+
+        client.answering = false;
+        keepalive.timer.expired = true;
+        assert_eq!(keepalive.poll(&mut client), None);
+        keepalive.timer.expired = true;
+        assert_eq!(keepalive.poll(&mut client), Some(LinkEvent::LinkDown));
+        assert!(keepalive.is_link_down());
+        keepalive.timer.expired = true;
+        assert_eq!(keepalive.poll(&mut client), None);
+
+        client.answering = true;
+        keepalive.timer.expired = true;
+        assert_eq!(keepalive.poll(&mut client), Some(LinkEvent::LinkUp));
+        assert!(!keepalive.is_link_down());
+        assert_eq!(client.sent, 5);
+    }
+    #[test]
+    fn unanswered_probe_in_non_blocking_mode() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        let (res_p, res_c) = unsafe { RES_Q.split() };
+        let (urc_p, urc_c) = unsafe { URC_Q.split() };
+        let (com_p, com_c) = unsafe { COM_Q.split() };
+        let (data_p, data_c) = unsafe { DATA_Q.split() };
+
+        let clock = VirtualClock::new();
+        let modem = MockModem::new(&clock);
+        modem.expect("AT\r\n");
+
+        let config = Config::new(Mode::NonBlocking).cmd_cooldown(Milliseconds(0));
+        let mut ingress: IngressManager<NoopUrcMatcher> =
+            IngressManager::new(res_p, urc_p, com_c, data_p, config, None);
+        let mut client = Client::new(
+            modem.transport(),
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            clock.timer(),
+            clock.timer(),
+            config,
+        );
+        let mut keepalive = Keepalive::new(clock.timer(), Milliseconds(1000), 2);
+
+        clock.advance(1000);
+        // Nothing pumps the modem while the keepalive waits
+        clock.set_auto_advance(true);
+        assert_eq!(keepalive.poll(&mut client), None);
+        assert_eq!(clock.now(), 1000 + Attention.max_timeout().0);
+        modem.pump(&mut ingress);
+        assert!(modem.is_done());
+
+        clock.set_auto_advance(false);
+        assert_eq!(keepalive.poll(&mut client), None);
+        clock.advance(1000);
+        clock.set_auto_advance(true);
+        assert_eq!(keepalive.poll(&mut client), Some(LinkEvent::LinkDown));
+        assert_eq!(modem.unexpected(), None);
+    }
+}
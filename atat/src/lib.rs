@@ -62,7 +62,7 @@
 //! #[derive(Clone, AtatCmd)]
 //! #[at_cmd("+CSGT", NoResponse)]
 //! pub struct SetGreetingText<'a> {
-//!     #[at_arg(position = 0)]
+//!     #[at_arg(position = 0, len = 32)]
 //!     pub text: &'a str,
 //! }
 //!
@@ -81,6 +81,166 @@
 //!
 //! ```
 //!
+//! The `AT` prefix, the `=` separating the command from its arguments, and
+//! the `\r\n` termination can be overridden with `cmd_prefix`, `value_sep` and
+//! `termination`, e.g. for dial strings like `ATD*99***1#`, or Telit `AT#`
+//! commands:
+//! ```
+//! #[derive(Clone, AtatCmd)]
+//! #[at_cmd("D*99***", NoResponse, value_sep = false, termination = "#\r\n")]
+//! pub struct DialContext {
+//!     #[at_arg(position = 0)]
+//!     pub cid: u8,
+//! }
+//!
+//! #[derive(Clone, AtatCmd)]
+//! #[at_cmd("SGACT", NoResponse, cmd_prefix = "AT#")]
+//! pub struct ActivateContext {
+//!     #[at_arg(position = 0)]
+//!     pub cid: u8,
+//!     #[at_arg(position = 1)]
+//!     pub stat: u8,
+//! }
+//! ```
+//!
+//! A command with several parameter sets can be derived on an enum, with a
+//! variant per parameter set. A variant can add constant arguments, e.g. the
+//! parameter tag of `AT+UPSD=<profile_id>,<param_tag>,<param_val>`:
+//! ```
+//! #[derive(Clone, AtatCmd)]
+//! #[at_cmd("+UPSD", NoResponse)]
+//! pub enum SetPacketSwitchedConfig<'a> {
+//!     #[at_arg(value = 1, position = 1)]
+//!     Apn {
+//!         #[at_arg(position = 0)]
+//!         profile_id: u8,
+//!         #[at_arg(position = 2, len = 32)]
+//!         apn: &'a str,
+//!     },
+//!     #[at_arg(value = 2, position = 1)]
+//!     Username {
+//!         #[at_arg(position = 0)]
+//!         profile_id: u8,
+//!         #[at_arg(position = 2, len = 32)]
+//!         username: &'a str,
+//!     },
+//! }
+//! ```
+//!
+//! Constant arguments can be added to structs as well, and positions can be
+//! left empty with `skip`, e.g. for reserved parameters. This serializes as
+//! `AT+COPS=<mode>,,"<operator>"`:
+//! ```
+//! #[derive(Clone, AtatCmd)]
+//! #[at_cmd("+COPS", NoResponse)]
+//! #[at_arg(skip, position = 1)]
+//! pub struct SelectOperator<'a> {
+//!     #[at_arg(position = 0)]
+//!     pub mode: u8,
+//!     #[at_arg(position = 2, len = 16)]
+//!     pub operator: &'a str,
+//! }
+//! ```
+//!
+//! `bool` fields are represented as `0`/`1` with `#[at_arg(format = "0/1")]`,
+//! as `"ON"`/`"OFF"` with `format = "ON/OFF"`, or by the presence of a
+//! trailing parameter with `format = "presence"`, see `serde_at::format`.
+//!
+//! Fields in a format of their own, e.g. the clock string
+//! `"yy/MM/dd,hh:mm:ss+zz"`, can be (de)serialized by functions with the
+//! signatures of `serde::Serialize::serialize` and
+//! `serde::Deserialize::deserialize`. The `len` of such a field has to be
+//! given, if it differs from the length of the field type:
+//! ```
+//! #[derive(Clone, AtatCmd)]
+//! #[at_cmd("+CCLK", NoResponse)]
+//! pub struct SetClock {
+//!     #[at_arg(position = 0, serialize_with = "serialize_clock", len = 22)]
+//!     pub clock: Clock,
+//! }
+//!
+//! #[derive(Clone, AtatResp)]
+//! pub struct ClockResponse {
+//!     #[at_arg(position = 0, deserialize_with = "deserialize_clock")]
+//!     pub clock: Clock,
+//! }
+//! ```
+//!
+//! Responses can group consecutive parameters in a nested `AtatResp` struct,
+//! taking its parameters in place with `#[at_arg(flatten)]`:
+//! ```
+//! #[derive(Clone, AtatResp)]
+//! pub struct Location {
+//!     #[at_arg(position = 0)]
+//!     pub lat: f32,
+//!     #[at_arg(position = 1)]
+//!     pub lon: f32,
+//!     #[at_arg(position = 2)]
+//!     pub alt: u16,
+//! }
+//!
+//! // +QGPSLOC: 120553,59.33,18.06,31,7
+//! #[derive(Clone, AtatResp)]
+//! pub struct GnssFix {
+//!     #[at_arg(position = 0)]
+//!     pub time: u32,
+//!     #[at_arg(position = 1, flatten)]
+//!     pub location: Location,
+//!     #[at_arg(position = 2)]
+//!     pub satellites: Option<u8>,
+//! }
+//! ```
+//!
+//! Repeated groups of parameters in parentheses are deserialized into a
+//! `Vec` of tuples, ending at the end of the response or at an empty
+//! parameter:
+//! ```
+//! // +COPS: (2,"Telia","Telia","24001",7),(1,"Tele2","Tele2","24007",2),,(0-4),(0-2)
+//! #[derive(Clone, AtatResp)]
+//! pub struct OperatorList<'a> {
+//!     #[at_arg(position = 0)]
+//!     pub operators: Vec<(u8, String<U16>, String<U8>, String<U6>, u8), U4>,
+//!     #[at_arg(position = 1)]
+//!     pub modes: Option<&'a [u8]>,
+//!     #[at_arg(position = 2)]
+//!     pub formats: Option<&'a [u8]>,
+//! }
+//! ```
+//!
+//! Responses made up of a keyword only can be deserialized into unit enums,
+//! with the keywords given as for `AtatEnum`:
+//! ```
+//! // +CPIN: SIM PIN
+//! #[derive(Clone, AtatResp)]
+//! pub enum SimState {
+//!     #[at_arg(rename = "READY")]
+//!     Ready,
+//!     #[at_arg(rename = "SIM PIN")]
+//!     SimPin,
+//!     #[at_arg(rename = "SIM PUK")]
+//!     SimPuk,
+//! }
+//!
+//! #[derive(Clone, AtatCmd)]
+//! #[at_cmd("+CPIN?", SimState)]
+//! pub struct GetSimState;
+//! ```
+//!
+//! Responses carrying arbitrary data, which might contain e.g. `OK` itself,
+//! can name the prefix of the line announcing its length through `payload`.
+//! Final result codes are then only looked for after that many bytes:
+//! ```
+//! // +USORD: 0,6,"\r\nOK\r\n"
+//! #[derive(Clone, AtatCmd)]
+//! #[at_cmd("+USORD", SocketData, payload = "+USORD:")]
+//! pub struct ReadSocketData {
+//!     #[at_arg(position = 0)]
+//!     pub socket: u8,
+//!     #[at_arg(position = 1)]
+//!     pub length: usize,
+//! }
+//! ```
+//!
 //! ### Basic usage example (More available in examples folder):
 //! ```
 //! mod common;
@@ -122,6 +282,7 @@
 //!
 //!     let mut timer = Timer::tim7(p.TIM7, 1.hz(), clocks, &mut rcc.apb1r1);
 //!     let at_timer = Timer::tim6(p.TIM6, 100.hz(), clocks, &mut rcc.apb1r1);
+//!     let cooldown_timer = Timer::tim15(p.TIM15, 100.hz(), clocks, &mut rcc.apb2);
 //!
 //!     let mut serial = Serial::usart2(
 //!         p.USART2,
@@ -134,7 +295,7 @@
 //!     serial.listen(Rxne);
 //!
 //!     let (tx, rx) = serial.split();
-//!     let (mut client, ingress) = atat::new(tx, at_timer, atat::Config::new(atat::Mode::Timeout));
+//!     let (mut client, ingress) = atat::new(tx, at_timer, cooldown_timer, atat::Config::new(atat::Mode::Timeout));
 //!
 //!     unsafe { INGRESS = Some(ingress) };
 //!     unsafe { RX = Some(rx) };
@@ -178,41 +339,121 @@
 //! - **`derive`** *(enabled by default)* — Enables and re-exports [`atat_derive`].
 //! - **`logging`** *(disabled by default)* — Prints useful logging
 //! information, including incoming and outgoing bytes on the `TRACE` level.
+//! - **`trace`** *(disabled by default)* — Keeps the last frames digested by
+//! the ingress manager in a [`TraceRing`](struct.TraceRing.html).
+//! - **`commands`** *(disabled by default)* — Enables the [`commands`] of
+//! 3GPP TS 27.007 and 27.005, shared by most cellular modems.
+//! - **`nal`** *(disabled by default)* — Enables the [`nal`] building blocks
+//! for implementing `embedded-nal` on top of a client.
+//! - **`alloc`** *(disabled by default)* — For targets with a heap, allocates
+//! responses on the heap when the response pool is exhausted, rather than
+//! dropping them, and allows responses with `alloc` `String` and `Vec`
+//! fields, growing to fit rather than truncating.
+//! - **`critical-section`** *(disabled by default)* — Guards the queues
+//! between the client and the ingress manager by critical sections of the
+//! [`critical-section`](https://crates.io/crates/critical-section) crate,
+//! rather than relying on lock-free queues, for running them on different
+//! cores (e.g. ESP32, RP2040) or RTOS threads. The application has to provide
+//! a critical section implementation, e.g. through its HAL.
+//! - **`std`** *(disabled by default)* — Implies `alloc`. Enables the
+//! [`SerialClient`], running the client and ingress manager against a serial
+//! port on a host with `std`, and the [`transcript`]s recording a session to
+//! replay it in tests.
+//!
+//! [`SerialClient`]: serial_client/struct.SerialClient.html
+//! [`transcript`]: transcript/index.html
+//! [`commands`]: commands/index.html
+//! [`nal`]: nal/index.html
 
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 // #![feature(test)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
 #[macro_use]
 extern crate nb;
 extern crate ufmt;
 extern crate void;
 
+pub mod chain;
 mod client;
+#[cfg(feature = "commands")]
+pub mod commands;
+#[cfg(feature = "critical-section")]
+mod cs_queue;
+pub mod dialect;
+pub mod dma;
 mod error;
+pub mod fetch;
+pub mod flow_control;
 mod ingress_manager;
+pub mod init;
+pub mod keepalive;
+#[cfg(feature = "nal")]
+pub mod nal;
+pub mod power;
 mod queues;
+#[cfg(feature = "std")]
+pub mod serial_client;
+pub mod shared;
+pub mod sms;
+mod stats;
+pub mod testing;
+mod text;
+mod timer;
+mod trace;
 mod traits;
+#[cfg(feature = "std")]
+pub mod transcript;
+mod truncatable;
+pub mod upload;
+pub mod ussd;
+pub mod v25ter;
+pub mod wake;
+pub mod worker;
 
 #[cfg(feature = "derive")]
 pub use atat_derive;
 
-use embedded_hal::{serial, timer::CountDown};
-use heapless::{consts, spsc::Queue};
+use embedded_hal::timer::CountDown;
+use heapless::consts;
 
-pub use self::client::Client;
-pub use self::error::Error;
-pub use self::ingress_manager::{IngressManager, NoopUrcMatcher, UrcMatcher, UrcMatcherResult};
-use self::queues::{ComQueue, ResQueue, UrcQueue};
-pub use self::traits::{AtatClient, AtatCmd, AtatResp, AtatUrc};
+pub use self::client::{Client, DataMode, Deadline, Seq, SuspendedState};
+use self::dialect::{Dialect, V25ter};
+pub use self::error::{Error, ErrorContext};
+pub use self::ingress_manager::{
+    IngressManager, IsrProducer, NoopUrcMatcher, ThreadConsumer, UrcMatcher, UrcMatcherResult,
+};
+pub use self::queues::ResponseGuard;
+use self::queues::{
+    ComQueue, DataQueue, PriorityUrcQueue, ResQueue, RxQueue, StaticQueue, UrcQueue,
+};
+pub use self::stats::{DropCause, DropStats, FillLevel, QueueLevels, QueueStats};
+use self::stats::{DropCounter, QueueCounters};
+pub use self::text::{FromText, KeyValues, Lines};
+pub use self::timer::{Milliseconds, MonotonicTimer, NoTimer, TimerlessMode};
+pub use self::trace::{FrameKind, TraceDirection, TraceHandler};
+#[cfg(feature = "trace")]
+pub use self::trace::{TraceFrame, TraceRing};
+#[doc(hidden)]
+pub use self::traits::AddLen;
+pub use self::traits::{
+    AtTransport, AtatClient, AtatCmd, AtatLen, AtatResp, AtatUrc, CmdInfo, CmdMeta, DynClient,
+    MultiStageCmd, ParseBorrowed, UrcParser, WithTimeout,
+};
+pub use self::truncatable::Truncatable;
 
 pub mod prelude {
     //! The prelude is a collection of all the traits in this crate
     //!
     //! The traits have been renamed to avoid collisions with other items when performing a glob import.
+    pub use crate::AtTransport as _atat_AtTransport;
     pub use crate::AtatClient as _atat_AtatClient;
     pub use crate::AtatCmd as _atat_AtatCmd;
+    pub use crate::AtatLen as _atat_AtatLen;
     pub use crate::AtatResp as _atat_AtatResp;
     pub use crate::AtatUrc as _atat_AtatUrc;
+    pub use crate::MultiStageCmd as _atat_MultiStageCmd;
 }
 
 /// Whether the AT client should block while waiting responses or return early.
@@ -224,6 +465,36 @@ pub enum Mode {
     NonBlocking,
     /// The function call will wait only up the max timeout of each command to complete the operation.
     Timeout,
+    /// The function call will not wait at all to complete the operation, like
+    /// `NonBlocking`, but a command still awaiting its response after its max
+    /// timeout fails with `Error::Timeout`.
+    NonBlockingTimeout,
+}
+
+impl Mode {
+    /// Whether commands time out after their max timeout in this mode.
+    pub fn has_timeout(self) -> bool {
+        match self {
+            Mode::Timeout | Mode::NonBlockingTimeout => true,
+            Mode::Blocking | Mode::NonBlocking => false,
+        }
+    }
+}
+
+/// What the ingress manager does with received bytes that do not fit its
+/// receive buffer.
+///
+/// URCs that do not fit are truncated regardless.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the bytes that do not fit. A response cut short this way never
+    /// completes, and times out.
+    DropNewest,
+    /// Discard the oldest line in the buffer to make room. A response cut
+    /// short this way is missing its first lines.
+    DropOldest,
+    /// Fail the response being received with `Error::Overflow`.
+    Error,
 }
 
 /// Commands that can be sent from the client to the ingress manager, for
@@ -241,20 +512,51 @@ pub enum Command {
     SetFormat(u8),
     /// Enable or disable AT echo, must be called after setting `ATE`
     SetEcho(bool),
+    /// Enable or disable numeric result codes, must be called after setting `ATV`
+    SetNumericResultCodes(bool),
+    /// Limit the length of the next response, as given by
+    /// [`AtatCmd::max_response_len`](trait.AtatCmd.html#method.max_response_len)
+    SetMaxResponseLen(usize),
+    /// Stream the payload of the next response, as given by
+    /// [`AtatCmd::stream_prefix`](trait.AtatCmd.html#method.stream_prefix)
+    StreamPayload(&'static str),
+    /// Look for the final result code of the next response only after its
+    /// payload, as given by
+    /// [`AtatCmd::payload_prefix`](trait.AtatCmd.html#method.payload_prefix)
+    DelimitPayload(&'static str),
+    /// Tag the following responses with the given generation, which the
+    /// client advances whenever it gives up on a command, e.g. on a timeout,
+    /// to discard the late responses to it
+    SetGeneration(u8),
+    /// Discard the echo of a payload of the given number of bytes, which is
+    /// being written after a prompt, rather than parsing it
+    SkipEcho(usize),
 }
 
-/// Configuration of both the ingress manager, and the AT client. Some of these
-/// parameters can be changed on the fly, through issuing a [`Command`] from the
-/// client.
+/// Configuration of both the ingress manager, and the AT client. Most of these
+/// parameters can be changed on the fly, through the setters of the client,
+/// e.g. [`Client::set_mode`], which issue a [`Command`] to the ingress manager
+/// where needed.
 ///
-/// [`Command`]: #Command
+/// [`Client::set_mode`]: struct.Client.html#method.set_mode
+/// [`Command`]: enum.Command.html
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub struct Config {
     mode: Mode,
     line_term_char: u8,
     format_char: u8,
     at_echo_enabled: bool,
-    cmd_cooldown: u32,
+    numeric_result_codes: bool,
+    cmd_cooldown: Milliseconds,
+    max_cmd_len: Option<usize>,
+    escape_guard_time: Milliseconds,
+    overflow_policy: OverflowPolicy,
+    tx_chunks: Option<(usize, Milliseconds)>,
+    priority_urcs: &'static [&'static str],
+    resilient_digest: bool,
+    tolerant_parsing: bool,
+    response_validation: bool,
+    payload_echo: bool,
 }
 
 impl Default for Config {
@@ -264,7 +566,17 @@ impl Default for Config {
             line_term_char: b'\r',
             format_char: b'\n',
             at_echo_enabled: true,
-            cmd_cooldown: 20,
+            numeric_result_codes: false,
+            cmd_cooldown: Milliseconds(20),
+            max_cmd_len: None,
+            escape_guard_time: Milliseconds(1000),
+            overflow_policy: OverflowPolicy::Error,
+            tx_chunks: None,
+            priority_urcs: &[],
+            resilient_digest: false,
+            tolerant_parsing: false,
+            response_validation: false,
+            payload_echo: false,
         }
     }
 }
@@ -277,57 +589,285 @@ impl Config {
         }
     }
 
+    /// Command line termination character S3 (default `\r`).
+    ///
+    /// Received lines may end with either this or the
+    /// [format character](#method.with_format_char), which tolerates devices
+    /// terminating lines with a bare `\r` or a bare `\n`.
     pub fn with_line_term(mut self, c: u8) -> Self {
         self.line_term_char = c;
         self
     }
 
+    /// Response formatting character S4 (default `\n`).
     pub fn with_format_char(mut self, c: u8) -> Self {
         self.format_char = c;
         self
     }
 
+    /// Whether the device echoes commands (`ATE1`, the default). Disabling
+    /// echo is not supported yet, the ingress manager panics on it.
     pub fn with_at_echo(mut self, e: bool) -> Self {
         self.at_echo_enabled = e;
         self
     }
 
-    pub fn cmd_cooldown(mut self, ms: u32) -> Self {
-        self.cmd_cooldown = ms;
+    /// Interpret numeric result codes (`ATV0`), e.g. `0` for `OK` and `4` for
+    /// `ERROR`, besides the verbose ones.
+    pub fn with_numeric_result_codes(mut self, n: bool) -> Self {
+        self.numeric_result_codes = n;
+        self
+    }
+
+    pub fn cmd_cooldown(mut self, cooldown: Milliseconds) -> Self {
+        self.cmd_cooldown = cooldown;
+        self
+    }
+
+    /// Refuse to send commands longer than `len` bytes, including the
+    /// termination, with `Error::CmdTooLong`.
+    pub fn max_cmd_len(mut self, len: usize) -> Self {
+        self.max_cmd_len = Some(len);
+        self
+    }
+
+    /// Guard time around the `+++` escape sequence, as set by `ATS12` (in
+    /// units of 20 ms, default 1 s).
+    pub fn escape_guard_time(mut self, guard_time: Milliseconds) -> Self {
+        self.escape_guard_time = guard_time;
+        self
+    }
+
+    /// What to do with received bytes that do not fit the receive buffer
+    /// (default `OverflowPolicy::Error`).
+    pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Send commands in chunks of `len` bytes, waiting `delay` in between,
+    /// for devices dropping bytes of commands sent in one burst.
+    ///
+    /// The delay is timed by the cooldown timer.
+    pub fn with_tx_chunks(mut self, len: usize, delay: Milliseconds) -> Self {
+        self.tx_chunks = Some((len, delay));
+        self
+    }
+
+    /// Pass the URCs starting with any of `prefixes`, e.g. `NO CARRIER` or
+    /// `+QIURC: "closed"`, through a separate high priority queue, which the
+    /// client drains first.
+    ///
+    /// Critical URCs are thus not dropped because the URC queue is full of
+    /// noisy ones, like signal quality reports. URCs dropped on either queue
+    /// are counted in the stats, see
+    /// [`IngressManager::stats`](struct.IngressManager.html#method.stats).
+    pub fn with_priority_urcs(mut self, prefixes: &'static [&'static str]) -> Self {
+        self.priority_urcs = prefixes;
+        self
+    }
+
+    /// Skip received lines holding control characters or non-ASCII bytes,
+    /// e.g. the garbage of a baud rate mismatch, or lines corrupted by lost
+    /// bytes, up to their terminator.
+    ///
+    /// Otherwise, such lines end up in responses failing to parse, or are
+    /// mistaken for URCs. Skipped lines are counted in
+    /// [`QueueStats::garbage_lines`](struct.QueueStats.html#structfield.garbage_lines),
+    /// which the application may watch to decide to reset the device.
+    ///
+    /// Not suited to devices sending raw binary data within responses, unless
+    /// streamed or framed as binary URCs by the [`Dialect`](dialect/trait.Dialect.html).
+    pub fn with_resilient_digest(mut self, r: bool) -> Self {
+        self.resilient_digest = r;
+        self
+    }
+
+    /// Tolerate variations of the response format emitted by some low-cost
+    /// modules, i.e. trailing whitespace before line terminators, result codes
+    /// in the wrong case, e.g. `ok` or `error`, and a missing space after the
+    /// colon of a prefix, e.g. `+CSQ:31,99`.
+    ///
+    /// The received lines are normalized by the ingress manager, so that
+    /// commands and URCs parse as with compliant devices.
+    pub fn with_tolerant_parsing(mut self, t: bool) -> Self {
+        self.tolerant_parsing = t;
+        self
+    }
+
+    /// Discard responses whose prefix does not match the
+    /// [`response_prefix`](trait.AtatCmd.html#method.response_prefix) of the
+    /// command awaiting a response, counting them as stale.
+    ///
+    /// This keeps the late response to a command that timed out from being
+    /// taken for the response to the next command. Responses without a
+    /// prefix, e.g. a bare `OK`, cannot be told apart. Pipelined commands are
+    /// not validated.
+    pub fn with_response_validation(mut self, v: bool) -> Self {
+        self.response_validation = v;
+        self
+    }
+
+    /// Whether the device echoes payloads written with `send_raw`, e.g. after
+    /// the `>` prompt of `AT+CIPSEND`.
+    ///
+    /// The ingress manager then discards as many received bytes as were
+    /// written, as the echo of binary data may contain anything, including
+    /// line terminators and result codes.
+    pub fn with_payload_echo(mut self, e: bool) -> Self {
+        self.payload_echo = e;
         self
     }
 }
 
-type ClientParser<Tx, T, U> = (Client<Tx, T>, IngressManager<U>);
+type ClientParser<Tx, T, C, U, D = V25ter> = (Client<Tx, T, C>, IngressManager<U, D>);
 
 /// Create a new Atat client instance.
 ///
-/// The `serial_tx` type must implement the embedded_hal
-/// [`serial::Write<u8>`][serialwrite] trait while the timer must implement the
-/// [`timer::CountDown`][timercountdown] trait.
+/// The `serial_tx` type must implement the [`AtTransport`] trait, which is
+/// implemented for every embedded_hal [`serial::Write<u8>`][serialwrite] with
+/// a `Debug` error, while the timers must implement the
+/// [`timer::CountDown`][timercountdown] trait. `timer` times out the responses
+/// in `Mode::Timeout` and `Mode::NonBlockingTimeout`, while
+/// `cooldown_timer` enforces the cooldown between commands, so that neither
+/// one can cut the other short.
 ///
+/// [`AtTransport`]: trait.AtTransport.html
 /// [serialwrite]: ../embedded_hal/serial/trait.Write.html
 /// [timercountdown]: ../embedded_hal/timer/trait.CountDown.html
-pub fn new<Tx, T, U>(
+pub fn new<Tx, T, C, U>(
+    serial_tx: Tx,
+    timer: T,
+    cooldown_timer: C,
+    config: Config,
+    custom_urc_matcher: Option<U>,
+) -> ClientParser<Tx, T, C, U>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
+    U: UrcMatcher<MaxLen = consts::U256>,
+{
+    new_with_dialect(serial_tx, timer, cooldown_timer, config, custom_urc_matcher)
+}
+
+/// Create a new Atat client instance, for a device speaking the [`Dialect`]
+/// `D`.
+///
+/// See [`new`](fn.new.html) for the requirements of the arguments.
+///
+/// [`Dialect`]: dialect/trait.Dialect.html
+pub fn new_with_dialect<Tx, T, C, U, D>(
     serial_tx: Tx,
     timer: T,
+    cooldown_timer: C,
     config: Config,
     custom_urc_matcher: Option<U>,
-) -> ClientParser<Tx, T, U>
+) -> ClientParser<Tx, T, C, U, D>
 where
-    Tx: serial::Write<u8>,
+    Tx: AtTransport,
     T: CountDown,
-    T::Time: From<u32>,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
     U: UrcMatcher<MaxLen = consts::U256>,
+    D: Dialect,
 {
-    static mut RES_QUEUE: ResQueue = Queue(heapless::i::Queue::u8());
-    static mut URC_QUEUE: UrcQueue = Queue(heapless::i::Queue::u8());
-    static mut COM_QUEUE: ComQueue = Queue(heapless::i::Queue::u8());
+    static mut RES_QUEUE: ResQueue = ResQueue::new();
+    static mut URC_QUEUE: UrcQueue = UrcQueue::NEW;
+    static mut PRIORITY_URC_QUEUE: PriorityUrcQueue = PriorityUrcQueue::NEW;
+    static mut COM_QUEUE: ComQueue = ComQueue::NEW;
+    static mut DATA_QUEUE: DataQueue = DataQueue::NEW;
+    static QUEUE_COUNTERS: QueueCounters = QueueCounters::new();
     let (res_p, res_c) = unsafe { RES_QUEUE.split() };
     let (urc_p, urc_c) = unsafe { URC_QUEUE.split() };
+    let (priority_urc_p, priority_urc_c) = unsafe { PRIORITY_URC_QUEUE.split() };
     let (com_p, com_c) = unsafe { COM_QUEUE.split() };
-    let parser = IngressManager::new(res_p, urc_p, com_c, config, custom_urc_matcher);
-    let client = Client::new(serial_tx, res_c, urc_c, com_p, timer, config);
+    let (data_p, data_c) = unsafe { DATA_QUEUE.split() };
+    let mut parser =
+        IngressManager::with_dialect(res_p, urc_p, com_c, data_p, config, custom_urc_matcher);
+    parser.set_priority_urc_queue(priority_urc_p);
+    parser.set_queue_counters(&QUEUE_COUNTERS);
+    let mut client = Client::new(
+        serial_tx,
+        res_c,
+        urc_c,
+        com_p,
+        data_c,
+        timer,
+        cooldown_timer,
+        config,
+    );
+    client.set_priority_urc_queue(priority_urc_c);
+    client.set_queue_counters(&QUEUE_COUNTERS);
 
     (client, parser)
 }
+
+/// Create a new Atat client instance, without a timer.
+///
+/// This saves a hardware timer on minimal applications, at the cost of the
+/// command cooldown, and of response timeouts. The timeout modes are
+/// therefore ruled out by the type of `mode`, which overrides the mode of
+/// `config`.
+///
+/// See [`new`](fn.new.html) for the requirements of the arguments, and
+/// [`NoTimer`](struct.NoTimer.html).
+pub fn new_without_timer<Tx, U>(
+    serial_tx: Tx,
+    mode: TimerlessMode,
+    mut config: Config,
+    custom_urc_matcher: Option<U>,
+) -> ClientParser<Tx, NoTimer, NoTimer, U>
+where
+    Tx: AtTransport,
+    U: UrcMatcher<MaxLen = consts::U256>,
+{
+    config.mode = mode.into();
+    new(
+        serial_tx,
+        NoTimer::new(),
+        NoTimer::new(),
+        config,
+        custom_urc_matcher,
+    )
+}
+
+type SplitClientParser<Tx, T, C, U> = (Client<Tx, T, C>, IsrProducer, ThreadConsumer<U>);
+
+/// Create a new Atat client instance, with the ingress manager split into an
+/// [`IsrProducer`] and a [`ThreadConsumer`].
+///
+/// The [`IsrProducer`] is fed with received bytes from the UART Rx interrupt,
+/// while the [`ThreadConsumer`] digests them from thread mode. This makes sure
+/// at compile time that bytes are only fed from one context, and that the
+/// processing is not done from an interrupt.
+///
+/// See [`new`](fn.new.html) for the requirements of the arguments.
+///
+/// [`IsrProducer`]: struct.IsrProducer.html
+/// [`ThreadConsumer`]: struct.ThreadConsumer.html
+pub fn new_split<Tx, T, C, U>(
+    serial_tx: Tx,
+    timer: T,
+    cooldown_timer: C,
+    config: Config,
+    custom_urc_matcher: Option<U>,
+) -> SplitClientParser<Tx, T, C, U>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
+    U: UrcMatcher<MaxLen = consts::U256>,
+{
+    static mut RX_QUEUE: RxQueue = RxQueue::NEW;
+    static RX_DROPPED: DropCounter = DropCounter::new();
+    let (client, parser) = new(serial_tx, timer, cooldown_timer, config, custom_urc_matcher);
+    let (isr, thread) = parser.split(unsafe { &mut RX_QUEUE }, &RX_DROPPED);
+
+    (client, isr, thread)
+}
@@ -0,0 +1,254 @@
+//! Building blocks for implementing `embedded-nal` on top of an `AtatClient`
+//!
+//! Drivers of modems with an internal IP stack implement the `TcpStack` and
+//! `UdpStack` traits of [`embedded-nal`] by commands creating, connecting and
+//! closing sockets, while incoming data and closed connections are announced
+//! by URCs. A [`SocketSet`] keeps track of the sockets in between:
+//!
+//! ```ignore
+//! use atat::nal::{Protocol, SocketHandle, SocketSet};
+//!
+//! impl TcpStack for Modem {
+//!     type TcpSocket = SocketHandle;
+//!     type Error = Error;
+//!
+//!     fn open(&self, _mode: Mode) -> Result<SocketHandle, Error> {
+//!         let resp = self.send(&CreateSocket { protocol: 6 })?;
+//!         let handle = SocketHandle(resp.socket);
+//!         self.sockets.borrow_mut().add(handle, Protocol::Tcp)?;
+//!         Ok(handle)
+//!     }
+//!
+//!     fn read(&self, socket: &mut SocketHandle, buf: &mut [u8]) -> nb::Result<usize, Error> {
+//!         self.handle_urcs();
+//!         let pending = self.sockets.borrow().pending(*socket);
+//!         if pending == 0 {
+//!             return Err(nb::Error::WouldBlock);
+//!         }
+//!         let resp = self.send(&ReadSocketData {
+//!             socket: socket.0,
+//!             length: core::cmp::min(pending, buf.len()),
+//!         })?;
+//!         self.sockets.borrow_mut().consume(*socket, resp.length);
+//!         // ...
+//!     }
+//!
+//!     // ...
+//! }
+//!
+//! fn handle_urcs(&self) {
+//!     let mut sockets = self.sockets.borrow_mut();
+//!     match self.client.borrow_mut().check_urc::<Urc>() {
+//!         Some(Urc::SocketDataAvailable(ev)) => sockets.data_available(SocketHandle(ev.socket), ev.length),
+//!         Some(Urc::SocketClosed(ev)) => sockets.closed_by_remote(SocketHandle(ev.socket)),
+//!         _ => {}
+//!     }
+//! }
+//! ```
+//!
+//! [`embedded-nal`]: https://crates.io/crates/embedded-nal
+//! [`SocketSet`]: struct.SocketSet.html
+
+use heapless::{ArrayLength, Vec};
+
+pub use embedded_nal;
+use embedded_nal::SocketAddr;
+
+/// Handle of a socket, as assigned by the device, e.g. `0` of `+USOCR: 0`
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct SocketHandle(pub u8);
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum SocketState {
+    /// Created, but not connected yet
+    Created,
+    Connected,
+    /// Closed by the remote end, with data possibly left to be read
+    Closed,
+}
+
+/// The bookkeeping of a socket
+#[derive(Debug, Clone, PartialEq)]
+pub struct Socket {
+    pub handle: SocketHandle,
+    pub protocol: Protocol,
+    pub state: SocketState,
+    pub remote: Option<SocketAddr>,
+    /// Number of received bytes announced by the device, not read yet
+    pub pending: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SocketSetError {
+    /// There is no room for another socket
+    Full,
+    /// A socket with the handle exists already
+    DuplicateHandle,
+    /// No socket has the handle
+    UnknownHandle,
+}
+
+/// Table of the open sockets, holding up to `N` of them, see the
+/// [module documentation](index.html).
+pub struct SocketSet<N: ArrayLength<Socket>> {
+    sockets: Vec<Socket, N>,
+}
+
+impl<N: ArrayLength<Socket>> SocketSet<N> {
+    pub fn new() -> Self {
+        SocketSet {
+            sockets: Vec::new(),
+        }
+    }
+
+    /// Add the socket `handle` created by the device.
+    pub fn add(&mut self, handle: SocketHandle, protocol: Protocol) -> Result<(), SocketSetError> {
+        if self.get(handle).is_some() {
+            return Err(SocketSetError::DuplicateHandle);
+        }
+        self.sockets
+            .push(Socket {
+                handle,
+                protocol,
+                state: SocketState::Created,
+                remote: None,
+                pending: 0,
+            })
+            .map_err(|_| SocketSetError::Full)
+    }
+
+    /// Remove the socket `handle`, once closed on the device.
+    pub fn remove(&mut self, handle: SocketHandle) -> Result<Socket, SocketSetError> {
+        let i = self
+            .sockets
+            .iter()
+            .position(|s| s.handle == handle)
+            .ok_or(SocketSetError::UnknownHandle)?;
+        Ok(self.sockets.swap_remove(i))
+    }
+
+    pub fn get(&self, handle: SocketHandle) -> Option<&Socket> {
+        self.sockets.iter().find(|s| s.handle == handle)
+    }
+
+    pub fn get_mut(&mut self, handle: SocketHandle) -> Option<&mut Socket> {
+        self.sockets.iter_mut().find(|s| s.handle == handle)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Socket> {
+        self.sockets.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sockets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sockets.is_empty()
+    }
+
+    /// Note the socket `handle` being connected to `remote`.
+    pub fn connected(&mut self, handle: SocketHandle, remote: SocketAddr) {
+        if let Some(socket) = self.get_mut(handle) {
+            socket.state = SocketState::Connected;
+            socket.remote = Some(remote);
+        }
+    }
+
+    /// Note the socket `handle` being closed by the remote end, e.g. on
+    /// `+UUSOCL: 0`.
+    pub fn closed_by_remote(&mut self, handle: SocketHandle) {
+        if let Some(socket) = self.get_mut(handle) {
+            socket.state = SocketState::Closed;
+        }
+    }
+
+    /// Note the device holding `available` unread bytes of the socket
+    /// `handle`, e.g. on `+UUSORD: 0,128`.
+    pub fn data_available(&mut self, handle: SocketHandle, available: usize) {
+        if let Some(socket) = self.get_mut(handle) {
+            socket.pending = available;
+        }
+    }
+
+    /// Note `len` bytes of the socket `handle` having been read.
+    pub fn consume(&mut self, handle: SocketHandle, len: usize) {
+        if let Some(socket) = self.get_mut(handle) {
+            socket.pending = socket.pending.saturating_sub(len);
+        }
+    }
+
+    /// Number of unread bytes of the socket `handle`.
+    pub fn pending(&self, handle: SocketHandle) -> usize {
+        self.get(handle).map(|s| s.pending).unwrap_or(0)
+    }
+
+    /// The first socket with unread bytes, if any.
+    pub fn next_pending(&self) -> Option<&Socket> {
+        self.sockets.iter().find(|s| s.pending > 0)
+    }
+}
+
+impl<N: ArrayLength<Socket>> Default for SocketSet<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use embedded_nal::{IpAddr, Ipv4Addr};
+    use heapless::consts;
+
+    #[test]
+    fn socket_bookkeeping() {
+        let mut sockets = SocketSet::<consts::U2>::new();
+        assert_eq!(sockets.add(SocketHandle(0), Protocol::Tcp), Ok(()));
+        assert_eq!(
+            sockets.add(SocketHandle(0), Protocol::Udp),
+            Err(SocketSetError::DuplicateHandle)
+        );
+        assert_eq!(sockets.add(SocketHandle(3), Protocol::Udp), Ok(()));
+        assert_eq!(
+            sockets.add(SocketHandle(4), Protocol::Udp),
+            Err(SocketSetError::Full)
+        );
+
+        let remote = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 80);
+        sockets.connected(SocketHandle(0), remote);
+        assert_eq!(
+            sockets.get(SocketHandle(0)).unwrap().state,
+            SocketState::Connected
+        );
+
+        sockets.data_available(SocketHandle(3), 128);
+        assert_eq!(sockets.pending(SocketHandle(3)), 128);
+        assert_eq!(sockets.next_pending().unwrap().handle, SocketHandle(3));
+        sockets.consume(SocketHandle(3), 100);
+        assert_eq!(sockets.pending(SocketHandle(3)), 28);
+        sockets.consume(SocketHandle(3), 28);
+        assert_eq!(sockets.next_pending(), None);
+
+        sockets.closed_by_remote(SocketHandle(0));
+        assert_eq!(
+            sockets.get(SocketHandle(0)).unwrap().state,
+            SocketState::Closed
+        );
+        assert_eq!(
+            sockets.remove(SocketHandle(0)).unwrap().remote,
+            Some(remote)
+        );
+        assert_eq!(
+            sockets.remove(SocketHandle(0)),
+            Err(SocketSetError::UnknownHandle)
+        );
+        assert_eq!(sockets.len(), 1);
+    }
+}
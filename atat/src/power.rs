@@ -0,0 +1,247 @@
+//! Powering the device, and recovering it from crashes
+//!
+//! A device that stops answering commands usually only recovers through its
+//! power or reset pins. A [`PoweredClient`] drives them through a
+//! [`PowerManager`], and hard resets the device after a number of consecutive
+//! command timeouts:
+//!
+//! ```ignore
+//! use atat::power::{PowerManager, PoweredClient};
+//!
+//! struct Pins {
+//!     pwr_on: PA5<Output<OpenDrain>>,
+//!     reset: PA6<Output<OpenDrain>>,
+//!     delay: Delay,
+//! }
+//!
+//! impl PowerManager for Pins {
+//!     fn power_on(&mut self) {
+//!         self.pwr_on.set_low().ok();
+//!         self.delay.delay_ms(150u32);
+//!         self.pwr_on.set_high().ok();
+//!     }
+//!
+//!     fn power_off(&mut self) {
+//!         self.pwr_on.set_low().ok();
+//!         self.delay.delay_ms(1500u32);
+//!         self.pwr_on.set_high().ok();
+//!     }
+//!
+//!     fn hard_reset(&mut self) {
+//!         self.reset.set_low().ok();
+//!         self.delay.delay_ms(50u32);
+//!         self.reset.set_high().ok();
+//!     }
+//! }
+//!
+//! let mut client = PoweredClient::new(client, pins, 3);
+//! client.power_on();
+//! ```
+//!
+//! [`PoweredClient`]: struct.PoweredClient.html
+//! [`PowerManager`]: trait.PowerManager.html
+
+use crate::error::Error;
+use crate::traits::{AtatClient, AtatCmd, AtatUrc};
+use crate::Mode;
+
+/// Control of the power supply and reset of the device, e.g. through GPIOs
+pub trait PowerManager {
+    fn power_on(&mut self);
+
+    fn power_off(&mut self);
+
+    /// Reset the device, e.g. by pulling its reset pin, or cycling its power
+    /// supply.
+    fn hard_reset(&mut self);
+}
+
+/// A client hard resetting the device after `max_timeouts` consecutive
+/// command timeouts, see the [module documentation](index.html).
+pub struct PoweredClient<C, P> {
+    client: C,
+    power: P,
+    max_timeouts: u8,
+    timeouts: u8,
+    resets: u32,
+}
+
+impl<C, P> PoweredClient<C, P>
+where
+    C: AtatClient,
+    P: PowerManager,
+{
+    pub fn new(client: C, power: P, max_timeouts: u8) -> Self {
+        PoweredClient {
+            client,
+            power,
+            max_timeouts,
+            timeouts: 0,
+            resets: 0,
+        }
+    }
+
+    pub fn client(&mut self) -> &mut C {
+        &mut self.client
+    }
+
+    pub fn release(self) -> (C, P) {
+        (self.client, self.power)
+    }
+
+    pub fn power_on(&mut self) {
+        self.timeouts = 0;
+        self.power.power_on();
+    }
+
+    pub fn power_off(&mut self) {
+        self.power.power_off();
+    }
+
+    pub fn hard_reset(&mut self) {
+        #[cfg(feature = "logging")]
+        log::warn!("Hard resetting the device");
+        self.timeouts = 0;
+        self.resets = self.resets.saturating_add(1);
+        self.power.hard_reset();
+    }
+
+    /// Number of hard resets, including the automatic ones.
+    pub fn resets(&self) -> u32 {
+        self.resets
+    }
+
+    /// Count the consecutive timeouts, hard resetting the device once there
+    /// are `max_timeouts` of them.
+    fn supervise<R>(&mut self, result: nb::Result<R, Error>) -> nb::Result<R, Error> {
+        match result {
+            Err(nb::Error::Other(Error::Timeout)) => {
+                self.timeouts = self.timeouts.saturating_add(1);
+                if self.timeouts >= self.max_timeouts {
+                    self.hard_reset();
+                }
+            }
+            Err(nb::Error::WouldBlock) => {}
+            _ => self.timeouts = 0,
+        }
+        result
+    }
+}
+
+impl<C, P> AtatClient for PoweredClient<C, P>
+where
+    C: AtatClient,
+    P: PowerManager,
+{
+    fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+        let result = self.client.send(cmd);
+        self.supervise(result)
+    }
+
+    fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+        self.client.check_urc::<URC>()
+    }
+
+    fn check_urc_all<URC: AtatUrc, F: FnMut(URC::Response)>(&mut self, f: F) -> usize {
+        self.client.check_urc_all::<URC, F>(f)
+    }
+
+    fn peek_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+        self.client.peek_urc::<URC>()
+    }
+
+    fn check_response<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+        let result = self.client.check_response(cmd);
+        self.supervise(result)
+    }
+
+    fn send_raw(&mut self, data: &[u8]) -> nb::Result<(), Error> {
+        self.client.send_raw(data)
+    }
+
+    fn get_mode(&self) -> Mode {
+        self.client.get_mode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v25ter::Attention;
+
+    /// Times out while the device is crashed
+    struct ClientMock {
+        crashed: bool,
+    }
+
+    impl AtatClient for ClientMock {
+        fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            self.check_response(cmd)
+        }
+
+        fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+            None
+        }
+
+        fn check_response<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            if self.crashed {
+                Err(nb::Error::Other(Error::Timeout))
+            } else {
+                Ok(cmd.parse("")?)
+            }
+        }
+
+        fn send_raw(&mut self, _data: &[u8]) -> nb::Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_mode(&self) -> Mode {
+            Mode::Timeout
+        }
+    }
+
+    #[derive(Default)]
+    struct PowerMock {
+        on: bool,
+        resets: u8,
+    }
+
+    impl PowerManager for PowerMock {
+        fn power_on(&mut self) {
+            self.on = true;
+        }
+
+        fn power_off(&mut self) {
+            self.on = false;
+        }
+
+        fn hard_reset(&mut self) {
+            self.resets += 1;
+        }
+    }
+
+    #[test]
+    fn reset_after_timeouts() {
+        let mut client = PoweredClient::new(ClientMock { crashed: true }, PowerMock::default(), 3);
+        client.power_on();
+
+        assert!(client.send(&Attention).is_err());
+        assert!(client.send(&Attention).is_err());
+        client.client().crashed = false;
+        assert!(client.send(&Attention).is_ok());
+
+        client.client().crashed = true;
+        for _ in 0..3 {
+            assert_eq!(
+                client.send(&Attention),
+                Err(nb::Error::Other(Error::Timeout))
+            );
+        }
+        assert_eq!(client.resets(), 1);
+
+        client.power_off();
+        let (_, power) = client.release();
+        assert_eq!(power.resets, 1);
+        assert!(!power.on);
+    }
+}
@@ -1,35 +1,439 @@
 //! Type definitions for the queues used in this crate.
 
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "critical-section")]
+use crate::cs_queue::{Consumer, Producer, Queue};
+#[cfg(not(feature = "critical-section"))]
 use heapless::spsc::{Consumer, Producer, Queue};
-use heapless::{consts, String};
+use heapless::{consts, ArrayLength, String};
 
 pub use crate::error::Error;
+use crate::stats::DropCause;
 pub use crate::Command;
 
 // Queue capacities
-type ComCapacity = consts::U3;
-type ResCapacity = consts::U5;
-type UrcCapacity = consts::U10;
+pub(crate) type ComCapacity = consts::U3;
+pub(crate) type ResCapacity = consts::U5;
+/// Number of responses that can be in flight at once, one for each slot of
+/// the response queue (`ResCapacity`), so that only the responses the client
+/// holds on to can exhaust the pool. Empty responses, e.g. to most set
+/// commands, take no buffer.
+const RES_POOL_SIZE: usize = 5;
+pub(crate) type UrcCapacity = consts::U10;
+pub(crate) type PriorityUrcCapacity = consts::U4;
+type RxCapacity = consts::U256;
+type DataCapacity = consts::U256;
 
 // Queue item types
 type ComItem = Command;
-type ResItem = Result<String<consts::U256>, Error>;
-type UrcItem = String<consts::U256>;
+pub(crate) type ResItem = ResFrame;
+type UrcItem = UrcFrame;
+type RxItem = u8;
+type DataItem = u8;
+
+/// A URC, as passed from the ingress manager to the client
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrcFrame {
+    pub line: String<consts::U256>,
+    /// The original length of the URC, if it was truncated to fit `line`
+    pub truncated_len: Option<usize>,
+}
+
+impl From<String<consts::U256>> for UrcFrame {
+    fn from(line: String<consts::U256>) -> Self {
+        Self {
+            line,
+            truncated_len: None,
+        }
+    }
+}
+
+/// A response, as passed from the ingress manager to the client
+#[derive(Debug, PartialEq)]
+pub struct ResFrame {
+    pub result: Result<ResponseGuard, Error>,
+    /// The generation of the client the response was received in, see
+    /// [`Command::SetGeneration`](../enum.Command.html#variant.SetGeneration)
+    pub generation: u8,
+}
+
+#[cfg(test)]
+impl ResFrame {
+    /// The result, with the response copied out of the pool.
+    pub fn into_result(self) -> Result<String<consts::U256>, Error> {
+        self.result.map(String::from)
+    }
+}
+
+/// The buffers of responses, shared by a producer allocating them and
+/// a consumer releasing them.
+pub(crate) struct ResponsePool {
+    slots: [UnsafeCell<String<consts::U256>>; RES_POOL_SIZE],
+    used: [AtomicBool; RES_POOL_SIZE],
+}
+
+// A slot is only written by the producer while it is unused, and only read
+// through the `ResponseGuard` holding it, which releases it on drop.
+unsafe impl Sync for ResponsePool {}
+
+impl ResponsePool {
+    // Only used to initialize the arrays
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: UnsafeCell<String<consts::U256>> =
+        UnsafeCell::new(String(heapless::i::String::new()));
+    #[allow(clippy::declare_interior_mutable_const)]
+    const UNUSED: AtomicBool = AtomicBool::new(false);
+
+    pub const fn new() -> Self {
+        Self {
+            slots: [Self::EMPTY; RES_POOL_SIZE],
+            used: [Self::UNUSED; RES_POOL_SIZE],
+        }
+    }
+
+    /// Copy `data` into a free buffer, returning `None` if all are in use.
+    fn alloc(&'static self, data: &str) -> Option<ResponseGuard> {
+        if data.is_empty() {
+            return Some(ResponseGuard::empty());
+        }
+        let index = self.used.iter().position(|u| !u.load(Ordering::Acquire))?;
+        // Safety: The slot is unused, and only the producer allocates.
+        let slot = unsafe { &mut *self.slots[index].get() };
+        slot.clear();
+        slot.push_str(data).ok()?;
+        self.used[index].store(true, Ordering::Release);
+        Some(ResponseGuard {
+            buffer: Buffer::Pooled(self, index as u8),
+        })
+    }
+}
+
+/// A response, held in a buffer of the response pool until dropped, so that
+/// responses are passed through the queue by handle rather than copied in and
+/// out of it, and can be parsed borrowing from the buffer, see
+/// [`DynClient::send_guarded`](trait.DynClient.html#method.send_guarded).
+pub struct ResponseGuard {
+    buffer: Buffer,
+}
+
+enum Buffer {
+    Empty,
+    Pooled(&'static ResponsePool, u8),
+    /// Allocated on the heap, when the pool is exhausted
+    #[cfg(feature = "alloc")]
+    Heap(alloc::string::String),
+}
+
+impl ResponseGuard {
+    /// An empty response, holding no buffer
+    pub fn empty() -> Self {
+        Self {
+            buffer: Buffer::Empty,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self.buffer {
+            Buffer::Empty => "",
+            // Safety: The slot is not written while this guard holds it.
+            Buffer::Pooled(pool, index) => unsafe { &*pool.slots[index as usize].get() },
+            #[cfg(feature = "alloc")]
+            Buffer::Heap(ref s) => s,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.as_str().as_bytes()
+    }
+}
+
+impl Deref for ResponseGuard {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Drop for ResponseGuard {
+    fn drop(&mut self) {
+        if let Buffer::Pooled(pool, index) = self.buffer {
+            pool.used[index as usize].store(false, Ordering::Release);
+        }
+    }
+}
+
+impl fmt::Debug for ResponseGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for ResponseGuard {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl From<ResponseGuard> for String<consts::U256> {
+    fn from(guard: ResponseGuard) -> Self {
+        // The response fits, as it was copied from a string of the same size
+        String::from(guard.as_str())
+    }
+}
+
+/// The response queue, along with the pool holding the buffers of the
+/// responses in it
+pub struct ResQueue {
+    queue: ResItemQueue,
+    pool: ResponsePool,
+}
+
+impl ResQueue {
+    pub const fn new() -> Self {
+        Self {
+            queue: ResItemQueue::NEW,
+            pool: ResponsePool::new(),
+        }
+    }
+
+    pub fn split(&'static mut self) -> (ResProducer, ResConsumer) {
+        let (producer, consumer) = self.queue.split();
+        (
+            ResProducer {
+                producer,
+                pool: &self.pool,
+            },
+            consumer,
+        )
+    }
+}
+
+/// The producer of the response queue, copying responses into the pool
+pub struct ResProducer {
+    producer: ResItemProducer,
+    pool: &'static ResponsePool,
+}
+
+impl ResProducer {
+    /// Enqueue a response received in `generation`, failing with the cause
+    /// if the queue is full or the pool is exhausted.
+    ///
+    /// With the `alloc` feature, responses are allocated on the heap when the
+    /// pool is exhausted.
+    pub fn enqueue(
+        &mut self,
+        result: Result<&str, Error>,
+        generation: u8,
+    ) -> Result<(), DropCause> {
+        if !self.producer.ready() {
+            return Err(DropCause::QueueFull);
+        }
+        let result = match result {
+            Ok(data) => Ok(self.alloc(data).ok_or(DropCause::PoolExhausted)?),
+            Err(e) => Err(e),
+        };
+        self.producer
+            .enqueue(ResFrame { result, generation })
+            .map_err(|_| DropCause::QueueFull)
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn alloc(&self, data: &str) -> Option<ResponseGuard> {
+        self.pool.alloc(data)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn alloc(&self, data: &str) -> Option<ResponseGuard> {
+        self.pool.alloc(data).or_else(|| {
+            Some(ResponseGuard {
+                buffer: Buffer::Heap(data.into()),
+            })
+        })
+    }
+}
+
+/// Queues which can be created in a `static`, whichever backend is used
+pub(crate) trait StaticQueue {
+    const NEW: Self;
+}
+
+#[cfg(not(feature = "critical-section"))]
+impl<T, N: ArrayLength<T>> StaticQueue for Queue<T, N, u8> {
+    const NEW: Self = Queue(heapless::i::Queue::u8());
+}
+
+#[cfg(not(feature = "critical-section"))]
+impl<T, N: ArrayLength<T>> StaticQueue for Queue<T, N, u16> {
+    const NEW: Self = Queue(heapless::i::Queue::u16());
+}
+
+#[cfg(feature = "critical-section")]
+impl<T, N: ArrayLength<T>> StaticQueue for Queue<T, N> {
+    const NEW: Self = Queue::new();
+}
 
 // Note: We could create a simple macro to define producer, consumer and queue,
 // but that would probably be harder to read than just the plain definitions.
 
-// Consumers
-pub(crate) type ComConsumer = Consumer<'static, ComItem, ComCapacity, u8>;
-pub(crate) type ResConsumer = Consumer<'static, ResItem, ResCapacity, u8>;
-pub(crate) type UrcConsumer = Consumer<'static, UrcItem, UrcCapacity, u8>;
+#[cfg(not(feature = "critical-section"))]
+mod ends {
+    use super::*;
+
+    // Consumers
+    pub(crate) type ComConsumer = Consumer<'static, ComItem, ComCapacity, u8>;
+    pub(crate) type ResConsumer = Consumer<'static, ResItem, ResCapacity, u8>;
+    pub(crate) type UrcConsumer = Consumer<'static, UrcItem, UrcCapacity, u8>;
+    pub(crate) type PriorityUrcConsumer = Consumer<'static, UrcItem, PriorityUrcCapacity, u8>;
+    pub(crate) type RxConsumer = Consumer<'static, RxItem, RxCapacity, u16>;
+    pub(crate) type DataConsumer = Consumer<'static, DataItem, DataCapacity, u16>;
+
+    // Producers
+    pub(crate) type ComProducer = Producer<'static, ComItem, ComCapacity, u8>;
+    pub(super) type ResItemProducer = Producer<'static, ResItem, ResCapacity, u8>;
+    pub(crate) type UrcProducer = Producer<'static, UrcItem, UrcCapacity, u8>;
+    pub(crate) type PriorityUrcProducer = Producer<'static, UrcItem, PriorityUrcCapacity, u8>;
+    pub(crate) type RxProducer = Producer<'static, RxItem, RxCapacity, u16>;
+    pub(crate) type DataProducer = Producer<'static, DataItem, DataCapacity, u16>;
+
+    // Queues
+    pub(crate) type ComQueue = Queue<ComItem, ComCapacity, u8>;
+    pub(super) type ResItemQueue = Queue<ResItem, ResCapacity, u8>;
+    pub(crate) type UrcQueue = Queue<UrcItem, UrcCapacity, u8>;
+    pub(crate) type PriorityUrcQueue = Queue<UrcItem, PriorityUrcCapacity, u8>;
+    pub(crate) type RxQueue = Queue<RxItem, RxCapacity, u16>;
+    pub(crate) type DataQueue = Queue<DataItem, DataCapacity, u16>;
+}
+
+#[cfg(feature = "critical-section")]
+mod ends {
+    use super::*;
+
+    // Consumers
+    pub(crate) type ComConsumer = Consumer<ComItem, ComCapacity>;
+    pub(crate) type ResConsumer = Consumer<ResItem, ResCapacity>;
+    pub(crate) type UrcConsumer = Consumer<UrcItem, UrcCapacity>;
+    pub(crate) type PriorityUrcConsumer = Consumer<UrcItem, PriorityUrcCapacity>;
+    pub(crate) type RxConsumer = Consumer<RxItem, RxCapacity>;
+    pub(crate) type DataConsumer = Consumer<DataItem, DataCapacity>;
+
+    // Producers
+    pub(crate) type ComProducer = Producer<ComItem, ComCapacity>;
+    pub(super) type ResItemProducer = Producer<ResItem, ResCapacity>;
+    pub(crate) type UrcProducer = Producer<UrcItem, UrcCapacity>;
+    pub(crate) type PriorityUrcProducer = Producer<UrcItem, PriorityUrcCapacity>;
+    pub(crate) type RxProducer = Producer<RxItem, RxCapacity>;
+    pub(crate) type DataProducer = Producer<DataItem, DataCapacity>;
+
+    // Queues
+    pub(crate) type ComQueue = Queue<ComItem, ComCapacity>;
+    pub(super) type ResItemQueue = Queue<ResItem, ResCapacity>;
+    pub(crate) type UrcQueue = Queue<UrcItem, UrcCapacity>;
+    pub(crate) type PriorityUrcQueue = Queue<UrcItem, PriorityUrcCapacity>;
+    pub(crate) type RxQueue = Queue<RxItem, RxCapacity>;
+    pub(crate) type DataQueue = Queue<DataItem, DataCapacity>;
+}
+
+pub(crate) use ends::*;
+
+/// Plays the part of the ingress manager towards a client under test, tagging
+/// the responses with the generation the client has advanced to.
+#[cfg(test)]
+pub(crate) struct ResMock {
+    pub res_p: ResProducer,
+    com_c: ComConsumer,
+    generation: u8,
+}
+
+#[cfg(test)]
+impl ResMock {
+    pub fn new(res_p: ResProducer, com_c: ComConsumer) -> Self {
+        Self {
+            res_p,
+            com_c,
+            generation: 0,
+        }
+    }
+
+    /// Enqueue `result`, failing if the queue is full.
+    pub fn enqueue(&mut self, result: Result<String<consts::U256>, Error>) -> Result<(), ()> {
+        while let Some(com) = self.com_c.dequeue() {
+            if let Command::SetGeneration(generation) = com {
+                self.generation = generation;
+            }
+        }
+        self.res_p
+            .enqueue(result.as_deref().map_err(Clone::clone), self.generation)
+            .map_err(drop)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn response_pool() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        let (mut res_p, mut res_c) = unsafe { RES_Q.split() };
+
+        for i in 0..RES_POOL_SIZE {
+            res_p.enqueue(Ok(["+CSQ: 20,99", "OK"][i % 2]), 0).unwrap();
+        }
+        // The queue is full, before the pool is exhausted
+        assert_eq!(res_p.enqueue(Ok(""), 0), Err(DropCause::QueueFull));
+
+        // Dropping a response releases its buffer
+        let frame = res_c.dequeue().unwrap();
+        assert_eq!(&*frame.result.unwrap(), "+CSQ: 20,99");
+        res_p.enqueue(Ok("+CSQ: 21,99"), 1).unwrap();
+
+        // Holding on to a response keeps its buffer
+        let held = res_c.dequeue().unwrap().result.unwrap();
+        assert_eq!(&*held, "OK");
+        #[cfg(not(feature = "alloc"))]
+        assert_eq!(
+            res_p.enqueue(Ok("+CSQ: 22,99"), 1),
+            Err(DropCause::PoolExhausted)
+        );
+        // Empty responses take no buffer
+        res_p.enqueue(Ok(""), 1).unwrap();
+        drop(held);
+
+        for resp in &["+CSQ: 20,99", "OK", "+CSQ: 20,99"] {
+            assert_eq!(res_c.dequeue().unwrap().into_result().unwrap(), *resp);
+        }
+        let frame = res_c.dequeue().unwrap();
+        assert_eq!(frame.generation, 1);
+        assert_eq!(frame.into_result().unwrap(), "+CSQ: 21,99");
+        assert_eq!(res_c.dequeue().unwrap().into_result().unwrap(), "");
+        assert!(res_c.dequeue().is_none());
+    }
+
+    #[test]
+    fn pool_size() {
+        let levels = crate::stats::QueueCounters::new().levels();
+        assert_eq!(RES_POOL_SIZE, levels.responses.capacity);
+    }
 
-// Producers
-pub(crate) type ComProducer = Producer<'static, ComItem, ComCapacity, u8>;
-pub(crate) type ResProducer = Producer<'static, ResItem, ResCapacity, u8>;
-pub(crate) type UrcProducer = Producer<'static, UrcItem, UrcCapacity, u8>;
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn heap_fallback() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        let (mut res_p, mut res_c) = unsafe { RES_Q.split() };
 
-// Queues
-pub(crate) type ComQueue = Queue<ComItem, ComCapacity, u8>;
-pub(crate) type ResQueue = Queue<ResItem, ResCapacity, u8>;
-pub(crate) type UrcQueue = Queue<UrcItem, UrcCapacity, u8>;
+        for _ in 0..=RES_POOL_SIZE {
+            res_p.enqueue(Ok("+CSQ: 20,99"), 0).unwrap();
+        }
+        for _ in 0..=RES_POOL_SIZE {
+            assert_eq!(
+                res_c.dequeue().unwrap().into_result().unwrap(),
+                "+CSQ: 20,99"
+            );
+        }
+    }
+}
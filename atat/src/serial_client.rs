@@ -0,0 +1,341 @@
+//! The [`Client`] and [`IngressManager`] of an MCU, run on a host with `std`
+//!
+//! A [`SerialClient`] runs the very client and ingress manager used on
+//! target, so that the command definitions, URC matchers and the digester
+//! itself can be tried against a real modem on a development machine. The
+//! received bytes are read from the port by a background thread, feeding the
+//! ingress manager, while the timeouts and cooldowns are measured by
+//! [`StdTimer`]s:
+//!
+//! ```ignore
+//! let port = serialport::new("/dev/ttyUSB2", 115_200)
+//!     .timeout(Duration::from_millis(10))
+//!     .open()?;
+//! let reader = port.try_clone()?;
+//! let mut client = SerialClient::new(
+//!     reader,
+//!     port,
+//!     Config::new(Mode::Timeout),
+//!     None::<NoopUrcMatcher>,
+//! );
+//! let imei = client.send(&GetImei)?;
+//! ```
+//!
+//! The background thread only notices that the client has been dropped in
+//! between reads, so the port should be configured with a short read timeout.
+//!
+//! [`Client`]: ../struct.Client.html
+//! [`IngressManager`]: ../struct.IngressManager.html
+//! [`SerialClient`]: struct.SerialClient.html
+//! [`StdTimer`]: struct.StdTimer.html
+
+use std::boxed::Box;
+use std::io::{self, Read, Write};
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use embedded_hal::timer::CountDown;
+use heapless::consts;
+use void::Void;
+
+use crate::queues::{ComQueue, DataQueue, PriorityUrcQueue, ResQueue, StaticQueue, UrcQueue};
+use crate::stats::QueueCounters;
+use crate::traits::AtTransport;
+use crate::{Client, Config, IngressManager, UrcMatcher};
+
+/// Pause of the background thread, when there is nothing to read
+const IDLE_POLL: Duration = Duration::from_millis(1);
+
+/// A count down in ms, measured by `std::time::Instant`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdTimer {
+    deadline: Option<Instant>,
+}
+
+impl StdTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CountDown for StdTimer {
+    type Time = u32;
+
+    fn start<T>(&mut self, count: T)
+    where
+        T: Into<Self::Time>,
+    {
+        let ms = u64::from(count.into());
+        self.deadline = Some(Instant::now() + Duration::from_millis(ms));
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        match self.deadline {
+            Some(deadline) if Instant::now() < deadline => Err(nb::Error::WouldBlock),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A `std::io::Write` port, as the transport of a [`Client`](../struct.Client.html).
+#[derive(Debug)]
+pub struct IoTransport<W>(pub W);
+
+impl<W: Write> AtTransport for IoTransport<W> {
+    type Error = io::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        self.0.write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
+
+type SerialClientInner<W> = Client<IoTransport<W>, StdTimer, StdTimer>;
+
+/// The queues between the client and the ingress manager, on the heap
+struct Queues {
+    res: ResQueue,
+    urc: UrcQueue,
+    priority_urc: PriorityUrcQueue,
+    com: ComQueue,
+    data: DataQueue,
+    counters: QueueCounters,
+}
+
+/// A [`Client`](../struct.Client.html) writing to a `std::io::Write` port,
+/// with its ingress manager reading from a `std::io::Read` port in a
+/// background thread, see the [module documentation](index.html).
+///
+/// Dereferences to the client, which implements
+/// [`AtatClient`](../trait.AtatClient.html). The background thread is stopped,
+/// and the queues freed, when the client is dropped.
+pub struct SerialClient<W: Write> {
+    /// Dropped before the queues it borrows are freed
+    client: ManuallyDrop<SerialClientInner<W>>,
+    running: Arc<AtomicBool>,
+    ingress: Option<JoinHandle<()>>,
+    /// Borrowed by the client and the ingress manager for as long as they
+    /// live, so they are only freed after both have been dropped
+    queues: *mut Queues,
+}
+
+// The queues are only accessed through the client and the ingress manager
+unsafe impl<W: Write> Send for SerialClient<W> where SerialClientInner<W>: Send {}
+
+impl<W: Write> SerialClient<W> {
+    /// Create a client writing to `writer`, and start a thread digesting the
+    /// bytes read from `reader`, usually a clone of the same port.
+    ///
+    /// As the queues are allocated on the heap rather than in statics, any
+    /// number of clients can be created, e.g. one per test, each freeing its
+    /// queues when dropped.
+    pub fn new<R, U>(reader: R, writer: W, config: Config, custom_urc_matcher: Option<U>) -> Self
+    where
+        R: Read + Send + 'static,
+        U: UrcMatcher<MaxLen = consts::U256> + Send + 'static,
+    {
+        let queues = Box::into_raw(Box::new(Queues {
+            res: ResQueue::new(),
+            urc: UrcQueue::NEW,
+            priority_urc: PriorityUrcQueue::NEW,
+            com: ComQueue::NEW,
+            data: DataQueue::NEW,
+            counters: QueueCounters::new(),
+        }));
+        // Only freed in `drop`, after the client and the ingress manager
+        let Queues {
+            res,
+            urc,
+            priority_urc,
+            com,
+            data,
+            counters,
+        } = unsafe { &mut *queues };
+        let (res_p, res_c) = res.split();
+        let (urc_p, urc_c) = urc.split();
+        let (priority_urc_p, priority_urc_c) = priority_urc.split();
+        let (com_p, com_c) = com.split();
+        let (data_p, data_c) = data.split();
+        let counters: &'static QueueCounters = counters;
+
+        let mut ingress: IngressManager<U> =
+            IngressManager::new(res_p, urc_p, com_c, data_p, config, custom_urc_matcher);
+        ingress.set_priority_urc_queue(priority_urc_p);
+        ingress.set_queue_counters(counters);
+        let mut client = Client::new(
+            IoTransport(writer),
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            StdTimer::new(),
+            StdTimer::new(),
+            config,
+        );
+        client.set_priority_urc_queue(priority_urc_c);
+        client.set_queue_counters(counters);
+
+        let running = Arc::new(AtomicBool::new(true));
+        let ingress = {
+            let running = running.clone();
+            thread::spawn(move || digest(reader, ingress, &running))
+        };
+
+        Self {
+            client: ManuallyDrop::new(client),
+            running,
+            ingress: Some(ingress),
+            queues,
+        }
+    }
+}
+
+/// Feed the bytes read from `reader` to `ingress`, until the client is
+/// dropped, or the port fails.
+fn digest<R, U>(mut reader: R, mut ingress: IngressManager<U>, running: &AtomicBool)
+where
+    R: Read,
+    U: UrcMatcher<MaxLen = consts::U256>,
+{
+    let mut chunk = [0; 256];
+    while running.load(Ordering::Acquire) {
+        match reader.read(&mut chunk) {
+            Ok(0) => thread::sleep(IDLE_POLL),
+            Ok(len) => ingress.write(&chunk[..len]),
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                thread::sleep(IDLE_POLL)
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_e) => {
+                #[cfg(feature = "logging")]
+                log::error!("Failed to read from the port: {:?}", _e);
+                break;
+            }
+        }
+        ingress.digest();
+    }
+}
+
+impl<W: Write> Deref for SerialClient<W> {
+    type Target = SerialClientInner<W>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<W: Write> DerefMut for SerialClient<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+impl<W: Write> Drop for SerialClient<W> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(ingress) = self.ingress.take() {
+            ingress.join().ok();
+        }
+        // The ingress manager has been dropped by the thread, either way
+        unsafe {
+            ManuallyDrop::drop(&mut self.client);
+            drop(Box::from_raw(self.queues));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::traits::{AtatClient, AtatUrc};
+    use crate::v25ter::Attention;
+    use crate::{Error, Mode, NoopUrcMatcher};
+    use heapless::String;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::vec::Vec;
+
+    /// Reads the bytes sent by the modem mock
+    struct ReaderMock(Receiver<Vec<u8>>);
+
+    impl Read for ReaderMock {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.recv_timeout(Duration::from_millis(10)) {
+                Ok(data) => {
+                    buf[..data.len()].copy_from_slice(&data);
+                    Ok(data.len())
+                }
+                Err(_) => Err(io::ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+
+    /// Echoes the commands written, answering `OK` and a URC
+    struct ModemMock(Sender<Vec<u8>>);
+
+    impl Write for ModemMock {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.send(buf.to_vec()).ok();
+            if buf.ends_with(b"\r\n") {
+                self.0.send(b"\r\nOK\r\n+CSQ: 20,99\r\n".to_vec()).ok();
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct SignalQuality;
+
+    impl AtatUrc for SignalQuality {
+        type Response = String<consts::U32>;
+
+        fn parse(resp: &str) -> Result<Self::Response, Error> {
+            Ok(String::from(resp.trim_end()))
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let (tx, rx) = mpsc::channel();
+        let mut client = SerialClient::new(
+            ReaderMock(rx),
+            ModemMock(tx),
+            Config::new(Mode::Timeout),
+            None::<NoopUrcMatcher>,
+        );
+
+        assert!(client.send(&Attention).is_ok());
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let urc = loop {
+            if let Some(urc) = client.check_urc::<SignalQuality>() {
+                break urc;
+            }
+            assert!(Instant::now() < deadline, "URC not received");
+            thread::sleep(IDLE_POLL);
+        };
+        assert_eq!(urc, String::<consts::U32>::from("+CSQ: 20,99"));
+        assert_eq!(client.queue_levels().unwrap().responses.high_watermark, 1);
+    }
+
+    #[test]
+    fn timer() {
+        let mut timer = StdTimer::new();
+        assert_eq!(timer.wait(), Ok(()));
+        timer.start(20u32);
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+        thread::sleep(Duration::from_millis(25));
+        assert_eq!(timer.wait(), Ok(()));
+    }
+}
@@ -0,0 +1,283 @@
+//! Sharing a client between multiple drivers
+//!
+//! Driver stacks often consist of several layers, e.g. sockets, SMS and GNSS,
+//! that each need to send commands. A [`SharedClient`] hands out
+//! [`ClientHandle`]s to them, which implement [`AtatClient`] themselves:
+//!
+//! ```ignore
+//! use atat::shared::SharedClient;
+//!
+//! let shared = SharedClient::new(client);
+//! let mut sockets = SocketLayer::new(shared.handle().unwrap());
+//! let mut sms = SmsLayer::new(shared.handle().unwrap());
+//! ```
+//!
+//! Once a handle has sent a command, the other handles get `WouldBlock` until
+//! the response has been collected, so a response is always received by the
+//! handle that sent the command. URCs are received by whichever handle checks
+//! for them first.
+//!
+//! The client is guarded by a [`Mutex`], which is a `RefCell` by default, for
+//! handles used from a single execution context. With the `std` feature,
+//! `std::sync::Mutex` can be used to share the client between threads:
+//!
+//! ```ignore
+//! let shared: SharedClient<_, std::sync::Mutex<_>> = SharedClient::new(client);
+//! ```
+//!
+//! [`SharedClient`]: struct.SharedClient.html
+//! [`ClientHandle`]: struct.ClientHandle.html
+//! [`AtatClient`]: ../trait.AtatClient.html
+//! [`Mutex`]: trait.Mutex.html
+
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+use crate::error::Error;
+use crate::traits::{AtatClient, AtatCmd, AtatUrc};
+use crate::Mode;
+
+/// Exclusive access to data shared between handles, e.g. through a critical
+/// section, or a mutex of the OS.
+pub trait Mutex<T> {
+    /// Run `f` with exclusive access to the data.
+    fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R;
+}
+
+impl<T> Mutex<T> for RefCell<T> {
+    fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Mutex<T> for std::sync::Mutex<T> {
+    fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        // A panicking handle leaves the client in a consistent state
+        let mut guard = self
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        f(&mut guard)
+    }
+}
+
+/// The shared client, along with the handle owning it.
+pub struct Arbitrated<C> {
+    client: C,
+    /// The handle awaiting the response to its command, if any
+    owner: Option<u8>,
+    /// The id of the next handle, if any is left
+    next_id: Option<u8>,
+}
+
+impl<C> From<C> for Arbitrated<C> {
+    fn from(client: C) -> Self {
+        Arbitrated {
+            client,
+            owner: None,
+            next_id: Some(0),
+        }
+    }
+}
+
+/// A client shared between multiple [`ClientHandle`]s, see the
+/// [module documentation](index.html).
+///
+/// [`ClientHandle`]: struct.ClientHandle.html
+pub struct SharedClient<C, M = RefCell<Arbitrated<C>>>
+where
+    M: Mutex<Arbitrated<C>>,
+{
+    inner: M,
+    _client: PhantomData<C>,
+}
+
+impl<C, M> SharedClient<C, M>
+where
+    C: AtatClient,
+    M: Mutex<Arbitrated<C>> + From<Arbitrated<C>>,
+{
+    pub fn new(client: C) -> Self {
+        SharedClient {
+            inner: M::from(Arbitrated::from(client)),
+            _client: PhantomData,
+        }
+    }
+}
+
+impl<C, M> SharedClient<C, M>
+where
+    C: AtatClient,
+    M: Mutex<Arbitrated<C>>,
+{
+    /// Create a new handle to the client.
+    ///
+    /// Returns `None` once 256 handles have been created, as further handles
+    /// could not be told apart.
+    pub fn handle(&self) -> Option<ClientHandle<'_, C, M>> {
+        let id = self.inner.lock(|a| {
+            let id = a.next_id?;
+            a.next_id = id.checked_add(1);
+            Some(id)
+        })?;
+        Some(ClientHandle { shared: self, id })
+    }
+
+    /// Run `f` with exclusive access to the client, e.g. to reset it.
+    pub fn with_client<R>(&self, f: impl FnOnce(&mut C) -> R) -> R {
+        self.inner.lock(|a| f(&mut a.client))
+    }
+}
+
+/// Handle to a [`SharedClient`](struct.SharedClient.html).
+pub struct ClientHandle<'a, C, M>
+where
+    M: Mutex<Arbitrated<C>>,
+{
+    shared: &'a SharedClient<C, M>,
+    id: u8,
+}
+
+impl<'a, C, M> ClientHandle<'a, C, M>
+where
+    C: AtatClient,
+    M: Mutex<Arbitrated<C>>,
+{
+    /// Run `f` on the client, unless another handle is awaiting a response,
+    /// and keep the client to this handle while `f` returns `WouldBlock`.
+    fn arbitrate<R>(
+        &mut self,
+        f: impl FnOnce(&mut C) -> nb::Result<R, Error>,
+    ) -> nb::Result<R, Error> {
+        let id = self.id;
+        self.shared.inner.lock(|a| {
+            if matches!(a.owner, Some(owner) if owner != id) {
+                return Err(nb::Error::WouldBlock);
+            }
+            let result = f(&mut a.client);
+            a.owner = match result {
+                Err(nb::Error::WouldBlock) => Some(id),
+                _ => None,
+            };
+            result
+        })
+    }
+}
+
+impl<'a, C, M> AtatClient for ClientHandle<'a, C, M>
+where
+    C: AtatClient,
+    M: Mutex<Arbitrated<C>>,
+{
+    fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+        self.arbitrate(|client| client.send(cmd))
+    }
+
+    fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+        self.shared.with_client(|client| client.check_urc::<URC>())
+    }
+
+    fn check_urc_all<URC: AtatUrc, F: FnMut(URC::Response)>(&mut self, f: F) -> usize {
+        self.shared
+            .with_client(|client| client.check_urc_all::<URC, F>(f))
+    }
+
+    fn peek_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+        self.shared.with_client(|client| client.peek_urc::<URC>())
+    }
+
+    fn check_response<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+        self.arbitrate(|client| client.check_response(cmd))
+    }
+
+    fn send_raw(&mut self, data: &[u8]) -> nb::Result<(), Error> {
+        self.arbitrate(|client| client.send_raw(data))
+    }
+
+    fn get_mode(&self) -> Mode {
+        self.shared.with_client(|client| client.get_mode())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v25ter::Attention;
+
+    /// Answers each command after `ready` is set
+    struct ClientMock {
+        sent: usize,
+        awaiting: bool,
+        ready: bool,
+    }
+
+    impl AtatClient for ClientMock {
+        fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            if !self.awaiting {
+                self.sent += 1;
+                self.awaiting = true;
+            }
+            self.check_response(cmd)
+        }
+
+        fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+            None
+        }
+
+        fn check_response<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            if self.awaiting && self.ready {
+                self.awaiting = false;
+                self.ready = false;
+                Ok(cmd.parse("")?)
+            } else {
+                Err(nb::Error::WouldBlock)
+            }
+        }
+
+        fn send_raw(&mut self, _data: &[u8]) -> nb::Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_mode(&self) -> Mode {
+            Mode::NonBlocking
+        }
+    }
+
+    #[test]
+    fn arbitration() {
+        let shared: SharedClient<_> = SharedClient::new(ClientMock {
+            sent: 0,
+            awaiting: false,
+            ready: false,
+        });
+        let mut sockets = shared.handle().unwrap();
+        let mut sms = shared.handle().unwrap();
+
+        assert_eq!(sockets.send(&Attention), Err(nb::Error::WouldBlock));
+        assert_eq!(sms.send(&Attention), Err(nb::Error::WouldBlock));
+        assert_eq!(shared.with_client(|c| c.sent), 1);
+
+        // The response belongs to the handle that sent the command
+        shared.with_client(|c| c.ready = true);
+        assert_eq!(sms.check_response(&Attention), Err(nb::Error::WouldBlock));
+        assert!(sockets.check_response(&Attention).is_ok());
+
+        assert_eq!(sms.send(&Attention), Err(nb::Error::WouldBlock));
+        assert_eq!(sockets.send(&Attention), Err(nb::Error::WouldBlock));
+        assert_eq!(shared.with_client(|c| c.sent), 2);
+        assert_eq!(sms.get_mode(), Mode::NonBlocking);
+    }
+
+    #[test]
+    fn handle_ids_exhausted() {
+        let shared: SharedClient<_> = SharedClient::new(ClientMock {
+            sent: 0,
+            awaiting: false,
+            ready: false,
+        });
+        for _ in 0..256 {
+            assert!(shared.handle().is_some());
+        }
+        assert!(shared.handle().is_none());
+    }
+}
@@ -0,0 +1,272 @@
+//! SMS send flows (`AT+CMGS` and `AT+CMGW`, 3GPP TS 27.005 section 3.5)
+//!
+//! These are [`MultiStageCmd`]s, sent through
+//! [`AtatClient::send_multi_stage`]:
+//!
+//! ```ignore
+//! // Text mode (`AT+CMGF=1`)
+//! let SmsReference { reference } = client.send_multi_stage(&SendSms {
+//!     number: "+4512345678",
+//!     text: "Hello",
+//! })?;
+//! ```
+//!
+//! [`MultiStageCmd`]: ../trait.MultiStageCmd.html
+//! [`AtatClient::send_multi_stage`]: ../trait.AtatClient.html#method.send_multi_stage
+
+use core::fmt::Write;
+
+use heapless::{consts, String};
+
+use crate::error::{Error, ErrorContext};
+use crate::timer::Milliseconds;
+use crate::traits::{AtatCmd, AtatResp, MultiStageCmd};
+
+/// CTRL-Z, terminating the message body
+const CTRL_Z: char = '\x1a';
+
+/// Response to the command line: the `>` prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt;
+
+impl AtatResp for Prompt {}
+
+/// Final response of a send flow: the message reference `<mr>` of
+/// `+CMGS`, or the storage index `<index>` of `+CMGW`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmsReference {
+    pub reference: u16,
+}
+
+impl AtatResp for SmsReference {}
+
+#[derive(Debug, Clone)]
+enum SmsArg<'a> {
+    Number(&'a str),
+    Length(u8),
+}
+
+/// First stage of a send flow, e.g. `AT+CMGS="+4512345678"<CR>`
+#[derive(Debug, Clone)]
+pub struct SmsCommandLine<'a> {
+    cmd: &'static str,
+    arg: SmsArg<'a>,
+}
+
+impl<'a> AtatCmd for SmsCommandLine<'a> {
+    type CommandLen = consts::U64;
+    type Response = Prompt;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        match self.try_as_string() {
+            Ok(s) => s,
+            Err(_) => panic!("Failed to serialize command"),
+        }
+    }
+
+    /// Fails with `Error::CmdTooLong` if the number is too long, rather than
+    /// sending a truncated one.
+    fn try_as_string(&self) -> Result<String<Self::CommandLen>, Error> {
+        let mut s = String::new();
+        match self.arg {
+            SmsArg::Number(number) => write!(s, "AT{}=\"{}\"\r", self.cmd, number),
+            SmsArg::Length(length) => write!(s, "AT{}={}\r", self.cmd, length),
+        }
+        .map_err(|_| Error::CmdTooLong(s.capacity() + 1, s.capacity()))?;
+        Ok(s)
+    }
+
+    fn parse(&self, _resp: &str) -> Result<Self::Response, Error> {
+        Ok(Prompt)
+    }
+}
+
+/// Second stage of a send flow: the message body, or hex encoded PDU,
+/// terminated by CTRL-Z
+#[derive(Debug, Clone)]
+pub struct SmsPayload<'a> {
+    body: &'a str,
+}
+
+impl<'a> AtatCmd for SmsPayload<'a> {
+    // A PDU is at most 176 octets, hex encoded
+    type CommandLen = consts::U353;
+    type Response = SmsReference;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        match self.try_as_string() {
+            Ok(s) => s,
+            Err(_) => panic!("Failed to serialize command"),
+        }
+    }
+
+    /// Fails with `Error::CmdTooLong` if the body is too long, as truncating
+    /// it would drop the terminating CTRL-Z as well.
+    fn try_as_string(&self) -> Result<String<Self::CommandLen>, Error> {
+        let mut s = String::new();
+        write!(s, "{}{}", self.body, CTRL_Z)
+            .map_err(|_| Error::CmdTooLong(s.capacity() + 1, s.capacity()))?;
+        Ok(s)
+    }
+
+    fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+        // The response may be preceded by the echo of the body
+        let start = resp
+            .rfind("+CMG")
+            .ok_or_else(|| Error::ParseString(ErrorContext::response(resp).expected("+CMG")))?;
+        resp[start..]
+            .split_once(':')
+            .and_then(|(_, params)| params.split(',').next())
+            .and_then(|reference| reference.trim().parse().ok())
+            .map(|reference| SmsReference { reference })
+            .ok_or_else(|| Error::ParseString(ErrorContext::response(resp)))
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        Milliseconds(120_000)
+    }
+
+    fn force_receive_state(&self) -> bool {
+        true
+    }
+}
+
+/// Send a message in text mode (`AT+CMGF=1`)
+#[derive(Debug, Clone)]
+pub struct SendSms<'a> {
+    pub number: &'a str,
+    pub text: &'a str,
+}
+
+impl<'a> MultiStageCmd for SendSms<'a> {
+    type Command = SmsCommandLine<'a>;
+    type Payload = SmsPayload<'a>;
+
+    fn command(&self) -> Self::Command {
+        SmsCommandLine {
+            cmd: "+CMGS",
+            arg: SmsArg::Number(self.number),
+        }
+    }
+
+    fn payload(&self) -> Self::Payload {
+        SmsPayload { body: self.text }
+    }
+}
+
+/// Write a message to storage in text mode (`AT+CMGF=1`)
+#[derive(Debug, Clone)]
+pub struct WriteSms<'a> {
+    pub number: &'a str,
+    pub text: &'a str,
+}
+
+impl<'a> MultiStageCmd for WriteSms<'a> {
+    type Command = SmsCommandLine<'a>;
+    type Payload = SmsPayload<'a>;
+
+    fn command(&self) -> Self::Command {
+        SmsCommandLine {
+            cmd: "+CMGW",
+            arg: SmsArg::Number(self.number),
+        }
+    }
+
+    fn payload(&self) -> Self::Payload {
+        SmsPayload { body: self.text }
+    }
+}
+
+/// Send a message in PDU mode (`AT+CMGF=0`)
+#[derive(Debug, Clone)]
+pub struct SendSmsPdu<'a> {
+    /// Length of the TPDU in octets, excluding the SMSC address
+    pub length: u8,
+    /// The hex encoded PDU, including the SMSC address
+    pub pdu: &'a str,
+}
+
+impl<'a> MultiStageCmd for SendSmsPdu<'a> {
+    type Command = SmsCommandLine<'a>;
+    type Payload = SmsPayload<'a>;
+
+    fn command(&self) -> Self::Command {
+        SmsCommandLine {
+            cmd: "+CMGS",
+            arg: SmsArg::Length(self.length),
+        }
+    }
+
+    fn payload(&self) -> Self::Payload {
+        SmsPayload { body: self.pdu }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn send_sms() {
+        let cmd = SendSms {
+            number: "+4512345678",
+            text: "Hello",
+        };
+        assert_eq!(cmd.command().as_string(), "AT+CMGS=\"+4512345678\"\r");
+        assert_eq!(cmd.payload().as_string(), "Hello\x1a");
+
+        let cmd = SendSmsPdu {
+            length: 18,
+            pdu: "0011000B915412345678F80000AA05C8329BFD06",
+        };
+        assert_eq!(cmd.command().as_string(), "AT+CMGS=18\r");
+    }
+
+    #[test]
+    fn too_long() {
+        let text = [b'a'; 353];
+        let cmd = SendSms {
+            number: "+4512345678",
+            text: core::str::from_utf8(&text[..352]).unwrap(),
+        };
+        assert_eq!(cmd.payload().try_as_string().unwrap().len(), 353);
+
+        let cmd = SendSms {
+            number: "+4512345678",
+            text: core::str::from_utf8(&text).unwrap(),
+        };
+        assert_eq!(
+            cmd.payload().try_as_string(),
+            Err(Error::CmdTooLong(354, 353))
+        );
+
+        let cmd = SendSms {
+            number: core::str::from_utf8(&text[..60]).unwrap(),
+            text: "Hello",
+        };
+        assert_eq!(
+            cmd.command().try_as_string(),
+            Err(Error::CmdTooLong(65, 64))
+        );
+    }
+
+    #[test]
+    fn sms_reference() {
+        let payload = WriteSms {
+            number: "+4512345678",
+            text: "Hello",
+        }
+        .payload();
+        assert_eq!(payload.parse("+CMGW: 3"), Ok(SmsReference { reference: 3 }));
+        assert_eq!(
+            payload.parse("Hello\x1a\r\n+CMGS: 12,\"20/05/01,12:00:00+08\""),
+            Ok(SmsReference { reference: 12 })
+        );
+        assert_eq!(
+            payload.parse("Hello\x1a"),
+            Err(Error::ParseString(
+                ErrorContext::response("Hello\x1a").expected("+CMG")
+            ))
+        );
+    }
+}
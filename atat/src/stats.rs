@@ -0,0 +1,220 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use heapless::ArrayLength;
+
+use crate::queues::{ComCapacity, PriorityUrcCapacity, ResCapacity, UrcCapacity};
+
+/// Cause of a dropped frame
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum DropCause {
+    /// The queue to the client was full, i.e. the application polls too slowly
+    QueueFull,
+    /// The frame could not be parsed into the requested type
+    ParseFailed,
+    /// The frame was not recognized as any kind of response, and discarded
+    Filtered,
+    /// A response was received while no command was awaiting one, e.g. after
+    /// a timeout
+    Stale,
+    /// The frame exceeded the buffer
+    Overflow,
+    /// Every buffer of the response pool was taken, by queued responses or
+    /// by `ResponseGuard`s the client still holds
+    PoolExhausted,
+}
+
+/// Counters of the frames dropped for each [`DropCause`](enum.DropCause.html)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DropStats {
+    pub queue_full: u32,
+    pub parse_failed: u32,
+    pub filtered: u32,
+    pub stale: u32,
+    pub overflow: u32,
+    pub pool_exhausted: u32,
+}
+
+impl DropStats {
+    pub(crate) fn record(&mut self, cause: DropCause) {
+        let counter = match cause {
+            DropCause::QueueFull => &mut self.queue_full,
+            DropCause::ParseFailed => &mut self.parse_failed,
+            DropCause::Filtered => &mut self.filtered,
+            DropCause::Stale => &mut self.stale,
+            DropCause::Overflow => &mut self.overflow,
+            DropCause::PoolExhausted => &mut self.pool_exhausted,
+        };
+        *counter = counter.saturating_add(1);
+    }
+
+    /// Number of frames dropped for any cause
+    pub fn total(&self) -> u32 {
+        self.queue_full
+            .saturating_add(self.parse_failed)
+            .saturating_add(self.filtered)
+            .saturating_add(self.stale)
+            .saturating_add(self.overflow)
+            .saturating_add(self.pool_exhausted)
+    }
+}
+
+/// Dropped frames, per queue.
+///
+/// Both the ingress manager and the client keep their own stats, of the frames
+/// dropped on their side of the queues, and of the bytes and responses lost
+/// along the way.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct QueueStats {
+    pub responses: DropStats,
+    pub urcs: DropStats,
+    /// URCs of the high priority lane, see
+    /// [`Config::with_priority_urcs`](struct.Config.html#method.with_priority_urcs)
+    pub priority_urcs: DropStats,
+    /// Writes to the data queue in data mode, that were cut short
+    pub data: DropStats,
+    /// Received bytes discarded by the ingress manager, e.g. the rest of a
+    /// truncated URC, unrecognized text, or bytes an
+    /// [`IsrProducer`](struct.IsrProducer.html) had no room for
+    pub dropped_bytes: u32,
+    /// Received lines skipped as garbage, see
+    /// [`Config::with_resilient_digest`](struct.Config.html#method.with_resilient_digest)
+    pub garbage_lines: u32,
+    /// Commands that timed out awaiting their response
+    pub timeouts: u32,
+}
+
+impl QueueStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reset all counters.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Fill level of a queue
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FillLevel {
+    /// Items currently in the queue
+    pub len: usize,
+    /// Most items that have been in the queue at once
+    pub high_watermark: usize,
+    pub capacity: usize,
+}
+
+/// Fill levels of the queues between the ingress manager and the client, for
+/// tuning their capacities.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct QueueLevels {
+    pub responses: FillLevel,
+    pub urcs: FillLevel,
+    pub priority_urcs: FillLevel,
+    pub commands: FillLevel,
+}
+
+/// Counts the items passing through a queue.
+///
+/// Each counter is only written by one end of the queue, so that plain loads
+/// and stores suffice, even on targets without atomic read-modify-write.
+pub(crate) struct QueueCounter {
+    enqueued: AtomicUsize,
+    dequeued: AtomicUsize,
+    /// Written by the producer
+    high_watermark: AtomicUsize,
+}
+
+impl QueueCounter {
+    const fn new() -> Self {
+        Self {
+            enqueued: AtomicUsize::new(0),
+            dequeued: AtomicUsize::new(0),
+            high_watermark: AtomicUsize::new(0),
+        }
+    }
+
+    /// Called by the producer, after enqueueing an item.
+    pub(crate) fn enqueued(&self) {
+        let enqueued = self.enqueued.load(Ordering::Relaxed).wrapping_add(1);
+        self.enqueued.store(enqueued, Ordering::Release);
+        let len = self.len();
+        if len > self.high_watermark.load(Ordering::Relaxed) {
+            self.high_watermark.store(len, Ordering::Relaxed);
+        }
+    }
+
+    /// Called by the consumer, after dequeueing an item.
+    pub(crate) fn dequeued(&self) {
+        let dequeued = self.dequeued.load(Ordering::Relaxed).wrapping_add(1);
+        self.dequeued.store(dequeued, Ordering::Release);
+    }
+
+    fn len(&self) -> usize {
+        // The dequeued count is read first, so that it cannot overtake the
+        // enqueued one
+        let dequeued = self.dequeued.load(Ordering::Acquire);
+        let enqueued = self.enqueued.load(Ordering::Acquire);
+        enqueued.wrapping_sub(dequeued)
+    }
+
+    fn level<N: ArrayLength<u8>>(&self) -> FillLevel {
+        FillLevel {
+            len: core::cmp::min(self.len(), N::to_usize()),
+            high_watermark: self.high_watermark.load(Ordering::Relaxed),
+            capacity: N::to_usize(),
+        }
+    }
+}
+
+/// Counts the received bytes dropped by an `IsrProducer`, as the queue to its
+/// `ThreadConsumer` was full.
+///
+/// Only written by the producer, like a [`QueueCounter`].
+pub(crate) struct DropCounter(AtomicUsize);
+
+impl DropCounter {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicUsize::new(0))
+    }
+
+    /// Called by the producer, after dropping `n` bytes.
+    pub(crate) fn dropped(&self, n: usize) {
+        let dropped = self.0.load(Ordering::Relaxed).wrapping_add(n);
+        self.0.store(dropped, Ordering::Release);
+    }
+
+    /// Bytes dropped so far, wrapping around.
+    pub(crate) fn count(&self) -> usize {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// The counters of the queues between the ingress manager and the client,
+/// shared by both.
+pub(crate) struct QueueCounters {
+    pub(crate) responses: QueueCounter,
+    pub(crate) urcs: QueueCounter,
+    pub(crate) priority_urcs: QueueCounter,
+    pub(crate) commands: QueueCounter,
+}
+
+impl QueueCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            responses: QueueCounter::new(),
+            urcs: QueueCounter::new(),
+            priority_urcs: QueueCounter::new(),
+            commands: QueueCounter::new(),
+        }
+    }
+
+    pub(crate) fn levels(&self) -> QueueLevels {
+        QueueLevels {
+            responses: self.responses.level::<ResCapacity>(),
+            urcs: self.urcs.level::<UrcCapacity>(),
+            priority_urcs: self.priority_urcs.level::<PriorityUrcCapacity>(),
+            commands: self.commands.level::<ComCapacity>(),
+        }
+    }
+}
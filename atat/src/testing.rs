@@ -0,0 +1,528 @@
+//! Helpers for testing drivers built on top of atat
+//!
+//! Timeouts, cooldowns and pacing depend on a `CountDown` timer. Rather than
+//! waiting for them in real time, a test can hand out [`VirtualTimer`]s of a
+//! [`VirtualClock`], and advance the clock explicitly:
+//!
+//! ```ignore
+//! use atat::testing::VirtualClock;
+//!
+//! let clock = VirtualClock::new();
+//! let mut client = Client::new(tx, res_c, urc_c, com_p, data_c, clock.timer(), clock.timer(), config);
+//!
+//! assert_eq!(client.send(&cmd), Err(nb::Error::WouldBlock));
+//! assert_eq!(clock.last_started(), Some(cmd.max_timeout().0));
+//!
+//! clock.advance(cmd.max_timeout().0);
+//! assert_eq!(client.check_response(&cmd), Err(nb::Error::Other(Error::Timeout)));
+//! ```
+//!
+//! A [`MockModem`] plays the device on the other end, answering the commands
+//! sent by the client according to a script, in the time of such a clock:
+//!
+//! ```ignore
+//! use atat::testing::{MockModem, VirtualClock};
+//!
+//! let clock = VirtualClock::new();
+//! let modem = MockModem::new(&clock);
+//! modem
+//!     .expect("AT+CSQ\r\n")
+//!     .reply_after(50, "\r\n+CSQ: 20,99\r\nOK\r\n")
+//!     .urc(100, "\r\n+CREG: 5\r\n");
+//!
+//! let mut client = Client::new(modem.transport(), res_c, urc_c, com_p, data_c, clock.timer(), clock.timer(), config);
+//! assert_eq!(client.send(&GetSignalQuality), Err(nb::Error::WouldBlock));
+//! clock.advance(50);
+//! modem.pump(&mut ingress);
+//! assert_eq!(client.check_response(&GetSignalQuality), Ok(SignalQuality { rssi: 20, ber: 99 }));
+//! assert!(modem.is_done());
+//! ```
+//!
+//! The client has to be used in a non-blocking mode, as nothing pumps the
+//! modem while it blocks.
+//!
+//! [`VirtualTimer`]: struct.VirtualTimer.html
+//! [`VirtualClock`]: struct.VirtualClock.html
+//! [`MockModem`]: struct.MockModem.html
+
+use core::cell::{Cell, RefCell};
+
+use embedded_hal::timer::CountDown;
+use heapless::{consts, String, Vec};
+use void::Void;
+
+use crate::dialect::Dialect;
+use crate::traits::AtTransport;
+use crate::{IngressManager, UrcMatcher};
+
+/// Number of started durations kept by a [`VirtualClock`](struct.VirtualClock.html)
+type StartedLen = consts::U32;
+
+/// Number of expected commands, and of scheduled chunks, of a
+/// [`MockModem`](struct.MockModem.html)
+type ScriptLen = consts::U16;
+/// Number of replies to an expected command
+type RepliesLen = consts::U4;
+type ChunkLen = consts::U128;
+/// Bytes to be received, and the time they are due, or their delay
+type Chunk = (u32, Vec<u8, ChunkLen>);
+
+/// A source of virtual time, in milliseconds, that only moves when advanced.
+#[derive(Debug, Default)]
+pub struct VirtualClock {
+    now: Cell<u32>,
+    auto_advance: Cell<bool>,
+    started: RefCell<Vec<u32, StartedLen>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Milliseconds passed since the clock was created.
+    pub fn now(&self) -> u32 {
+        self.now.get()
+    }
+
+    /// Let `ms` milliseconds pass.
+    pub fn advance(&self, ms: u32) {
+        self.now.set(self.now.get().saturating_add(ms));
+    }
+
+    /// If enabled, waiting on a running timer advances the clock up to its
+    /// expiry, rather than blocking.
+    ///
+    /// This lets blocking waits, e.g. for the command cooldown, complete in a
+    /// single threaded test, while still accounting for the time they took.
+    pub fn set_auto_advance(&self, enabled: bool) {
+        self.auto_advance.set(enabled);
+    }
+
+    /// A timer counting down in the time of this clock.
+    pub fn timer(&self) -> VirtualTimer<'_> {
+        VirtualTimer {
+            clock: self,
+            deadline: None,
+        }
+    }
+
+    /// The durations the timers of this clock were started with, oldest
+    /// first. Only the first 32 are kept, until cleared.
+    pub fn started(&self) -> Vec<u32, StartedLen> {
+        self.started.borrow().clone()
+    }
+
+    /// The duration of the most recently started timer.
+    pub fn last_started(&self) -> Option<u32> {
+        self.started.borrow().last().copied()
+    }
+
+    /// Forget the started durations.
+    pub fn clear_started(&self) {
+        self.started.borrow_mut().clear();
+    }
+}
+
+/// A `CountDown` in the virtual time of a [`VirtualClock`].
+///
+/// A timer that was never started has expired. Once expired, it stays expired
+/// until started again.
+///
+/// [`VirtualClock`]: struct.VirtualClock.html
+#[derive(Debug, Clone)]
+pub struct VirtualTimer<'a> {
+    clock: &'a VirtualClock,
+    deadline: Option<u32>,
+}
+
+impl<'a> VirtualTimer<'a> {
+    /// Milliseconds left until the timer expires.
+    pub fn remaining(&self) -> u32 {
+        self.deadline
+            .map_or(0, |deadline| deadline.saturating_sub(self.clock.now()))
+    }
+}
+
+impl<'a> CountDown for VirtualTimer<'a> {
+    type Time = u32;
+
+    fn start<T>(&mut self, count: T)
+    where
+        T: Into<Self::Time>,
+    {
+        let count = count.into();
+        self.clock.started.borrow_mut().push(count).ok();
+        self.deadline = Some(self.clock.now().saturating_add(count));
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        match self.remaining() {
+            0 => Ok(()),
+            remaining if self.clock.auto_advance.get() => {
+                self.clock.advance(remaining);
+                Ok(())
+            }
+            _ => Err(nb::Error::WouldBlock),
+        }
+    }
+}
+
+/// A command expected by a [`MockModem`](struct.MockModem.html), with the
+/// replies scheduled once it has been received.
+#[derive(Debug)]
+struct Expectation {
+    cmd: String<consts::U64>,
+    /// Replies, and their delays after the command
+    replies: Vec<Chunk, RepliesLen>,
+}
+
+/// A scriptable device, answering the commands written to its
+/// [`transport`](#method.transport), see the [module documentation](index.html).
+///
+/// The commands are expected in the order of the script. A command that is
+/// not expected is recorded, see [`unexpected`](#method.unexpected), and not
+/// answered, so that the client times out. Commands are echoed, unless
+/// disabled by [`set_echo`](#method.set_echo).
+#[derive(Debug)]
+pub struct MockModem<'c> {
+    clock: &'c VirtualClock,
+    echo: Cell<bool>,
+    script: RefCell<Vec<Expectation, ScriptLen>>,
+    /// Bytes written since the last complete command
+    written: RefCell<Vec<u8, consts::U256>>,
+    /// Bytes to be received by the ingress manager, and when
+    scheduled: RefCell<Vec<Chunk, ScriptLen>>,
+    unexpected: RefCell<Option<String<consts::U64>>>,
+}
+
+impl<'c> MockModem<'c> {
+    pub fn new(clock: &'c VirtualClock) -> Self {
+        Self {
+            clock,
+            echo: Cell::new(true),
+            script: RefCell::new(Vec::new()),
+            written: RefCell::new(Vec::new()),
+            scheduled: RefCell::new(Vec::new()),
+            unexpected: RefCell::new(None),
+        }
+    }
+
+    /// Echo the bytes written, like a device with `ATE1` (default `true`).
+    pub fn set_echo(&self, echo: bool) {
+        self.echo.set(echo);
+    }
+
+    /// Expect the client to send `cmd`, e.g. `"AT+CSQ\r\n"`, after the
+    /// commands expected so far. Without a [`reply`](#method.reply), the
+    /// command times out.
+    pub fn expect(&self, cmd: &str) -> &Self {
+        let expectation = Expectation {
+            cmd: String::from(cmd),
+            replies: Vec::new(),
+        };
+        if self.script.borrow_mut().push(expectation).is_err() {
+            panic!("MockModem: too many expected commands");
+        }
+        self
+    }
+
+    /// Reply to the last expected command with `lines`, e.g.
+    /// `"\r\n+CSQ: 20,99\r\nOK\r\n"`, as soon as it is received.
+    pub fn reply(&self, lines: &str) -> &Self {
+        self.reply_after(0, lines)
+    }
+
+    /// Reply to the last expected command with `lines`, `delay_ms` after it
+    /// is received.
+    pub fn reply_after(&self, delay_ms: u32, lines: &str) -> &Self {
+        let mut script = self.script.borrow_mut();
+        let expectation = script
+            .last_mut()
+            .expect("MockModem: reply without an expected command");
+        if expectation
+            .replies
+            .push((delay_ms, chunk(lines.as_bytes())))
+            .is_err()
+        {
+            panic!("MockModem: too many replies");
+        }
+        self
+    }
+
+    /// Send the unsolicited `lines`, e.g. `"\r\n+CREG: 5\r\n"`, `delay_ms`
+    /// from now.
+    pub fn urc(&self, delay_ms: u32, lines: &str) -> &Self {
+        self.schedule(delay_ms, lines.as_bytes());
+        self
+    }
+
+    /// Send `bytes` right away, e.g. line noise.
+    pub fn inject(&self, bytes: &[u8]) -> &Self {
+        self.schedule(0, bytes);
+        self
+    }
+
+    /// The transport of the client, writing to this modem.
+    pub fn transport(&self) -> MockTransport<'_, 'c> {
+        MockTransport { modem: self }
+    }
+
+    /// Write the bytes that are due by now to `ingress`, and digest them.
+    /// Returns whether any bytes were due.
+    pub fn pump<U, D>(&self, ingress: &mut IngressManager<U, D>) -> bool
+    where
+        U: UrcMatcher<MaxLen = consts::U256>,
+        D: Dialect,
+    {
+        let now = self.clock.now();
+        let mut pumped = false;
+        loop {
+            // The earliest chunk that is due, scheduled first
+            let next = {
+                let scheduled = self.scheduled.borrow();
+                scheduled
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (due, _))| *due <= now)
+                    .min_by_key(|(i, (due, _))| (*due, *i))
+                    .map(|(i, _)| i)
+            };
+            match next {
+                Some(i) => {
+                    let (_, bytes) = remove(&mut self.scheduled.borrow_mut(), i);
+                    ingress.write(&bytes);
+                    pumped = true;
+                }
+                None => break,
+            }
+        }
+        ingress.digest();
+        pumped
+    }
+
+    /// Whether all expected commands have been received, and all replies
+    /// and URCs pumped.
+    pub fn is_done(&self) -> bool {
+        self.script.borrow().is_empty() && self.scheduled.borrow().is_empty()
+    }
+
+    /// The first command received that was not expected.
+    pub fn unexpected(&self) -> Option<String<consts::U64>> {
+        self.unexpected.borrow().clone()
+    }
+
+    fn schedule(&self, delay_ms: u32, bytes: &[u8]) {
+        let due = self.clock.now().saturating_add(delay_ms);
+        if self
+            .scheduled
+            .borrow_mut()
+            .push((due, chunk(bytes)))
+            .is_err()
+        {
+            panic!("MockModem: too many scheduled replies");
+        }
+    }
+
+    fn receive(&self, bytes: &[u8]) {
+        if self.echo.get() {
+            self.schedule(0, bytes);
+        }
+        let mut written = self.written.borrow_mut();
+        written.extend_from_slice(bytes).ok();
+
+        let mut script = self.script.borrow_mut();
+        match script.first() {
+            Some(next) if written.starts_with(next.cmd.as_bytes()) => {
+                let expectation = remove(&mut script, 0);
+                *written = written
+                    .iter()
+                    .skip(expectation.cmd.len())
+                    .copied()
+                    .collect();
+                for (delay_ms, reply) in expectation.replies {
+                    self.schedule(delay_ms, &reply);
+                }
+            }
+            Some(next) if next.cmd.as_bytes().starts_with(&written) => {}
+            // Wait for the rest of a command while none is expected
+            None if !written.contains(&b'\n') => {}
+            _ => {
+                let mut unexpected = self.unexpected.borrow_mut();
+                if unexpected.is_none() {
+                    let mut cmd = String::new();
+                    for c in written.iter() {
+                        cmd.push(*c as char).ok();
+                    }
+                    *unexpected = Some(cmd);
+                }
+                written.clear();
+            }
+        }
+    }
+}
+
+/// The [`AtTransport`](../trait.AtTransport.html) of a
+/// [`MockModem`](struct.MockModem.html).
+#[derive(Debug, Clone)]
+pub struct MockTransport<'m, 'c> {
+    modem: &'m MockModem<'c>,
+}
+
+impl<'m, 'c> AtTransport for MockTransport<'m, 'c> {
+    type Error = Void;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        self.modem.receive(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+fn chunk(bytes: &[u8]) -> Vec<u8, ChunkLen> {
+    let mut chunk = Vec::new();
+    if chunk.extend_from_slice(bytes).is_err() {
+        panic!("MockModem: reply longer than 128 bytes");
+    }
+    chunk
+}
+
+/// Remove the item at `index`, preserving the order of the others.
+fn remove<T, N: heapless::ArrayLength<T>>(vec: &mut Vec<T, N>, index: usize) -> T {
+    let mut rest: Vec<T, N> = Vec::new();
+    while vec.len() > index + 1 {
+        rest.push(vec.pop().unwrap()).ok();
+    }
+    let item = vec.pop().unwrap();
+    while let Some(t) = rest.pop() {
+        vec.push(t).ok();
+    }
+    item
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate as atat;
+    use crate::atat_derive::{AtatCmd, AtatResp};
+    use crate::queues::{ComQueue, DataQueue, ResQueue, StaticQueue, UrcQueue};
+    use crate::traits::{AtatClient, AtatUrc};
+    use crate::{Client, Config, Error, Milliseconds, Mode, NoopUrcMatcher};
+
+    #[derive(Clone, AtatResp, PartialEq, Debug)]
+    pub struct SignalQuality {
+        #[at_arg(position = 0)]
+        rssi: u8,
+        #[at_arg(position = 1)]
+        ber: u8,
+    }
+
+    #[derive(Clone, AtatCmd)]
+    #[at_cmd("+CSQ", SignalQuality, timeout_ms = 300)]
+    pub struct GetSignalQuality;
+
+    struct AnyUrc;
+
+    impl AtatUrc for AnyUrc {
+        type Response = String<consts::U32>;
+
+        fn parse(resp: &str) -> Result<Self::Response, Error> {
+            Ok(String::from(resp.trim()))
+        }
+    }
+
+    #[test]
+    fn count_down() {
+        let clock = VirtualClock::new();
+        let mut timer = clock.timer();
+        assert_eq!(timer.wait(), Ok(()));
+
+        timer.start(100u32);
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+        clock.advance(99);
+        assert_eq!(timer.remaining(), 1);
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+        clock.advance(1);
+        assert_eq!(timer.wait(), Ok(()));
+        assert_eq!(timer.wait(), Ok(()));
+
+        timer.start(50u32);
+        clock.set_auto_advance(true);
+        assert_eq!(timer.wait(), Ok(()));
+        assert_eq!(clock.now(), 150);
+        assert_eq!(clock.started(), [100, 50]);
+        assert_eq!(clock.last_started(), Some(50));
+    }
+
+    #[test]
+    fn mock_modem() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        let (res_p, res_c) = unsafe { RES_Q.split() };
+        let (urc_p, urc_c) = unsafe { URC_Q.split() };
+        let (com_p, com_c) = unsafe { COM_Q.split() };
+        let (data_p, data_c) = unsafe { DATA_Q.split() };
+
+        let clock = VirtualClock::new();
+        let modem = MockModem::new(&clock);
+        modem
+            .expect("AT+CSQ\r\n")
+            .reply_after(50, "\r\n+CSQ: 20,99\r\nOK\r\n")
+            .urc(60, "\r\n+CREG: 5\r\n")
+            .expect("AT+CSQ\r\n");
+
+        let config = Config::new(Mode::NonBlockingTimeout).cmd_cooldown(Milliseconds(0));
+        let mut ingress: IngressManager<NoopUrcMatcher> =
+            IngressManager::new(res_p, urc_p, com_c, data_p, config, None);
+        let mut client = Client::new(
+            modem.transport(),
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            clock.timer(),
+            clock.timer(),
+            config,
+        );
+
+        // Replied after a delay
+        assert_eq!(client.send(&GetSignalQuality), Err(nb::Error::WouldBlock));
+        modem.pump(&mut ingress);
+        assert_eq!(
+            client.check_response(&GetSignalQuality),
+            Err(nb::Error::WouldBlock)
+        );
+        clock.advance(50);
+        modem.inject(b"\x00\xff\r\n");
+        assert!(modem.pump(&mut ingress));
+        assert_eq!(
+            client.check_response(&GetSignalQuality),
+            Ok(SignalQuality { rssi: 20, ber: 99 })
+        );
+
+        // Not replied
+        clock.advance(10);
+        assert_eq!(client.send(&GetSignalQuality), Err(nb::Error::WouldBlock));
+        modem.pump(&mut ingress);
+        clock.advance(300);
+        assert_eq!(
+            client.check_response(&GetSignalQuality),
+            Err(nb::Error::Other(Error::Timeout))
+        );
+        assert_eq!(client.check_urc::<AnyUrc>(), Some(String::from("+CREG: 5")));
+        assert!(modem.is_done());
+        assert_eq!(modem.unexpected(), None);
+
+        // Not expected
+        assert_eq!(client.send(&GetSignalQuality), Err(nb::Error::WouldBlock));
+        assert_eq!(
+            modem.unexpected(),
+            Some(String::<consts::U64>::from("AT+CSQ\r\n"))
+        );
+    }
+}
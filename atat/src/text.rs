@@ -0,0 +1,183 @@
+use heapless::{ArrayLength, String, Vec};
+
+use crate::error::{Error, ErrorContext};
+use crate::traits::AtatResp;
+
+/// A response that is free text rather than `+PREFIX: a,b,c` parameters,
+/// e.g. a firmware version banner or an `AT&V` profile dump.
+///
+/// Used by commands marked `parse_text`:
+///
+/// ```ignore
+/// #[derive(Clone, AtatCmd)]
+/// #[at_cmd("I", KeyValues<consts::U8, consts::U16, consts::U32>, parse_text = true)]
+/// pub struct GetInfo;
+/// ```
+pub trait FromText: Sized {
+    /// Parse the lines of the response `resp`.
+    fn from_text(resp: &str) -> Result<Self, Error>;
+}
+
+/// The non-empty lines of a response, up to `N` lines of up to `L` bytes
+/// each.
+///
+/// Fails with `Error::Overflow` if the response has more or longer lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lines<N, L>
+where
+    N: ArrayLength<String<L>>,
+    L: ArrayLength<u8>,
+{
+    pub lines: Vec<String<L>, N>,
+}
+
+impl<N, L> Lines<N, L>
+where
+    N: ArrayLength<String<L>>,
+    L: ArrayLength<u8>,
+{
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+}
+
+impl<N, L> FromText for Lines<N, L>
+where
+    N: ArrayLength<String<L>>,
+    L: ArrayLength<u8>,
+{
+    fn from_text(resp: &str) -> Result<Self, Error> {
+        let mut lines = Vec::new();
+        for line in text_lines(resp) {
+            lines.push(string(line)?).map_err(|_| Error::Overflow)?;
+        }
+        Ok(Lines { lines })
+    }
+}
+
+impl<N, L> AtatResp for Lines<N, L>
+where
+    N: ArrayLength<String<L>>,
+    L: ArrayLength<u8>,
+{
+}
+
+/// The `key: value` lines of a response, e.g. `Revision: BG96MAR02A07M1G`,
+/// up to `N` pairs with keys of up to `K` and values of up to `V` bytes.
+///
+/// Keys and values are split at the first `:` of a line, and trimmed. Lines
+/// without a `:`, e.g. headings, are skipped. Fails with `Error::Overflow` if
+/// the response has more or longer pairs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValues<N, K, V>
+where
+    N: ArrayLength<(String<K>, String<V>)>,
+    K: ArrayLength<u8>,
+    V: ArrayLength<u8>,
+{
+    pub pairs: Vec<(String<K>, String<V>), N>,
+}
+
+impl<N, K, V> KeyValues<N, K, V>
+where
+    N: ArrayLength<(String<K>, String<V>)>,
+    K: ArrayLength<u8>,
+    V: ArrayLength<u8>,
+{
+    /// The value of the first pair with `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl<N, K, V> FromText for KeyValues<N, K, V>
+where
+    N: ArrayLength<(String<K>, String<V>)>,
+    K: ArrayLength<u8>,
+    V: ArrayLength<u8>,
+{
+    fn from_text(resp: &str) -> Result<Self, Error> {
+        let mut pairs = Vec::new();
+        for line in text_lines(resp) {
+            let mut split = line.splitn(2, ':');
+            if let (Some(key), Some(value)) = (split.next(), split.next()) {
+                pairs
+                    .push((string(key.trim())?, string(value.trim())?))
+                    .map_err(|_| Error::Overflow)?;
+            }
+        }
+        if pairs.is_empty() && !resp.trim().is_empty() {
+            return Err(Error::ParseString(ErrorContext::response(resp)));
+        }
+        Ok(KeyValues { pairs })
+    }
+}
+
+impl<N, K, V> AtatResp for KeyValues<N, K, V>
+where
+    N: ArrayLength<(String<K>, String<V>)>,
+    K: ArrayLength<u8>,
+    V: ArrayLength<u8>,
+{
+}
+
+/// The non-empty lines of `resp`, without their line terminators.
+fn text_lines(resp: &str) -> impl Iterator<Item = &str> {
+    resp.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+}
+
+fn string<N: ArrayLength<u8>>(s: &str) -> Result<String<N>, Error> {
+    let mut string = String::new();
+    string.push_str(s).map_err(|_| Error::Overflow)?;
+    Ok(string)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use heapless::consts;
+
+    #[test]
+    fn lines() {
+        let banner = "Quectel\r\nBG96\r\n\r\nRevision: BG96MAR02A07M1G";
+        let lines = Lines::<consts::U4, consts::U32>::from_text(banner).unwrap();
+        assert_eq!(
+            lines.iter().collect::<Vec<_, consts::U4>>(),
+            ["Quectel", "BG96", "Revision: BG96MAR02A07M1G"]
+        );
+
+        assert_eq!(
+            Lines::<consts::U2, consts::U32>::from_text(banner),
+            Err(Error::Overflow)
+        );
+        assert_eq!(
+            Lines::<consts::U4, consts::U8>::from_text(banner),
+            Err(Error::Overflow)
+        );
+    }
+
+    #[test]
+    fn key_values() {
+        let info = "ACTIVE PROFILE:\r\nManufacturer: u-blox\r\nModel: SARA-R410M-02B\r\nIMEI:356938035643809";
+        let pairs = KeyValues::<consts::U4, consts::U16, consts::U16>::from_text(info).unwrap();
+        assert_eq!(pairs.get("ACTIVE PROFILE"), Some(""));
+        assert_eq!(pairs.get("Model"), Some("SARA-R410M-02B"));
+        assert_eq!(pairs.get("IMEI"), Some("356938035643809"));
+        assert_eq!(pairs.get("Revision"), None);
+        assert_eq!(pairs.iter().count(), 4);
+
+        assert_eq!(
+            KeyValues::<consts::U4, consts::U16, consts::U16>::from_text("ERROR"),
+            Err(Error::ParseString(ErrorContext::response("ERROR")))
+        );
+    }
+}
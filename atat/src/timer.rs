@@ -0,0 +1,200 @@
+use embedded_hal::timer::CountDown;
+use void::Void;
+
+use crate::Mode;
+
+/// A duration in milliseconds, the unit of the timeouts and cooldowns of the
+/// client.
+///
+/// The timers of the client are started with a `Milliseconds`, converted to
+/// their `Time` through `From<Milliseconds>`, rather than with a raw `u32`.
+/// That way, a timer counting in other units, e.g. a HAL timer started with
+/// the frequency of its timeouts, fails to compile rather than silently
+/// counting down the wrong time, and has to be wrapped with a conversion
+/// matching its tick rate. Timers with a `Time` of `u32` count milliseconds.
+#[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Milliseconds(pub u32);
+
+impl From<Milliseconds> for u32 {
+    fn from(ms: Milliseconds) -> Self {
+        ms.0
+    }
+}
+
+impl From<Milliseconds> for core::time::Duration {
+    fn from(ms: Milliseconds) -> Self {
+        core::time::Duration::from_millis(u64::from(ms.0))
+    }
+}
+
+/// A stand-in for a timer, for applications that cannot spare a hardware
+/// timer for the client.
+///
+/// Every count down completes immediately, which disables the command
+/// cooldown. As response timeouts cannot be measured, a client without timer
+/// can only be created through [`new_without_timer`], which only accepts a
+/// [`TimerlessMode`]. A `NoTimer` cannot be constructed otherwise, so that it
+/// cannot be passed to [`new`](fn.new.html) along with a timeout mode.
+///
+/// [`new_without_timer`]: fn.new_without_timer.html
+/// [`TimerlessMode`]: enum.TimerlessMode.html
+#[derive(Debug, Clone, Copy)]
+pub struct NoTimer(());
+
+impl NoTimer {
+    pub(crate) const fn new() -> Self {
+        NoTimer(())
+    }
+}
+
+impl CountDown for NoTimer {
+    type Time = u32;
+
+    fn start<T>(&mut self, _count: T)
+    where
+        T: Into<Self::Time>,
+    {
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        Ok(())
+    }
+}
+
+/// A `CountDown` on top of a monotonic clock, e.g. an RTIC monotonic or
+/// `embassy-time`, rather than a hardware timer of its own.
+///
+/// `now` returns the milliseconds passed since some point in time, wrapping
+/// around. A timer that was never started has expired, and once expired, it
+/// stays expired until started again.
+///
+/// Blocking waits of the client, e.g. for the command cooldown, or for a
+/// response in `Mode::Timeout`, call the hook set through [`with_yield`]
+/// whenever the timer is still running, which can sleep or yield to other
+/// tasks rather than spinning:
+///
+/// ```ignore
+/// // RTIC
+/// let timer = MonotonicTimer::new(|| {
+///     Milliseconds(monotonics::now().duration_since_epoch().to_millis() as u32)
+/// })
+/// .with_yield(cortex_m::asm::wfi);
+///
+/// // embassy-time
+/// let timer = MonotonicTimer::new(|| Milliseconds(Instant::now().as_millis() as u32));
+/// ```
+///
+/// [`with_yield`]: #method.with_yield
+pub struct MonotonicTimer<F, Y = fn()> {
+    now: F,
+    yield_now: Y,
+    /// The start, and the duration of the count down
+    running: Option<(u32, u32)>,
+}
+
+impl<F> MonotonicTimer<F>
+where
+    F: FnMut() -> Milliseconds,
+{
+    pub fn new(now: F) -> Self {
+        Self {
+            now,
+            yield_now: || {},
+            running: None,
+        }
+    }
+}
+
+impl<F, Y> MonotonicTimer<F, Y>
+where
+    F: FnMut() -> Milliseconds,
+    Y: FnMut(),
+{
+    /// Call `yield_now` on every wait while the timer is running.
+    pub fn with_yield<Y2: FnMut()>(self, yield_now: Y2) -> MonotonicTimer<F, Y2> {
+        MonotonicTimer {
+            now: self.now,
+            yield_now,
+            running: self.running,
+        }
+    }
+
+    /// Milliseconds left until the timer expires.
+    pub fn remaining(&mut self) -> u32 {
+        match self.running {
+            Some((start, duration)) => duration.saturating_sub((self.now)().0.wrapping_sub(start)),
+            None => 0,
+        }
+    }
+}
+
+impl<F, Y> CountDown for MonotonicTimer<F, Y>
+where
+    F: FnMut() -> Milliseconds,
+    Y: FnMut(),
+{
+    type Time = u32;
+
+    fn start<T>(&mut self, count: T)
+    where
+        T: Into<Self::Time>,
+    {
+        self.running = Some(((self.now)().0, count.into()));
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        if self.remaining() == 0 {
+            self.running = None;
+            Ok(())
+        } else {
+            (self.yield_now)();
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+/// The modes that do not depend on a timer, see [`NoTimer`](struct.NoTimer.html).
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum TimerlessMode {
+    /// See [`Mode::Blocking`](enum.Mode.html#variant.Blocking)
+    Blocking,
+    /// See [`Mode::NonBlocking`](enum.Mode.html#variant.NonBlocking)
+    NonBlocking,
+}
+
+impl From<TimerlessMode> for Mode {
+    fn from(mode: TimerlessMode) -> Self {
+        match mode {
+            TimerlessMode::Blocking => Mode::Blocking,
+            TimerlessMode::NonBlocking => Mode::NonBlocking,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn monotonic_timer() {
+        let now = Cell::new(u32::MAX - 10);
+        let yields = Cell::new(0);
+        let mut timer = MonotonicTimer::new(|| Milliseconds(now.get()))
+            .with_yield(|| yields.set(yields.get() + 1));
+        assert_eq!(timer.wait(), Ok(()));
+
+        // Across the wrap around of the clock
+        timer.start(Milliseconds(100));
+        now.set(now.get().wrapping_add(60));
+        assert_eq!(timer.remaining(), 40);
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+        assert_eq!(yields.get(), 1);
+
+        now.set(now.get().wrapping_add(40));
+        assert_eq!(timer.wait(), Ok(()));
+        now.set(now.get().wrapping_add(u32::MAX / 2));
+        assert_eq!(timer.wait(), Ok(()));
+        assert_eq!(yields.get(), 1);
+    }
+}
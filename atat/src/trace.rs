@@ -0,0 +1,87 @@
+#[cfg(feature = "trace")]
+use heapless::{consts, String, Vec};
+
+use crate::error::Error;
+
+/// Number of frames kept by the [`TraceRing`](struct.TraceRing.html)
+#[cfg(feature = "trace")]
+type TraceCapacity = consts::U4;
+
+/// Direction of the raw bytes passed to a [`TraceHandler`](type.TraceHandler.html)
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum TraceDirection {
+    /// Transmitted to the device
+    Tx,
+    /// Received from the device
+    Rx,
+}
+
+/// Receives the raw bytes transmitted to, and received from the device, chunk
+/// by chunk, before any parsing, e.g. to mirror the traffic to RTT or a log
+/// file.
+///
+/// The transmitted bytes are passed to the handler set by
+/// [`Client::set_trace_handler`](struct.Client.html#method.set_trace_handler),
+/// the received ones to the handler set by
+/// [`IngressManager::set_trace_handler`](struct.IngressManager.html#method.set_trace_handler).
+pub type TraceHandler = fn(TraceDirection, &[u8]);
+
+/// Kind of a frame recorded by the [`TraceRing`](struct.TraceRing.html)
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameKind {
+    /// A response, passed on to the client
+    Response,
+    /// An unsolicited response code
+    Urc,
+    /// The bytes that resulted in the given error
+    Error(Error),
+}
+
+/// A raw frame, as digested by the ingress manager
+#[cfg(feature = "trace")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub kind: FrameKind,
+    pub data: String<consts::U256>,
+}
+
+/// Bounded ring of the last frames digested by the ingress manager, that can
+/// be dumped on demand, e.g. when a response fails to parse.
+#[cfg(feature = "trace")]
+#[derive(Debug, Default)]
+pub struct TraceRing {
+    frames: Vec<TraceFrame, TraceCapacity>,
+    /// Index of the oldest frame, once the ring is full
+    next: usize,
+}
+
+#[cfg(feature = "trace")]
+impl TraceRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a frame, overwriting the oldest one if the ring is full.
+    pub(crate) fn record(&mut self, kind: FrameKind, data: &str) {
+        let frame = TraceFrame {
+            kind,
+            data: String::from(data),
+        };
+        if let Err(frame) = self.frames.push(frame) {
+            self.frames[self.next] = frame;
+            self.next = (self.next + 1) % self.frames.len();
+        }
+    }
+
+    /// Iterate the recorded frames, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &TraceFrame> {
+        let (newest, oldest) = self.frames.split_at(self.next);
+        oldest.iter().chain(newest.iter())
+    }
+
+    /// Discard all recorded frames.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.next = 0;
+    }
+}
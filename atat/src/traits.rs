@@ -1,6 +1,10 @@
 use crate::error::Error;
+use crate::queues::ResponseGuard;
+use crate::timer::Milliseconds;
 use crate::Mode;
-use heapless::{ArrayLength, String};
+use core::ops::{Add, Mul};
+use embedded_hal::serial;
+use heapless::{consts, ArrayLength, String};
 
 pub trait AtatErr {}
 
@@ -18,17 +22,206 @@ pub trait AtatErr {}
 /// ```
 pub trait AtatResp {}
 
+/// Response of a read command, that is either a value or nothing but `OK`,
+/// e.g. reading an empty phonebook entry. Parses to `None` if only the final
+/// result code arrived.
+impl<T: AtatResp> AtatResp for Option<T> {}
+
+/// Responses of a [`CommandChain`](chain/struct.CommandChain.html)
+impl<A: AtatResp, B: AtatResp> AtatResp for (A, B) {}
+
+/// The maximum serialized length of a type, used to size the command buffer
+/// of derived [`AtatCmd`] implementations at compile time.
+///
+/// This trait can be derived by the [`atat_derive`] crate, for structs as well
+/// as `serde_repr` enums (using the `#[repr(..)]` type). `#[derive(AtatCmd)]`
+/// and `#[derive(AtatEnum)]` implement it automatically.
+///
+/// String slices have no maximum length, and must be annotated with their
+/// maximum length using `#[at_arg(len = ..)]`.
+///
+/// [`AtatCmd`]: trait.AtatCmd.html
+/// [`atat_derive`]: https://crates.io/crates/atat_derive
+///
+/// Example:
+/// ```
+/// use atat::prelude::*;
+///
+/// #[derive(Clone, PartialEq, Serialize_repr, Deserialize_repr, AtatLen)]
+/// #[repr(u8)]
+/// pub enum Functionality {
+///     Min = 0,
+///     Full = 1,
+/// }
+/// ```
+pub trait AtatLen {
+    /// The maximum length of `Self` when serialized.
+    type Len: ArrayLength<u8>;
+}
+
+macro_rules! impl_atat_len {
+    ($($type:ty => $len:ident),*) => {
+        $(
+            impl AtatLen for $type {
+                type Len = consts::$len;
+            }
+        )*
+    };
+}
+
+impl_atat_len! {
+    bool => U5,
+    u8 => U3,
+    u16 => U5,
+    u32 => U10,
+    u64 => U20,
+    u128 => U39,
+    usize => U20,
+    i8 => U4,
+    i16 => U6,
+    i32 => U11,
+    i64 => U20,
+    i128 => U40,
+    isize => U20,
+    f32 => U16,
+    f64 => U32,
+    serde_at::format::NumericBool<bool> => U1,
+    serde_at::format::OnOff<bool> => U5,
+    serde_at::format::Presence<bool> => U1
+}
+
+impl<T: AtatLen> AtatLen for Option<T> {
+    type Len = T::Len;
+}
+
+impl<T: AtatLen> AtatLen for &T {
+    type Len = T::Len;
+}
+
+impl<N> AtatLen for String<N>
+where
+    N: ArrayLength<u8> + Add<consts::U2>,
+    <N as Add<consts::U2>>::Output: ArrayLength<u8>,
+{
+    // Quotes are added around the string when serialized
+    type Len = <N as Add<consts::U2>>::Output;
+}
+
+macro_rules! impl_encoded_len {
+    ($($encoding:ident),*) => {
+        $(
+            impl<N> AtatLen for serde_at::encoding::$encoding<String<N>>
+            where
+                N: ArrayLength<u8> + Mul<consts::U4>,
+                <N as Mul<consts::U4>>::Output: Add<consts::U2>,
+                <<N as Mul<consts::U4>>::Output as Add<consts::U2>>::Output: ArrayLength<u8>,
+            {
+                // Every byte of the string is encoded by at most four hex
+                // digits, and quotes are added around the result
+                type Len = <<N as Mul<consts::U4>>::Output as Add<consts::U2>>::Output;
+            }
+        )*
+    };
+}
+
+impl_encoded_len!(Ucs2Hex, Gsm7Hex);
+
+/// Type level sum of a length and the [`AtatLen`] of `T`, for the derives
+/// adding up lengths that depend on type parameters.
+///
+/// [`AtatLen`]: trait.AtatLen.html
+#[doc(hidden)]
+pub trait AddLen<T: AtatLen> {
+    type Output: ArrayLength<u8>;
+}
+
+impl<L, T> AddLen<T> for L
+where
+    T: AtatLen,
+    L: Add<T::Len>,
+    <L as Add<T::Len>>::Output: ArrayLength<u8>,
+{
+    type Output = <L as Add<T::Len>>::Output;
+}
+
+/// A command sent in two stages, where the command line is answered by a `>`
+/// prompt before the payload is sent. Examples are `AT+CMGS` and `AT+CMGW`,
+/// taking the message body as payload, terminated by CTRL-Z.
+///
+/// As the modem may echo the payload without a line terminator, the
+/// `Payload` should return `true` from
+/// [`AtatCmd::force_receive_state`](trait.AtatCmd.html#method.force_receive_state).
+pub trait MultiStageCmd {
+    /// The command line, answered by the prompt
+    type Command: AtatCmd;
+    /// The payload, answered by the final response
+    type Payload: AtatCmd;
+
+    fn command(&self) -> Self::Command;
+
+    fn payload(&self) -> Self::Payload;
+}
+
+/// Byte transport towards the modem.
+///
+/// This is implemented for every embedded_hal `serial::Write<u8>`, but can be
+/// implemented directly for modules exposing their AT interface through e.g.
+/// the byte FIFO of a SPI or I2C register map.
+///
+/// The `Debug` rendering of the error is carried by `Error::Write`.
+pub trait AtTransport {
+    type Error: core::fmt::Debug;
+
+    /// Write all of `buf`, blocking until the transport has accepted it.
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+
+    /// Block until all written bytes have been transmitted.
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<T> AtTransport for T
+where
+    T: serial::Write<u8>,
+    T::Error: core::fmt::Debug,
+{
+    type Error = T::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        for c in buf {
+            block!(self.write(*c))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        block!(serial::Write::flush(self))
+    }
+}
+
 pub trait AtatUrc {
     /// The type of the response. Usually the enum this trait is implemented on.
     type Response;
 
     /// Parse the string response into a `Self::Response` instance.
     fn parse(resp: &str) -> Result<Self::Response, Error>;
+
+    /// Parse a URC that was truncated to fit the URC queue, from an original
+    /// length of `original_len` bytes.
+    ///
+    /// Truncated URCs are dropped with `Error::Overflow` by default. The
+    /// derive parses variants marked `truncatable` into a
+    /// [`Truncatable`](struct.Truncatable.html).
+    fn parse_truncated(_resp: &str, _original_len: usize) -> Result<Self::Response, Error> {
+        Err(Error::Overflow)
+    }
 }
 
 /// This trait needs to be implemented for every command type.
 ///
-/// It can also be derived by the [`atat_derive`] crate.
+/// It can also be derived by the [`atat_derive`] crate, in which case
+/// `CommandLen` is calculated from the [`AtatLen`] of every argument.
+///
+/// [`AtatLen`]: trait.AtatLen.html
 ///
 /// [`atat_derive`]: https://crates.io/crates/atat_derive
 ///
@@ -71,6 +264,17 @@ pub trait AtatCmd {
     /// Return the command as a heapless `String`.
     fn as_string(&self) -> String<Self::CommandLen>;
 
+    /// Return the command as a heapless `String`, failing with
+    /// `Error::CmdTooLong` if it does not fit `CommandLen`, rather than
+    /// panicking or truncating it, or with `Error::Serialize` if an argument
+    /// cannot be serialized.
+    ///
+    /// This is what the clients send. As the full length of a command that
+    /// does not fit is unknown, it is reported as one more than `CommandLen`.
+    fn try_as_string(&self) -> Result<String<Self::CommandLen>, Error> {
+        Ok(self.as_string())
+    }
+
     /// Parse the string response into a `Self::Response` instance.
     fn parse(&self, resp: &str) -> Result<Self::Response, Error>;
 
@@ -79,9 +283,71 @@ pub trait AtatCmd {
         false
     }
 
-    /// The max timeout in milliseconds.
-    fn max_timeout_ms(&self) -> u32 {
-        1000
+    /// The max time to wait for the response.
+    fn max_timeout(&self) -> Milliseconds {
+        Milliseconds(1000)
+    }
+
+    /// The cooldown between the response to this command and the next
+    /// command, overriding the cooldown of the client, e.g.
+    /// `Some(Milliseconds(0))` for commands that can be sent back-to-back, or
+    /// a longer one for network operations.
+    fn cooldown(&self) -> Option<Milliseconds> {
+        None
+    }
+
+    /// The max length of the command, including the termination.
+    ///
+    /// Longer commands are refused by the client with `Error::CmdTooLong`,
+    /// rather than sent to the modem. If the client is also configured with
+    /// a max command length, the smaller one applies.
+    fn max_cmd_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// The max length of the response, if known.
+    ///
+    /// This is passed on to the ingress manager, which fails fast with
+    /// `Error::Overflow` once the response exceeds it.
+    fn max_response_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// The prefix of a size delimited response, whose payload is too large to
+    /// be buffered, e.g. `+URDFILE:` or `+HTTPREAD:`.
+    ///
+    /// The payload follows the last number of the header, quoted (`+URDFILE:
+    /// "f.txt",5,"hello"`) or on the next line (`+HTTPREAD: 5\r\nhello`). The
+    /// ingress manager streams it to its stream sink, or to
+    /// [`Client::read_stream`](struct.Client.html#method.read_stream), and
+    /// only the header is left in the response.
+    fn stream_prefix(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The prefix of a size delimited response, whose payload is buffered
+    /// along with the header, e.g. `+USORD:`.
+    ///
+    /// The payload follows the last number of the header, as for
+    /// [`stream_prefix`](#method.stream_prefix). The ingress manager only
+    /// looks for the final result code after the payload, so that payloads
+    /// holding e.g. `\r\nOK\r\n` are received in full. URCs carrying
+    /// payloads are framed by
+    /// [`Dialect::binary_urc_len`](dialect/trait.Dialect.html#method.binary_urc_len)
+    /// instead.
+    fn payload_prefix(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The prefix of the information text answering this command, e.g.
+    /// `+CSQ`.
+    ///
+    /// If the client validates response prefixes, see
+    /// [`Config::with_response_validation`](struct.Config.html#method.with_response_validation),
+    /// a response starting with another prefix is discarded as stale, e.g.
+    /// the late response to a command that timed out.
+    fn response_prefix(&self) -> Option<&'static str> {
+        None
     }
 
     /// Force the ingress manager into receive state immediately after sending
@@ -89,6 +355,157 @@ pub trait AtatCmd {
     fn force_receive_state(&self) -> bool {
         false
     }
+
+    /// Whether the modem answers this command at all.
+    ///
+    /// Fire-and-forget commands (e.g. `ATO`, or commands issued right before
+    /// a reset) return as soon as they are flushed, with the response parsed
+    /// from an empty string. `Self::Response` should thus be a unit struct.
+    fn expects_response(&self) -> bool {
+        true
+    }
+}
+
+/// Parsing the response to a command borrowing from the buffer it was
+/// received in, for responses with `&str` or `&[u8]` fields, which would
+/// otherwise be copied into heapless strings and vectors.
+///
+/// The response is held in a [`ResponseGuard`], returned by
+/// [`DynClient::send_guarded`], until the guard is dropped:
+///
+/// ```ignore
+/// #[derive(Deserialize)]
+/// pub struct FileContents<'a> {
+///     pub name: &'a str,
+///     pub data: &'a [u8],
+/// }
+///
+/// impl<'a> ParseBorrowed<'a> for ReadFile {
+///     type Borrowed = FileContents<'a>;
+///
+///     fn parse_borrowed(&self, resp: &'a [u8]) -> Result<Self::Borrowed, Error> {
+///         serde_at::from_slice(resp).map_err(|_| Error::ParseString(ErrorContext::new()))
+///     }
+/// }
+///
+/// let resp = block!(client.send_guarded(&ReadFile { name: "data.bin" }))?;
+/// let contents = ReadFile { name: "data.bin" }.parse_borrowed(resp.as_bytes())?;
+/// ```
+///
+/// [`ResponseGuard`]: struct.ResponseGuard.html
+/// [`DynClient::send_guarded`]: trait.DynClient.html#method.send_guarded
+pub trait ParseBorrowed<'a>: AtatCmd {
+    /// The type of the response, borrowing from the response buffer
+    type Borrowed: 'a;
+
+    /// Parse the response, borrowing from `resp`.
+    fn parse_borrowed(&self, resp: &'a [u8]) -> Result<Self::Borrowed, Error>;
+}
+
+/// Static, machine-readable description of a command.
+///
+/// Emitted by `#[derive(AtatCmd)]` as the associated constant `CMD_INFO` when
+/// `catalog = true` is given in the `at_cmd` attribute, allowing firmware to
+/// expose a list of supported commands, or host tooling to generate scripts
+/// from the driver's type definitions.
+///
+/// Example:
+/// ```
+/// #[derive(Clone, AtatCmd)]
+/// #[at_cmd("+CFUN", NoResponse, timeout_ms = 180000, catalog = true)]
+/// pub struct SetModuleFunctionality {
+///     #[at_arg(position = 0)]
+///     pub fun: Functionality,
+/// }
+///
+/// const SUPPORTED: &[atat::CmdInfo] = &[SetModuleFunctionality::CMD_INFO];
+/// assert_eq!(SUPPORTED[0].syntax, "AT+CFUN=<fun>");
+/// ```
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct CmdInfo {
+    /// Name of the command type, e.g. `"SetModuleFunctionality"`
+    pub name: &'static str,
+    /// Command syntax with placeholders for the arguments, e.g. `"AT+CFUN=<fun>"`
+    pub syntax: &'static str,
+    /// The max timeout in milliseconds
+    pub timeout_ms: u32,
+    /// Name of the response type, e.g. `"NoResponse"`
+    pub response: &'static str,
+}
+
+/// Wrapper overriding the timeout of a command, chosen at runtime, e.g. for
+/// `AT+COPS=0` depending on the network conditions.
+///
+/// Usually created by [`AtatClient::send_with_timeout`].
+///
+/// [`AtatClient::send_with_timeout`]: trait.AtatClient.html#method.send_with_timeout
+#[derive(Debug, Clone)]
+pub struct WithTimeout<'a, A> {
+    pub cmd: &'a A,
+    pub timeout: Milliseconds,
+}
+
+impl<'a, A> WithTimeout<'a, A> {
+    pub fn new(cmd: &'a A, timeout: Milliseconds) -> Self {
+        WithTimeout { cmd, timeout }
+    }
+}
+
+impl<'a, A: AtatCmd> AtatCmd for WithTimeout<'a, A> {
+    type CommandLen = A::CommandLen;
+    type Response = A::Response;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        self.cmd.as_string()
+    }
+
+    fn try_as_string(&self) -> Result<String<Self::CommandLen>, Error> {
+        self.cmd.try_as_string()
+    }
+
+    fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+        self.cmd.parse(resp)
+    }
+
+    fn can_abort(&self) -> bool {
+        self.cmd.can_abort()
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        self.timeout
+    }
+
+    fn cooldown(&self) -> Option<Milliseconds> {
+        self.cmd.cooldown()
+    }
+
+    fn max_cmd_len(&self) -> Option<usize> {
+        self.cmd.max_cmd_len()
+    }
+
+    fn max_response_len(&self) -> Option<usize> {
+        self.cmd.max_response_len()
+    }
+
+    fn stream_prefix(&self) -> Option<&'static str> {
+        self.cmd.stream_prefix()
+    }
+
+    fn payload_prefix(&self) -> Option<&'static str> {
+        self.cmd.payload_prefix()
+    }
+
+    fn response_prefix(&self) -> Option<&'static str> {
+        self.cmd.response_prefix()
+    }
+
+    fn force_receive_state(&self) -> bool {
+        self.cmd.force_receive_state()
+    }
+
+    fn expects_response(&self) -> bool {
+        self.cmd.expects_response()
+    }
 }
 
 pub trait AtatClient {
@@ -135,6 +552,38 @@ pub trait AtatClient {
     /// ```
     fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response>;
 
+    /// Check every URC in queue, passing them to `f` in order of arrival,
+    /// and return the number of URCs passed to `f`.
+    ///
+    /// This drains a burst of URCs in one call, rather than polling
+    /// [`check_urc`](#tymethod.check_urc) in a loop. URCs failing to parse
+    /// are dropped, but unless overridden by the client, draining stops at
+    /// the first of them.
+    ///
+    /// Example:
+    /// ```
+    /// client.check_urc_all::<Urc, _>(|urc| match urc {
+    ///     Urc::MessageWaitingIndication(..) => { /* ... */ }
+    /// });
+    /// ```
+    fn check_urc_all<URC: AtatUrc, F: FnMut(URC::Response)>(&mut self, mut f: F) -> usize {
+        let mut n = 0;
+        while let Some(urc) = self.check_urc::<URC>() {
+            f(urc);
+            n += 1;
+        }
+        n
+    }
+
+    /// Parse the next URC in queue, without taking it off the queue, e.g. to
+    /// decide which task should handle it.
+    ///
+    /// Returns `None` if the queue is empty, the URC fails to parse, or the
+    /// client cannot peek.
+    fn peek_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+        None
+    }
+
     /// Check if there are any responses enqueued from the ingress manager.
     ///
     /// The function will return `nb::Error::WouldBlock` until a response or an
@@ -145,6 +594,59 @@ pub trait AtatClient {
     /// [`send`]: #method.send
     fn check_response<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error>;
 
+    /// Send raw bytes that are not an [`AtatCmd`], e.g. PPP frames, firmware
+    /// payloads or vendor specific escape sequences.
+    ///
+    /// Like [`send`](#method.send), this waits for the cooldown since the
+    /// last response or URC, and returns `WouldBlock` while a command is
+    /// awaiting its response. No response is awaited for the bytes.
+    ///
+    /// [`AtatCmd`]: trait.AtatCmd.html
+    fn send_raw(&mut self, data: &[u8]) -> nb::Result<(), Error>;
+
+    /// Send an AT command like [`send`], but with a timeout of `timeout`
+    /// rather than its `max_timeout`.
+    ///
+    /// [`send`]: #method.send
+    fn send_with_timeout<A: AtatCmd>(
+        &mut self,
+        cmd: &A,
+        timeout: Milliseconds,
+    ) -> nb::Result<A::Response, Error> {
+        self.send(&WithTimeout::new(cmd, timeout))
+    }
+
+    /// Send a [`MultiStageCmd`]: the command line is sent and its `>` prompt
+    /// awaited, after which the payload is sent.
+    ///
+    /// The wait for the prompt always blocks. Apart from that, the mode of the
+    /// client applies to the payload as with [`send`], so in NonBlocking mode
+    /// `self.check_response(&cmd.payload())` can be called until it no longer
+    /// returns nb::Error::WouldBlock.
+    ///
+    /// A payload that does not fit its `CommandLen` fails with
+    /// `Error::CmdTooLong` before the command line is sent.
+    ///
+    /// [`MultiStageCmd`]: trait.MultiStageCmd.html
+    /// [`send`]: #method.send
+    fn send_multi_stage<M: MultiStageCmd>(
+        &mut self,
+        cmd: &M,
+    ) -> nb::Result<<M::Payload as AtatCmd>::Response, Error> {
+        // Once prompted, the device would wait for the payload in vain
+        cmd.payload().try_as_string()?;
+        let command = cmd.command();
+        match self.send(&command) {
+            Err(nb::Error::WouldBlock) => {
+                block!(self.check_response(&command))?;
+            }
+            res => {
+                res?;
+            }
+        }
+        self.send(&cmd.payload())
+    }
+
     /// Get the configured mode of the client.
     ///
     /// Options are:
@@ -153,3 +655,183 @@ pub trait AtatClient {
     /// - Timeout
     fn get_mode(&self) -> Mode;
 }
+
+/// Send `cmd` and await its response within the timeout of the client, for
+/// the helpers running a sequence of commands, like [`init::run`].
+///
+/// Returns `Error::Unsupported` without sending anything, if the client is in
+/// a mode without a timeout, as a device that does not answer would block it
+/// forever.
+///
+/// [`init::run`]: init/fn.run.html
+pub(crate) fn send_bounded<C, A>(client: &mut C, cmd: &A) -> Result<A::Response, Error>
+where
+    C: AtatClient + ?Sized,
+    A: AtatCmd,
+{
+    if !client.get_mode().has_timeout() {
+        return Err(Error::Unsupported);
+    }
+    match client.send(cmd) {
+        // Sending again would restart the timeout in `Mode::NonBlockingTimeout`
+        Err(nb::Error::WouldBlock) => block!(client.check_response(cmd)),
+        Err(nb::Error::Other(e)) => Err(e),
+        Ok(resp) => Ok(resp),
+    }
+}
+
+/// The properties of an [`AtatCmd`] that the client acts on, apart from its
+/// serialization and parsing.
+///
+/// [`AtatCmd`]: trait.AtatCmd.html
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CmdMeta {
+    pub timeout_ms: u32,
+    pub cooldown: Option<Milliseconds>,
+    pub max_cmd_len: Option<usize>,
+    pub max_response_len: Option<usize>,
+    pub stream_prefix: Option<&'static str>,
+    pub payload_prefix: Option<&'static str>,
+    pub response_prefix: Option<&'static str>,
+    pub force_receive_state: bool,
+    pub expects_response: bool,
+    pub can_abort: bool,
+}
+
+impl CmdMeta {
+    pub fn of<A: AtatCmd>(cmd: &A) -> Self {
+        CmdMeta {
+            timeout_ms: cmd.max_timeout().0,
+            cooldown: cmd.cooldown(),
+            max_cmd_len: cmd.max_cmd_len(),
+            max_response_len: cmd.max_response_len(),
+            stream_prefix: cmd.stream_prefix(),
+            payload_prefix: cmd.payload_prefix(),
+            response_prefix: cmd.response_prefix(),
+            force_receive_state: cmd.force_receive_state(),
+            expects_response: cmd.expects_response(),
+            can_abort: cmd.can_abort(),
+        }
+    }
+}
+
+/// Parses an unparsed URC, given its original length if it was truncated,
+/// see [`DynClient::check_urc_bytes`](trait.DynClient.html#tymethod.check_urc_bytes).
+pub type UrcParser<'a> = dyn FnMut(&str, Option<usize>) -> Result<(), Error> + 'a;
+
+/// The byte level core of an AT client, sending serialized commands and
+/// returning their unparsed responses.
+///
+/// Unlike [`AtatClient`], this trait is object safe, so a driver can hold a
+/// `&mut dyn DynClient` rather than being generic over the client, and
+/// [`AtatClient`] is implemented on top of it, parsing the responses:
+///
+/// ```ignore
+/// struct Driver<'a> {
+///     client: &'a mut dyn DynClient,
+/// }
+///
+/// impl<'a> Driver<'a> {
+///     fn signal_quality(&mut self) -> Result<SignalQuality, Error> {
+///         Ok(block!(self.client.send(&GetSignalQuality))?)
+///     }
+/// }
+/// ```
+///
+/// [`AtatClient`]: trait.AtatClient.html
+pub trait DynClient {
+    /// Send the serialized command `cmd`, with the properties `meta`, like
+    /// [`AtatClient::send`](trait.AtatClient.html#tymethod.send), returning
+    /// its unparsed response.
+    fn send_bytes(&mut self, cmd: &[u8], meta: &CmdMeta) -> nb::Result<ResponseGuard, Error>;
+
+    /// Check for the unparsed response to the command sent, like
+    /// [`AtatClient::check_response`](trait.AtatClient.html#tymethod.check_response).
+    fn check_response_bytes(&mut self) -> nb::Result<ResponseGuard, Error>;
+
+    /// Send `cmd`, returning its unparsed response, to be parsed borrowing
+    /// from the response buffer with
+    /// [`ParseBorrowed::parse_borrowed`](trait.ParseBorrowed.html#tymethod.parse_borrowed).
+    fn send_guarded<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<ResponseGuard, Error>
+    where
+        Self: Sized,
+    {
+        self.send_bytes(cmd.try_as_string()?.as_bytes(), &CmdMeta::of(cmd))
+    }
+
+    /// Hand the next URC to `parse`, along with its original length if it
+    /// was truncated, returning whether there was one. URCs failing to parse
+    /// are dropped.
+    fn check_urc_bytes(&mut self, parse: &mut UrcParser<'_>) -> bool;
+
+    /// Pass the next URC in queue to `parse`, like
+    /// [`check_urc_bytes`](#tymethod.check_urc_bytes), but without taking it
+    /// off the queue.
+    fn peek_urc_bytes(&mut self, parse: &mut UrcParser<'_>) -> bool;
+
+    /// See [`AtatClient::send_raw`](trait.AtatClient.html#tymethod.send_raw).
+    fn send_raw_bytes(&mut self, data: &[u8]) -> nb::Result<(), Error>;
+
+    /// See [`AtatClient::get_mode`](trait.AtatClient.html#tymethod.get_mode).
+    fn mode(&self) -> Mode;
+}
+
+/// The typed client, for the byte level core. Only the serialization and
+/// parsing is generic over the command, which keeps clients used with many
+/// command types small.
+impl<C: DynClient + ?Sized> AtatClient for C {
+    fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+        let resp = self.send_bytes(cmd.try_as_string()?.as_bytes(), &CmdMeta::of(cmd))?;
+        Ok(cmd.parse(&resp)?)
+    }
+
+    fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+        let mut urc = None;
+        self.check_urc_bytes(&mut |line, truncated_len| {
+            urc = Some(match truncated_len {
+                Some(len) => URC::parse_truncated(line, len)?,
+                None => URC::parse(line)?,
+            });
+            Ok(())
+        });
+        urc
+    }
+
+    fn check_urc_all<URC: AtatUrc, F: FnMut(URC::Response)>(&mut self, mut f: F) -> usize {
+        let mut n = 0;
+        while self.check_urc_bytes(&mut |line, truncated_len| {
+            f(match truncated_len {
+                Some(len) => URC::parse_truncated(line, len)?,
+                None => URC::parse(line)?,
+            });
+            n += 1;
+            Ok(())
+        }) {}
+        n
+    }
+
+    fn peek_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+        let mut urc = None;
+        self.peek_urc_bytes(&mut |line, truncated_len| {
+            urc = Some(match truncated_len {
+                Some(len) => URC::parse_truncated(line, len)?,
+                None => URC::parse(line)?,
+            });
+            Ok(())
+        });
+        urc
+    }
+
+    fn check_response<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+        let resp = self.check_response_bytes()?;
+        Ok(cmd.parse(&resp)?)
+    }
+
+    fn send_raw(&mut self, data: &[u8]) -> nb::Result<(), Error> {
+        self.send_raw_bytes(data)
+    }
+
+    fn get_mode(&self) -> Mode {
+        self.mode()
+    }
+}
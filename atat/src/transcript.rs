@@ -0,0 +1,331 @@
+//! Recording the raw traffic of a session, and replaying it in tests
+//!
+//! A transcript has a line per chunk of bytes transmitted or received, with
+//! the milliseconds since the start of the recording, e.g.
+//!
+//! ```text
+//! 0 tx "AT+CSQ\r\n"
+//! 3 rx "AT+CSQ\r\r\n+CSQ: 20,99\r\n"
+//! 4 rx "\r\nOK\r\n"
+//! ```
+//!
+//! Printable ASCII is written as is, other bytes as `\r`, `\n` or `\xNN`.
+//!
+//! A [`Recorder`] is fed from the [`TraceHandler`]s of the client and the
+//! ingress manager, e.g. on a host running the [`SerialClient`]:
+//!
+//! ```ignore
+//! static RECORDER: Mutex<Option<Recorder<File>>> = Mutex::new(None);
+//!
+//! fn record(direction: TraceDirection, data: &[u8]) {
+//!     if let Some(recorder) = RECORDER.lock().unwrap().as_mut() {
+//!         recorder.record(direction, data).ok();
+//!     }
+//! }
+//!
+//! *RECORDER.lock().unwrap() = Some(Recorder::new(File::create("session.txt")?));
+//! client.set_trace_handler(record);
+//! ```
+//!
+//! A field-reported parsing bug then becomes a regression test, by replaying
+//! the received bytes of the transcript into an ingress manager:
+//!
+//! ```ignore
+//! let mut replayer = Replayer::parse(include_str!("session.txt"))?;
+//! replayer.replay(&mut ingress);
+//! assert_eq!(res_c.dequeue().unwrap().result.as_deref(), Ok("+CSQ: 20,99"));
+//! ```
+//!
+//! [`Recorder`]: struct.Recorder.html
+//! [`TraceHandler`]: ../type.TraceHandler.html
+//! [`SerialClient`]: ../serial_client/struct.SerialClient.html
+
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::time::Instant;
+use std::vec::Vec;
+
+use heapless::consts;
+
+use crate::dialect::Dialect;
+use crate::trace::TraceDirection;
+use crate::{IngressManager, UrcMatcher};
+
+/// A chunk of bytes transmitted or received, in a transcript
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Milliseconds since the start of the recording
+    pub at_ms: u32,
+    pub direction: TraceDirection,
+    pub data: Vec<u8>,
+}
+
+impl Entry {
+    /// Parse a line of a transcript.
+    pub fn parse(line: &str) -> io::Result<Self> {
+        let mut split = line.trim().splitn(3, ' ');
+        let (at_ms, direction, data) = match (split.next(), split.next(), split.next()) {
+            (Some(at_ms), Some(direction), Some(data)) => (at_ms, direction, data),
+            _ => return Err(invalid(line)),
+        };
+        let at_ms = at_ms.parse().map_err(|_| invalid(line))?;
+        let direction = match direction {
+            "tx" => TraceDirection::Tx,
+            "rx" => TraceDirection::Rx,
+            _ => return Err(invalid(line)),
+        };
+        let data = unescape(data).ok_or_else(|| invalid(line))?;
+        Ok(Self {
+            at_ms,
+            direction,
+            data,
+        })
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            TraceDirection::Tx => "tx",
+            TraceDirection::Rx => "rx",
+        };
+        write!(f, "{} {} \"", self.at_ms, direction)?;
+        for byte in &self.data {
+            match byte {
+                b'\r' => f.write_str("\\r")?,
+                b'\n' => f.write_str("\\n")?,
+                b'"' | b'\\' => write!(f, "\\{}", *byte as char)?,
+                0x20..=0x7e => f.write_char(*byte as char)?,
+                _ => write!(f, "\\x{:02x}", byte)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid transcript line: {:?}", line),
+    )
+}
+
+/// The bytes of a quoted and escaped `s`.
+fn unescape(s: &str) -> Option<Vec<u8>> {
+    let s = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut data = Vec::new();
+    let mut bytes = s.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            data.push(byte);
+            continue;
+        }
+        match bytes.next()? {
+            b'r' => data.push(b'\r'),
+            b'n' => data.push(b'\n'),
+            b'x' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = core::str::from_utf8(&hex).ok()?;
+                data.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            c => data.push(c),
+        }
+    }
+    Some(data)
+}
+
+/// Writes the chunks passed to [`record`](#method.record) as a transcript.
+#[derive(Debug)]
+pub struct Recorder<W> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start recording to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Write a chunk of bytes transmitted or received just now.
+    pub fn record(&mut self, direction: TraceDirection, data: &[u8]) -> io::Result<()> {
+        let elapsed = self.start.elapsed().as_millis();
+        let entry = Entry {
+            at_ms: core::cmp::min(elapsed, u128::from(u32::MAX)) as u32,
+            direction,
+            data: data.to_vec(),
+        };
+        writeln!(self.writer, "{}", entry)
+    }
+
+    /// Stop recording, releasing the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Replays the received bytes of a transcript into an ingress manager.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    entries: Vec<Entry>,
+    /// Index of the next entry to replay
+    next: usize,
+}
+
+impl Replayer {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Self { entries, next: 0 }
+    }
+
+    /// Parse a transcript, skipping empty lines and `#` comments.
+    pub fn parse(transcript: &str) -> io::Result<Self> {
+        let entries = transcript
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(Entry::parse)
+            .collect::<io::Result<_>>()?;
+        Ok(Self::new(entries))
+    }
+
+    /// The entries of the transcript.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// All bytes transmitted in the transcript, to compare with the bytes
+    /// sent by a client under test.
+    pub fn transmitted(&self) -> Vec<u8> {
+        self.entries
+            .iter()
+            .filter(|e| e.direction == TraceDirection::Tx)
+            .flat_map(|e| e.data.iter().copied())
+            .collect()
+    }
+
+    /// Write the received chunks recorded up to `now_ms` to `ingress`,
+    /// digesting after each, e.g. driven by a
+    /// [`VirtualClock`](../testing/struct.VirtualClock.html). Returns
+    /// whether any chunks were due.
+    pub fn pump<U, D>(&mut self, now_ms: u32, ingress: &mut IngressManager<U, D>) -> bool
+    where
+        U: UrcMatcher<MaxLen = consts::U256>,
+        D: Dialect,
+    {
+        let start = self.next;
+        while let Some(entry) = self.entries.get(self.next) {
+            if entry.at_ms > now_ms {
+                break;
+            }
+            if entry.direction == TraceDirection::Rx {
+                ingress.write(&entry.data);
+                ingress.digest();
+            }
+            self.next += 1;
+        }
+        self.next > start
+    }
+
+    /// Write all remaining received chunks to `ingress`.
+    pub fn replay<U, D>(&mut self, ingress: &mut IngressManager<U, D>)
+    where
+        U: UrcMatcher<MaxLen = consts::U256>,
+        D: Dialect,
+    {
+        self.pump(u32::MAX, ingress);
+    }
+
+    /// Whether all entries have been replayed.
+    pub fn is_done(&self) -> bool {
+        self.next == self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::queues::{ComQueue, DataQueue, ResQueue, StaticQueue, UrcQueue};
+    use crate::{Config, Error, Mode, NoopUrcMatcher};
+
+    const TRANSCRIPT: &str = "# +CSQ split across reads
+0 tx \"AT+CSQ\\r\\n\"
+3 rx \"AT+CSQ\\r\\r\\n+CSQ: 20,\"
+4 rx \"99\\r\\nOK\\r\\n\"
+
+120 rx \"\\r\\n+UUSORD: 0,\\\"\\x00\\xff\\\"\\r\\n\"
+";
+
+    #[test]
+    fn format() {
+        let replayer = Replayer::parse(TRANSCRIPT).unwrap();
+        let entries = replayer.entries();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[3],
+            Entry {
+                at_ms: 120,
+                direction: TraceDirection::Rx,
+                data: b"\r\n+UUSORD: 0,\"\x00\xff\"\r\n".to_vec(),
+            }
+        );
+        let lines = TRANSCRIPT
+            .lines()
+            .filter(|l| l.starts_with(char::is_numeric));
+        for (entry, line) in entries.iter().zip(lines) {
+            assert_eq!(entry.to_string(), line);
+        }
+        assert_eq!(replayer.transmitted(), b"AT+CSQ\r\n");
+
+        assert!(Entry::parse("0 tx AT").is_err());
+        assert!(Entry::parse("0 up \"AT\"").is_err());
+        assert!(Entry::parse("0 tx \"\\x4\"").is_err());
+    }
+
+    #[test]
+    fn record() {
+        let mut recorder = Recorder::new(Vec::new());
+        recorder.record(TraceDirection::Tx, b"AT\r\n").unwrap();
+        recorder.record(TraceDirection::Rx, b"\r\nOK\r\n").unwrap();
+        let transcript = String::from_utf8(recorder.into_inner()).unwrap();
+
+        let replayer = Replayer::parse(&transcript).unwrap();
+        let data: Vec<_> = replayer.entries().iter().map(|e| &e.data[..]).collect();
+        assert_eq!(data, [&b"AT\r\n"[..], &b"\r\nOK\r\n"[..]]);
+    }
+
+    #[test]
+    fn replay() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        let (res_p, mut res_c) = unsafe { RES_Q.split() };
+        let (urc_p, mut urc_c) = unsafe { URC_Q.split() };
+        let (_com_p, com_c) = unsafe { COM_Q.split() };
+        let (data_p, _data_c) = unsafe { DATA_Q.split() };
+        let mut ingress: IngressManager<NoopUrcMatcher> = IngressManager::new(
+            res_p,
+            urc_p,
+            com_c,
+            data_p,
+            Config::new(Mode::Timeout),
+            None,
+        );
+
+        let mut replayer = Replayer::parse(TRANSCRIPT).unwrap();
+        assert!(replayer.pump(3, &mut ingress));
+        assert_eq!(res_c.dequeue(), None);
+        assert!(!replayer.pump(3, &mut ingress));
+        replayer.pump(4, &mut ingress);
+        assert_eq!(
+            res_c.dequeue().map(|r| r.into_result()),
+            Some(Ok::<_, Error>(heapless::String::from("+CSQ: 20,99")))
+        );
+
+        replayer.replay(&mut ingress);
+        assert!(replayer.is_done());
+        assert!(urc_c.dequeue().unwrap().line.starts_with("+UUSORD: 0,"));
+    }
+}
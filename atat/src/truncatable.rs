@@ -0,0 +1,72 @@
+use serde::{Deserialize, Deserializer};
+
+use crate::error::{Error, ErrorContext};
+
+/// The payload of a URC, that is truncated rather than dropped if the URC
+/// exceeds the URC queue.
+///
+/// Wrapped by URC variants marked `truncatable`:
+///
+/// ```ignore
+/// #[derive(Clone, AtatUrc)]
+/// pub enum Urc {
+///     #[at_urc("+UUSORD", truncatable = true)]
+///     SocketData(Truncatable<SocketData>),
+/// }
+/// ```
+///
+/// The incomplete last parameter of a truncated URC is cut off, and the
+/// remaining parameters are parsed into `value`, leaving trailing `Option`
+/// fields `None`. The application can then fetch the complete data through a
+/// read command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Truncatable<T> {
+    pub value: T,
+    /// The original length of the URC in bytes, if it was truncated
+    pub truncated_len: Option<usize>,
+}
+
+impl<T> Truncatable<T> {
+    pub fn is_truncated(&self) -> bool {
+        self.truncated_len.is_some()
+    }
+
+    /// Parse a URC that was truncated from `original_len` bytes.
+    pub fn from_truncated<'de>(resp: &'de str, original_len: usize) -> Result<Self, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        // Cut off at the last parameter separator outside of quotes
+        let mut quoted = false;
+        let mut end = None;
+        for (i, c) in resp.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                ',' if !quoted => end = Some(i),
+                _ => {}
+            }
+        }
+        let resp = &resp[..end.ok_or(Error::Overflow)?];
+
+        Ok(Self {
+            value: serde_at::from_str(resp)
+                .map_err(|_| Error::ParseString(ErrorContext::response(resp)))?,
+            truncated_len: Some(original_len),
+        })
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Truncatable<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(|value| Self {
+            value,
+            truncated_len: None,
+        })
+    }
+}
@@ -0,0 +1,318 @@
+//! Uploading payloads too large for a single command, e.g. files or firmware
+//!
+//! Commands like `AT+USODL` or `AT+QFUPL` take a payload after a `>` prompt,
+//! and acknowledge it with a final result code, possibly preceded by e.g. a
+//! checksum. An [`Upload`] describes such a command, which [`upload`] repeats
+//! for every chunk pulled from a [`ChunkSource`]:
+//!
+//! ```ignore
+//! use atat::upload::{self, Upload};
+//!
+//! struct WriteFile<'a> {
+//!     name: &'a str,
+//! }
+//!
+//! impl<'a> Upload for WriteFile<'a> {
+//!     type Command = AppendFile<'a>;
+//!
+//!     fn command(&self, _offset: usize, len: usize) -> Self::Command {
+//!         // AT+UDWNFILE="<name>",<len>
+//!         AppendFile { name: self.name, len }
+//!     }
+//! }
+//!
+//! let mut chunk = [0; 512];
+//! let written = upload::upload(&mut client, &WriteFile { name: "fw.bin" }, &mut image, &mut chunk)?;
+//! ```
+//!
+//! [`Upload`]: trait.Upload.html
+//! [`upload`]: fn.upload.html
+//! [`ChunkSource`]: trait.ChunkSource.html
+
+use heapless::{consts, String};
+
+use crate::error::Error;
+use crate::timer::Milliseconds;
+use crate::traits::{send_bounded, AtatClient, AtatCmd, AtatResp};
+
+/// The source of an upload, read chunk by chunk
+pub trait ChunkSource {
+    /// Read the next bytes into `buf`, returning the number of bytes read, or
+    /// zero at the end of the source.
+    fn read(&mut self, buf: &mut [u8]) -> usize;
+}
+
+impl ChunkSource for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let len = core::cmp::min(buf.len(), self.len());
+        buf[..len].copy_from_slice(&self[..len]);
+        *self = &self[len..];
+        len
+    }
+}
+
+/// A command uploading a payload in chunks, each announced by a command line
+/// answered by a prompt
+pub trait Upload {
+    /// The command line announcing a chunk, answered by the prompt.
+    type Command: AtatCmd;
+
+    /// The command line announcing the chunk of `len` bytes at `offset`.
+    fn command(&self, offset: usize, len: usize) -> Self::Command;
+
+    /// Check the acknowledgment of the chunk of `len` bytes at `offset`,
+    /// e.g. `+QFUPL: 1024,3fa2`. Any response is accepted by default.
+    fn check_ack(&self, _resp: &str, _offset: usize, _len: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// How long the device may take to acknowledge a chunk.
+    fn ack_timeout(&self) -> Milliseconds {
+        Milliseconds(5000)
+    }
+}
+
+/// The acknowledgment of a chunk, that has been checked by
+/// [`Upload::check_ack`](trait.Upload.html#method.check_ack)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkAck;
+
+impl AtatResp for ChunkAck {}
+
+/// Awaits the acknowledgment of a chunk, without sending anything
+struct AwaitAck<'a, U> {
+    upload: &'a U,
+    offset: usize,
+    len: usize,
+}
+
+impl<'a, U: Upload> AtatCmd for AwaitAck<'a, U> {
+    type CommandLen = consts::U1;
+    type Response = ChunkAck;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        String::new()
+    }
+
+    fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+        self.upload.check_ack(resp, self.offset, self.len)?;
+        Ok(ChunkAck)
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        self.upload.ack_timeout()
+    }
+
+    // The chunk is not echoed
+    fn force_receive_state(&self) -> bool {
+        true
+    }
+}
+
+/// Upload everything read from `source` with `upload`, in chunks of up to
+/// `buf.len()` bytes, returning the number of bytes uploaded.
+///
+/// Each chunk is announced, sent after the prompt, and its acknowledgment
+/// awaited for at most
+/// [`Upload::ack_timeout`](trait.Upload.html#method.ack_timeout). The
+/// waits are bounded by the client, so it has to be in `Mode::Timeout` or
+/// `Mode::NonBlockingTimeout`; in the modes without a timeout,
+/// `Error::Unsupported` is returned before anything is read or sent. The
+/// upload stops at the first chunk failing.
+pub fn upload<C, U, S>(
+    client: &mut C,
+    upload: &U,
+    source: &mut S,
+    buf: &mut [u8],
+) -> Result<usize, Error>
+where
+    C: AtatClient,
+    U: Upload,
+    S: ChunkSource,
+{
+    if !client.get_mode().has_timeout() {
+        return Err(Error::Unsupported);
+    }
+    let mut offset = 0;
+    loop {
+        let len = source.read(buf);
+        if len == 0 {
+            return Ok(offset);
+        }
+        #[cfg(feature = "logging")]
+        log::debug!("Uploading {} bytes at offset {}", len, offset);
+
+        send_bounded(client, &upload.command(offset, len))?;
+        block!(client.send_raw(&buf[..len]))?;
+        send_bounded(
+            client,
+            &AwaitAck {
+                upload,
+                offset,
+                len,
+            },
+        )?;
+        offset += len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorContext;
+    use crate::traits::AtatUrc;
+    use crate::Mode;
+    use core::fmt::Write;
+    use heapless::Vec;
+
+    /// Answers the command lines with the prompt, and the chunks with their
+    /// length
+    #[derive(Default)]
+    struct ClientMock {
+        sent: Vec<String<consts::U64>, consts::U8>,
+        chunk_len: usize,
+        blocking: bool,
+    }
+
+    impl AtatClient for ClientMock {
+        fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            let cmd_line = cmd.as_string();
+            if cmd_line.is_empty() {
+                let mut ack = String::<consts::U16>::new();
+                write!(ack, "+UPLOAD: {}", self.chunk_len).ok();
+                Ok(cmd.parse(&ack)?)
+            } else {
+                self.sent.push(String::from(cmd_line.as_str())).ok();
+                Ok(cmd.parse("")?)
+            }
+        }
+
+        fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+            None
+        }
+
+        fn check_response<A: AtatCmd>(&mut self, _cmd: &A) -> nb::Result<A::Response, Error> {
+            Err(nb::Error::WouldBlock)
+        }
+
+        fn send_raw(&mut self, data: &[u8]) -> nb::Result<(), Error> {
+            self.chunk_len = data.len();
+            self.sent
+                .push(String::from(core::str::from_utf8(data).unwrap()))
+                .ok();
+            Ok(())
+        }
+
+        fn get_mode(&self) -> Mode {
+            if self.blocking {
+                Mode::Blocking
+            } else {
+                Mode::Timeout
+            }
+        }
+    }
+
+    struct UploadCmd {
+        len: usize,
+    }
+
+    impl AtatCmd for UploadCmd {
+        type CommandLen = consts::U32;
+        type Response = ChunkAck;
+
+        fn as_string(&self) -> String<Self::CommandLen> {
+            let mut s = String::new();
+            write!(s, "AT+UPLOAD={}\r", self.len).ok();
+            s
+        }
+
+        fn parse(&self, _resp: &str) -> Result<Self::Response, Error> {
+            Ok(ChunkAck)
+        }
+    }
+
+    /// Expects every chunk to be acknowledged with its length, as long as it
+    /// is not longer than `max_len`
+    struct TestUpload {
+        max_len: usize,
+    }
+
+    impl Upload for TestUpload {
+        type Command = UploadCmd;
+
+        fn command(&self, _offset: usize, len: usize) -> Self::Command {
+            UploadCmd { len }
+        }
+
+        fn check_ack(&self, resp: &str, _offset: usize, len: usize) -> Result<(), Error> {
+            match resp.trim_start_matches("+UPLOAD: ").parse::<usize>() {
+                Ok(acked) if acked == len && len <= self.max_len => Ok(()),
+                _ => Err(Error::InvalidResponse(ErrorContext::response(resp))),
+            }
+        }
+    }
+
+    #[test]
+    fn upload_in_chunks() {
+        let mut client = ClientMock::default();
+        let mut source: &[u8] = b"hello world";
+        let mut buf = [0; 4];
+        assert_eq!(
+            upload(
+                &mut client,
+                &TestUpload { max_len: 4 },
+                &mut source,
+                &mut buf
+            ),
+            Ok(11)
+        );
+        assert_eq!(
+            client.sent,
+            [
+                "AT+UPLOAD=4\r",
+                "hell",
+                "AT+UPLOAD=4\r",
+                "o wo",
+                "AT+UPLOAD=3\r",
+                "rld"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejected_chunk() {
+        let mut client = ClientMock::default();
+        let mut source: &[u8] = b"hello world";
+        let mut buf = [0; 8];
+        assert_eq!(
+            upload(
+                &mut client,
+                &TestUpload { max_len: 4 },
+                &mut source,
+                &mut buf
+            ),
+            Err(Error::InvalidResponse(ErrorContext::response("+UPLOAD: 8")))
+        );
+        assert_eq!(client.sent, ["AT+UPLOAD=8\r", "hello wo"]);
+    }
+    #[test]
+    fn mode_without_timeout() {
+        let mut client = ClientMock {
+            blocking: true,
+            ..ClientMock::default()
+        };
+        let mut source: &[u8] = b"hello world";
+        let mut buf = [0; 8];
+        assert_eq!(
+            upload(
+                &mut client,
+                &TestUpload { max_len: 8 },
+                &mut source,
+                &mut buf
+            ),
+            Err(Error::Unsupported)
+        );
+        assert!(client.sent.is_empty());
+        assert_eq!(source.len(), 11);
+    }
+}
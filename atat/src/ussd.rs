@@ -0,0 +1,443 @@
+//! Helpers for USSD sessions (`AT+CUSD`, 3GPP TS 27.007 section 7.15)
+//!
+//! A USSD request is acknowledged by the modem with `OK`, while the actual
+//! reply from the network arrives later as a `+CUSD:` URC. [`UssdSession`]
+//! keeps track of this, including multi-step menus and timeouts.
+//!
+//! Add [`UssdResponse`] to the URC enum of the application, and hand it to
+//! the session when it is received:
+//!
+//! ```ignore
+//! #[derive(Clone, AtatUrc)]
+//! pub enum Urc {
+//!     #[at_urc("+CUSD")]
+//!     Ussd(atat::ussd::UssdResponse),
+//! }
+//!
+//! let mut session = UssdSession::new(timer, Milliseconds(30_000));
+//! session.send(&mut client, "*100#")?;
+//! loop {
+//!     if let Some(Urc::Ussd(resp)) = client.check_urc::<Urc>() {
+//!         session.handle_urc(resp);
+//!     }
+//!     match session.poll(&mut client) {
+//!         Ok(reply) => break reply,
+//!         Err(nb::Error::WouldBlock) => continue,
+//!         Err(e) => return Err(e),
+//!     }
+//! }
+//! ```
+//!
+//! [`UssdSession`]: struct.UssdSession.html
+//! [`UssdResponse`]: struct.UssdResponse.html
+
+use core::fmt::{self, Write};
+
+use embedded_hal::timer::CountDown;
+use heapless::{consts, String};
+use serde::de::{self, SeqAccess, Visitor};
+use serde_at::encoding::Ucs2Hex;
+
+use crate::error::{Error, ErrorContext};
+use crate::timer::Milliseconds;
+use crate::traits::{AtatClient, AtatCmd, AtatResp, AtatUrc};
+
+/// Data coding scheme of the GSM 7-bit default alphabet, language unspecified
+pub const DCS_GSM7: u8 = 15;
+
+/// Empty response of the `AT+CUSD` commands
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoResponse;
+
+impl AtatResp for NoResponse {}
+
+/// Send a USSD string, e.g. `*100#`, or the reply to a menu of an ongoing
+/// session.
+///
+/// The string is at most 182 characters.
+#[derive(Debug, Clone)]
+pub struct SendUssd<'a> {
+    pub code: &'a str,
+    pub dcs: u8,
+}
+
+impl<'a> AtatCmd for SendUssd<'a> {
+    type CommandLen = consts::U201;
+    type Response = NoResponse;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        match self.try_as_string() {
+            Ok(s) => s,
+            Err(_) => panic!("Failed to serialize command"),
+        }
+    }
+
+    /// Fails with `Error::CmdTooLong` if the string is too long, rather than
+    /// sending a truncated code.
+    fn try_as_string(&self) -> Result<String<Self::CommandLen>, Error> {
+        let mut s = String::new();
+        write!(s, "AT+CUSD=1,\"{}\",{}\r\n", self.code, self.dcs)
+            .map_err(|_| Error::CmdTooLong(s.capacity() + 1, s.capacity()))?;
+        Ok(s)
+    }
+
+    fn parse(&self, _resp: &str) -> Result<Self::Response, Error> {
+        Ok(NoResponse)
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        Milliseconds(10000)
+    }
+}
+
+/// Cancel the ongoing USSD session
+#[derive(Debug, Clone)]
+pub struct CancelUssd;
+
+impl AtatCmd for CancelUssd {
+    type CommandLen = consts::U11;
+    type Response = NoResponse;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        String::from("AT+CUSD=2\r\n")
+    }
+
+    fn parse(&self, _resp: &str) -> Result<Self::Response, Error> {
+        Ok(NoResponse)
+    }
+}
+
+/// Status `<m>` of a `+CUSD:` URC
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum UssdStatus {
+    /// No further user action required
+    NoFurtherAction,
+    /// Further user action required, e.g. a menu selection
+    FurtherActionRequired,
+    /// The session was terminated by the network
+    Terminated,
+    /// Another local client has responded
+    OtherClientResponded,
+    /// The operation is not supported
+    NotSupported,
+    /// The network timed out
+    NetworkTimeout,
+}
+
+impl UssdStatus {
+    fn from_u8(m: u8) -> Option<Self> {
+        Some(match m {
+            0 => UssdStatus::NoFurtherAction,
+            1 => UssdStatus::FurtherActionRequired,
+            2 => UssdStatus::Terminated,
+            3 => UssdStatus::OtherClientResponded,
+            4 => UssdStatus::NotSupported,
+            5 => UssdStatus::NetworkTimeout,
+            _ => return None,
+        })
+    }
+}
+
+/// A `+CUSD: <m>[,<str>,<dcs>]` URC, with `<str>` decoded according to
+/// `<dcs>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UssdResponse {
+    pub status: UssdStatus,
+    pub text: Option<String<consts::U256>>,
+    pub dcs: Option<u8>,
+}
+
+impl AtatResp for UssdResponse {}
+
+/// Whether the data coding scheme denotes UCS-2 (3GPP TS 23.038 section 5).
+fn is_ucs2(dcs: u8) -> bool {
+    dcs == 0x11 || ((dcs & 0xC0 == 0x40 || dcs & 0xF0 == 0x90) && dcs & 0x0C == 0x08)
+}
+
+struct UssdResponseVisitor;
+
+impl<'de> Visitor<'de> for UssdResponseVisitor {
+    type Value = UssdResponse;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("struct UssdResponse")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let m = seq
+            .next_element::<u8>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let status = UssdStatus::from_u8(m).ok_or_else(|| {
+            de::Error::invalid_value(de::Unexpected::Unsigned(u64::from(m)), &self)
+        })?;
+        let text = seq.next_element::<String<consts::U256>>()?;
+        let dcs = seq.next_element::<u8>()?;
+
+        let text = match (text, dcs) {
+            (Some(text), Some(dcs)) if is_ucs2(dcs) => Some(
+                Ucs2Hex::from_hex(&text)
+                    .ok_or_else(|| de::Error::custom("invalid UCS-2 text"))?
+                    .0,
+            ),
+            (text, _) => text,
+        };
+
+        Ok(UssdResponse { status, text, dcs })
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for UssdResponse {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(
+            "UssdResponse",
+            &["status", "text", "dcs"],
+            UssdResponseVisitor,
+        )
+    }
+}
+
+impl AtatUrc for UssdResponse {
+    type Response = Self;
+
+    fn parse(resp: &str) -> Result<Self::Response, Error> {
+        serde_at::from_str(resp).map_err(|_| Error::ParseString(ErrorContext::response(resp)))
+    }
+}
+
+/// State of a [`UssdSession`](struct.UssdSession.html)
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum SessionState {
+    /// No session is ongoing
+    Idle,
+    /// Waiting for the network to reply
+    AwaitingReply,
+    /// The network asked for further user action, e.g. a menu selection
+    MenuOpen,
+}
+
+/// Tracks a USSD session across the request, the `+CUSD:` URCs and any
+/// further menu steps, cancelling it when the network does not reply within
+/// the timeout.
+pub struct UssdSession<T: CountDown> {
+    timer: T,
+    timeout: Milliseconds,
+    state: SessionState,
+    reply: Option<UssdResponse>,
+}
+
+impl<T> UssdSession<T>
+where
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Create a new session, waiting at most `timeout` for each reply.
+    pub fn new(timer: T, timeout: Milliseconds) -> Self {
+        UssdSession {
+            timer,
+            timeout,
+            state: SessionState::Idle,
+            reply: None,
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Send a USSD string, or the reply to an open menu, in the GSM 7-bit
+    /// default alphabet.
+    pub fn send<C: AtatClient>(&mut self, client: &mut C, code: &str) -> nb::Result<(), Error> {
+        client.send(&SendUssd {
+            code,
+            dcs: DCS_GSM7,
+        })?;
+        self.reply = None;
+        self.state = SessionState::AwaitingReply;
+        self.timer.start(self.timeout);
+        Ok(())
+    }
+
+    /// Hand a received `+CUSD:` URC to the session.
+    pub fn handle_urc(&mut self, resp: UssdResponse) {
+        if let SessionState::AwaitingReply = self.state {
+            self.reply = Some(resp);
+        }
+    }
+
+    /// Check for the reply of the network.
+    ///
+    /// If no reply is received within the timeout, the session is cancelled
+    /// and `Error::Timeout` is returned.
+    pub fn poll<C: AtatClient>(&mut self, client: &mut C) -> nb::Result<UssdResponse, Error> {
+        if let Some(reply) = self.reply.take() {
+            self.state = match reply.status {
+                UssdStatus::FurtherActionRequired => SessionState::MenuOpen,
+                _ => SessionState::Idle,
+            };
+            return Ok(reply);
+        }
+
+        if let SessionState::AwaitingReply = self.state {
+            if self.timer.wait().is_ok() {
+                self.cancel(client).ok();
+                return Err(nb::Error::Other(Error::Timeout));
+            }
+        }
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Cancel the ongoing session.
+    pub fn cancel<C: AtatClient>(&mut self, client: &mut C) -> nb::Result<(), Error> {
+        self.reply = None;
+        self.state = SessionState::Idle;
+        client.send(&CancelUssd)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Mode;
+    use heapless::Vec;
+    use void::Void;
+
+    struct CdMock {
+        expired: bool,
+    }
+
+    impl CountDown for CdMock {
+        type Time = u32;
+        fn start<T>(&mut self, _count: T)
+        where
+            T: Into<Self::Time>,
+        {
+            self.expired = false;
+        }
+        fn wait(&mut self) -> nb::Result<(), Void> {
+            if self.expired {
+                Ok(())
+            } else {
+                Err(nb::Error::WouldBlock)
+            }
+        }
+    }
+
+    struct ClientMock {
+        sent: Vec<String<consts::U256>, consts::U4>,
+    }
+
+    impl AtatClient for ClientMock {
+        fn send<A: AtatCmd>(&mut self, cmd: &A) -> nb::Result<A::Response, Error> {
+            self.sent.push(String::from(cmd.as_string().as_str())).ok();
+            Ok(cmd.parse("")?)
+        }
+
+        fn check_urc<URC: AtatUrc>(&mut self) -> Option<URC::Response> {
+            None
+        }
+
+        fn check_response<A: AtatCmd>(&mut self, _cmd: &A) -> nb::Result<A::Response, Error> {
+            Err(nb::Error::WouldBlock)
+        }
+
+        fn send_raw(&mut self, _data: &[u8]) -> nb::Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_mode(&self) -> Mode {
+            Mode::Blocking
+        }
+    }
+
+    #[test]
+    fn send_ussd() {
+        let cmd = SendUssd {
+            code: "*100#",
+            dcs: DCS_GSM7,
+        };
+        assert_eq!(cmd.as_string(), "AT+CUSD=1,\"*100#\",15\r\n");
+        assert_eq!(CancelUssd.as_string(), "AT+CUSD=2\r\n");
+
+        let code = [b'1'; 200];
+        let cmd = SendUssd {
+            code: core::str::from_utf8(&code).unwrap(),
+            dcs: DCS_GSM7,
+        };
+        assert_eq!(cmd.try_as_string(), Err(Error::CmdTooLong(202, 201)));
+    }
+
+    #[test]
+    fn ussd_response() {
+        assert_eq!(
+            UssdResponse::parse("+CUSD: 1,\"1. Balance 2. Bundles\",15"),
+            Ok(UssdResponse {
+                status: UssdStatus::FurtherActionRequired,
+                text: Some(String::from("1. Balance 2. Bundles")),
+                dcs: Some(15),
+            })
+        );
+        assert_eq!(
+            UssdResponse::parse("+CUSD: 0,\"00420061006C0061006E00630065003A00200035002020AC\",72"),
+            Ok(UssdResponse {
+                status: UssdStatus::NoFurtherAction,
+                text: Some(String::from("Balance: 5 €")),
+                dcs: Some(72),
+            })
+        );
+        assert_eq!(
+            UssdResponse::parse("+CUSD: 2"),
+            Ok(UssdResponse {
+                status: UssdStatus::Terminated,
+                text: None,
+                dcs: None,
+            })
+        );
+        assert_eq!(
+            UssdResponse::parse("+CUSD: 9"),
+            Err(Error::ParseString(ErrorContext::response("+CUSD: 9")))
+        );
+    }
+
+    #[test]
+    fn session() {
+        let mut client = ClientMock { sent: Vec::new() };
+        let mut session = UssdSession::new(CdMock { expired: false }, Milliseconds(30_000));
+
+        session.send(&mut client, "*100#").unwrap();
+        assert_eq!(session.state(), SessionState::AwaitingReply);
+        assert_eq!(session.poll(&mut client), Err(nb::Error::WouldBlock));
+
+        session.handle_urc(UssdResponse::parse("+CUSD: 1,\"1. Balance\",15").unwrap());
+        assert_eq!(
+            session.poll(&mut client).map(|r| r.status),
+            Ok(UssdStatus::FurtherActionRequired)
+        );
+        assert_eq!(session.state(), SessionState::MenuOpen);
+
+        session.send(&mut client, "1").unwrap();
+        session.timer.expired = true;
+        assert_eq!(
+            session.poll(&mut client),
+            Err(nb::Error::Other(Error::Timeout))
+        );
+        assert_eq!(session.state(), SessionState::Idle);
+        assert_eq!(
+            client.sent,
+            [
+                "AT+CUSD=1,\"*100#\",15\r\n",
+                "AT+CUSD=1,\"1\",15\r\n",
+                "AT+CUSD=2\r\n"
+            ]
+            .iter()
+            .map(|s| String::from(*s))
+            .collect::<Vec<String<consts::U256>, consts::U4>>()
+        );
+    }
+}
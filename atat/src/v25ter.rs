@@ -0,0 +1,140 @@
+//! Typed responses for the result codes of ITU-T V.25ter
+//!
+//! The final result codes `NO CARRIER`, `BUSY`, `NO DIALTONE` and
+//! `NO ANSWER` are returned as the corresponding [`Error`] variants, while
+//! `RING`, and `NO CARRIER` outside of a response, are received as URCs.
+//!
+//! [`Error`]: ../enum.Error.html
+
+use core::fmt;
+
+use heapless::{consts, String};
+use serde::de::{self, Visitor};
+
+use crate::error::Error;
+use crate::timer::Milliseconds;
+use crate::traits::{AtatCmd, AtatResp};
+
+/// The `CONNECT [<rate>]` final result code, e.g. answering `ATD*99#`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connect {
+    /// The connection rate, if reported
+    pub rate: Option<u32>,
+}
+
+impl AtatResp for Connect {}
+
+struct ConnectVisitor;
+
+impl<'de> Visitor<'de> for ConnectVisitor {
+    type Value = Connect;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a CONNECT result code")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        if !v.starts_with(b"CONNECT") {
+            return Err(E::invalid_value(de::Unexpected::Bytes(v), &self));
+        }
+        // The rate may be followed by e.g. the protocol, as in `9600/V42`
+        let rate = v[b"CONNECT".len()..]
+            .iter()
+            .skip_while(|c| **c == b' ')
+            .take_while(|c| c.is_ascii_digit())
+            .fold(None, |rate: Option<u32>, c| {
+                Some(rate.unwrap_or(0) * 10 + u32::from(c - b'0'))
+            });
+        Ok(Connect { rate })
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Connect {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(ConnectVisitor)
+    }
+}
+
+/// The bare `AT` command, used to check that the device is in command mode,
+/// e.g. to resynchronize after a raw transfer.
+#[derive(Debug, Clone)]
+pub struct Attention;
+
+impl AtatCmd for Attention {
+    type CommandLen = consts::U4;
+    type Response = CommandMode;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        String::from("AT\r\n")
+    }
+
+    fn parse(&self, _resp: &str) -> Result<Self::Response, Error> {
+        Ok(CommandMode)
+    }
+}
+
+/// The `+++` escape sequence, switching from data mode back to command mode.
+///
+/// This has to be surrounded by guard times of silence, so rather than
+/// sending it directly, use [`DataMode::escape`].
+///
+/// [`DataMode::escape`]: ../struct.DataMode.html#method.escape
+#[derive(Debug, Clone)]
+pub struct EscapeSequence;
+
+/// Response to the [`EscapeSequence`](struct.EscapeSequence.html) and
+/// [`Attention`](struct.Attention.html): the device is in command mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandMode;
+
+impl AtatResp for CommandMode {}
+
+impl AtatCmd for EscapeSequence {
+    type CommandLen = consts::U3;
+    type Response = CommandMode;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        String::from("+++")
+    }
+
+    fn parse(&self, _resp: &str) -> Result<Self::Response, Error> {
+        Ok(CommandMode)
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        Milliseconds(2000)
+    }
+
+    fn force_receive_state(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn connect() {
+        assert_eq!(
+            serde_at::from_str::<Connect>("CONNECT"),
+            Ok(Connect { rate: None })
+        );
+        assert_eq!(
+            serde_at::from_str::<Connect>("CONNECT 115200"),
+            Ok(Connect { rate: Some(115200) })
+        );
+        assert_eq!(
+            serde_at::from_str::<Connect>("CONNECT 9600/V42"),
+            Ok(Connect { rate: Some(9600) })
+        );
+        assert!(serde_at::from_str::<Connect>("RING").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Waking the device up before sending commands
+//!
+//! A device in a power saving mode may miss the first bytes sent to it, until
+//! woken up, e.g. by pulsing DTR, or sending dummy bytes. A [`WakingTx`] runs
+//! a [`WakeHandler`] before writing to the device, whenever nothing has been
+//! written for a while:
+//!
+//! ```ignore
+//! use atat::wake::{WakeHandler, WakingTx};
+//!
+//! struct PulseDtr(PA8<Output<PushPull>>, Delay);
+//!
+//! impl WakeHandler for PulseDtr {
+//!     fn wake<Tx: AtTransport>(&mut self, _tx: &mut Tx) -> Result<(), Tx::Error> {
+//!         self.0.set_low().ok();
+//!         self.1.delay_ms(100u32);
+//!         self.0.set_high().ok();
+//!         self.1.delay_ms(200u32);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let tx = WakingTx::new(tx, PulseDtr(dtr, delay), idle_timer, Milliseconds(5_000));
+//! let (client, ingress) = atat::new(tx, timer, cooldown_timer, config, None);
+//! ```
+//!
+//! [`WakingTx`]: struct.WakingTx.html
+//! [`WakeHandler`]: trait.WakeHandler.html
+
+use embedded_hal::timer::CountDown;
+
+use crate::timer::Milliseconds;
+use crate::traits::AtTransport;
+
+/// Action waking the device up
+pub trait WakeHandler {
+    /// Wake the device up, before `tx` writes to it, and wait until it
+    /// accepts commands.
+    fn wake<Tx: AtTransport>(&mut self, tx: &mut Tx) -> Result<(), Tx::Error>;
+}
+
+/// A transport waking the device up with a [`WakeHandler`] after `idle` of
+/// nothing being written, see the [module documentation](index.html).
+///
+/// The device is woken up before it is first written to, as well.
+///
+/// [`WakeHandler`]: trait.WakeHandler.html
+pub struct WakingTx<Tx, W, T> {
+    tx: Tx,
+    handler: W,
+    timer: T,
+    idle: Milliseconds,
+    awake: bool,
+}
+
+impl<Tx, W, T> WakingTx<Tx, W, T>
+where
+    Tx: AtTransport,
+    W: WakeHandler,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    pub fn new(tx: Tx, handler: W, timer: T, idle: Milliseconds) -> Self {
+        WakingTx {
+            tx,
+            handler,
+            timer,
+            idle,
+            awake: false,
+        }
+    }
+
+    pub fn release(self) -> (Tx, W, T) {
+        (self.tx, self.handler, self.timer)
+    }
+}
+
+impl<Tx, W, T> AtTransport for WakingTx<Tx, W, T>
+where
+    Tx: AtTransport,
+    W: WakeHandler,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    type Error = Tx::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        if !self.awake || self.timer.wait().is_ok() {
+            #[cfg(feature = "logging")]
+            log::debug!("Waking the device up");
+            self.handler.wake(&mut self.tx)?;
+            self.awake = true;
+        }
+        let result = self.tx.write_all(buf);
+        self.timer.start(self.idle);
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let result = self.tx.flush();
+        self.timer.start(self.idle);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::VirtualClock;
+    use heapless::{consts, String};
+
+    struct TxMock(String<consts::U32>);
+
+    impl AtTransport for TxMock {
+        type Error = ();
+
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+            for c in buf {
+                self.0.push(*c as char)?;
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Wakes the device up by sending a dummy line
+    struct DummyLine;
+
+    impl WakeHandler for DummyLine {
+        fn wake<Tx: AtTransport>(&mut self, tx: &mut Tx) -> Result<(), Tx::Error> {
+            tx.write_all(b"\r\n")
+        }
+    }
+
+    #[test]
+    fn wake_when_idle() {
+        let clock = VirtualClock::new();
+        let mut tx = WakingTx::new(
+            TxMock(String::new()),
+            DummyLine,
+            clock.timer(),
+            Milliseconds(1000),
+        );
+
+        tx.write_all(b"AT\r\n").unwrap();
+        tx.flush().unwrap();
+        clock.advance(999);
+        tx.write_all(b"ATI\r\n").unwrap();
+        tx.flush().unwrap();
+        clock.advance(1000);
+        tx.write_all(b"AT\r\n").unwrap();
+
+        let (tx, _, _) = tx.release();
+        assert_eq!(tx.0, "\r\nAT\r\nATI\r\n\r\nAT\r\n");
+    }
+}
@@ -0,0 +1,471 @@
+//! Issuing commands from multiple tasks
+//!
+//! Rather than sharing `&mut Client` between tasks, the client can be handed
+//! to a single [`Worker`], which drains a queue of [`Request`]s. A task
+//! submits a command through its own [`ResponseSlot`], and later collects the
+//! response from it:
+//!
+//! ```ignore
+//! use atat::worker::{RequestQueue, ResponseSlot, Worker};
+//!
+//! static mut REQUESTS: RequestQueue = Queue(heapless::i::Queue::u8());
+//! static SOCKET_SLOT: ResponseSlot = ResponseSlot::new();
+//!
+//! let (mut requests, requests_c) = unsafe { REQUESTS.split() };
+//! let mut worker = Worker::new(client, requests_c);
+//!
+//! // Socket task
+//! block!(SOCKET_SLOT.submit(&mut requests, &GetSocketStatus { id: 0 }))?;
+//! let status = block!(SOCKET_SLOT.check(&GetSocketStatus { id: 0 }))?;
+//!
+//! // Worker task
+//! loop {
+//!     worker.poll();
+//! }
+//! ```
+//!
+//! The worker sends one command at a time, respecting the cooldown and the
+//! timeout of each command. The client has to be in
+//! `Mode::NonBlockingTimeout`, so that `poll` does not block, and a command
+//! that is not answered fails with `Error::Timeout` rather than holding up
+//! the queue. The request producer is shared by the submitting tasks, e.g. as
+//! a locked resource.
+//!
+//! [`Worker`]: struct.Worker.html
+//! [`Request`]: struct.Request.html
+//! [`ResponseSlot`]: struct.ResponseSlot.html
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embedded_hal::timer::CountDown;
+use heapless::spsc::{Consumer, Producer, Queue};
+use heapless::{consts, String};
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::timer::Milliseconds;
+use crate::traits::{AtTransport, AtatClient, AtatCmd, AtatResp};
+use crate::Mode;
+
+type RequestCapacity = consts::U4;
+
+/// Queue of the requests to a [`Worker`](struct.Worker.html)
+pub type RequestQueue = Queue<Request, RequestCapacity, u8>;
+pub type RequestProducer = Producer<'static, Request, RequestCapacity, u8>;
+pub type RequestConsumer = Consumer<'static, Request, RequestCapacity, u8>;
+
+type ResponseItem = Result<String<consts::U256>, Error>;
+
+/// A serialized command, and the slot to put its response into
+pub struct Request {
+    cmd: String<consts::U256>,
+    timeout_ms: u32,
+    cooldown: Option<Milliseconds>,
+    expects_response: bool,
+    slot: &'static ResponseSlot,
+}
+
+impl Request {
+    fn new<A: AtatCmd>(cmd: &A, slot: &'static ResponseSlot) -> Result<Self, Error> {
+        let cmd_string = cmd.try_as_string()?;
+        let mut s = String::new();
+        s.push_str(&cmd_string)
+            .map_err(|_| Error::CmdTooLong(cmd_string.len(), s.capacity()))?;
+        Ok(Request {
+            cmd: s,
+            timeout_ms: cmd.max_timeout().0,
+            cooldown: cmd.cooldown(),
+            expects_response: cmd.expects_response(),
+            slot,
+        })
+    }
+}
+
+/// The submitted command, as sent by the worker
+struct Submitted<'a>(&'a Request);
+
+/// The unparsed response to a [`Submitted`] command
+struct RawResponse(String<consts::U256>);
+
+impl AtatResp for RawResponse {}
+
+impl<'a> AtatCmd for Submitted<'a> {
+    type CommandLen = consts::U256;
+    type Response = RawResponse;
+
+    fn as_string(&self) -> String<Self::CommandLen> {
+        self.0.cmd.clone()
+    }
+
+    fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+        Ok(RawResponse(String::from(resp)))
+    }
+
+    fn max_timeout(&self) -> Milliseconds {
+        Milliseconds(self.0.timeout_ms)
+    }
+
+    fn cooldown(&self) -> Option<Milliseconds> {
+        self.0.cooldown
+    }
+
+    fn expects_response(&self) -> bool {
+        self.0.expects_response
+    }
+}
+
+const FREE: u8 = 0;
+const PENDING: u8 = 1;
+const READY: u8 = 2;
+
+/// Place for the response to a submitted command, owned by the submitting
+/// task.
+///
+/// A slot holds the response to one command at a time.
+pub struct ResponseSlot {
+    state: AtomicU8,
+    response: UnsafeCell<Option<ResponseItem>>,
+}
+
+// The response is only written by the worker while the slot is pending, and
+// only read by the owner once it is ready.
+unsafe impl Sync for ResponseSlot {}
+
+impl ResponseSlot {
+    pub const fn new() -> Self {
+        ResponseSlot {
+            state: AtomicU8::new(FREE),
+            response: UnsafeCell::new(None),
+        }
+    }
+
+    /// Whether a command has been submitted, and its response is not yet
+    /// collected.
+    pub fn is_pending(&self) -> bool {
+        self.state.load(Ordering::Acquire) != FREE
+    }
+
+    /// Submit `cmd` to the worker through `requests`.
+    ///
+    /// Returns `WouldBlock` if the queue is full, or the response to the
+    /// previous command has not been collected yet.
+    pub fn submit<A: AtatCmd>(
+        &'static self,
+        requests: &mut RequestProducer,
+        cmd: &A,
+    ) -> nb::Result<(), Error> {
+        if self.is_pending() || !requests.ready() {
+            return Err(nb::Error::WouldBlock);
+        }
+        let request = Request::new(cmd, self)?;
+        self.state.store(PENDING, Ordering::Release);
+        if requests.enqueue(request).is_err() {
+            self.state.store(FREE, Ordering::Release);
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+
+    /// Collect the response to the submitted command `cmd`.
+    ///
+    /// Returns `Error::Aborted` if no command was submitted.
+    pub fn check<A: AtatCmd>(&self, cmd: &A) -> nb::Result<A::Response, Error> {
+        match self.state.load(Ordering::Acquire) {
+            READY => {}
+            PENDING => return Err(nb::Error::WouldBlock),
+            _ => return Err(nb::Error::Other(Error::Aborted)),
+        }
+        let response = unsafe { (*self.response.get()).take() };
+        self.state.store(FREE, Ordering::Release);
+        match response {
+            Some(Ok(resp)) => Ok(cmd.parse(&resp)?),
+            Some(Err(e)) => Err(nb::Error::Other(e)),
+            None => Err(nb::Error::Other(Error::Aborted)),
+        }
+    }
+
+    fn fulfill(&self, response: ResponseItem) {
+        unsafe { *self.response.get() = Some(response) };
+        self.state.store(READY, Ordering::Release);
+    }
+}
+
+impl Default for ResponseSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends the requested commands through the client, one at a time, see the
+/// [module documentation](index.html).
+pub struct Worker<Tx, T, C = T>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    C: CountDown,
+{
+    client: Client<Tx, T, C>,
+    requests: RequestConsumer,
+    /// The request that has been sent, and is awaiting its response
+    current: Option<Request>,
+}
+
+impl<Tx, T, C> Worker<Tx, T, C>
+where
+    Tx: AtTransport,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    C: CountDown,
+    C::Time: From<Milliseconds>,
+{
+    /// Create a worker draining `requests` through `client`.
+    ///
+    /// # Panics
+    ///
+    /// If the client is not in `Mode::NonBlockingTimeout`.
+    pub fn new(client: Client<Tx, T, C>, requests: RequestConsumer) -> Self {
+        assert!(
+            client.get_mode() == Mode::NonBlockingTimeout,
+            "The client of a worker has to be in Mode::NonBlockingTimeout"
+        );
+        Worker {
+            client,
+            requests,
+            current: None,
+        }
+    }
+
+    /// The client, e.g. to check for URCs. Its mode must not be changed.
+    pub fn client(&mut self) -> &mut Client<Tx, T, C> {
+        &mut self.client
+    }
+
+    /// Whether no request is in progress or queued.
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none() && !self.requests.ready()
+    }
+
+    /// Send the next request, or check for the response to the current one.
+    pub fn poll(&mut self) {
+        let result = match self.current {
+            Some(ref request) => self.client.check_response(&Submitted(request)),
+            None => match self.requests.dequeue() {
+                Some(request) => {
+                    let request = self.current.get_or_insert(request);
+                    self.client.send(&Submitted(request))
+                }
+                None => return,
+            },
+        };
+
+        let response = match result {
+            Ok(RawResponse(resp)) => Ok(resp),
+            Err(nb::Error::WouldBlock) => return,
+            Err(nb::Error::Other(e)) => Err(e),
+        };
+        if let Some(request) = self.current.take() {
+            request.slot.fulfill(response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::queues::{ComQueue, DataQueue, ResMock, ResQueue, StaticQueue, UrcQueue};
+    use crate::testing::VirtualClock;
+    use crate::Config;
+    use core::cell::RefCell;
+    use embedded_hal::serial;
+
+    struct TxMock<'a> {
+        s: &'a RefCell<String<consts::U64>>,
+    }
+
+    impl<'a> serial::Write<u8> for TxMock<'a> {
+        type Error = ();
+
+        fn write(&mut self, c: u8) -> nb::Result<(), Self::Error> {
+            self.s
+                .borrow_mut()
+                .push(c as char)
+                .map_err(nb::Error::Other)
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    struct Cmd(&'static str);
+
+    #[derive(Debug, PartialEq)]
+    struct Resp(String<consts::U32>);
+
+    impl AtatResp for Resp {}
+
+    impl AtatCmd for Cmd {
+        type CommandLen = consts::U32;
+        type Response = Resp;
+
+        fn as_string(&self) -> String<Self::CommandLen> {
+            String::from(self.0)
+        }
+
+        fn parse(&self, resp: &str) -> Result<Self::Response, Error> {
+            Ok(Resp(String::from(resp)))
+        }
+    }
+
+    #[test]
+    fn requests_from_two_tasks() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        static mut REQUESTS: RequestQueue = Queue(heapless::i::Queue::u8());
+        static SOCKET: ResponseSlot = ResponseSlot::new();
+        static SMS: ResponseSlot = ResponseSlot::new();
+
+        let (res_p, res_c) = unsafe { RES_Q.split() };
+        let (_urc_p, urc_c) = unsafe { URC_Q.split() };
+        let (com_p, com_c) = unsafe { COM_Q.split() };
+        let mut res_p = ResMock::new(res_p, com_c);
+        let (_data_p, data_c) = unsafe { DATA_Q.split() };
+        let (mut requests, requests_c) = unsafe { REQUESTS.split() };
+
+        let clock = VirtualClock::new();
+        let sent = RefCell::new(String::new());
+        let tx = TxMock { s: &sent };
+        let client = Client::new(
+            tx,
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            clock.timer(),
+            clock.timer(),
+            Config::new(Mode::NonBlockingTimeout).cmd_cooldown(Milliseconds(0)),
+        );
+        let mut worker = Worker::new(client, requests_c);
+
+        assert_eq!(
+            SOCKET.check(&Cmd("AT+USOCR=6\r\n")),
+            Err(nb::Error::Other(Error::Aborted))
+        );
+        SOCKET
+            .submit(&mut requests, &Cmd("AT+USOCR=6\r\n"))
+            .unwrap();
+        SMS.submit(&mut requests, &Cmd("AT+CMGF=1\r\n")).unwrap();
+        assert_eq!(
+            SOCKET.submit(&mut requests, &Cmd("AT+USOCR=6\r\n")),
+            Err(nb::Error::WouldBlock)
+        );
+
+        worker.poll();
+        worker.poll();
+        assert_eq!(*sent.borrow(), "AT+USOCR=6\r\n");
+        assert_eq!(
+            SOCKET.check(&Cmd("AT+USOCR=6\r\n")),
+            Err(nb::Error::WouldBlock)
+        );
+
+        res_p.enqueue(Ok(String::from("+USOCR: 0"))).unwrap();
+        worker.poll();
+        worker.poll();
+        res_p.enqueue(Ok(String::new())).unwrap();
+        worker.poll();
+        assert!(worker.is_idle());
+        assert_eq!(*sent.borrow(), "AT+USOCR=6\r\nAT+CMGF=1\r\n");
+
+        assert_eq!(SMS.check(&Cmd("AT+CMGF=1\r\n")), Ok(Resp(String::new())));
+        assert_eq!(
+            SOCKET.check(&Cmd("AT+USOCR=6\r\n")),
+            Ok(Resp(String::from("+USOCR: 0")))
+        );
+        assert!(!SOCKET.is_pending());
+    }
+    #[test]
+    fn request_timing_out() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        static mut REQUESTS: RequestQueue = Queue(heapless::i::Queue::u8());
+        static SLOT: ResponseSlot = ResponseSlot::new();
+        static NEXT: ResponseSlot = ResponseSlot::new();
+
+        let (_res_p, res_c) = unsafe { RES_Q.split() };
+        let (_urc_p, urc_c) = unsafe { URC_Q.split() };
+        let (com_p, _com_c) = unsafe { COM_Q.split() };
+        let (_data_p, data_c) = unsafe { DATA_Q.split() };
+        let (mut requests, requests_c) = unsafe { REQUESTS.split() };
+
+        let clock = VirtualClock::new();
+        let sent = RefCell::new(String::new());
+        let client = Client::new(
+            TxMock { s: &sent },
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            clock.timer(),
+            clock.timer(),
+            Config::new(Mode::NonBlockingTimeout).cmd_cooldown(Milliseconds(0)),
+        );
+        let mut worker = Worker::new(client, requests_c);
+
+        SLOT.submit(&mut requests, &Cmd("AT+COPS=0\r\n")).unwrap();
+        NEXT.submit(&mut requests, &Cmd("AT+CSQ\r\n")).unwrap();
+        worker.poll();
+        clock.advance(999);
+        worker.poll();
+        assert_eq!(
+            SLOT.check(&Cmd("AT+COPS=0\r\n")),
+            Err(nb::Error::WouldBlock)
+        );
+
+        clock.advance(1);
+        worker.poll();
+        assert_eq!(
+            SLOT.check(&Cmd("AT+COPS=0\r\n")),
+            Err(nb::Error::Other(Error::Timeout))
+        );
+
+        // The queue is not held up
+        worker.poll();
+        assert_eq!(*sent.borrow(), "AT+COPS=0\r\nAT+CSQ\r\n");
+        assert!(NEXT.is_pending());
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_blocking_client() {
+        static mut RES_Q: ResQueue = ResQueue::new();
+        static mut URC_Q: UrcQueue = UrcQueue::NEW;
+        static mut COM_Q: ComQueue = ComQueue::NEW;
+        static mut DATA_Q: DataQueue = DataQueue::NEW;
+        static mut REQUESTS: RequestQueue = Queue(heapless::i::Queue::u8());
+
+        let (_res_p, res_c) = unsafe { RES_Q.split() };
+        let (_urc_p, urc_c) = unsafe { URC_Q.split() };
+        let (com_p, _com_c) = unsafe { COM_Q.split() };
+        let (_data_p, data_c) = unsafe { DATA_Q.split() };
+        let (_requests, requests_c) = unsafe { REQUESTS.split() };
+
+        let clock = VirtualClock::new();
+        let sent = RefCell::new(String::new());
+        let client = Client::new(
+            TxMock { s: &sent },
+            res_c,
+            urc_c,
+            com_p,
+            data_c,
+            clock.timer(),
+            clock.timer(),
+            Config::new(Mode::NonBlocking),
+        );
+        Worker::new(client, requests_c);
+    }
+}
@@ -1,10 +1,17 @@
 use crate::proc_macro::TokenStream;
-use crate::proc_macro2::Literal;
+use crate::proc_macro2::{Literal, Span, TokenStream as TokenStream2};
 
 use quote::{format_ident, quote};
-use syn::{Attribute, Data, DataStruct, DeriveInput, Fields, FieldsNamed, Ident, Result};
+use syn::{
+    Attribute, Data, DataEnum, DataStruct, DeriveInput, ExprPath, Fields, FieldsNamed,
+    GenericParam, Generics, Ident, Lifetime, LifetimeDef, Result, Type, Variant,
+};
 
-use crate::helpers::{get_field_names, get_ident, get_lit, get_name_ident_lit};
+use crate::helpers::{get_encoding, get_lit, get_name_ident_lit, get_type, get_with, has_flag};
+use crate::len::{
+    consts, field_len, fields_len_bounds, generate_len_output, generic_sum, is_generic, sum,
+    with_bounds,
+};
 
 pub fn atat_cmd(item: DeriveInput) -> TokenStream {
     match item.data {
@@ -14,30 +21,255 @@ pub fn atat_cmd(item: DeriveInput) -> TokenStream {
                 DataStruct {
                     fields: Fields::Named(fields),
                     ..
-                } => generate_cmd_output(&item.ident, &item.generics, &at_cmd_attr, Some(&fields)),
+                } => generate_cmd_output(
+                    &item.ident,
+                    &item.generics,
+                    &at_cmd_attr,
+                    CmdArgs::Struct(Some(&fields), &item.attrs),
+                ),
                 DataStruct {
                     fields: Fields::Unit,
                     ..
-                } => {
-                    let at_cmd_attr = get_cmd_response(&item.attrs).unwrap();
-                    generate_cmd_output(&item.ident, &item.generics, &at_cmd_attr, None)
-                }
+                } => generate_cmd_output(
+                    &item.ident,
+                    &item.generics,
+                    &at_cmd_attr,
+                    CmdArgs::Struct(None, &item.attrs),
+                ),
                 _ => panic!("Cannot handle unnamed struct fields"),
             }
         }
+        Data::Enum(DataEnum { variants, .. }) => {
+            let at_cmd_attr = get_cmd_response(&item.attrs).unwrap();
+            let variants: Vec<Variant> = variants.into_iter().collect();
+            generate_cmd_output(
+                &item.ident,
+                &item.generics,
+                &at_cmd_attr,
+                CmdArgs::Enum(&variants),
+            )
+        }
         _ => {
-            panic!("AtatCmd can only be applied to structs!");
+            panic!("AtatCmd can only be applied to structs and enums!");
         }
     }
 }
 
+/// The source of the arguments of a command
+enum CmdArgs<'a> {
+    /// The fields of a struct, if any, and its attributes
+    Struct(Option<&'a FieldsNamed>, &'a [Attribute]),
+    /// The variants of an enum, each being a parameter set of the command,
+    /// e.g. the tag/value forms of `+UPSD`
+    Enum(&'a [Variant]),
+}
+
+/// An argument of a command, as serialized
+struct Arg {
+    /// Name of the field, or of the constant
+    name: String,
+    /// Expression referencing the value
+    value: TokenStream2,
+    /// Type level max length of the value
+    len: TokenStream2,
+    /// Bounds on the type of the value, required if it is generic
+    bounds: Vec<TokenStream2>,
+}
+
+/// The value `value` of type `ty`, wrapped in a type serializing it with the
+/// function `path`, given by `#[at_arg(serialize_with = "path")]`.
+fn serialize_with(
+    name: &Ident,
+    generics: &Generics,
+    path: &ExprPath,
+    ty: &Type,
+    value: TokenStream2,
+) -> TokenStream2 {
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+    let mut wrapper_generics = generics.clone();
+    wrapper_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeDef::new(Lifetime::new("'__a", Span::call_site()))),
+    );
+    let (wrapper_impl_generics, wrapper_ty_generics, _) = wrapper_generics.split_for_impl();
+    quote! {
+        {
+            struct __SerializeWith #wrapper_impl_generics #where_clause {
+                value: &'__a #ty,
+                phantom: serde::export::PhantomData<#name #ty_generics>,
+            }
+            impl #wrapper_impl_generics serde::Serialize for __SerializeWith #wrapper_ty_generics #where_clause {
+                fn serialize<__S>(
+                    &self,
+                    serializer: __S,
+                ) -> serde::export::Result<__S::Ok, __S::Error>
+                where
+                    __S: serde::Serializer,
+                {
+                    #path(self.value, serializer)
+                }
+            }
+            &__SerializeWith {
+                value: #value,
+                phantom: serde::export::PhantomData::<#name #ty_generics>,
+            }
+        }
+    }
+}
+
+/// The arguments of a struct or enum variant, in the order of their
+/// positions, with the field values referenced by `value_of`.
+///
+/// A struct or variant can add a constant argument with
+/// `#[at_arg(value = 1)]`, or an empty argument with `#[at_arg(skip)]`, at the
+/// `position` given along with it (0 by default).
+fn get_args(
+    name: &Ident,
+    generics: &Generics,
+    fields: Option<&FieldsNamed>,
+    attrs: &[Attribute],
+    value_of: impl Fn(&Ident) -> TokenStream2,
+) -> Vec<Arg> {
+    let position = |attr: &Attribute| -> usize {
+        get_name_ident_lit(&attr.tokens, "position")
+            .ok()
+            .map(|p| p.parse().expect("Position argument must be an integer!"))
+            .unwrap_or(0)
+    };
+
+    let mut args: Vec<(usize, Arg)> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("at_arg"))
+        .filter_map(|attr| {
+            let arg = if has_flag(&attr.tokens, "skip") {
+                // Serialized as nothing, leaving the position empty
+                Arg {
+                    name: String::from("skip"),
+                    value: quote! {
+                        {
+                            struct __Skip;
+                            impl serde::Serialize for __Skip {
+                                fn serialize<__S>(
+                                    &self,
+                                    serializer: __S,
+                                ) -> serde::export::Result<__S::Ok, __S::Error>
+                                where
+                                    __S: serde::Serializer,
+                                {
+                                    serializer.serialize_bytes(b"")
+                                }
+                            }
+                            &__Skip
+                        }
+                    },
+                    len: consts(0),
+                    bounds: vec![],
+                }
+            } else {
+                let value = get_name_ident_lit(&attr.tokens, "value").ok()?;
+                let lit: syn::Lit = syn::parse_str(&value).expect("Invalid constant argument!");
+                Arg {
+                    name: String::from("value"),
+                    value: quote! { &#lit },
+                    len: consts(value.len()),
+                    bounds: vec![],
+                }
+            };
+            Some((position(attr), arg))
+        })
+        .collect();
+
+    for field in fields.iter().flat_map(|f| f.named.iter()) {
+        let ident = field.ident.as_ref().unwrap();
+        let value = value_of(ident);
+        let ty = &field.ty;
+        let mut bounds = fields_len_bounds(core::iter::once(field));
+        let value = match (get_with(field, "serialize_with"), get_encoding(field)) {
+            (Some(path), _) => serialize_with(name, generics, &path, ty, value),
+            (None, Some(encoding)) => quote! { &#encoding(#value) },
+            (None, None) => {
+                bounds.push(quote! { #ty: serde::Serialize });
+                value
+            }
+        };
+        let arg = Arg {
+            name: ident.to_string(),
+            value,
+            len: field_len(field),
+            bounds,
+        };
+        let pos = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("at_arg"))
+            .map_or(0, position);
+        args.push((pos, arg));
+    }
+
+    args.sort_by_key(|(pos, _)| *pos);
+    args.into_iter().map(|(_, arg)| arg).collect()
+}
+
+/// Serialization of `args`, as a struct named `name_str`.
+fn serialize_args(name_str: &str, args: &[Arg]) -> TokenStream2 {
+    let len = args.len();
+    let names = args.iter().map(|arg| &arg.name);
+    let values = args.iter().map(|arg| &arg.value);
+    quote! {
+        let mut serde_state = match serde::Serializer::serialize_struct(
+            serializer,
+            #name_str,
+            #len,
+        ) {
+            serde::export::Ok(val) => val,
+            serde::export::Err(err) => {
+                return serde::export::Err(err);
+            }
+        };
+
+        #(
+            match serde::ser::SerializeStruct::serialize_field(
+                &mut serde_state,
+                #names,
+                #values,
+            ) {
+                serde::export::Ok(val) => val,
+                serde::export::Err(err) => {
+                    return serde::export::Err(err);
+                }
+            };
+        )*
+
+        serde::ser::SerializeStruct::end(serde_state)
+    }
+}
+
+/// Type level max lengths of `args`, and of the separating commas.
+fn args_lens(args: &[Arg]) -> Vec<TokenStream2> {
+    let mut lens: Vec<TokenStream2> = args.iter().map(|arg| arg.len.clone()).collect();
+    if !lens.is_empty() {
+        lens.push(consts(lens.len() - 1));
+    }
+    lens
+}
+
 #[derive(Debug)]
 struct AtCmdAttr {
     cmd: Literal,
-    resp: Ident,
+    resp: Type,
     timeout_ms: Option<u32>,
+    cooldown_ms: Option<u32>,
+    response_len: Option<usize>,
+    stream: Option<String>,
+    payload: Option<String>,
+    max_cmd_len: Option<usize>,
     abortable: Option<bool>,
     force_receive_state: Option<bool>,
+    expects_response: Option<bool>,
+    catalog: Option<bool>,
+    resp_prefix: Option<String>,
+    parse_raw: Option<bool>,
+    parse_text: Option<bool>,
     value_sep: bool,
     cmd_prefix: String,
     termination: String,
@@ -53,21 +285,37 @@ fn get_parsed_ident<T: core::str::FromStr>(attr: &Attribute, needle: &str) -> Op
     }
 }
 
+/// The value of the string literal argument `needle`, with any escape
+/// sequences (e.g. `"\x1a"`) resolved.
+fn get_parsed_str(attr: &Attribute, needle: &str) -> Option<String> {
+    get_name_ident_lit(&attr.tokens, needle)
+        .ok()
+        .and_then(|lit| syn::parse_str::<syn::LitStr>(&lit).ok())
+        .map(|lit| lit.value())
+}
+
 fn get_cmd_response(attrs: &[Attribute]) -> Result<AtCmdAttr> {
     if let Some(attr) = attrs.iter().find(|attr| attr.path.is_ident("at_cmd")) {
         Ok(AtCmdAttr {
             cmd: get_lit(&attr.tokens)?,
-            resp: get_ident(&attr.tokens)?,
+            resp: get_type(&attr.tokens)?,
             timeout_ms: get_parsed_ident(&attr, "timeout_ms"),
+            cooldown_ms: get_parsed_ident(&attr, "cooldown_ms"),
+            response_len: get_parsed_ident(&attr, "response_len"),
+            stream: get_parsed_str(&attr, "stream"),
+            payload: get_parsed_str(&attr, "payload"),
+            max_cmd_len: get_parsed_ident(&attr, "max_cmd_len"),
             abortable: get_parsed_ident(&attr, "abortable"),
             force_receive_state: get_parsed_ident(&attr, "force_receive_state"),
+            expects_response: get_parsed_ident(&attr, "expects_response"),
+            catalog: get_parsed_ident(&attr, "catalog"),
+            resp_prefix: get_parsed_str(&attr, "resp_prefix"),
+            parse_raw: get_parsed_ident(&attr, "parse_raw"),
+            parse_text: get_parsed_ident(&attr, "parse_text"),
             value_sep: get_parsed_ident(&attr, "value_sep").unwrap_or_else(|| true),
-            cmd_prefix: get_parsed_ident(&attr, "cmd_prefix")
-                .unwrap_or_else(|| String::from("AT"))
-                .replace("\"", ""),
-            termination: get_parsed_ident(&attr, "termination")
-                .unwrap_or_else(|| String::from("\r\n"))
-                .replace("\"", ""),
+            cmd_prefix: get_parsed_str(&attr, "cmd_prefix").unwrap_or_else(|| String::from("AT")),
+            termination: get_parsed_str(&attr, "termination")
+                .unwrap_or_else(|| String::from("\r\n")),
         })
     } else {
         panic!("Failed to find non-optional at_cmd attribute!",)
@@ -78,20 +326,195 @@ fn generate_cmd_output(
     name: &Ident,
     generics: &syn::Generics,
     attr: &AtCmdAttr,
-    fields: Option<&FieldsNamed>,
+    args: CmdArgs,
 ) -> TokenStream {
     let name_str = &name.to_string();
     let cmd = &attr.cmd;
     let response = &attr.resp;
 
-    let (field_names, _, field_names_str) = get_field_names(fields);
-    let len = field_names.len();
+    // The serialization of the arguments, whether there are any, the max
+    // lengths adding up to their length, the names of the arguments listed
+    // in the catalog, and the bounds on their types
+    let (serialize, has_args, lens, arg_names, mut bounds): (_, _, _, _, Vec<_>) = match args {
+        CmdArgs::Struct(fields, attrs) => {
+            let args = get_args(name, generics, fields, attrs, |name| quote! { &self.#name });
+            (
+                serialize_args(name_str, &args),
+                !args.is_empty(),
+                args_lens(&args),
+                args.iter().map(|arg| arg.name.clone()).collect(),
+                args.into_iter().flat_map(|arg| arg.bounds).collect(),
+            )
+        }
+        CmdArgs::Enum(variants) => {
+            let arms = variants.iter().map(|variant| {
+                let variant_name = &variant.ident;
+                let (pattern, fields) = match &variant.fields {
+                    Fields::Named(fields) => {
+                        let names = fields.named.iter().map(|f| &f.ident);
+                        (
+                            quote! { #name::#variant_name { #(#names),* } },
+                            Some(fields),
+                        )
+                    }
+                    Fields::Unit => (quote! { #name::#variant_name }, None),
+                    Fields::Unnamed(_) => panic!("Cannot handle unnamed variant fields"),
+                };
+                let args = get_args(
+                    name,
+                    generics,
+                    fields,
+                    &variant.attrs,
+                    |name| quote! { #name },
+                );
+                let body = serialize_args(name_str, &args);
+                (quote! { #pattern => { #body } }, args)
+            });
+            let (arms, args): (Vec<_>, Vec<_>) = arms.unzip();
+            // The sum of the variants is used as an upper bound, as there is
+            // no way to express the maximum of the variants
+            let lens = args.iter().flat_map(|args| args_lens(args)).collect();
+            if let Some(true) = attr.catalog {
+                panic!("The catalog cannot describe the parameter sets of an enum!");
+            }
+            (
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                },
+                args.iter().any(|args| !args.is_empty()),
+                lens,
+                vec![],
+                args.into_iter()
+                    .flatten()
+                    .flat_map(|arg| arg.bounds)
+                    .collect(),
+            )
+        }
+    };
+    let termination = &attr.termination;
+
+    let value_sep = &attr.value_sep;
+    let cmd_prefix = &attr.cmd_prefix;
+    let sub_len = syn::parse_str::<syn::LitStr>(&cmd.to_string())
+        .expect("AT Command must be a string literal!")
+        .value()
+        .len();
+    let subcmd_len = format_ident!("U{}", sub_len);
+
+    // The command length is the maximum length of the arguments, plus prefix,
+    // command, value separator and termination
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let overhead =
+        cmd_prefix.len() + sub_len + if *value_sep && has_args { 1 } else { 0 } + termination.len();
+
+    // Lengths depending on type parameters can only be added up given the
+    // bounds required by the sums. As these bounds cannot be expressed on
+    // the `AtatLen` of the command itself, its arguments are added up again
+    let (args_len, cmd_len, len_generics, generics) = if is_generic(generics) {
+        let (args_len, args_bounds) = generic_sum(&lens, generics);
+        bounds.extend(args_bounds);
+        let len_generics = with_bounds(generics, bounds);
+        let mut cmd_lens = lens;
+        cmd_lens.push(consts(overhead));
+        let (cmd_len, cmd_bounds) = generic_sum(&cmd_lens, generics);
+        let generics = with_bounds(&len_generics, cmd_bounds);
+        (args_len, cmd_len, len_generics, generics)
+    } else {
+        let cmd_len = sum(vec![
+            quote! { <#name #ty_generics as atat::AtatLen>::Len },
+            consts(overhead),
+        ]);
+        (sum(lens), cmd_len, generics.clone(), generics.clone())
+    };
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let timeout = if let Some(timeout_ms) = &attr.timeout_ms {
         quote! {
-            fn max_timeout_ms(&self) -> u32 {
-                #timeout_ms
+            fn max_timeout(&self) -> atat::Milliseconds {
+                atat::Milliseconds(#timeout_ms)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let cooldown = if let Some(cooldown_ms) = &attr.cooldown_ms {
+        quote! {
+            fn cooldown(&self) -> Option<atat::Milliseconds> {
+                Some(atat::Milliseconds(#cooldown_ms))
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let max_cmd_len = if let Some(max_cmd_len) = &attr.max_cmd_len {
+        quote! {
+            fn max_cmd_len(&self) -> Option<usize> {
+                Some(#max_cmd_len)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let response_len = if let Some(response_len) = &attr.response_len {
+        quote! {
+            fn max_response_len(&self) -> Option<usize> {
+                Some(#response_len)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let stream = if let Some(stream) = &attr.stream {
+        quote! {
+            fn stream_prefix(&self) -> Option<&'static str> {
+                Some(#stream)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let payload = if let Some(payload) = &attr.payload {
+        quote! {
+            fn payload_prefix(&self) -> Option<&'static str> {
+                Some(#payload)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // The information text answering e.g. `AT+CSQ` starts with `+CSQ:`
+    let response_prefix = attr.resp_prefix.clone().or_else(|| {
+        let cmd_str = cmd.to_string().replace("\"", "");
+        let name: String = cmd_str
+            .chars()
+            .enumerate()
+            .take_while(|(i, c)| {
+                if *i == 0 {
+                    *c == '+'
+                } else {
+                    c.is_ascii_alphanumeric()
+                }
+            })
+            .map(|(_, c)| c)
+            .collect();
+        if name.len() > 1 {
+            Some(name)
+        } else {
+            None
+        }
+    });
+    let response_prefix = if let Some(prefix) = response_prefix {
+        quote! {
+            fn response_prefix(&self) -> Option<&'static str> {
+                Some(#prefix)
             }
         }
     } else {
@@ -118,45 +541,144 @@ fn generate_cmd_output(
         quote! {}
     };
 
-    let termination = &attr.termination;
+    let expects_response = if let Some(expects_response) = &attr.expects_response {
+        quote! {
+            fn expects_response(&self) -> bool {
+                #expects_response
+            }
+        }
+    } else {
+        quote! {}
+    };
 
-    let value_sep = &attr.value_sep;
-    let cmd_prefix = &attr.cmd_prefix;
-    let sub_len = cmd.to_string().replace("\"", "").len();
-    let subcmd_len = format_ident!("U{}", sub_len);
-    // let cmd_len = format_ident!("U{}", calculate_cmd_len(sub_len, fields, termination.len()));
+    let catalog = if let Some(true) = &attr.catalog {
+        let cmd_str = cmd.to_string().replace("\"", "");
+        let args: Vec<String> = arg_names.iter().map(|n| format!("<{}>", n)).collect();
+        let syntax = format!(
+            "{}{}{}{}",
+            attr.cmd_prefix,
+            cmd_str,
+            if !args.is_empty() && attr.value_sep {
+                "="
+            } else {
+                ""
+            },
+            args.join(",")
+        );
+        let timeout_ms = attr.timeout_ms.unwrap_or(1000);
+        let response_str = quote!(#response).to_string().replace(' ', "");
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics #name #ty_generics #where_clause {
+                pub const CMD_INFO: atat::CmdInfo = atat::CmdInfo {
+                    name: #name_str,
+                    syntax: #syntax,
+                    timeout_ms: #timeout_ms,
+                    response: #response_str,
+                };
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let parse = match (&attr.resp_prefix, &attr.parse_raw) {
+        (Some(prefix), _) => quote! {
+            let resp = resp.trim_start();
+            let resp = resp
+                .strip_prefix(#prefix)
+                .map_or(resp, |rest| rest.trim_start_matches(':'));
+            serde_at::from_str_raw::<#response>(resp)
+        },
+        (None, Some(true)) => quote! {
+            serde_at::from_str_raw::<#response>(resp)
+        },
+        _ => quote! {
+            serde_at::from_str::<#response>(resp)
+        },
+    };
+
+    let context = match &attr.resp_prefix {
+        Some(prefix) => quote! {
+            atat::ErrorContext::response(resp).expected(#prefix)
+        },
+        None => quote! {
+            atat::ErrorContext::response(resp)
+        },
+    };
+
+    let parse = if let Some(true) = &attr.parse_text {
+        quote! {
+            <#response as atat::FromText>::from_text(resp)
+        }
+    } else {
+        quote! {
+            { #parse }.map_err(|e| {
+                atat::Error::ParseString(#context)
+            })
+        }
+    };
+
+    let len_impl = generate_len_output(name, &len_generics, args_len);
 
     TokenStream::from(quote! {
         #[automatically_derived]
         impl #impl_generics atat::AtatCmd for #name #ty_generics #where_clause {
             type Response = #response;
-            type CommandLen = heapless::consts::U2048;
+            type CommandLen = #cmd_len;
 
             fn as_string(&self) -> heapless::String<Self::CommandLen> {
+                match self.try_as_string() {
+                    Ok(s) => s,
+                    Err(_) => panic!("Failed to serialize command")
+                }
+            }
+
+            fn try_as_string(&self) -> core::result::Result<heapless::String<Self::CommandLen>, atat::Error> {
                 let s: heapless::String<heapless::consts::#subcmd_len> = heapless::String::from(#cmd);
-                match serde_at::to_string(self, s, serde_at::SerializeOptions {
+                serde_at::to_string(self, s, serde_at::SerializeOptions {
                     value_sep: #value_sep,
                     cmd_prefix: #cmd_prefix,
                     termination: #termination
-                }) {
-                    Ok(s) => s,
-                    Err(_) => panic!("Failed to serialize command")
-                }
+                })
+                .map_err(|e| match e {
+                    serde_at::ser::Error::BufferFull => {
+                        let max_len = heapless::String::<Self::CommandLen>::new().capacity();
+                        atat::Error::CmdTooLong(max_len + 1, max_len)
+                    }
+                    _ => atat::Error::Serialize,
+                })
             }
 
             fn parse(&self, resp: &str) -> core::result::Result<#response, atat::Error> {
-                serde_at::from_str::<#response>(resp).map_err(|e| {
-                    atat::Error::ParseString
-                })
+                #parse
             }
 
             #timeout
 
+            #cooldown
+
+            #max_cmd_len
+
+            #response_len
+
+            #stream
+
+            #payload
+
+            #response_prefix
+
             #abortable
 
             #force_receive
+
+            #expects_response
         }
 
+        #catalog
+
+        #len_impl
+
         #[automatically_derived]
         impl #impl_generics serde::Serialize for #name #ty_generics #where_clause {
             fn serialize<S>(
@@ -166,31 +688,7 @@ fn generate_cmd_output(
             where
                 S: serde::Serializer,
             {
-                let mut serde_state = match serde::Serializer::serialize_struct(
-                    serializer,
-                    #name_str,
-                    #len,
-                ) {
-                    serde::export::Ok(val) => val,
-                    serde::export::Err(err) => {
-                        return serde::export::Err(err);
-                    }
-                };
-
-                #(
-                    match serde::ser::SerializeStruct::serialize_field(
-                        &mut serde_state,
-                        #field_names_str,
-                        &self.#field_names,
-                    ) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
-                        }
-                    };
-                )*
-
-                serde::ser::SerializeStruct::end(serde_state)
+                #serialize
             }
         }
 
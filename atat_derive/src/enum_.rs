@@ -0,0 +1,209 @@
+use crate::proc_macro::TokenStream;
+use crate::proc_macro2::{Literal, Span};
+
+use quote::quote;
+use syn::{
+    punctuated::Punctuated, token::Comma, Attribute, Data, DataEnum, DeriveInput, Fields,
+    GenericParam, Ident, Lifetime, LifetimeDef, Variant,
+};
+
+use crate::helpers::get_name_ident_lit;
+use crate::len::{consts, generate_len_output};
+
+pub fn atat_enum(item: DeriveInput) -> TokenStream {
+    match item.data {
+        Data::Enum(DataEnum { variants, .. }) => {
+            let (variant_names, variant_strs) = get_variants(&item.attrs, &variants, "AtatEnum");
+            generate_enum_output(&item.ident, &item.generics, &variant_names, &variant_strs)
+        }
+        _ => {
+            panic!("AtatEnum can only be applied to enums!");
+        }
+    }
+}
+
+/// Names of the unit `variants`, and the strings representing them, as
+/// renamed by `#[at_enum(rename_all = "..")]` and `#[at_arg(rename = "..")]`.
+pub fn get_variants(
+    attrs: &[Attribute],
+    variants: &Punctuated<Variant, Comma>,
+    derive: &str,
+) -> (Vec<Ident>, Vec<String>) {
+    let rename_all = get_rename(attrs, "at_enum", "rename_all");
+    variants
+        .iter()
+        .map(|variant| {
+            if let Fields::Unit = variant.fields {
+            } else {
+                panic!("{} only supports unit variants", derive);
+            }
+            let name = variant.ident.clone();
+            let s = get_rename(&variant.attrs, "at_arg", "rename")
+                .unwrap_or_else(|| apply_rename_all(&name.to_string(), rename_all.as_deref()));
+            (name, s)
+        })
+        .unzip()
+}
+
+fn get_rename(attrs: &[Attribute], path: &str, needle: &str) -> Option<String> {
+    attrs
+        .iter()
+        .find(|attr| attr.path.is_ident(path))
+        .and_then(|attr| get_name_ident_lit(&attr.tokens, needle).ok())
+        .map(|s| s.replace("\"", ""))
+}
+
+fn split_words(name: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut current = String::new();
+    for c in name.chars() {
+        if c.is_uppercase() && !current.is_empty() {
+            words.push(current);
+            current = String::new();
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn apply_rename_all(name: &str, rule: Option<&str>) -> String {
+    match rule {
+        None | Some("PascalCase") => String::from(name),
+        Some("lowercase") => name.to_lowercase(),
+        Some("UPPERCASE") => name.to_uppercase(),
+        Some("camelCase") => {
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(c) => c.to_lowercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+        Some("snake_case") => split_words(name).join("_").to_lowercase(),
+        Some("SCREAMING_SNAKE_CASE") => split_words(name).join("_").to_uppercase(),
+        Some("kebab-case") => split_words(name).join("-").to_lowercase(),
+        Some("SCREAMING-KEBAB-CASE") => split_words(name).join("-").to_uppercase(),
+        Some(r) => panic!("Unknown rename_all rule: {}", r),
+    }
+}
+
+fn generate_enum_output(
+    name: &Ident,
+    generics: &syn::Generics,
+    variant_names: &[Ident],
+    variant_strs: &[String],
+) -> TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Quotes are added around the variant when serialized
+    let max_len = variant_strs.iter().map(|s| s.len()).max().unwrap_or(0);
+    let len_impl = generate_len_output(name, generics, consts(max_len + 2));
+    let deserialize_impl = generate_deserialize(name, generics, variant_names, variant_strs);
+
+    TokenStream::from(quote! {
+        #[automatically_derived]
+        impl #impl_generics serde::Serialize for #name #ty_generics #where_clause {
+            fn serialize<S>(
+                &self,
+                serializer: S,
+            ) -> serde::export::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                serde::Serializer::serialize_str(
+                    serializer,
+                    match self {
+                        #(
+                            #name::#variant_names => #variant_strs,
+                        )*
+                    },
+                )
+            }
+        }
+
+        #deserialize_impl
+
+        #len_impl
+    })
+}
+
+/// `Deserialize` of a unit enum from the strings representing its variants.
+pub fn generate_deserialize(
+    name: &Ident,
+    generics: &syn::Generics,
+    variant_names: &[Ident],
+    variant_strs: &[String],
+) -> proc_macro2::TokenStream {
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+    let variant_bytestrs = variant_strs
+        .iter()
+        .map(|s| Literal::byte_string(s.as_bytes()));
+    let expecting = format!("one of the variants of enum {}", name);
+
+    let mut serde_generics = generics.clone();
+    serde_generics
+        .params
+        .push(GenericParam::Lifetime(LifetimeDef::new(Lifetime::new(
+            "'de",
+            Span::call_site(),
+        ))));
+    let (serde_impl_generics, _, _) = serde_generics.split_for_impl();
+
+    quote! {
+        #[automatically_derived]
+        impl #serde_impl_generics serde::Deserialize<'de> for #name #ty_generics #where_clause {
+            fn deserialize<D>(deserializer: D) -> serde::export::Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                const VARIANTS: &'static [&'static str] = &[#(#variant_strs),*];
+
+                struct EnumVisitor;
+                impl<'de> serde::de::Visitor<'de> for EnumVisitor {
+                    type Value = #name;
+                    fn expecting(
+                        &self,
+                        formatter: &mut serde::export::Formatter,
+                    ) -> serde::export::fmt::Result {
+                        serde::export::Formatter::write_str(formatter, #expecting)
+                    }
+                    fn visit_str<E>(
+                        self,
+                        value: &str,
+                    ) -> serde::export::Result<Self::Value, E>
+                    where
+                        E: serde::de::Error,
+                    {
+                        match value {
+                            #(
+                                #variant_strs => serde::export::Ok(#name::#variant_names),
+                            )*
+                            _ => serde::export::Err(serde::de::Error::unknown_variant(value, VARIANTS)),
+                        }
+                    }
+                    fn visit_bytes<E>(
+                        self,
+                        value: &[u8],
+                    ) -> serde::export::Result<Self::Value, E>
+                    where
+                        E: serde::de::Error,
+                    {
+                        match value {
+                            #(
+                                #variant_bytestrs => serde::export::Ok(#name::#variant_names),
+                            )*
+                            _ => serde::export::Err(serde::de::Error::invalid_value(
+                                serde::de::Unexpected::Bytes(value),
+                                &self,
+                            )),
+                        }
+                    }
+                }
+
+                serde::Deserializer::deserialize_str(deserializer, EnumVisitor)
+            }
+        }
+    }
+}
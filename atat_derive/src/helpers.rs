@@ -1,7 +1,7 @@
 use crate::proc_macro2::{Literal, TokenStream, TokenTree};
 use quote::quote;
 
-use syn::{spanned::Spanned, Error, FieldsNamed, Ident, Result, Type};
+use syn::{spanned::Spanned, Error, ExprPath, Field, FieldsNamed, Ident, Result, Type};
 
 pub fn stream_from_tokens(tokens: &proc_macro2::TokenStream) -> TokenStream {
     for f in tokens.clone() {
@@ -21,13 +21,29 @@ pub fn get_lit(tokens: &proc_macro2::TokenStream) -> Result<Literal> {
     Err(Error::new(tokens.span(), "Cannot find AT Command!"))
 }
 
-pub fn get_ident(tokens: &proc_macro2::TokenStream) -> Result<Ident> {
-    for l in stream_from_tokens(tokens) {
-        if let TokenTree::Ident(ident) = l {
-            return Ok(ident);
+/// The type following the AT command literal, e.g. `NoResponse` or
+/// `Option<PhonebookEntry>`, up to the next top level `,`.
+pub fn get_type(tokens: &proc_macro2::TokenStream) -> Result<Type> {
+    let mut ty = TokenStream::new();
+    let mut depth = 0usize;
+    for l in stream_from_tokens(tokens)
+        .into_iter()
+        .skip_while(|l| !matches!(l, TokenTree::Ident(_)))
+    {
+        if let TokenTree::Punct(p) = &l {
+            match p.as_char() {
+                '<' => depth += 1,
+                '>' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => break,
+                _ => {}
+            }
         }
+        ty.extend(core::iter::once(l));
     }
-    Err(Error::new(tokens.span(), "Cannot find ident type!"))
+    if ty.is_empty() {
+        return Err(Error::new(tokens.span(), "Cannot find response type!"));
+    }
+    syn::parse2(ty)
 }
 
 pub fn get_name_ident_lit(tokens: &proc_macro2::TokenStream, needle: &str) -> Result<String> {
@@ -63,6 +79,22 @@ pub fn get_name_ident_lit(tokens: &proc_macro2::TokenStream, needle: &str) -> Re
     Err(Error::new(tokens.span(), "Cannot find literal type!"))
 }
 
+/// Whether the flag `needle` is given, without a value, e.g. `skip` in
+/// `#[at_arg(skip, position = 1)]`.
+pub fn has_flag(tokens: &proc_macro2::TokenStream, needle: &str) -> bool {
+    let mut tokens = stream_from_tokens(tokens).into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if let TokenTree::Ident(i) = token {
+            let has_value =
+                matches!(tokens.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '=');
+            if i == needle && !has_value {
+                return true;
+            }
+        }
+    }
+    false
+}
+
 pub fn get_field_names(fields: Option<&FieldsNamed>) -> (Vec<Ident>, Vec<Type>, Vec<String>) {
     if let Some(fields) = fields {
         let (mut field_name_pos, mut field_type_pos): (Vec<(Ident, usize)>, Vec<(Type, usize)>) = {
@@ -129,64 +161,112 @@ pub fn get_field_names(fields: Option<&FieldsNamed>) -> (Vec<Ident>, Vec<Type>,
     }
 }
 
-// fn get_string_length(ty: &Type) -> usize {
-//     let type_string = quote! { #ty }.to_string();
-//     let len = match type_string.replace(" ", "").as_str() {
-//         "str" => panic!("String slices must be annotated with a length using #[at_arg()]"),
-//         "tuple" => panic!("Tuples are not supported!"),
-//         "char" => "a".len(),
-//         "bool" => "false".len(),
-//         "isize" => format!("{:?}", std::isize::MAX).len(),
-//         "usize" => format!("{:?}", std::usize::MAX).len(),
-//         "u8" => format!("{:?}", std::u8::MAX).len(),
-//         "u16" => format!("{:?}", std::u16::MAX).len(),
-//         "u32" => format!("{:?}", std::u32::MAX).len(),
-//         "u64" => format!("{:?}", std::u64::MAX).len(),
-//         "u128" => format!("{:?}", std::u128::MAX).len(),
-//         "i8" => format!("{:?}", std::i8::MIN).len(),
-//         "i16" => format!("{:?}", std::i16::MIN).len(),
-//         "i32" => format!("{:?}", std::i32::MIN).len(),
-//         "i64" => format!("{:?}", std::i64::MIN).len(),
-//         "i128" => format!("{:?}", std::i128::MIN).len(),
-//         "f32" => format!("{:?}", std::f32::MIN).len(),
-//         "f64" => format!("{:?}", std::f64::MIN).len(),
-//         _ => {
-//             // println!("Unexpected type: {:?}", type_string);
-//             0
-//         }
-//     };
+/// Wrapper type implementing the `#[at_arg(encoding = "..")]` or
+/// `#[at_arg(format = "..")]` of `field`.
+pub fn get_encoding(field: &Field) -> Option<TokenStream> {
+    let attr = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("at_arg"))?;
+    if let Ok(format) = get_name_ident_lit(&attr.tokens, "format") {
+        return Some(match format.replace("\"", "").as_str() {
+            "0/1" => quote! { serde_at::format::NumericBool },
+            "ON/OFF" => quote! { serde_at::format::OnOff },
+            "presence" => quote! { serde_at::format::Presence },
+            f => panic!("Unknown format: {}", f),
+        });
+    }
+    get_name_ident_lit(&attr.tokens, "encoding")
+        .ok()
+        .map(|encoding| match encoding.replace("\"", "").as_str() {
+            "ucs2-hex" => quote! { serde_at::encoding::Ucs2Hex },
+            "gsm7-hex" => quote! { serde_at::encoding::Gsm7Hex },
+            e => panic!("Unknown encoding: {}", e),
+        })
+}
 
-//     // println!("Got len! {:?}: {:?}", type_string, len);
-//     len
-// }
+/// Encodings of the fields, in the same order as `get_field_names`.
+pub fn get_field_encodings(fields: Option<&FieldsNamed>) -> Vec<Option<TokenStream>> {
+    if let Some(fields) = fields {
+        let mut encoding_pos: Vec<(Option<TokenStream>, usize)> = fields
+            .named
+            .iter()
+            .map(|field| {
+                let pos = field
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path.is_ident("at_arg"))
+                    .and_then(|attr| get_name_ident_lit(&attr.tokens, "position").ok())
+                    .map(|p| p.parse().expect("Position argument must be an integer!"))
+                    .unwrap_or(0);
+                (get_encoding(field), pos)
+            })
+            .collect();
+        encoding_pos.sort_by(|(_, a), (_, b)| a.cmp(b));
+        encoding_pos.into_iter().map(|(e, _)| e).collect()
+    } else {
+        vec![]
+    }
+}
 
-// fn next_power_of_2(mut n: usize) -> usize {
-//     n -= 1;
-//     while (n & (n - 1)) != 0 {
-//         n = n & (n - 1);
-//     }
-//     return n << 1;
-// }
+/// Whether the flag `needle` is given in `#[at_arg(..)]` of the fields, e.g.
+/// `flatten`, in the same order as `get_field_names`.
+pub fn get_field_flags(fields: Option<&FieldsNamed>, needle: &str) -> Vec<bool> {
+    if let Some(fields) = fields {
+        let mut flag_pos: Vec<(bool, usize)> = fields
+            .named
+            .iter()
+            .map(|field| {
+                let attr = field.attrs.iter().find(|attr| attr.path.is_ident("at_arg"));
+                let pos = attr
+                    .and_then(|attr| get_name_ident_lit(&attr.tokens, "position").ok())
+                    .map(|p| p.parse().expect("Position argument must be an integer!"))
+                    .unwrap_or(0);
+                (
+                    matches!(attr, Some(attr) if has_flag(&attr.tokens, needle)),
+                    pos,
+                )
+            })
+            .collect();
+        flag_pos.sort_by_key(|(_, pos)| *pos);
+        flag_pos.into_iter().map(|(f, _)| f).collect()
+    } else {
+        vec![]
+    }
+}
 
-// pub fn calculate_cmd_len(
-//     subcmd_len: usize,
-//     fields: Option<&FieldsNamed>,
-//     termination_len: usize,
-// ) -> usize {
-//     let fields_len = if let Some(fields) = fields {
-//         fields
-//             .named
-//             .iter()
-//             .map(|field| get_string_length(&field.ty))
-//             .sum()
-//     } else {
-//         0
-//     };
+/// The function given by `#[at_arg(<needle> = "path")]` of `field`, i.e.
+/// `serialize_with` or `deserialize_with`.
+pub fn get_with(field: &Field, needle: &str) -> Option<ExprPath> {
+    field
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("at_arg"))
+        .and_then(|attr| get_name_ident_lit(&attr.tokens, needle).ok())
+        .map(|path| syn::parse_str(&path.replace("\"", "")).expect("Invalid function path!"))
+}
 
-//     let total_len = fields_len + subcmd_len + termination_len;
-//     if total_len <= 1024 {
-//         total_len
-//     } else {
-//         next_power_of_2(total_len)
-//     }
-// }
+/// Functions given by `#[at_arg(<needle> = "path")]` of the fields, in the
+/// same order as `get_field_names`.
+pub fn get_field_withs(fields: Option<&FieldsNamed>, needle: &str) -> Vec<Option<ExprPath>> {
+    if let Some(fields) = fields {
+        let mut with_pos: Vec<(Option<ExprPath>, usize)> = fields
+            .named
+            .iter()
+            .map(|field| {
+                let pos = field
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path.is_ident("at_arg"))
+                    .and_then(|attr| get_name_ident_lit(&attr.tokens, "position").ok())
+                    .map(|p| p.parse().expect("Position argument must be an integer!"))
+                    .unwrap_or(0);
+                (get_with(field, needle), pos)
+            })
+            .collect();
+        with_pos.sort_by_key(|(_, pos)| *pos);
+        with_pos.into_iter().map(|(w, _)| w).collect()
+    } else {
+        vec![]
+    }
+}
@@ -0,0 +1,236 @@
+use crate::proc_macro::TokenStream;
+use crate::proc_macro2::{TokenStream as TokenStream2, TokenTree};
+
+use quote::{format_ident, quote};
+use syn::{
+    Attribute, Data, DataEnum, DataStruct, DeriveInput, Field, Generics, Ident, Type,
+    WherePredicate,
+};
+
+use crate::helpers::{get_encoding, get_name_ident_lit};
+
+pub fn atat_len(item: DeriveInput) -> TokenStream {
+    let mut generics = item.generics.clone();
+    let len = match item.data {
+        Data::Struct(DataStruct { fields, .. }) if is_generic(&generics) => {
+            let (len, sum_bounds) = generic_sum(&fields_lens(fields.iter()), &generics);
+            let mut bounds = fields_len_bounds(fields.iter());
+            bounds.extend(sum_bounds);
+            generics = with_bounds(&generics, bounds);
+            len
+        }
+        Data::Struct(DataStruct { fields, .. }) => fields_len(fields.iter()),
+        Data::Enum(DataEnum { variants, .. }) => {
+            if variants.iter().all(|v| v.fields.is_empty()) {
+                repr_len(&item.attrs)
+            } else {
+                // Newtype variants are serialized as `<index>,<value>`. The sum
+                // of all variant values is used as an upper bound, as there is
+                // no way to express the maximum of the variants.
+                let values = fields_len(variants.iter().flat_map(|v| v.fields.iter()));
+                sum(vec![
+                    repr_len(&item.attrs),
+                    quote! { heapless::consts::U1 },
+                    values,
+                ])
+            }
+        }
+        _ => panic!("AtatLen can only be applied to structs and enums!"),
+    };
+
+    TokenStream::from(generate_len_output(&item.ident, &generics, len))
+}
+
+pub fn generate_len_output(
+    name: &Ident,
+    generics: &syn::Generics,
+    len: TokenStream2,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics atat::AtatLen for #name #ty_generics #where_clause {
+            type Len = #len;
+        }
+    }
+}
+
+/// Type level sum of `lens`.
+pub fn sum(lens: Vec<TokenStream2>) -> TokenStream2 {
+    lens.into_iter()
+        .fold(quote! { heapless::consts::U0 }, |acc, len| {
+            quote! { <#acc as core::ops::Add<#len>>::Output }
+        })
+}
+
+/// Whether `generics` has type or const parameters, that the lengths of the
+/// fields may depend on.
+pub fn is_generic(generics: &Generics) -> bool {
+    generics.type_params().next().is_some() || generics.const_params().next().is_some()
+}
+
+/// `generics`, with the `bounds` added to its where clause.
+///
+/// Bounds on types not depending on the type parameters are left out, as
+/// they hold anyway, and would hide the implementations they refer to, e.g.
+/// the `AtatLen::Len` of `u8` could no longer be resolved given `u8:
+/// AtatLen`.
+pub fn with_bounds(generics: &Generics, bounds: Vec<TokenStream2>) -> Generics {
+    let mut predicates = vec![];
+    for bound in bounds {
+        let predicate: WherePredicate = syn::parse2(bound.clone()).expect("Invalid bound!");
+        if let WherePredicate::Type(_) = predicate {
+            if !depends_on(&bound, generics) {
+                continue;
+            }
+        }
+        predicates.push(predicate);
+    }
+    let mut generics = generics.clone();
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+}
+
+/// Whether `tokens` refer to a type or const parameter of `generics`.
+fn depends_on(tokens: &TokenStream2, generics: &Generics) -> bool {
+    tokens.clone().into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => {
+            generics.type_params().any(|p| p.ident == ident)
+                || generics.const_params().any(|p| p.ident == ident)
+        }
+        TokenTree::Group(group) => depends_on(&group.stream(), generics),
+        _ => false,
+    })
+}
+
+/// The type `T` of a length `<T as atat::AtatLen>::Len`.
+fn len_of(len: &TokenStream2) -> Option<Type> {
+    let path: syn::TypePath = syn::parse2(len.clone()).ok()?;
+    match (path.qself, path.path.segments.last()) {
+        (Some(qself), Some(last)) if last.ident == "Len" => Some(*qself.ty),
+        _ => None,
+    }
+}
+
+/// The type level sum of `lens`, some of which depend on the parameters of
+/// `generics`, along with the bounds required to use it as the length of a
+/// buffer.
+///
+/// The bounds are stated on the lengths depending on the parameters, which
+/// are added up first. The lengths of other types are added through
+/// `atat::AddLen`, as e.g. `<u8 as AtatLen>::Len` is not resolved within a
+/// bound.
+pub fn generic_sum(
+    lens: &[TokenStream2],
+    generics: &Generics,
+) -> (TokenStream2, Vec<TokenStream2>) {
+    let (mut lens, rest): (Vec<_>, Vec<_>) = lens
+        .iter()
+        .cloned()
+        .partition(|len| depends_on(len, generics));
+    lens.extend(rest);
+    let mut lens = lens.into_iter();
+    let mut acc = lens
+        .next()
+        .unwrap_or_else(|| quote! { heapless::consts::U0 });
+    let mut bounds = vec![];
+    for len in lens {
+        match len_of(&len) {
+            Some(ty) => {
+                bounds.push(quote! { #acc: atat::AddLen<#ty> });
+                acc = quote! { <#acc as atat::AddLen<#ty>>::Output };
+            }
+            None => {
+                bounds.push(quote! { #acc: core::ops::Add<#len> });
+                acc = quote! { <#acc as core::ops::Add<#len>>::Output };
+            }
+        }
+    }
+    bounds.push(quote! { #acc: heapless::ArrayLength<u8> });
+    (acc, bounds)
+}
+
+/// A `heapless::consts` type representing `n`.
+pub fn consts(n: usize) -> TokenStream2 {
+    let ident = format_ident!("U{}", n);
+    quote! { heapless::consts::#ident }
+}
+
+fn get_len_arg(attrs: &[Attribute]) -> Option<usize> {
+    attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("at_arg"))
+        .and_then(|attr| get_name_ident_lit(&attr.tokens, "len").ok())
+        .map(|l| l.parse().expect("len argument must be an integer!"))
+}
+
+/// The bound required by the length of `field`, if it is taken from the
+/// `AtatLen` of its type.
+fn field_len_bound(field: &Field) -> Option<TokenStream2> {
+    if get_len_arg(&field.attrs).is_some() {
+        return None;
+    }
+    let ty = &field.ty;
+    Some(match get_encoding(field) {
+        Some(encoding) => quote! { #encoding<#ty>: atat::AtatLen },
+        None => quote! { #ty: atat::AtatLen },
+    })
+}
+
+/// The bounds required by the lengths of `fields`.
+pub fn fields_len_bounds<'a>(fields: impl Iterator<Item = &'a Field>) -> Vec<TokenStream2> {
+    fields.filter_map(field_len_bound).collect()
+}
+
+pub fn field_len(field: &Field) -> TokenStream2 {
+    let is_ref = matches!(field.ty, Type::Reference(_));
+
+    if let Some(encoding) = get_encoding(field) {
+        let ty = &field.ty;
+        return match get_len_arg(&field.attrs) {
+            // The length argument is the number of characters, each taking
+            // up to four hex digits when encoded
+            Some(len) => consts(len * 4 + 2),
+            None => quote! { <#encoding<#ty> as atat::AtatLen>::Len },
+        };
+    }
+
+    match (get_len_arg(&field.attrs), is_ref) {
+        // Quotes are added around string slices when serialized
+        (Some(len), true) => consts(len + 2),
+        (Some(len), false) => consts(len),
+        (None, _) => {
+            let ty = &field.ty;
+            quote! { <#ty as atat::AtatLen>::Len }
+        }
+    }
+}
+
+/// Maximum lengths of the given fields, and of the separating commas.
+pub fn fields_lens<'a>(fields: impl Iterator<Item = &'a Field>) -> Vec<TokenStream2> {
+    let mut lens: Vec<TokenStream2> = fields.map(field_len).collect();
+    if !lens.is_empty() {
+        lens.push(consts(lens.len() - 1));
+    }
+    lens
+}
+
+/// Maximum length of the given fields, including the separating commas.
+pub fn fields_len<'a>(fields: impl Iterator<Item = &'a Field>) -> TokenStream2 {
+    sum(fields_lens(fields))
+}
+
+fn repr_len(attrs: &[Attribute]) -> TokenStream2 {
+    let repr = attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("repr"))
+        .map(|attr| {
+            let ident: Ident = attr.parse_args().expect("Unsupported repr attribute");
+            quote! { #ident }
+        })
+        // Unit variants are serialized by their `u32` index by default
+        .unwrap_or_else(|| quote! { u32 });
+
+    quote! { <#repr as atat::AtatLen>::Len }
+}
@@ -4,14 +4,15 @@ extern crate proc_macro;
 extern crate proc_macro2;
 
 mod cmd;
+mod enum_;
 mod helpers;
+mod len;
 mod resp;
 mod urc;
 
 use crate::proc_macro::TokenStream;
-use quote::quote;
 
-#[proc_macro_derive(AtatResp, attributes(at_arg))]
+#[proc_macro_derive(AtatResp, attributes(at_enum, at_arg))]
 pub fn derive_atat_resp(input: TokenStream) -> TokenStream {
     resp::atat_resp(syn::parse(input).expect("Failed to parse input stream!"))
 }
@@ -21,10 +22,14 @@ pub fn derive_atat_urc(input: TokenStream) -> TokenStream {
     urc::atat_urc(syn::parse(input).expect("Failed to parse input stream!"))
 }
 
-#[proc_macro_derive(AtatEnum, attributes(at_arg))]
-pub fn derive_atat_enum(_input: TokenStream) -> TokenStream {
-    // let item: DeriveInput = syn::parse(input).expect("Failed to parse input stream!";)
-    TokenStream::from(quote! {})
+#[proc_macro_derive(AtatEnum, attributes(at_enum, at_arg))]
+pub fn derive_atat_enum(input: TokenStream) -> TokenStream {
+    enum_::atat_enum(syn::parse(input).expect("Failed to parse input stream!"))
+}
+
+#[proc_macro_derive(AtatLen, attributes(at_arg))]
+pub fn derive_atat_len(input: TokenStream) -> TokenStream {
+    len::atat_len(syn::parse(input).expect("Failed to parse input stream!"))
 }
 
 #[proc_macro_derive(AtatCmd, attributes(at_cmd, at_arg))]
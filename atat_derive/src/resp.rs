@@ -3,10 +3,13 @@ use crate::proc_macro2::{Literal, Span};
 
 use quote::{format_ident, quote};
 use syn::{
-    Data, DataStruct, DeriveInput, Fields, FieldsNamed, GenericParam, Ident, Lifetime, LifetimeDef,
+    Data, DataEnum, DataStruct, DeriveInput, Fields, FieldsNamed, GenericParam, Ident, Lifetime,
+    LifetimeDef,
 };
 
-use crate::helpers::get_field_names;
+use crate::enum_::{generate_deserialize, get_variants};
+use crate::helpers::{get_field_encodings, get_field_flags, get_field_names, get_field_withs};
+use crate::len::{is_generic, with_bounds};
 
 pub fn atat_resp(item: DeriveInput) -> TokenStream {
     match item.data {
@@ -22,13 +25,26 @@ pub fn atat_resp(item: DeriveInput) -> TokenStream {
 
             _ => panic!("Cannot handle unnamed struct fields"),
         },
+        Data::Enum(DataEnum { variants, .. }) => {
+            let (variant_names, variant_strs) = get_variants(&item.attrs, &variants, "AtatResp");
+            let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+            let name = &item.ident;
+            let deserialize_impl =
+                generate_deserialize(name, &item.generics, &variant_names, &variant_strs);
+            TokenStream::from(quote! {
+                #[automatically_derived]
+                impl #impl_generics atat::AtatResp for #name #ty_generics #where_clause {}
+
+                #deserialize_impl
+            })
+        }
         _ => {
             // item.span()
             //     .unstable()
             //     .error("AtatResp can only be applied to structs!")
             //     .emit();
             // TokenStream::new()
-            panic!("AtatResp can only be applied to structs!");
+            panic!("AtatResp can only be applied to structs and enums!");
         }
     }
 }
@@ -47,6 +63,8 @@ fn generate_resp_output(
         .unzip();
     let anon_field_ind64: Vec<u64> = anon_field_ind.iter().map(|i| *i as u64).collect();
     let anon_field_ind128: Vec<u128> = anon_field_ind.iter().map(|i| *i as u128).collect();
+    let field_withs = get_field_withs(fields, "deserialize_with");
+    let field_flattens = get_field_flags(fields, "flatten");
     let len = field_names.len();
     let visitor = format_ident!("{}Visitor", name_str);
     let field_visitor = format_ident!("{}FieldVisitor", name_str);
@@ -59,26 +77,142 @@ fn generate_resp_output(
     let struct_name = format!("struct {}", name);
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let mut serde_generics = generics.clone();
+    // Borrowed fields outlive the deserializer, and generic fields have to
+    // be deserializable
+    let mut bounds: Vec<_> = generics
+        .lifetimes()
+        .map(|def| {
+            let lifetime = &def.lifetime;
+            quote! { 'de: #lifetime }
+        })
+        .collect();
+    if is_generic(generics) {
+        bounds.extend(
+            field_types
+                .iter()
+                .zip(get_field_encodings(fields))
+                .zip(field_withs.iter())
+                .filter(|(_, with)| with.is_none())
+                .map(|((ty, encoding), _)| match encoding {
+                    Some(encoding) => quote! { #encoding<#ty>: serde::Deserialize<'de> },
+                    None => quote! { #ty: serde::Deserialize<'de> },
+                }),
+        );
+    }
+    let mut serde_generics = with_bounds(generics, bounds);
+    serde_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeDef::new(Lifetime::new("'de", Span::call_site()))),
+    );
+    let (serde_impl_generics, serde_ty_generics, serde_where_clause) =
+        serde_generics.split_for_impl();
 
-    serde_generics
-        .params
-        .push(GenericParam::Lifetime(LifetimeDef::new(Lifetime::new(
-            "'de",
-            Span::call_site(),
-        ))));
-    let (serde_impl_generics, _, _) = serde_generics.split_for_impl();
+    // Fields with `deserialize_with` are deserialized through a wrapper type
+    // calling the function, fields with an encoding through the wrapper type
+    // of the encoding
+    let mut wrappers = Vec::new();
+    let (de_types, field_values): (Vec<_>, Vec<_>) = field_types
+        .iter()
+        .zip(get_field_encodings(fields))
+        .zip(field_withs.iter())
+        .zip(anon_field.iter())
+        .enumerate()
+        .map(|(i, (((ty, encoding), with), anon))| match (with, encoding) {
+            (Some(path), _) => {
+                let wrapper = format_ident!("__DeserializeWith{}", i);
+                wrappers.push(quote! {
+                    struct #wrapper #serde_impl_generics #serde_where_clause {
+                        value: #ty,
+                        phantom: serde::export::PhantomData<#name #ty_generics>,
+                        lifetime: serde::export::PhantomData<&'de ()>,
+                    }
+                    impl #serde_impl_generics serde::Deserialize<'de> for #wrapper #serde_ty_generics #serde_where_clause {
+                        fn deserialize<__D>(
+                            deserializer: __D,
+                        ) -> serde::export::Result<Self, __D::Error>
+                        where
+                            __D: serde::Deserializer<'de>,
+                        {
+                            serde::export::Ok(#wrapper {
+                                value: match #path(deserializer) {
+                                    serde::export::Ok(val) => val,
+                                    serde::export::Err(err) => {
+                                        return serde::export::Err(err);
+                                    }
+                                },
+                                phantom: serde::export::PhantomData,
+                                lifetime: serde::export::PhantomData,
+                            })
+                        }
+                    }
+                });
+                (quote! { #wrapper #serde_ty_generics }, quote! { #anon.value })
+            }
+            (None, Some(encoding)) => (quote! { #encoding<#ty> }, quote! { #anon.0 }),
+            (None, None) => (quote! { #ty }, quote! { #anon }),
+        })
+        .unzip();
+
+    // Flattened fields take their arguments from the sequence of the
+    // enclosing struct
+    let seq_fields = de_types
+        .iter()
+        .zip(field_flattens.iter())
+        .zip(anon_field.iter().zip(anon_field_ind.iter()))
+        .zip(field_names_str.iter())
+        .map(|(((de_type, flatten), (anon, ind)), name)| {
+            if *flatten {
+                quote! {
+                    let #anon = match <#de_type as serde::Deserialize>::deserialize(
+                        serde::de::value::SeqAccessDeserializer::new(&mut seq),
+                    ) {
+                        serde::export::Ok(val) => val,
+                        serde::export::Err(err) => {
+                            return serde::export::Err(err);
+                        }
+                    };
+                }
+            } else {
+                quote! {
+                    let #anon =
+                        match match serde::de::SeqAccess::next_element::<#de_type>(&mut seq) {
+                            serde::export::Ok(val) => val,
+                            serde::export::Err(err) => {
+                                return serde::export::Err(err);
+                            }
+                        } {
+                            serde::export::Some(value) => value,
+                            // Missing trailing parameters are
+                            // accepted for `Option` fields only
+                            serde::export::None => {
+                                match serde::private::de::missing_field::<#de_type, A::Error>(
+                                    #name,
+                                ) {
+                                    serde::export::Ok(val) => val,
+                                    serde::export::Err(_) => {
+                                        return serde::export::Err(::serde::de::Error::invalid_length(
+                                            #ind,
+                                            &#invalid_len_err,
+                                        ));
+                                    }
+                                }
+                            }
+                        };
+                }
+            }
+        });
 
     TokenStream::from(quote! {
         #[automatically_derived]
         impl #impl_generics atat::AtatResp for #name #ty_generics #where_clause {}
 
         #[automatically_derived]
-        impl #serde_impl_generics serde::Deserialize<'de> for #name #ty_generics #where_clause {
+        impl #serde_impl_generics serde::Deserialize<'de> for #name #ty_generics #serde_where_clause {
             fn deserialize<D>(deserializer: D) -> serde::export::Result<Self, D::Error>
             where
                 D: serde::Deserializer<'de>,
             {
+                #(#wrappers)*
                 #[allow(non_camel_case_types)]
                 enum #enum_field {
                     #(#anon_field,)*
@@ -166,12 +300,12 @@ fn generate_resp_output(
                         serde::Deserializer::deserialize_identifier(deserializer, #field_visitor)
                     }
                 }
-                struct #visitor<'de> {
-                    marker: serde::export::PhantomData<#name>,
+                struct #visitor #serde_impl_generics #serde_where_clause {
+                    marker: serde::export::PhantomData<#name #ty_generics>,
                     lifetime: serde::export::PhantomData<&'de ()>,
                 }
-                impl<'de> serde::de::Visitor<'de> for #visitor<'de> {
-                    type Value = #name;
+                impl #serde_impl_generics serde::de::Visitor<'de> for #visitor #serde_ty_generics #serde_where_clause {
+                    type Value = #name #ty_generics;
                     fn expecting(
                         &self,
                         formatter: &mut serde::export::Formatter,
@@ -186,26 +320,10 @@ fn generate_resp_output(
                     where
                         A: serde::de::SeqAccess<'de>,
                     {
-                        #(
-                            let #anon_field =
-                                match match serde::de::SeqAccess::next_element::<#field_types>(&mut seq) {
-                                    serde::export::Ok(val) => val,
-                                    serde::export::Err(err) => {
-                                        return serde::export::Err(err);
-                                    }
-                                } {
-                                    serde::export::Some(value) => value,
-                                    serde::export::None => {
-                                        return serde::export::Err(::serde::de::Error::invalid_length(
-                                            #anon_field_ind,
-                                            &#invalid_len_err,
-                                        ));
-                                    }
-                                };
-                        )*
+                        #(#seq_fields)*
                         serde::export::Ok(#name {
                             #(
-                                #field_names: #anon_field
+                                #field_names: #field_values
                             ),*
                         })
                     }
@@ -218,7 +336,7 @@ fn generate_resp_output(
                         A: serde::de::MapAccess<'de>,
                     {
                         #(
-                            let mut #anon_field: serde::export::Option<#field_types> = serde::export::None;
+                            let mut #anon_field: serde::export::Option<#de_types> = serde::export::None;
                         )*
                         while let serde::export::Some(key) =
                             match serde::de::MapAccess::next_key::<#enum_field>(&mut map) {
@@ -239,7 +357,7 @@ fn generate_resp_output(
                                             );
                                         }
                                         #anon_field = serde::export::Some(
-                                            match serde::de::MapAccess::next_value::<#field_types>(&mut map) {
+                                            match serde::de::MapAccess::next_value::<#de_types>(&mut map) {
                                                 serde::export::Ok(val) => val,
                                                 serde::export::Err(err) => {
                                                     return serde::export::Err(err);
@@ -276,7 +394,7 @@ fn generate_resp_output(
                         )*
                         serde::export::Ok(#name {
                             #(
-                                #field_names: #anon_field
+                                #field_names: #field_values
                             ),*
                         })
                     }
@@ -287,7 +405,7 @@ fn generate_resp_output(
                     #name_str,
                     FIELDS,
                     #visitor {
-                        marker: serde::export::PhantomData::<#name>,
+                        marker: serde::export::PhantomData::<#name #ty_generics>,
                         lifetime: serde::export::PhantomData,
                     },
                 )
@@ -4,30 +4,47 @@ use quote::quote;
 
 use syn::{Attribute, Data, DataEnum, DeriveInput, Fields, Ident, Type, Variant};
 
-use crate::helpers::get_lit;
+use crate::helpers::{get_lit, get_name_ident_lit, has_flag};
 
 #[derive(Debug)]
 struct AtUrcAttr {
     pub variant_name: Ident,
-    pub variant_field_type: Type,
+    /// The field of the variant, or `None` for a unit variant, matching a
+    /// URC without arguments, e.g. `RING`
+    pub variant_field_type: Option<Type>,
     pub cmd: Literal,
+    /// Map the whole line after the URC code into the variant field, rather
+    /// than expecting `<code>: <args>`
+    pub whole_line: bool,
+    /// Parse a URC truncated to fit the URC queue into the
+    /// `atat::Truncatable` variant field, rather than dropping it
+    pub truncatable: bool,
+    /// Only match URCs whose first argument is this, e.g. `"recv"` of
+    /// `+QIURC: "recv",<id>`, mapping the following arguments into the
+    /// variant field
+    pub discriminator: Option<String>,
+    /// Match the URC code and discriminator regardless of their case, e.g.
+    /// `+Cring` for `+CRING`
+    pub ci: bool,
 }
 
-fn get_type(variant: &Variant) -> Type {
+fn get_type(variant: &Variant) -> Option<Type> {
     if variant.fields.len() > 1 {
         panic!("AtatUrc does not support more than one field per variant");
     }
     match variant.fields {
-        Fields::Unnamed(ref f) => f
-            .unnamed
-            .first()
-            .expect(
-                "AtatUrc does not support unit variants, \
-                please add unnamed field to a struct, \
-                implementing AtatResp",
-            )
-            .ty
-            .clone(),
+        Fields::Unnamed(ref f) => Some(
+            f.unnamed
+                .first()
+                .expect(
+                    "AtatUrc does not support empty tuple variants, \
+                    please add unnamed field to a struct, \
+                    implementing AtatResp",
+                )
+                .ty
+                .clone(),
+        ),
+        Fields::Unit => None,
         _ => panic!("AtatUrc does not support named fields in variants"),
     }
 }
@@ -39,6 +56,10 @@ pub fn atat_urc(item: DeriveInput) -> TokenStream {
                 .iter()
                 .map(|variant| AtUrcAttr {
                     cmd: get_urc_code(&variant.attrs),
+                    whole_line: get_bool_arg(&variant.attrs, "whole_line"),
+                    truncatable: get_bool_arg(&variant.attrs, "truncatable"),
+                    discriminator: get_str_arg(&variant.attrs, "discriminator"),
+                    ci: get_bool_arg(&variant.attrs, "ci"),
                     variant_field_type: get_type(&variant),
                     variant_name: variant.ident.clone(),
                 })
@@ -59,6 +80,29 @@ fn get_urc_code(attrs: &[Attribute]) -> Literal {
     }
 }
 
+/// The flag `name`, given as `name = true` or just `name`.
+fn get_bool_arg(attrs: &[Attribute], name: &str) -> bool {
+    match attrs.iter().find(|attr| attr.path.is_ident("at_urc")) {
+        Some(attr) => {
+            has_flag(&attr.tokens, name)
+                || get_name_ident_lit(&attr.tokens, name)
+                    .ok()
+                    .and_then(|lit| lit.parse::<bool>().ok())
+                    .unwrap_or(false)
+        }
+        None => false,
+    }
+}
+
+fn get_str_arg(attrs: &[Attribute], name: &str) -> Option<String> {
+    attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("at_urc"))
+        .and_then(|attr| get_name_ident_lit(&attr.tokens, name).ok())
+        .and_then(|lit| syn::parse_str::<syn::LitStr>(&lit).ok())
+        .map(|lit| lit.value())
+}
+
 fn generate_urc_output(
     name: &Ident,
     generics: &syn::Generics,
@@ -66,12 +110,112 @@ fn generate_urc_output(
 ) -> TokenStream {
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let variant_names: Vec<Ident> = urc_attrs.iter().map(|a| a.variant_name.clone()).collect();
-    let variant_field_types: Vec<Type> = urc_attrs
+    let (unit_attrs, field_attrs): (Vec<&AtUrcAttr>, Vec<&AtUrcAttr>) = urc_attrs
+        .iter()
+        .partition(|a| a.variant_field_type.is_none());
+    let (discriminated_attrs, field_attrs): (Vec<&AtUrcAttr>, Vec<&AtUrcAttr>) = field_attrs
+        .into_iter()
+        .partition(|a| a.discriminator.is_some());
+    let (line_attrs, arg_attrs): (Vec<&AtUrcAttr>, Vec<&AtUrcAttr>) =
+        field_attrs.into_iter().partition(|a| a.whole_line);
+
+    let unit_variants: Vec<_> = unit_attrs
+        .iter()
+        .map(|a| {
+            let variant_name = &a.variant_name;
+            let cmd = &a.cmd;
+            let ci = a.ci;
+            match &a.discriminator {
+                Some(discriminator) => quote! {
+                    if discriminated(#cmd, #discriminator, #ci).is_some() {
+                        return Ok(#name::#variant_name);
+                    }
+                },
+                None if ci => quote! {
+                    if resp.trim().eq_ignore_ascii_case(#cmd) {
+                        return Ok(#name::#variant_name);
+                    }
+                },
+                None => quote! {
+                    if resp.trim() == #cmd {
+                        return Ok(#name::#variant_name);
+                    }
+                },
+            }
+        })
+        .collect();
+
+    let discriminated_variant_names: Vec<Ident> = discriminated_attrs
+        .iter()
+        .map(|a| a.variant_name.clone())
+        .collect();
+    let discriminated_variant_field_types: Vec<&Type> = discriminated_attrs
+        .iter()
+        .filter_map(|a| a.variant_field_type.as_ref())
+        .collect();
+    let discriminated_cmds: Vec<Literal> =
+        discriminated_attrs.iter().map(|a| a.cmd.clone()).collect();
+    let discriminators: Vec<&String> = discriminated_attrs
+        .iter()
+        .filter_map(|a| a.discriminator.as_ref())
+        .collect();
+    let discriminated_cis: Vec<bool> = discriminated_attrs.iter().map(|a| a.ci).collect();
+
+    // Case insensitive codes cannot be matched by the `match` of the exact
+    // codes below
+    let (ci_attrs, arg_attrs): (Vec<&AtUrcAttr>, Vec<&AtUrcAttr>) =
+        arg_attrs.into_iter().partition(|a| a.ci);
+    let ci_variant_names: Vec<Ident> = ci_attrs.iter().map(|a| a.variant_name.clone()).collect();
+    let ci_variant_field_types: Vec<&Type> = ci_attrs
+        .iter()
+        .filter_map(|a| a.variant_field_type.as_ref())
+        .collect();
+    let ci_cmds: Vec<Literal> = ci_attrs.iter().map(|a| a.cmd.clone()).collect();
+
+    let variant_names: Vec<Ident> = arg_attrs.iter().map(|a| a.variant_name.clone()).collect();
+    let variant_field_types: Vec<&Type> = arg_attrs
+        .iter()
+        .filter_map(|a| a.variant_field_type.as_ref())
+        .collect();
+    let cmds: Vec<Literal> = arg_attrs.iter().map(|a| a.cmd.clone()).collect();
+
+    let line_variant_names: Vec<Ident> =
+        line_attrs.iter().map(|a| a.variant_name.clone()).collect();
+    let line_variant_field_types: Vec<&Type> = line_attrs
         .iter()
-        .map(|a| a.variant_field_type.clone())
+        .filter_map(|a| a.variant_field_type.as_ref())
         .collect();
-    let cmds: Vec<Literal> = urc_attrs.iter().map(|a| a.cmd.clone()).collect();
+    let line_cmds: Vec<Literal> = line_attrs.iter().map(|a| a.cmd.clone()).collect();
+    let line_cis: Vec<bool> = line_attrs.iter().map(|a| a.ci).collect();
+
+    let truncatable_attrs: Vec<&AtUrcAttr> = arg_attrs
+        .iter()
+        .chain(ci_attrs.iter())
+        .filter(|a| a.truncatable)
+        .copied()
+        .collect();
+    let parse_truncated = if truncatable_attrs.is_empty() {
+        quote! {}
+    } else {
+        let truncatable_variant_names: Vec<Ident> = truncatable_attrs
+            .iter()
+            .map(|a| a.variant_name.clone())
+            .collect();
+        let truncatable_cmds: Vec<Literal> =
+            truncatable_attrs.iter().map(|a| a.cmd.clone()).collect();
+        quote! {
+            fn parse_truncated(resp: &str, original_len: usize) -> ::core::result::Result<Self::Response, atat::Error> {
+                match resp.splitn(2, ':').next() {
+                    #(
+                        Some(#truncatable_cmds) => Ok(#name::#truncatable_variant_names(
+                            atat::Truncatable::from_truncated(resp, original_len)?,
+                        )),
+                    )*
+                    _ => Err(atat::Error::Overflow),
+                }
+            }
+        }
+    };
 
     TokenStream::from(quote! {
         #[automatically_derived]
@@ -79,19 +223,85 @@ fn generate_urc_output(
             type Response = #name;
 
             fn parse(resp: &str) -> ::core::result::Result<Self::Response, atat::Error> {
+                // The remainder of the line following `code`, which is matched
+                // regardless of its case if `ci`
+                #[allow(unused)]
+                let strip_code = |code: &str, ci: bool| {
+                    let head = resp.get(..code.len())?;
+                    if head == code || (ci && head.eq_ignore_ascii_case(code)) {
+                        Some(&resp[code.len()..])
+                    } else {
+                        None
+                    }
+                };
+                // The arguments of a `<code>: <args>` URC following the
+                // first argument, if it is `discriminator`, unquoted
+                #[allow(unused)]
+                let discriminated = |code: &str, discriminator: &str, ci: bool| {
+                    let rest = strip_code(code, ci)?.strip_prefix(':')?;
+                    let mut args = rest.trim().splitn(2, ',');
+                    let first = args.next()?.trim().trim_matches('"');
+                    if first == discriminator || (ci && first.eq_ignore_ascii_case(discriminator)) {
+                        Some(args.next().unwrap_or("").trim_start())
+                    } else {
+                        None
+                    }
+                };
+                #(
+                    #unit_variants
+                )*
+                #(
+                    if let Some(args) = discriminated(#discriminated_cmds, #discriminators, #discriminated_cis) {
+                        return Ok(#name::#discriminated_variant_names(
+                            serde_at::from_str_raw::<#discriminated_variant_field_types>(args)
+                                .map_err(|_| atat::Error::ParseString(
+                                    atat::ErrorContext::response(resp).expected(#discriminated_cmds),
+                                ))?,
+                        ));
+                    }
+                )*
+                #(
+                    if let Some(rest) = strip_code(#line_cmds, #line_cis) {
+                        let line = rest
+                            .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+                            .trim_end();
+                        return Ok(#name::#line_variant_names(
+                            serde_at::from_str_raw::<#line_variant_field_types>(line)
+                                .map_err(|_| atat::Error::ParseString(
+                                    atat::ErrorContext::response(resp).expected(#line_cmds),
+                                ))?,
+                        ));
+                    }
+                )*
+                #(
+                    if matches!(strip_code(#ci_cmds, true), Some(rest) if rest.starts_with(':')) {
+                        return Ok(#name::#ci_variant_names(
+                            serde_at::from_str::<#ci_variant_field_types>(resp)
+                                .map_err(|_| atat::Error::ParseString(
+                                    atat::ErrorContext::response(resp).expected(#ci_cmds),
+                                ))?,
+                        ));
+                    }
+                )*
                 if let Some(cmd) = resp.splitn(2, ':').next() {
                     Ok(match cmd {
                         #(
                             #cmds => #name::#variant_names(serde_at::from_str::<#variant_field_types>(resp).map_err(|e| {
-                                atat::Error::ParseString
+                                atat::Error::ParseString(
+                                    atat::ErrorContext::response(resp).expected(#cmds),
+                                )
                             })?),
                         )*
-                        _ => return Err(atat::Error::InvalidResponse)
+                        _ => return Err(atat::Error::InvalidResponse(
+                            atat::ErrorContext::response(resp),
+                        ))
                     })
                 } else {
-                    Err(atat::Error::InvalidResponse)
+                    Err(atat::Error::InvalidResponse(atat::ErrorContext::response(resp)))
                 }
             }
+
+            #parse_truncated
         }
     })
 }
@@ -43,6 +43,9 @@ pub enum Error {
     /// Expected this character to be either a `','` or a `'}'`.
     ExpectedObjectCommaOrEnd,
 
+    /// Expected this character to be either a `','` or a `')'`.
+    ExpectedGroupCommaOrEnd,
+
     /// Expected to parse either a `true`, `false`, or a `null`.
     ExpectedSomeIdent,
 
@@ -81,11 +84,17 @@ pub enum Error {
 pub(crate) struct Deserializer<'b> {
     slice: &'b [u8],
     index: usize,
+    /// Number of enclosing groups, e.g. `(2,"Telia")`
+    depth: usize,
 }
 
 impl<'a> Deserializer<'a> {
     fn new(slice: &'a [u8]) -> Deserializer<'_> {
-        Deserializer { slice, index: 0 }
+        Deserializer {
+            slice,
+            index: 0,
+            depth: 0,
+        }
     }
 
     fn eat_char(&mut self) {
@@ -135,23 +144,22 @@ impl<'a> Deserializer<'a> {
         }
     }
 
+    /// Parse an unquoted string, until the next `','`, the end of the
+    /// enclosing group or the end of input, without trailing whitespace.
     fn parse_bytes(&mut self) -> Result<&'a [u8]> {
         let start = self.index;
         loop {
             match self.peek() {
-                Some(c) => {
-                    if (c as char).is_alphanumeric() {
-                        self.eat_char()
-                    } else {
-                        return Err(Error::EofWhileParsingString);
-                    }
-                }
-                None => {
-                    let end = self.index;
-                    return Ok(&self.slice[start..end]);
-                }
+                Some(b',') | None => break,
+                Some(b')') if self.depth > 0 => break,
+                Some(_) => self.eat_char(),
             }
         }
+        let mut end = self.index;
+        while end > start && matches!(self.slice[end - 1], b' ' | b'\t' | b'\r' | b'\n') {
+            end -= 1;
+        }
+        Ok(&self.slice[start..end])
     }
 
     /// Consumes all the whitespace characters and returns a peek into the next character
@@ -412,7 +420,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             }
             _ => {
                 if (peek as char).is_alphabetic() {
-                    visitor.visit_bytes(self.parse_bytes()?)
+                    visitor.visit_borrowed_bytes(self.parse_bytes()?)
                 } else {
                     Err(Error::InvalidType)
                 }
@@ -420,36 +428,45 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         }
     }
 
-    /// Unsupported. String is not available in no-std.
-    fn deserialize_string<V>(self, _visitor: V) -> Result<V::Value>
+    /// For `String`s of `alloc`, parsed like `&str`.
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unreachable!()
+        self.deserialize_str(visitor)
     }
 
-    /// Unsupported
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    /// Borrows the bytes of a quoted or unquoted string from the input.
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unreachable!()
+        match self.parse_whitespace().ok_or(Error::EofWhileParsingValue)? {
+            b'"' => {
+                self.eat_char();
+                visitor.visit_borrowed_bytes(self.parse_str()?.as_bytes())
+            }
+            _ => visitor.visit_borrowed_bytes(self.parse_bytes()?),
+        }
     }
 
-    /// Unsupported
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    /// For `Vec<u8>`s of `alloc`, parsed like `&[u8]`.
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unreachable!()
+        self.deserialize_bytes(visitor)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.parse_whitespace().ok_or(Error::EofWhileParsingValue)?;
-        visitor.visit_some(self)
+        // Nothing but the final result code, e.g. an empty phonebook entry
+        match self.parse_whitespace() {
+            None => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
     }
 
     /// Unsupported. Use a more specific deserialize_* method
@@ -482,23 +499,38 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         Ok(visitor.visit_seq(SeqAccess::new(self))?)
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    /// Parses a group of parameters in parentheses, e.g. the operators
+    /// `(2,"Telia","Telia","24001",7)` of `+COPS: (..),(..)`.
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unreachable!()
+        match self.parse_whitespace().ok_or(Error::EofWhileParsingValue)? {
+            b'(' => self.eat_char(),
+            _ => return Err(Error::InvalidType),
+        }
+        self.depth += 1;
+        let value = visitor.visit_seq(SeqAccess::new(self))?;
+        self.depth -= 1;
+        match self.parse_whitespace().ok_or(Error::EofWhileParsingList)? {
+            b')' => {
+                self.eat_char();
+                Ok(value)
+            }
+            _ => Err(Error::ExpectedGroupCommaOrEnd),
+        }
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
-        _visitor: V,
+        len: usize,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unreachable!()
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
@@ -606,6 +638,9 @@ impl fmt::Display for Error {
                      or a \
                      `'}'`."
                 }
+                Error::ExpectedGroupCommaOrEnd => {
+                    "Expected this character to be either a `','` or a `')'`."
+                }
                 Error::ExpectedSomeIdent => {
                     "Expected to parse either a `true`, `false`, or a \
                      `null`."
@@ -634,16 +669,15 @@ impl fmt::Display for Error {
 }
 
 /// Deserializes an instance of type `T` from bytes of AT Response text
+///
+/// Anything up to and including the first `':'` (the response prefix, e.g.
+/// `+CSQ:`) is skipped.
 pub fn from_slice<'a, T>(v: &'a [u8]) -> Result<T>
 where
     T: de::Deserialize<'a>,
 {
     if let Some(sp) = v.splitn(2, |&c| c == b':').last() {
-        let mut de = Deserializer::new(sp);
-        let value = de::Deserialize::deserialize(&mut de)?;
-        de.end()?;
-
-        Ok(value)
+        from_slice_raw(sp)
     } else {
         Err(Error::CustomError)
     }
@@ -657,6 +691,28 @@ where
     from_slice(s.as_bytes())
 }
 
+/// Deserializes an instance of type `T` from bytes of AT Response text,
+/// without skipping a response prefix.
+pub fn from_slice_raw<'a, T>(v: &'a [u8]) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    let mut de = Deserializer::new(v);
+    let value = de::Deserialize::deserialize(&mut de)?;
+    de.end()?;
+
+    Ok(value)
+}
+
+/// Deserializes an instance of type T from a string of AT Response text,
+/// without skipping a response prefix.
+pub fn from_str_raw<'a, T>(s: &'a str) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    from_slice_raw(s.as_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use heapless::{consts, String};
@@ -693,6 +749,20 @@ mod tests {
             })
         );
     }
+    #[test]
+    fn optional_response() {
+        assert_eq!(
+            crate::from_str("+CFG: 2,56,false"),
+            Ok(Some(CFG {
+                p1: 2,
+                p2: 56,
+                p3: false
+            }))
+        );
+        assert_eq!(crate::from_str::<Option<CFG>>(""), Ok(None));
+        assert_eq!(crate::from_str::<Option<CFG>>("\r\n"), Ok(None));
+    }
+
     #[test]
     fn simple_string() {
         assert_eq!(
@@ -717,4 +787,117 @@ mod tests {
     fn newtype_struct() {
         assert_eq!(crate::from_str("+CCID: 15"), Ok(Handle(15)));
     }
+
+    #[test]
+    fn unquoted_string() {
+        assert_eq!(
+            crate::from_str_raw("Model: SARA-R410M"),
+            Ok(StringTest {
+                string: String::from("Model: SARA-R410M")
+            })
+        );
+        assert_eq!(
+            crate::from_str("+CGMI: u-blox"),
+            Ok(StringTest {
+                string: String::from("u-blox")
+            })
+        );
+    }
+
+    #[test]
+    fn whitespace_tolerance() {
+        assert_eq!(
+            crate::from_str("+CFG:2, 56 ,false \r\n"),
+            Ok(CFG {
+                p1: 2,
+                p2: 56,
+                p3: false
+            })
+        );
+        assert_eq!(
+            crate::from_str("+CGMI:u-blox  \r\n"),
+            Ok(StringTest {
+                string: String::from("u-blox")
+            })
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        data: &'a [u8],
+    }
+
+    #[test]
+    fn borrowed() {
+        assert_eq!(
+            crate::from_slice(b"+URDFILE: \"data.bin\",\"AB\x00\"\r\n"),
+            Ok(Borrowed {
+                name: "data.bin",
+                data: b"AB\x00"
+            })
+        );
+        assert_eq!(
+            crate::from_str("+URDFILE: data.bin, 42 "),
+            Ok(Borrowed {
+                name: "data.bin",
+                data: b"42"
+            })
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Owned {
+        name: std::string::String,
+        id: u8,
+    }
+
+    #[test]
+    fn owned() {
+        assert_eq!(
+            crate::from_str("+URDFILE: \"data.bin\",42"),
+            Ok(Owned {
+                name: "data.bin".into(),
+                id: 42
+            })
+        );
+        assert_eq!(
+            crate::from_str("+URDFILE: data.bin,42"),
+            Ok(Owned {
+                name: "data.bin".into(),
+                id: 42
+            })
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Operators<'a> {
+        operators: heapless::Vec<(u8, &'a str, &'a str, &'a str, u8), consts::U4>,
+        modes: Option<(&'a [u8],)>,
+        formats: Option<&'a [u8]>,
+    }
+
+    #[test]
+    fn repeated_groups() {
+        assert_eq!(
+            crate::from_str(
+                "+COPS: (2,\"Telia\",\"Telia\",\"24001\",7),(1,\"Tele2\",\"Tele2\",\"24007\",2),,(0-4),(0-2)"
+            ),
+            Ok(Operators {
+                operators: [
+                    (2, "Telia", "Telia", "24001", 7),
+                    (1, "Tele2", "Tele2", "24007", 2)
+                ]
+                .iter()
+                .cloned()
+                .collect(),
+                modes: Some((b"0-4",)),
+                formats: Some(b"(0-2)"),
+            })
+        );
+        assert_eq!(
+            crate::from_str::<Operators<'_>>("+COPS: (2,\"Telia\",\"Telia\",\"24001\",7,1)"),
+            Err(crate::de::Error::ExpectedGroupCommaOrEnd)
+        );
+    }
 }
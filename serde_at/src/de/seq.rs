@@ -20,25 +20,28 @@ impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        match self
-            .de
-            .parse_whitespace()
-            .ok_or(Error::EofWhileParsingList)?
-        {
-            b',' => {
+        match self.de.parse_whitespace() {
+            // Missing trailing elements are reported as the end of the
+            // sequence, allowing them to be mapped to `None` by the visitor.
+            None => return Ok(None),
+            Some(b',') => {
                 self.de.eat_char();
-                self.de
+                match self
+                    .de
                     .parse_whitespace()
                     .ok_or(Error::EofWhileParsingValue)?
-            }
-            c => {
-                if self.first {
-                    self.first = false;
-                    c
-                } else {
-                    return Ok(None);
+                {
+                    // So are empty elements, which end repeated groups, e.g.
+                    // `(..),(..),,(0-4)`
+                    b',' => return Ok(None),
+                    c => c,
                 }
             }
+            Some(c) if self.first => {
+                self.first = false;
+                c
+            }
+            Some(_) => return Ok(None),
         };
 
         Ok(Some(seed.deserialize(&mut *self.de)?))
@@ -0,0 +1,330 @@
+//! Text encodings used by modems for SMS text and USSD strings
+//!
+//! The wrapper types in this module transcode between Rust strings and the
+//! hex encoded representations, when serialized or deserialized. They are
+//! usually applied through `#[at_arg(encoding = "..")]` in the derives of
+//! `atat_derive`:
+//!
+//! - `"ucs2-hex"`: [`Ucs2Hex`], e.g. `"Hi"` <-> `"00480069"`
+//! - `"gsm7-hex"`: [`Gsm7Hex`], e.g. `"Hi"` <-> `"C834"`
+//!
+//! [`Ucs2Hex`]: struct.Ucs2Hex.html
+//! [`Gsm7Hex`]: struct.Gsm7Hex.html
+
+use core::fmt;
+
+use heapless::{ArrayLength, String};
+use serde::de::{self, Visitor};
+
+/// A string encoded as the hex representation of its UCS-2 code units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ucs2Hex<T>(pub T);
+
+/// A string encoded as the hex representation of packed GSM 7-bit default
+/// alphabet septets (3GPP TS 23.038).
+///
+/// Characters that cannot be represented in the GSM 7-bit alphabet are
+/// replaced by `'?'`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gsm7Hex<T>(pub T);
+
+/// The GSM 7-bit default alphabet
+const GSM7_BASIC: [char; 128] = [
+    '@', '£', '$', '¥', 'è', 'é', 'ù', 'ì', 'ò', 'Ç', '\n', 'Ø', 'ø', '\r', 'Å', 'å', //
+    'Δ', '_', 'Φ', 'Γ', 'Λ', 'Ω', 'Π', 'Ψ', 'Σ', 'Θ', 'Ξ', '\x1b', 'Æ', 'æ', 'ß', 'É', //
+    ' ', '!', '"', '#', '¤', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', //
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?', //
+    '¡', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', //
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'Ä', 'Ö', 'Ñ', 'Ü', '§', //
+    '¿', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', //
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'ä', 'ö', 'ñ', 'ü', 'à', //
+];
+
+/// The GSM 7-bit default alphabet extension table, entered through the
+/// escape septet `0x1B`
+const GSM7_EXTENSION: [(u8, char); 10] = [
+    (0x0A, '\x0c'),
+    (0x14, '^'),
+    (0x28, '{'),
+    (0x29, '}'),
+    (0x2F, '\\'),
+    (0x3C, '['),
+    (0x3D, '~'),
+    (0x3E, ']'),
+    (0x40, '|'),
+    (0x65, '€'),
+];
+
+const GSM7_ESCAPE: u8 = 0x1B;
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Iterate the bytes represented by the hex string `v`.
+fn hex_bytes<'a, E: de::Error>(v: &'a [u8]) -> Result<impl Iterator<Item = u8> + 'a, E> {
+    if v.len() % 2 != 0 || v.iter().any(|c| hex_value(*c).is_none()) {
+        return Err(E::custom("invalid hex string"));
+    }
+    Ok(v.chunks(2)
+        .map(|c| (hex_value(c[0]).unwrap() << 4) | hex_value(c[1]).unwrap()))
+}
+
+impl<N: ArrayLength<u8>> Ucs2Hex<String<N>> {
+    /// Decode an unquoted, hex encoded UCS-2 string.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        Ucs2HexVisitor(core::marker::PhantomData)
+            .visit_str::<de::value::Error>(hex)
+            .ok()
+    }
+}
+
+impl<T: AsRef<str>> fmt::Display for Ucs2Hex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for unit in self.0.as_ref().encode_utf16() {
+            write!(f, "{:04X}", unit)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: AsRef<str>> serde::Serialize for Ucs2Hex<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<N: ArrayLength<u8>> Gsm7Hex<String<N>> {
+    /// Decode an unquoted, hex encoded and packed GSM 7-bit string.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        Gsm7HexVisitor(core::marker::PhantomData)
+            .visit_str::<de::value::Error>(hex)
+            .ok()
+    }
+}
+
+impl<T: AsRef<str>> fmt::Display for Gsm7Hex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut acc: u16 = 0;
+        let mut bits = 0;
+        let mut push = |septet: u8, f: &mut fmt::Formatter<'_>| {
+            acc |= u16::from(septet) << bits;
+            bits += 7;
+            if bits >= 8 {
+                write!(f, "{:02X}", acc & 0xFF)?;
+                acc >>= 8;
+                bits -= 8;
+            }
+            Ok(())
+        };
+
+        for c in self.0.as_ref().chars() {
+            if let Some(septet) = GSM7_BASIC.iter().position(|&b| b == c && c != '\x1b') {
+                push(septet as u8, f)?;
+            } else if let Some((septet, _)) = GSM7_EXTENSION.iter().find(|(_, e)| *e == c) {
+                push(GSM7_ESCAPE, f)?;
+                push(*septet, f)?;
+            } else {
+                push(b'?', f)?;
+            }
+        }
+        if bits > 0 {
+            write!(f, "{:02X}", acc & 0xFF)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: AsRef<str>> serde::Serialize for Gsm7Hex<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+struct Ucs2HexVisitor<N>(core::marker::PhantomData<N>);
+
+impl<'de, N: ArrayLength<u8>> Visitor<'de> for Ucs2HexVisitor<N> {
+    type Value = Ucs2Hex<String<N>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a hex encoded UCS-2 string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        if v.len() % 4 != 0 {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        let mut bytes = hex_bytes::<E>(v)?;
+        let units = core::iter::from_fn(|| {
+            let hi = bytes.next()?;
+            let lo = bytes.next()?;
+            Some(u16::from(hi) << 8 | u16::from(lo))
+        });
+
+        let mut s = String::new();
+        for c in core::char::decode_utf16(units) {
+            let c = c.map_err(|_| E::custom("invalid UCS-2 code unit"))?;
+            s.push(c).map_err(|_| E::invalid_length(v.len(), &self))?;
+        }
+        Ok(Ucs2Hex(s))
+    }
+}
+
+impl<'de, N: ArrayLength<u8>> serde::Deserialize<'de> for Ucs2Hex<String<N>> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(Ucs2HexVisitor(core::marker::PhantomData))
+    }
+}
+
+struct Gsm7HexVisitor<N>(core::marker::PhantomData<N>);
+
+impl<'de, N: ArrayLength<u8>> Visitor<'de> for Gsm7HexVisitor<N> {
+    type Value = Gsm7Hex<String<N>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a hex encoded, packed GSM 7-bit string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        let octets = v.len() / 2;
+        // If the septets exactly fill the last octet, the last septet is
+        // padding, unless it contains a character.
+        let septets = octets * 8 / 7;
+
+        let mut s = String::new();
+        let mut acc: u16 = 0;
+        let mut bits = 0;
+        let mut escape = false;
+        let mut count = 0;
+        for byte in hex_bytes::<E>(v)? {
+            acc |= u16::from(byte) << bits;
+            bits += 8;
+            while bits >= 7 {
+                let septet = (acc & 0x7F) as u8;
+                acc >>= 7;
+                bits -= 7;
+                count += 1;
+
+                if count == septets && octets * 8 % 7 == 0 && (septet == 0 || septet == b'\r') {
+                    break;
+                }
+
+                let c = if escape {
+                    escape = false;
+                    GSM7_EXTENSION
+                        .iter()
+                        .find(|(e, _)| *e == septet)
+                        .map(|(_, c)| *c)
+                        .unwrap_or(' ')
+                } else if septet == GSM7_ESCAPE {
+                    escape = true;
+                    continue;
+                } else {
+                    GSM7_BASIC[septet as usize]
+                };
+                s.push(c).map_err(|_| E::invalid_length(v.len(), &self))?;
+            }
+        }
+        Ok(Gsm7Hex(s))
+    }
+}
+
+impl<'de, N: ArrayLength<u8>> serde::Deserialize<'de> for Gsm7Hex<String<N>> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(Gsm7HexVisitor(core::marker::PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::consts;
+
+    #[test]
+    fn ucs2_hex() {
+        let s: String<consts::U64> = crate::to_string(
+            &Ucs2Hex("Hej€"),
+            String::<consts::U1>::new(),
+            crate::SerializeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(s.as_str(), "\"00480065006A20AC\"");
+        assert_eq!(
+            crate::from_str_raw::<Ucs2Hex<String<consts::U16>>>("\"00480065006A20AC\"")
+                .unwrap()
+                .0,
+            "Hej€"
+        );
+    }
+
+    #[test]
+    fn gsm7_hex() {
+        let s: String<consts::U64> = crate::to_string(
+            &Gsm7Hex("*100#"),
+            String::<consts::U1>::new(),
+            crate::SerializeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(s.as_str(), "\"AA180C3602\"");
+
+        assert_eq!(
+            crate::from_str_raw::<Gsm7Hex<String<consts::U16>>>("\"AA180C3602\"")
+                .unwrap()
+                .0,
+            "*100#"
+        );
+
+        // Seven characters leave a padding septet in the last octet
+        let s: String<consts::U64> = crate::to_string(
+            &Gsm7Hex("1234567"),
+            String::<consts::U1>::new(),
+            crate::SerializeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            crate::from_str_raw::<Gsm7Hex<String<consts::U16>>>(&s)
+                .unwrap()
+                .0,
+            "1234567"
+        );
+
+        // Extension table characters are escaped
+        let s: String<consts::U64> = crate::to_string(
+            &Gsm7Hex("[5€]"),
+            String::<consts::U1>::new(),
+            crate::SerializeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            crate::from_str_raw::<Gsm7Hex<String<consts::U16>>>(&s)
+                .unwrap()
+                .0,
+            "[5€]"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Representations of flags used by modems
+//!
+//! `bool`s are serialized as `true`/`false` by default, which hardly any
+//! modem understands. The wrapper types in this module use the usual
+//! representations instead. They are usually applied through
+//! `#[at_arg(format = "..")]` in the derives of `atat_derive`:
+//!
+//! - `"0/1"`: [`NumericBool`], e.g. `true` <-> `1`
+//! - `"ON/OFF"`: [`OnOff`], e.g. `true` <-> `"ON"`
+//! - `"presence"`: [`Presence`], i.e. `true` if the parameter is given
+//!
+//! [`NumericBool`]: struct.NumericBool.html
+//! [`OnOff`]: struct.OnOff.html
+//! [`Presence`]: struct.Presence.html
+
+use core::borrow::Borrow;
+use core::fmt;
+
+use serde::de::{self, Visitor};
+
+/// A flag represented by `0` or `1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericBool<T>(pub T);
+
+/// A flag represented by the strings `"ON"` or `"OFF"`.
+///
+/// Deserialization accepts both quoted and unquoted strings, in any case.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OnOff<T>(pub T);
+
+/// A flag represented by the presence of a trailing parameter.
+///
+/// `true` is serialized as `1`, while `false` leaves the parameter out.
+/// Deserialization yields `true` for any value, and `false` if the parameter
+/// is missing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Presence<T>(pub T);
+
+impl<T: Borrow<bool>> serde::Serialize for NumericBool<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u8(u8::from(*self.0.borrow()))
+    }
+}
+
+impl<T: Borrow<bool>> serde::Serialize for OnOff<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(if *self.0.borrow() { "ON" } else { "OFF" })
+    }
+}
+
+impl<T: Borrow<bool>> serde::Serialize for Presence<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if *self.0.borrow() {
+            serializer.serialize_u8(1)
+        } else {
+            serializer.serialize_none()
+        }
+    }
+}
+
+struct NumericBoolVisitor;
+
+impl<'de> Visitor<'de> for NumericBoolVisitor {
+    type Value = NumericBool<bool>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("0 or 1")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        match v {
+            b"0" => Ok(NumericBool(false)),
+            b"1" => Ok(NumericBool(true)),
+            _ => Err(E::invalid_value(de::Unexpected::Bytes(v), &self)),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for NumericBool<bool> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(NumericBoolVisitor)
+    }
+}
+
+struct OnOffVisitor;
+
+impl<'de> Visitor<'de> for OnOffVisitor {
+    type Value = OnOff<bool>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ON or OFF")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        if v.eq_ignore_ascii_case(b"ON") {
+            Ok(OnOff(true))
+        } else if v.eq_ignore_ascii_case(b"OFF") {
+            Ok(OnOff(false))
+        } else {
+            Err(E::invalid_value(de::Unexpected::Bytes(v), &self))
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for OnOff<bool> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(OnOffVisitor)
+    }
+}
+
+struct PresenceVisitor;
+
+impl<'de> Visitor<'de> for PresenceVisitor {
+    type Value = Presence<bool>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("an optional parameter")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Presence(false))
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // Any value, e.g. `1` or `"x"`
+        <&[u8] as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(Presence(true))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Presence<bool> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_option(PresenceVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::{consts, String};
+
+    fn to_string<T: serde::Serialize>(value: &T) -> String<consts::U16> {
+        crate::to_string(
+            value,
+            String::<consts::U1>::new(),
+            crate::SerializeOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn numeric_bool() {
+        assert_eq!(to_string(&NumericBool(true)).as_str(), "1");
+        assert_eq!(to_string(&NumericBool(&false)).as_str(), "0");
+        assert!(crate::from_str_raw::<NumericBool<bool>>("1").unwrap().0);
+        assert!(!crate::from_str_raw::<NumericBool<bool>>("0").unwrap().0);
+        assert!(crate::from_str_raw::<NumericBool<bool>>("2").is_err());
+    }
+
+    #[test]
+    fn on_off() {
+        assert_eq!(to_string(&OnOff(true)).as_str(), "\"ON\"");
+        assert_eq!(to_string(&OnOff(false)).as_str(), "\"OFF\"");
+        assert!(crate::from_str_raw::<OnOff<bool>>("\"ON\"").unwrap().0);
+        assert!(!crate::from_str_raw::<OnOff<bool>>("off").unwrap().0);
+        assert!(crate::from_str_raw::<OnOff<bool>>("\"maybe\"").is_err());
+    }
+
+    #[test]
+    fn presence() {
+        assert_eq!(to_string(&Presence(true)).as_str(), "1");
+        assert!(crate::from_str_raw::<Presence<bool>>("1").unwrap().0);
+        assert!(!crate::from_str_raw::<Presence<bool>>("").unwrap().0);
+    }
+}
@@ -5,12 +5,14 @@
 #![cfg_attr(not(test), no_std)]
 
 pub mod de;
+pub mod encoding;
+pub mod format;
 pub mod ser;
 
 pub use serde;
 
 #[doc(inline)]
-pub use self::de::{from_slice, from_str};
+pub use self::de::{from_slice, from_slice_raw, from_str, from_str_raw};
 #[doc(inline)]
 pub use self::ser::{to_string, to_vec, SerializeOptions};
 
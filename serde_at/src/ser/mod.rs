@@ -46,6 +46,9 @@ impl<'a> Default for SerializeOptions<'a> {
 pub enum Error {
     /// Buffer is full
     BufferFull,
+    /// Error with a custom message that we had to discard, e.g. raised by a
+    /// custom serializer.
+    CustomError,
     #[doc(hidden)]
     __Extensible,
 }
@@ -64,7 +67,10 @@ impl From<u8> for Error {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Buffer is full")
+        match self {
+            Error::CustomError => write!(f, "Value could not be serialized"),
+            _ => write!(f, "Buffer is full"),
+        }
     }
 }
 
@@ -92,6 +98,18 @@ where
     }
 }
 
+impl<'a, B, C> Write for Serializer<'a, B, C>
+where
+    B: heapless::ArrayLength<u8>,
+    C: heapless::ArrayLength<u8>,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buf
+            .extend_from_slice(s.as_bytes())
+            .map_err(|_| fmt::Error)
+    }
+}
+
 // NOTE(serialize_*signed) This is basically the numtoa implementation minus the lookup tables,
 // which take 200+ bytes of ROM / Flash
 macro_rules! serialize_unsigned {
@@ -351,8 +369,14 @@ where
         unreachable!()
     }
 
-    fn collect_str<T: ?Sized>(self, _value: &T) -> Result<Self::Ok> {
-        unreachable!()
+    fn collect_str<T: ?Sized>(self, value: &T) -> Result<Self::Ok>
+    where
+        T: fmt::Display,
+    {
+        self.buf.push(b'"')?;
+        write!(self, "{}", value).map_err(|_| Error::BufferFull)?;
+        self.buf.push(b'"')?;
+        Ok(())
     }
 }
 
@@ -390,7 +414,7 @@ where
 
 impl ser::Error for Error {
     fn custom<T>(_msg: T) -> Self {
-        unreachable!()
+        Error::CustomError
     }
 }
 